@@ -0,0 +1,103 @@
+//! 序列化和回环传输的吞吐量基准
+//!
+//! 运行: `cargo bench`，只使用本机回环连接，不需要系统剪贴板。
+
+use clipboard_sync_alt::{ClipboardMessage, NetworkManager};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::io::Cursor;
+use std::time::Instant;
+
+const SCREEN_WIDTH: u32 = 1920;
+const SCREEN_HEIGHT: u32 = 1080;
+
+/// 一段约 4KB 的中英文混合文本
+fn sample_text() -> String {
+    "剪贴板同步 clipboard sync 测试文本，包含中文、English words 和标点。\n".repeat(50)
+}
+
+/// 生成类似截图的 1920x1080 PNG：大面积纯色窗口、渐变背景和细密的文字状纹理
+fn screenshot_png() -> Vec<u8> {
+    let image = image::RgbaImage::from_fn(SCREEN_WIDTH, SCREEN_HEIGHT, |x, y| {
+        let in_window = (200..1400).contains(&x) && (150..900).contains(&y);
+        if in_window {
+            // 窗口内每行像文字一样间断出现深色像素
+            let text_row = (y / 18) % 2 == 0 && (x * 7 + y * 13) % 11 < 4;
+            if text_row {
+                image::Rgba([30, 30, 30, 255])
+            } else {
+                image::Rgba([250, 250, 250, 255])
+            }
+        } else {
+            image::Rgba([(x * 255 / SCREEN_WIDTH) as u8, (y * 255 / SCREEN_HEIGHT) as u8, 160, 255])
+        }
+    });
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .expect("编码 PNG 失败");
+    png
+}
+
+fn samples() -> Vec<(&'static str, ClipboardMessage)> {
+    vec![
+        ("text_4k", ClipboardMessage::new_text(sample_text(), "bench".to_string(), "基准".to_string())),
+        (
+            "image_1080p",
+            ClipboardMessage::new_image(SCREEN_WIDTH, SCREEN_HEIGHT, screenshot_png(), "bench".to_string(), "基准".to_string()),
+        ),
+    ]
+}
+
+fn serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization");
+    for (name, message) in samples() {
+        let bytes = message.to_bytes().unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("to_bytes", name), &message, |b, message| {
+            b.iter(|| message.to_bytes().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("from_bytes", name), &bytes, |b, bytes| {
+            b.iter(|| ClipboardMessage::from_bytes(bytes).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn loopback(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (server, mut receiver, client) = runtime.block_on(async {
+        let server = NetworkManager::new("服务器".to_string());
+        let receiver = server.setup_message_handler().await;
+        let port = server.start_server(0).await.unwrap();
+        let client = NetworkManager::new("客户端".to_string());
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+        (server, receiver, client)
+    });
+
+    let mut group = c.benchmark_group("loopback");
+    for (name, message) in samples() {
+        group.throughput(Throughput::Bytes(message.to_bytes().unwrap().len() as u64));
+        // 从客户端广播到服务器收到并解析完成
+        group.bench_function(BenchmarkId::new("round_trip", name), |b| {
+            b.iter_custom(|iters| {
+                runtime.block_on(async {
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        client.broadcast_content(message.content.clone()).await.unwrap();
+                        receiver.recv().await.unwrap();
+                    }
+                    start.elapsed()
+                })
+            })
+        });
+    }
+    group.finish();
+
+    runtime.block_on(async {
+        client.shutdown().await;
+        server.shutdown().await;
+    });
+}
+
+criterion_group!(benches, serialization, loopback);
+criterion_main!(benches);
@@ -0,0 +1,4 @@
+fn main() {
+    // 记录编译目标，供 `info` 子命令显示
+    println!("cargo:rustc-env=BUILD_TARGET={}", std::env::var("TARGET").unwrap());
+}
@@ -0,0 +1,223 @@
+use anyhow::Result;
+use ipnet::IpNet;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::network_alternative::ClipboardContent;
+
+/// 解析网段，单个 IP 地址视为只包含该地址的网段（`/32` 或 `/128`）
+pub fn parse_net(value: &str) -> Result<IpNet> {
+    if let Ok(net) = value.parse::<IpNet>() {
+        return Ok(net);
+    }
+    value
+        .parse::<IpAddr>()
+        .map(IpNet::from)
+        .map_err(|_| anyhow::anyhow!("无效的网段 {:?}: 格式应为 IP 或 CIDR（如 192.168.1.0/24、fd00::/8）", value))
+}
+
+/// 按来源地址限制入站连接，拒绝规则优先于允许规则
+#[derive(Debug, Clone, Default)]
+pub struct AccessList {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl AccessList {
+    pub fn new(allow: Vec<IpNet>, deny: Vec<IpNet>) -> Self {
+        Self { allow, deny }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// 检查是否允许来自 `ip` 的连接：命中拒绝规则时拒绝；设置了允许规则时必须命中其中之一；没有规则时全部允许
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        // IPv4 映射的 IPv6 地址（::ffff:a.b.c.d）按 IPv4 匹配
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// 允许某个设备发送的内容类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentTypes {
+    pub text: bool,
+    pub image: bool,
+    pub clear: bool,
+}
+
+impl ContentTypes {
+    pub const ALL: Self = Self { text: true, image: true, clear: true };
+
+    pub fn is_all(&self) -> bool {
+        *self == Self::ALL
+    }
+
+    /// 是否允许该内容
+    pub fn allows(&self, content: &ClipboardContent) -> bool {
+        match content {
+            ClipboardContent::Text(_) | ClipboardContent::Color(_) | ClipboardContent::Html { .. } => self.text,
+            ClipboardContent::Image { .. } => self.image,
+            ClipboardContent::Clear => self.clear,
+        }
+    }
+}
+
+impl Default for ContentTypes {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl FromStr for ContentTypes {
+    type Err = anyhow::Error;
+
+    /// 解析逗号分隔的类型列表，如 `text,clear`；`all` 表示全部，`none` 表示全部拒绝
+    fn from_str(value: &str) -> Result<Self> {
+        let mut types = Self { text: false, image: false, clear: false };
+        for kind in value.split(',').map(str::trim) {
+            match kind {
+                "text" => types.text = true,
+                "image" => types.image = true,
+                "clear" => types.clear = true,
+                "all" => types = Self::ALL,
+                "none" => {}
+                other => return Err(anyhow::anyhow!("未知的内容类型: {} (可选: text, image, clear, all, none)", other)),
+            }
+        }
+        Ok(types)
+    }
+}
+
+impl fmt::Display for ContentTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_all() {
+            return f.write_str("all");
+        }
+        let kinds: Vec<&str> = [(self.text, "text"), (self.image, "image"), (self.clear, "clear")]
+            .into_iter()
+            .filter_map(|(allowed, kind)| allowed.then_some(kind))
+            .collect();
+        if kinds.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&kinds.join(","))
+        }
+    }
+}
+
+/// 解析 `<IP 或 CIDR>=<类型列表>` 形式的规则，如 `192.168.1.20=text,clear`
+pub fn parse_content_rule(value: &str) -> Result<(IpNet, ContentTypes)> {
+    let (net, types) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("无效的内容规则 {:?}: 格式应为 <IP 或 CIDR>=<类型>，如 192.168.1.20=text", value))?;
+    Ok((parse_net(net)?, types.parse()?))
+}
+
+/// 按设备地址限制它可以发送的内容类型，与同步方向等其他设置相互独立
+#[derive(Debug, Clone, Default)]
+pub struct ContentPolicy {
+    rules: Vec<(IpNet, ContentTypes)>,
+}
+
+impl ContentPolicy {
+    pub fn new(rules: Vec<(IpNet, ContentTypes)>) -> Self {
+        Self { rules }
+    }
+
+    /// 来自 `ip` 的设备允许发送的内容类型：使用第一条匹配的规则，没有匹配时全部允许
+    pub fn for_peer(&self, ip: IpAddr) -> ContentTypes {
+        let ip = ip.to_canonical();
+        self.rules
+            .iter()
+            .find(|(net, _)| net.contains(&ip))
+            .map(|(_, types)| *types)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(allow: &[&str], deny: &[&str]) -> AccessList {
+        let parse = |nets: &[&str]| nets.iter().map(|net| parse_net(net).unwrap()).collect();
+        AccessList::new(parse(allow), parse(deny))
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_no_rules_allows_everything() {
+        let access = AccessList::default();
+        assert!(access.is_empty());
+        assert!(access.permits(ip("203.0.113.7")));
+        assert!(access.permits(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let access = list(&["192.168.1.0/24", "fd00::/8"], &[]);
+        assert!(access.permits(ip("192.168.1.20")));
+        assert!(access.permits(ip("fd12::3")));
+        assert!(!access.permits(ip("192.168.2.20")));
+        assert!(!access.permits(ip("2001:db8::1")));
+        // 双栈监听时 IPv4 地址以映射形式出现
+        assert!(access.permits(ip("::ffff:192.168.1.20")));
+    }
+
+    #[test]
+    fn test_deny_takes_precedence() {
+        let access = list(&["10.0.0.0/8"], &["10.0.0.13"]);
+        assert!(access.permits(ip("10.0.0.12")));
+        assert!(!access.permits(ip("10.0.0.13")));
+
+        // 只有拒绝规则时其余地址仍然允许
+        let access = list(&[], &["2001:db8::/32"]);
+        assert!(!access.permits(ip("2001:db8::1")));
+        assert!(access.permits(ip("192.0.2.1")));
+    }
+
+    #[test]
+    fn test_content_types() {
+        let text_only: ContentTypes = "text".parse().unwrap();
+        assert!(text_only.allows(&ClipboardContent::Text("a".to_string())));
+        assert!(!text_only.allows(&ClipboardContent::Image { width: 1, height: 1, data: Vec::new() }));
+        assert!(!text_only.allows(&ClipboardContent::Clear));
+        assert_eq!(text_only.to_string(), "text");
+        
+        assert_eq!("text, clear".parse::<ContentTypes>().unwrap().to_string(), "text,clear");
+        assert_eq!("none".parse::<ContentTypes>().unwrap().to_string(), "none");
+        assert!("all".parse::<ContentTypes>().unwrap().is_all());
+        assert!("video".parse::<ContentTypes>().is_err());
+    }
+
+    #[test]
+    fn test_content_policy_first_match_wins() {
+        let policy = ContentPolicy::new(vec![
+            parse_content_rule("192.168.1.20=text").unwrap(),
+            parse_content_rule("192.168.1.0/24=text,clear").unwrap(),
+        ]);
+        assert_eq!(policy.for_peer(ip("192.168.1.20")).to_string(), "text");
+        assert_eq!(policy.for_peer(ip("::ffff:192.168.1.21")).to_string(), "text,clear");
+        // 没有匹配的规则时全部允许
+        assert!(policy.for_peer(ip("10.0.0.1")).is_all());
+        assert!(parse_content_rule("192.168.1.20").is_err());
+    }
+
+    #[test]
+    fn test_parse_net() {
+        assert_eq!(parse_net("192.168.1.5").unwrap(), parse_net("192.168.1.5/32").unwrap());
+        assert_eq!(parse_net("::1").unwrap(), parse_net("::1/128").unwrap());
+        assert!(parse_net("192.168.1.0/33").is_err());
+        assert!(parse_net("laptop").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::frame::{self, Frame};
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 握手的最长等待时间，防止对方不发送数据一直占用连接
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const NONCE_LEN: usize = 16;
+
+/// 预共享密钥认证：双方各发送一个随机数，再用密钥对双方随机数计算 HMAC 证明自己持有相同的密钥
+///
+/// 握手是对称的，主动发起和被动接受的连接执行相同的流程。
+/// 证明中包含双方的随机数且顺序不同，对方无法把我们的证明反射回来冒充。
+pub async fn authenticate<R, W>(reader: &mut R, writer: &mut W, secret: &[u8]) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange(reader, writer, secret))
+        .await
+        .map_err(|_| anyhow::anyhow!("认证超时"))?
+}
+
+async fn exchange<R, W>(reader: &mut R, writer: &mut W, secret: &[u8]) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let local_nonce = *uuid::Uuid::new_v4().as_bytes();
+    writer.write_all(&frame::encode_handshake(&local_nonce)).await?;
+    let peer_nonce = read_handshake(reader).await?;
+    if peer_nonce.len() != NONCE_LEN || peer_nonce == local_nonce {
+        return Err(anyhow::anyhow!("无效的认证随机数"));
+    }
+
+    let proof = prove(secret, &peer_nonce, &local_nonce).finalize().into_bytes();
+    writer.write_all(&frame::encode_handshake(&proof)).await?;
+    let peer_proof = read_handshake(reader).await?;
+
+    prove(secret, &local_nonce, &peer_nonce)
+        .verify_slice(&peer_proof)
+        .map_err(|_| anyhow::anyhow!("认证失败：双方的密钥不一致"))
+}
+
+/// 对 `challenge || own_nonce` 计算 HMAC
+fn prove(secret: &[u8], challenge: &[u8], own_nonce: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC 接受任意长度的密钥");
+    mac.update(challenge);
+    mac.update(own_nonce);
+    mac
+}
+
+async fn read_handshake<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    match frame::read_frame(reader).await? {
+        Some(Frame::Handshake(data)) => Ok(data),
+        Some(_) => Err(anyhow::anyhow!("对方未进行认证，可能没有设置密钥")),
+        None => Err(anyhow::anyhow!("认证过程中连接已断开，对方可能拒绝了密钥")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn handshake(a: &'static [u8], b: &'static [u8]) -> (Result<()>, Result<()>) {
+        let (left, right) = tokio::io::duplex(1024);
+        let (mut left_reader, mut left_writer) = tokio::io::split(left);
+        let (mut right_reader, mut right_writer) = tokio::io::split(right);
+        tokio::join!(
+            authenticate(&mut left_reader, &mut left_writer, a),
+            authenticate(&mut right_reader, &mut right_writer, b),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_matching_secrets_authenticate() {
+        let (left, right) = handshake(b"correct horse", b"correct horse").await;
+        assert!(left.is_ok());
+        assert!(right.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_secrets_rejected() {
+        let (left, right) = handshake(b"correct horse", b"battery staple").await;
+        assert!(left.is_err());
+        assert!(right.is_err());
+    }
+}
@@ -1,97 +1,242 @@
+#[cfg(feature = "image-support")]
 use anyhow::Result;
-use arboard::{Clipboard, ImageData};
-use std::sync::{Arc, Mutex};
+use arboard::Clipboard;
+#[cfg(feature = "image-support")]
+use arboard::ImageData;
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(feature = "image-support")]
+use image::io::Reader as ImageReader;
+#[cfg(feature = "image-support")]
 use image::{ImageFormat, RgbaImage};
+use std::hash::{DefaultHasher, Hash, Hasher};
+#[cfg(feature = "image-support")]
 use std::io::Cursor;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{SyncError, SyncResult};
+use crate::selection::{self, ChangeWatcher, Selection, SelectionTracker, Target};
+use crate::text_encoding::FallbackEncoding;
+
+/// 默认允许写入剪贴板的最大图片像素数（6400 万像素）
+pub const DEFAULT_MAX_IMAGE_PIXELS: u64 = 64_000_000;
+/// 默认的最小图片边长（像素），宽或高小于该值的剪贴板图片视为没有图片
+pub const DEFAULT_MIN_IMAGE_SIZE: u32 = 8;
+/// 写入剪贴板的默认尝试次数
+pub const DEFAULT_WRITE_ATTEMPTS: u32 = 3;
+/// 两次写入尝试之间的默认间隔
+pub const DEFAULT_WRITE_RETRY_DELAY: Duration = Duration::from_millis(50);
+/// 连续多少次访问失败后重新初始化剪贴板
+pub const DEFAULT_REINIT_AFTER_FAILURES: u32 = 3;
+/// 剪贴板恢复正常之前最多重新初始化的次数
+pub const DEFAULT_MAX_REINITS: u32 = 5;
 
 /// 剪贴板内容类型
 #[derive(Debug, Clone, PartialEq)]
 pub enum ClipboardContentType {
     Text,
     Image,
+    /// 带格式的 HTML（同时有纯文本形式），只在优先 HTML 时识别
+    Html,
     Empty,
 }
 
-/// 剪贴板管理器 - 负责读写剪贴板内容
-#[derive(Clone)]
-pub struct ClipboardManager {
-    clipboard: Arc<Mutex<Clipboard>>,
+/// 剪贴板同时包含多种形式（文本、图片、HTML）时的优先策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentPriority {
+    /// 优先文本（默认），仅在没有文本时才读取图片，不读取 HTML
+    #[default]
+    PreferText,
+    /// 优先图片
+    PreferImage,
+    /// 优先 HTML（如从浏览器复制的带格式内容），没有 HTML 时与优先文本相同
+    PreferHtml,
 }
 
-impl ClipboardManager {
-    /// 创建新的剪贴板管理器
-    pub fn new() -> Result<Self> {
-        let clipboard = Clipboard::new()
-            .map_err(|e| anyhow::anyhow!("无法初始化剪贴板: {}", e))?;
-        
-        Ok(Self {
-            clipboard: Arc::new(Mutex::new(clipboard)),
-        })
-    }
+impl std::str::FromStr for ContentPriority {
+    type Err = String;
 
-    /// 获取剪贴板中的文字内容
-    pub fn get_text(&self) -> Result<String> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        clipboard.get_text()
-            .map_err(|e| anyhow::anyhow!("读取剪贴板失败: {}", e))
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ContentPriority::PreferText),
+            "image" => Ok(ContentPriority::PreferImage),
+            "html" => Ok(ContentPriority::PreferHtml),
+            other => Err(format!("未知的优先策略: {} (可选: text, image, html)", other)),
+        }
     }
+}
 
-    /// 设置剪贴板文字内容
-    pub fn set_text(&self, text: &str) -> Result<()> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        clipboard.set_text(text)
-            .map_err(|e| anyhow::anyhow!("写入剪贴板失败: {}", e))
+/// 根据文本/HTML/图片是否存在及优先策略判断内容类型：优先的形式存在时使用它，否则依次为文本、图片、HTML
+fn classify(has_text: bool, has_html: bool, has_image: bool, priority: ContentPriority) -> ClipboardContentType {
+    match priority {
+        ContentPriority::PreferHtml if has_html => ClipboardContentType::Html,
+        ContentPriority::PreferImage if has_image => ClipboardContentType::Image,
+        _ if has_text => ClipboardContentType::Text,
+        _ if has_image => ClipboardContentType::Image,
+        _ if has_html => ClipboardContentType::Html,
+        _ => ClipboardContentType::Empty,
     }
+}
 
-    /// 获取剪贴板中的图片内容
-    pub fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        match clipboard.get_image() {
-            Ok(image_data) => {
-                // 将 RGBA 数据转换为 PNG 格式
-                let png_data = self.rgba_to_png(&image_data)?;
-                Ok(Some((image_data.width as u32, image_data.height as u32, png_data)))
+/// 获取锁，持有锁的线程 panic 导致锁中毒时恢复并继续使用，而不是让整个进程崩溃
+fn lock_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        eprintln!("⚠️ 剪贴板锁已中毒，已恢复并继续运行");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// 执行剪贴板写操作，剪贴板被其他进程占用等暂时性错误时间隔 `delay` 重试，最多尝试 `attempts` 次
+///
+/// 重试期间阻塞当前线程，异步代码中应在 `spawn_blocking` 中调用写入方法
+fn with_retry<T>(
+    attempts: u32,
+    delay: Duration,
+    mut op: impl FnMut() -> std::result::Result<T, arboard::Error>,
+) -> std::result::Result<T, arboard::Error> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(arboard::Error::ClipboardOccupied | arboard::Error::Unknown { .. }) if attempt < attempts => {
+                attempt += 1;
+                std::thread::sleep(delay);
             }
-            Err(_) => Ok(None),
+            result => return result,
         }
     }
-    
-    /// 设置剪贴板图片内容
-    pub fn set_image(&self, width: u32, height: u32, png_data: &[u8]) -> Result<()> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        
-        // 将 PNG 数据转换为 RGBA
-        let image_data = self.png_to_rgba(width, height, png_data)?;
-        clipboard.set_image(image_data)
-            .map_err(|e| anyhow::anyhow!("写入剪贴板图片失败: {}", e))
-    }
-    
-    /// 检测剪贴板内容类型
-    pub fn get_content_type(&self) -> ClipboardContentType {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        
-        // 先检查是否有图片
-        if clipboard.get_image().is_ok() {
-            return ClipboardContentType::Image;
+}
+
+/// 剪贴板句柄可能已失效的错误；内容不存在或格式转换失败说明句柄仍然可用
+fn indicates_stale_handle(error: &arboard::Error) -> bool {
+    !matches!(error, arboard::Error::ContentNotAvailable | arboard::Error::ConversionFailure)
+}
+
+/// 一次访问失败后的处理
+#[derive(Debug, PartialEq, Eq)]
+enum RecoveryAction {
+    /// 继续累计失败次数
+    Wait,
+    /// 重新初始化剪贴板，附带这是第几次
+    Reinit(u32),
+    /// 已达到重新初始化次数上限，不再尝试（只返回一次）
+    GiveUp,
+}
+
+/// 连续访问失败的计数，会话切换（锁屏解锁、显示器重新连接）后剪贴板句柄可能一直失败
+#[derive(Debug, Default)]
+struct Recovery {
+    failures: u32,
+    reinits: u32,
+    gave_up: bool,
+}
+
+impl Recovery {
+    /// 记录一次失败，连续失败 `after` 次时重新初始化，最多 `max` 次
+    fn record_failure(&mut self, after: u32, max: u32) -> RecoveryAction {
+        self.failures += 1;
+        if self.failures < after.max(1) {
+            return RecoveryAction::Wait;
         }
-        
-        // 再检查是否有文本
-        if let Ok(text) = clipboard.get_text() {
-            if !text.is_empty() {
-                return ClipboardContentType::Text;
+        if self.reinits >= max {
+            if self.gave_up {
+                return RecoveryAction::Wait;
             }
+            self.gave_up = true;
+            return RecoveryAction::GiveUp;
         }
-        
-        ClipboardContentType::Empty
+        self.failures = 0;
+        self.reinits += 1;
+        RecoveryAction::Reinit(self.reinits)
     }
-    
-    /// 检查剪贴板是否有内容
-    pub fn has_content(&self) -> bool {
-        !matches!(self.get_content_type(), ClipboardContentType::Empty)
+
+    /// 访问成功后重新计数，返回此前是否重新初始化过
+    fn record_success(&mut self) -> bool {
+        let recovered = self.reinits > 0;
+        *self = Self::default();
+        recovered
     }
-    
-    /// 将 RGBA 数据转换为 PNG 格式
-    fn rgba_to_png(&self, image_data: &ImageData) -> Result<Vec<u8>> {
+}
+
+#[cfg(feature = "image-support")]
+/// 根据文件头识别图片格式
+fn image_reader(data: &[u8]) -> Result<ImageReader<Cursor<&[u8]>>> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    if reader.format().is_none() {
+        return Err(anyhow::anyhow!("无法识别的图片格式"));
+    }
+    Ok(reader)
+}
+
+#[cfg(feature = "image-support")]
+/// 读取图片头中的尺寸，不解码像素数据，支持 PNG、JPEG、WebP 等常见格式
+pub fn image_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    image_reader(data)?
+        .into_dimensions()
+        .map_err(|e| anyhow::anyhow!("图片解码失败: {}", e))
+}
+
+#[cfg(feature = "image-support")]
+/// 检查像素数是否超过上限
+fn check_pixels(width: u32, height: u32, max_pixels: u64) -> Result<()> {
+    let pixels = width as u64 * height as u64;
+    if pixels > max_pixels {
+        return Err(anyhow::anyhow!(
+            "图片过大: {}x{} ({} 像素) 超过上限 {} 像素",
+            width, height, pixels, max_pixels
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "image-support")]
+/// 将任意支持格式的图片解码为 RGBA，格式由文件头识别，与发送方选择的编码无关
+///
+/// 分配像素缓冲区之前先读取图片头，像素数超过 `max_pixels` 时返回错误
+pub fn decode_image(data: &[u8], max_pixels: u64) -> Result<ImageData<'static>> {
+    let (width, height) = image_dimensions(data)?;
+    check_pixels(width, height, max_pixels)?;
+    decode_rgba(data)
+}
+
+#[cfg(feature = "image-support")]
+/// 解码为 RGBA，不检查尺寸
+fn decode_rgba(data: &[u8]) -> Result<ImageData<'static>> {
+    let img = image_reader(data)?
+        .decode()
+        .map_err(|e| anyhow::anyhow!("图片解码失败: {}", e))?;
+    Ok(ImageData {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        bytes: img.to_rgba8().into_raw().into(),
+    })
+}
+
+#[cfg(feature = "image-support")]
+/// 剪贴板图片与同步数据之间的编解码器
+///
+/// 读取剪贴板时用 `encode` 把 RGBA 像素编码为发送的数据，写入剪贴板时用 `decode` 还原。
+/// 解码前会先用 `dimensions` 读取图片头中的尺寸并检查像素数上限，`decode` 只会收到尺寸已经过检查的数据
+pub trait ImageCodec: Send + Sync {
+    /// 把 RGBA 像素编码为发送的图片数据
+    fn encode(&self, image: &ImageData) -> Result<Vec<u8>>;
+
+    /// 把收到的图片数据解码为 RGBA 像素
+    fn decode(&self, data: &[u8]) -> Result<ImageData<'static>>;
+
+    /// 读取图片头中的尺寸，不解码像素数据；默认识别 PNG、JPEG、WebP 等 image 库支持的格式
+    fn dimensions(&self, data: &[u8]) -> Result<(u32, u32)> {
+        image_dimensions(data)
+    }
+}
+
+/// 默认的编解码器：发送 PNG，接收时按文件头识别 PNG、JPEG、WebP 等常见格式
+#[cfg(feature = "image-support")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PngCodec;
+
+#[cfg(feature = "image-support")]
+impl ImageCodec for PngCodec {
+    fn encode(&self, image_data: &ImageData) -> Result<Vec<u8>> {
         let rgba_image = RgbaImage::from_raw(
             image_data.width as u32, 
             image_data.height as u32, 
@@ -106,29 +251,774 @@ impl ClipboardManager {
         
         Ok(png_data)
     }
+
+    fn decode(&self, data: &[u8]) -> Result<ImageData<'static>> {
+        decode_rgba(data)
+    }
+}
+
+#[cfg(feature = "image-support")]
+/// 将收到的图片数据转换为 RGBA 格式
+///
+/// 分配像素缓冲区之前先检查尺寸：声明的像素数超过 `max_pixels`，
+/// 或图片头中的实际尺寸与声明不一致时返回错误，避免恶意图片耗尽内存
+fn image_to_rgba(
+    codec: &dyn ImageCodec,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    max_pixels: u64,
+) -> Result<ImageData<'static>> {
+    check_pixels(width, height, max_pixels)?;
+    
+    let (actual_width, actual_height) = codec.dimensions(data)?;
+    if (actual_width, actual_height) != (width, height) {
+        return Err(anyhow::anyhow!(
+            "图片尺寸不匹配: 声明 {}x{}，实际 {}x{}",
+            width, height, actual_width, actual_height
+        ));
+    }
     
-    /// 将 PNG 数据转换为 RGBA 格式
-    fn png_to_rgba(&self, width: u32, height: u32, png_data: &[u8]) -> Result<ImageData> {
-        let cursor = Cursor::new(png_data);
-        let img = image::load(cursor, ImageFormat::Png)
-            .map_err(|e| anyhow::anyhow!("PNG 解码失败: {}", e))?;
+    let image_data = codec.decode(data)?;
+    // 自定义编解码器解码出的像素也必须与声明一致，否则写入剪贴板时会读越界
+    if (image_data.width, image_data.height) != (width as usize, height as usize)
+        || image_data.bytes.len() != width as usize * height as usize * 4
+    {
+        return Err(anyhow::anyhow!("解码后的图片与声明的尺寸 {}x{} 不一致", width, height));
+    }
+    Ok(image_data)
+}
+
+/// 系统剪贴板的变化序号，平台不提供时返回 `None`
+#[cfg(windows)]
+fn change_sequence() -> Option<u64> {
+    clipboard_win::raw::seq_num().map(|seq| seq.get() as u64)
+}
+
+/// 系统剪贴板的变化序号，平台不提供时返回 `None`
+#[cfg(target_os = "macos")]
+fn change_sequence() -> Option<u64> {
+    // SAFETY: 只读取通用剪贴板的变化计数，不修改剪贴板，返回的对象由 `Retained` 管理引用计数
+    let count = unsafe { objc2_app_kit::NSPasteboard::generalPasteboard().changeCount() };
+    Some(count as u64)
+}
+
+/// 系统剪贴板的变化序号，平台不提供时返回 `None`
+#[cfg(not(any(windows, target_os = "macos")))]
+fn change_sequence() -> Option<u64> {
+    None
+}
+
+/// 剪贴板的底层实现，`ClipboardManager` 通过它读写剪贴板
+///
+/// 默认的 [`ArboardBackend`] 访问系统剪贴板；[`MemoryClipboard`] 把内容保存在内存中，
+/// 供没有桌面环境的测试使用。错误沿用 arboard 的类型，重试和重新初始化的判断对所有实现一致
+pub trait ClipboardBackend: Send {
+    fn get_text(&mut self) -> std::result::Result<String, arboard::Error>;
+
+    fn set_text(&mut self, text: &str) -> std::result::Result<(), arboard::Error>;
+
+    #[cfg(feature = "image-support")]
+    fn get_image(&mut self) -> std::result::Result<ImageData<'static>, arboard::Error>;
+
+    #[cfg(feature = "image-support")]
+    fn set_image(&mut self, image: ImageData<'_>) -> std::result::Result<(), arboard::Error>;
+
+    fn clear(&mut self) -> std::result::Result<(), arboard::Error>;
+
+    /// 写入文本并推入系统剪贴板历史，默认与 `set_text` 相同
+    fn set_text_with_history(&mut self, text: &str) -> std::result::Result<(), arboard::Error> {
+        self.set_text(text)
+    }
+
+    /// 重新创建底层的句柄，默认什么也不做
+    fn reinitialize(&mut self) -> std::result::Result<(), arboard::Error> {
+        Ok(())
+    }
+
+    /// 剪贴板的变化序号，不提供时返回 `None`，调用方照常轮询内容
+    fn change_sequence(&self) -> Option<u64> {
+        None
+    }
+
+    /// 读取剪贴板文本的原始字节，供 `get_text` 因不是有效的 UTF-8 而失败时回退解码；默认不支持
+    fn get_text_bytes(&mut self) -> std::result::Result<Vec<u8>, arboard::Error> {
+        Err(arboard::Error::ContentNotAvailable)
+    }
+
+    /// 读取剪贴板中的 HTML，默认不支持
+    fn get_html(&mut self) -> std::result::Result<String, arboard::Error> {
+        Err(arboard::Error::ContentNotAvailable)
+    }
+
+    /// 写入 HTML 及其纯文本形式，默认只写入纯文本
+    fn set_html(&mut self, _html: &str, alt_text: &str) -> std::result::Result<(), arboard::Error> {
+        self.set_text(alt_text)
+    }
+}
+
+/// 通过 arboard 访问系统剪贴板
+pub struct ArboardBackend {
+    clipboard: Clipboard,
+    /// 读写的选区，不区分选区的平台上总是 CLIPBOARD
+    selection: Selection,
+    /// 同时监控两个选区时各选区上次读到的文本
+    tracker: SelectionTracker,
+    /// X11 上监听选区变化，作为系统没有提供的变化序号
+    watcher: Option<ChangeWatcher>,
+}
+
+impl ArboardBackend {
+    pub fn new() -> std::result::Result<Self, arboard::Error> {
+        let selection = Selection::default();
+        Ok(Self {
+            clipboard: Clipboard::new()?,
+            selection,
+            tracker: SelectionTracker::default(),
+            watcher: ChangeWatcher::new(selection.text_targets()),
+        })
+    }
+
+    /// 设置读写的选区（Linux 上的 CLIPBOARD 或 PRIMARY），其他平台忽略
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        let selection = selection.effective();
+        if selection != self.selection {
+            self.watcher = ChangeWatcher::new(selection.text_targets());
+        }
+        self.selection = selection;
+        self
+    }
+}
+
+impl ClipboardBackend for ArboardBackend {
+    fn get_text(&mut self) -> std::result::Result<String, arboard::Error> {
+        match self.selection.text_targets() {
+            [target] => selection::get_text(&mut self.clipboard, *target),
+            _ => {
+                let clipboard = selection::get_text(&mut self.clipboard, Target::Clipboard).ok();
+                let primary = selection::get_text(&mut self.clipboard, Target::Primary).ok();
+                self.tracker.pick(clipboard, primary).ok_or(arboard::Error::ContentNotAvailable)
+            }
+        }
+    }
+
+    fn set_text(&mut self, text: &str) -> std::result::Result<(), arboard::Error> {
+        for target in self.selection.text_targets() {
+            selection::set_text(&mut self.clipboard, *target, text)?;
+        }
+        if self.selection == Selection::Both {
+            self.tracker.record_write(text);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "image-support")]
+    fn get_image(&mut self) -> std::result::Result<ImageData<'static>, arboard::Error> {
+        selection::get_image(&mut self.clipboard, self.selection.image_target())
+    }
+
+    #[cfg(feature = "image-support")]
+    fn set_image(&mut self, image: ImageData<'_>) -> std::result::Result<(), arboard::Error> {
+        selection::set_image(&mut self.clipboard, self.selection.image_target(), image)
+    }
+
+    fn clear(&mut self) -> std::result::Result<(), arboard::Error> {
+        for target in self.selection.text_targets() {
+            selection::clear(&mut self.clipboard, *target)?;
+        }
+        self.tracker.record_clear();
+        Ok(())
+    }
+
+    fn set_text_with_history(&mut self, text: &str) -> std::result::Result<(), arboard::Error> {
+        // 系统剪贴板历史只在不区分选区的 Windows 上可用
+        if !crate::os_history::is_supported() {
+            return self.set_text(text);
+        }
+        // 调用方持有剪贴板锁，直接调用系统接口不会与 arboard 的访问交错
+        crate::os_history::set_text(text).map_err(|e| arboard::Error::Unknown { description: e.to_string() })
+    }
+
+    fn reinitialize(&mut self) -> std::result::Result<(), arboard::Error> {
+        self.clipboard = Clipboard::new()?;
+        Ok(())
+    }
+
+    fn change_sequence(&self) -> Option<u64> {
+        match &self.watcher {
+            Some(watcher) => watcher.sequence(),
+            None => change_sequence(),
+        }
+    }
+
+    fn get_text_bytes(&mut self) -> std::result::Result<Vec<u8>, arboard::Error> {
+        let target = match self.selection.text_targets() {
+            [target] => *target,
+            _ => Target::Clipboard,
+        };
+        crate::text_encoding::read_raw(target)
+    }
+
+    fn get_html(&mut self) -> std::result::Result<String, arboard::Error> {
+        selection::get_html(&mut self.clipboard, self.selection.image_target())
+    }
+
+    fn set_html(&mut self, html: &str, alt_text: &str) -> std::result::Result<(), arboard::Error> {
+        selection::set_html(&mut self.clipboard, self.selection.image_target(), html, alt_text)?;
+        if self.selection == Selection::Both {
+            // HTML 只写入 CLIPBOARD，PRIMARY 写入纯文本
+            selection::set_text(&mut self.clipboard, Target::Primary, alt_text)?;
+            self.tracker.record_write(alt_text);
+        }
+        Ok(())
+    }
+}
+
+/// 内存中的剪贴板，用于测试和没有桌面环境的场合
+///
+/// 克隆出的句柄共享同一份内容：把一个句柄交给 `ClipboardManager::with_backend`，
+/// 用另一个句柄模拟用户复制或检查写入的结果
+#[derive(Clone, Default)]
+pub struct MemoryClipboard {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    /// 文本的原始字节，不是有效的 UTF-8 时读取文本失败
+    text: Option<Vec<u8>>,
+    /// 与文本同时存在的 HTML
+    html: Option<String>,
+    #[cfg(feature = "image-support")]
+    image: Option<ImageData<'static>>,
+    /// 每次写入后加一，模拟系统的变化序号
+    sequence: u64,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 放入原始字节作为文本，模拟其他程序写入非 UTF-8 编码的文本
+    pub fn set_text_bytes(&self, bytes: Vec<u8>) {
+        self.replace(Some(bytes), None);
+    }
+
+    /// 同时放入 HTML 和纯文本，模拟浏览器等程序一次放入多种形式
+    pub fn set_contents(&self, text: Option<&str>, html: Option<&str>) {
+        self.replace(text.map(|text| text.as_bytes().to_vec()), html.map(str::to_string));
+    }
+
+    /// 在现有的文本和 HTML 之外再放入一张图片，模拟同时包含文本和图片的复制
+    #[cfg(feature = "image-support")]
+    pub fn add_image(&self, image: ImageData<'static>) {
+        let mut state = lock_recover(&self.state);
+        state.image = Some(image);
+        state.sequence += 1;
+    }
+
+    /// 替换全部内容并推进变化序号
+    fn replace(&self, text: Option<Vec<u8>>, html: Option<String>) {
+        let mut state = lock_recover(&self.state);
+        state.text = text;
+        state.html = html;
+        #[cfg(feature = "image-support")]
+        {
+            state.image = None;
+        }
+        state.sequence += 1;
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_text(&mut self) -> std::result::Result<String, arboard::Error> {
+        let bytes = self.get_text_bytes()?;
+        String::from_utf8(bytes).map_err(|_| arboard::Error::ConversionFailure)
+    }
+
+    fn set_text(&mut self, text: &str) -> std::result::Result<(), arboard::Error> {
+        self.replace(Some(text.as_bytes().to_vec()), None);
+        Ok(())
+    }
+
+    #[cfg(feature = "image-support")]
+    fn get_image(&mut self) -> std::result::Result<ImageData<'static>, arboard::Error> {
+        lock_recover(&self.state).image.clone().ok_or(arboard::Error::ContentNotAvailable)
+    }
+
+    #[cfg(feature = "image-support")]
+    fn set_image(&mut self, image: ImageData<'_>) -> std::result::Result<(), arboard::Error> {
+        self.replace(None, None);
+        self.add_image(image.to_owned_img());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> std::result::Result<(), arboard::Error> {
+        self.replace(None, None);
+        Ok(())
+    }
+
+    fn change_sequence(&self) -> Option<u64> {
+        Some(lock_recover(&self.state).sequence)
+    }
+
+    fn get_text_bytes(&mut self) -> std::result::Result<Vec<u8>, arboard::Error> {
+        lock_recover(&self.state).text.clone().ok_or(arboard::Error::ContentNotAvailable)
+    }
+
+    fn get_html(&mut self) -> std::result::Result<String, arboard::Error> {
+        lock_recover(&self.state).html.clone().ok_or(arboard::Error::ContentNotAvailable)
+    }
+
+    fn set_html(&mut self, html: &str, alt_text: &str) -> std::result::Result<(), arboard::Error> {
+        self.set_contents(Some(alt_text), Some(html));
+        Ok(())
+    }
+}
+
+/// 记录上次观察到的变化序号
+#[derive(Debug, Default)]
+struct ChangeSequence {
+    last: Option<u64>,
+}
+
+impl ChangeSequence {
+    /// 记录当前序号，返回剪贴板是否可能已变化（没有序号时总是返回 true）
+    fn observe(&mut self, current: Option<u64>) -> bool {
+        match current {
+            Some(seq) => self.last.replace(seq) != Some(seq),
+            None => true,
+        }
+    }
+}
+
+/// 剪贴板管理器 - 负责读写剪贴板内容
+#[derive(Clone)]
+pub struct ClipboardManager {
+    clipboard: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+    priority: ContentPriority,
+    /// 类型检测时读到的原始图片，供随后的 get_image 复用，避免重复读取
+    #[cfg(feature = "image-support")]
+    image_cache: Arc<Mutex<Option<ImageData<'static>>>>,
+    sequence: Arc<Mutex<ChangeSequence>>,
+    /// 收到的图片解码前允许的最大像素数
+    #[cfg(feature = "image-support")]
+    max_image_pixels: u64,
+    /// 剪贴板图片的最小边长，更小的图片（部分程序操作期间临时放入的 1x1 占位图等）视为没有图片
+    #[cfg(feature = "image-support")]
+    min_image_size: u32,
+    /// 上次忽略的过小图片的尺寸，同一尺寸只记录一次日志
+    #[cfg(feature = "image-support")]
+    last_small_image: Arc<Mutex<Option<(usize, usize)>>>,
+    /// 读写剪贴板图片时使用的编解码器
+    #[cfg(feature = "image-support")]
+    codec: Arc<dyn ImageCodec>,
+    /// 写入剪贴板的尝试次数和重试间隔
+    write_attempts: u32,
+    write_retry_delay: Duration,
+    /// 最近一次发现本地（非同步写入的）变化的时间
+    last_local_change: Arc<Mutex<Option<SystemTime>>>,
+    /// 连续访问失败的计数
+    recovery: Arc<Mutex<Recovery>>,
+    /// 连续失败多少次后重新初始化剪贴板，以及最多重新初始化的次数（0 表示不重新初始化）
+    reinit_after: u32,
+    max_reinits: u32,
+    /// 写入文本时推入系统剪贴板历史
+    os_history: bool,
+    /// 剪贴板文本不是有效的 UTF-8 时使用的回退编码，`None` 时只接受 UTF-8
+    text_encoding: Option<FallbackEncoding>,
+    /// 上次回退解码的原始字节的哈希，同一内容只记录一次日志
+    last_lossy: Arc<Mutex<Option<u64>>>,
+}
+
+impl ClipboardManager {
+    /// 创建访问系统剪贴板的管理器
+    pub fn new() -> SyncResult<Self> {
+        Self::for_selection(Selection::default())
+    }
+
+    /// 创建访问系统剪贴板指定选区的管理器，不区分选区的平台上等同于 `new`
+    pub fn for_selection(selection: Selection) -> SyncResult<Self> {
+        let backend = ArboardBackend::new()
+            .map_err(|e| SyncError::ClipboardUnavailable(e.to_string()))?
+            .with_selection(selection);
+        Ok(Self::with_backend(backend))
+    }
+
+    /// 使用指定的底层实现创建管理器，如测试中使用 [`MemoryClipboard`]
+    pub fn with_backend(backend: impl ClipboardBackend + 'static) -> Self {
+        Self {
+            clipboard: Arc::new(Mutex::new(Box::new(backend))),
+            priority: ContentPriority::default(),
+            #[cfg(feature = "image-support")]
+            image_cache: Arc::new(Mutex::new(None)),
+            sequence: Arc::new(Mutex::new(ChangeSequence::default())),
+            #[cfg(feature = "image-support")]
+            max_image_pixels: DEFAULT_MAX_IMAGE_PIXELS,
+            #[cfg(feature = "image-support")]
+            min_image_size: DEFAULT_MIN_IMAGE_SIZE,
+            #[cfg(feature = "image-support")]
+            last_small_image: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "image-support")]
+            codec: Arc::new(PngCodec),
+            write_attempts: DEFAULT_WRITE_ATTEMPTS,
+            write_retry_delay: DEFAULT_WRITE_RETRY_DELAY,
+            last_local_change: Arc::new(Mutex::new(None)),
+            recovery: Arc::new(Mutex::new(Recovery::default())),
+            reinit_after: DEFAULT_REINIT_AFTER_FAILURES,
+            max_reinits: DEFAULT_MAX_REINITS,
+            os_history: false,
+            text_encoding: None,
+            last_lossy: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 设置文本与图片同时存在时的优先策略
+    pub fn with_priority(mut self, priority: ContentPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 设置写入剪贴板的图片的最大像素数，超出时拒绝解码
+    #[cfg(feature = "image-support")]
+    pub fn with_max_image_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_image_pixels = max_pixels;
+        self
+    }
+
+    /// 设置剪贴板图片的最小边长（像素），宽或高小于该值的图片视为没有图片，默认为 `DEFAULT_MIN_IMAGE_SIZE`
+    #[cfg(feature = "image-support")]
+    pub fn with_min_image_size(mut self, min_size: u32) -> Self {
+        self.min_image_size = min_size;
+        self
+    }
+
+    /// 使用自定义的图片编解码器代替默认的 PNG
+    #[cfg(feature = "image-support")]
+    pub fn with_codec(mut self, codec: impl ImageCodec + 'static) -> Self {
+        self.codec = Arc::new(codec);
+        self
+    }
+
+    /// 设置写入剪贴板失败时的尝试次数（至少一次）和重试间隔
+    pub fn with_write_retry(mut self, attempts: u32, delay: Duration) -> Self {
+        self.write_attempts = attempts.max(1);
+        self.write_retry_delay = delay;
+        self
+    }
+
+    /// 设置连续失败多少次后重新初始化剪贴板，以及剪贴板恢复前最多重新初始化的次数（0 表示不重新初始化）
+    pub fn with_recovery(mut self, after_failures: u32, max_reinits: u32) -> Self {
+        self.reinit_after = after_failures.max(1);
+        self.max_reinits = max_reinits;
+        self
+    }
+
+    /// 写入文本时推入系统剪贴板历史（Windows 上按 Win+V 查看），不支持的平台或底层实现照常写入
+    pub fn with_os_history(mut self, enabled: bool) -> Self {
+        self.os_history = enabled;
+        self
+    }
+
+    /// 剪贴板文本不是有效的 UTF-8 时改为读取原始字节并按指定编码解码，无法解码的部分替换为 U+FFFD；
+    /// `None`（默认）时这样的文本视为无法读取
+    pub fn with_text_encoding(mut self, encoding: Option<FallbackEncoding>) -> Self {
+        self.text_encoding = encoding;
+        self
+    }
+
+    /// 重新创建底层的剪贴板句柄
+    ///
+    /// 部分系统在会话切换（锁屏解锁、显示器重新连接）后原句柄会一直失败，重新创建后即可恢复
+    pub fn reinitialize(&self) -> SyncResult<()> {
+        lock_recover(&self.clipboard)
+            .reinitialize()
+            .map_err(|e| SyncError::ClipboardUnavailable(e.to_string()))?;
+        #[cfg(feature = "image-support")]
+        lock_recover(&self.image_cache).take();
+        Ok(())
+    }
+
+    /// 访问剪贴板，每次调用单独加锁
+    fn access<T>(
+        &self,
+        mut op: impl FnMut(&mut dyn ClipboardBackend) -> std::result::Result<T, arboard::Error>,
+    ) -> std::result::Result<T, arboard::Error> {
+        self.recover(|| op(lock_recover(&self.clipboard).as_mut()))
+    }
+
+    /// 执行剪贴板操作，连续失败达到阈值时重新初始化剪贴板并重试一次；`op` 自行加锁
+    fn recover<T>(
+        &self,
+        mut op: impl FnMut() -> std::result::Result<T, arboard::Error>,
+    ) -> std::result::Result<T, arboard::Error> {
+        let result = op();
+        match &result {
+            Err(e) if indicates_stale_handle(e) => {}
+            _ => {
+                self.record_success();
+                return result;
+            }
+        }
         
-        let rgba_img = img.to_rgba8();
-        let bytes = rgba_img.into_raw();
+        let action = lock_recover(&self.recovery).record_failure(self.reinit_after, self.max_reinits);
+        match action {
+            RecoveryAction::Wait => result,
+            RecoveryAction::GiveUp => {
+                eprintln!("⚠️ 已重新初始化剪贴板 {} 次仍然失败，不再尝试，直到剪贴板恢复", self.max_reinits);
+                result
+            }
+            RecoveryAction::Reinit(attempt) => {
+                status!("🔄 剪贴板连续 {} 次访问失败，重新初始化 ({}/{})", self.reinit_after, attempt, self.max_reinits);
+                if let Err(e) = self.reinitialize() {
+                    eprintln!("❌ 重新初始化剪贴板失败: {}", e);
+                    return result;
+                }
+                let retried = op();
+                if !matches!(&retried, Err(e) if indicates_stale_handle(e)) {
+                    self.record_success();
+                }
+                retried
+            }
+        }
+    }
+
+    /// 访问成功，此前重新初始化过时记录恢复
+    fn record_success(&self) {
+        if lock_recover(&self.recovery).record_success() {
+            status!("✅ 剪贴板已恢复访问");
+        }
+    }
+
+    /// 按配置重试剪贴板写操作，每次尝试单独加锁，等待期间不阻塞读取
+    fn write<T>(
+        &self,
+        mut op: impl FnMut(&mut dyn ClipboardBackend) -> std::result::Result<T, arboard::Error>,
+    ) -> std::result::Result<T, arboard::Error> {
+        self.recover(|| with_retry(self.write_attempts, self.write_retry_delay, || op(lock_recover(&self.clipboard).as_mut())))
+    }
+
+    /// 快速检查剪贴板自上次调用以来是否可能发生了变化
+    ///
+    /// Windows 和 macOS 上读取系统的变化序号，X11 上以 XFixes 的选区变化通知计数，序号未变时无需再读取内容；
+    /// 其他平台（以及连接不到 X 服务器时）没有廉价的变化通知，总是返回 true，由调用方照常轮询内容
+    pub fn poll_for_change(&self) -> bool {
+        let current = lock_recover(&self.clipboard).change_sequence();
+        lock_recover(&self.sequence).observe(current)
+    }
+
+    /// 记录发现了一次本地变化（用户复制了新内容），时间为当前时间
+    pub fn record_local_change(&self) {
+        *lock_recover(&self.last_local_change) = Some(SystemTime::now());
+    }
+
+    /// 最近一次发现本地变化的时间，尚未发现过时为 `None`
+    pub fn last_local_change(&self) -> Option<SystemTime> {
+        *lock_recover(&self.last_local_change)
+    }
+
+    /// 读取文本，不是有效的 UTF-8 时按回退编码解码
+    fn read_text(&self) -> std::result::Result<String, arboard::Error> {
+        let result = self.access(|clipboard| clipboard.get_text());
+        let encoding = match (&result, self.text_encoding) {
+            (Err(arboard::Error::ConversionFailure), Some(encoding)) => encoding,
+            _ => return result,
+        };
+        let bytes = self.access(|clipboard| clipboard.get_text_bytes())?;
+        let (text, had_errors) = encoding.decode(&bytes);
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = hasher.finish();
+        if lock_recover(&self.last_lossy).replace(hash) != Some(hash) {
+            if had_errors {
+                status!("🔤 剪贴板文本不是有效的 UTF-8，已按 {} 解码，无法解码的部分已替换为 �", encoding);
+            } else {
+                status!("🔤 剪贴板文本不是有效的 UTF-8，已按 {} 解码", encoding);
+            }
+        }
+        Ok(text)
+    }
+
+    /// 获取剪贴板中的文字内容
+    pub fn get_text(&self) -> SyncResult<String> {
+        self.read_text()
+            .map_err(|e| SyncError::Clipboard(format!("读取剪贴板失败: {}", e)))
+    }
+
+    /// 设置剪贴板文字内容，空字符串等同于清空剪贴板（部分平台拒绝写入空文本）
+    pub fn set_text(&self, text: &str) -> SyncResult<()> {
+        if text.is_empty() {
+            return self.clear();
+        }
+        self.write(|clipboard| {
+            if self.os_history {
+                clipboard.set_text_with_history(text)
+            } else {
+                clipboard.set_text(text)
+            }
+        })
+            .map_err(|e| SyncError::Clipboard(format!("写入剪贴板失败: {}", e)))
+    }
+
+    /// 获取剪贴板中的 HTML
+    pub fn get_html(&self) -> SyncResult<String> {
+        self.access(|clipboard| clipboard.get_html())
+            .map_err(|e| SyncError::Clipboard(format!("读取剪贴板 HTML 失败: {}", e)))
+    }
+
+    /// 写入 HTML 及其纯文本形式，不支持 HTML 的程序粘贴纯文本
+    pub fn set_html(&self, html: &str, alt_text: &str) -> SyncResult<()> {
+        self.write(|clipboard| clipboard.set_html(html, alt_text))
+            .map_err(|e| SyncError::Clipboard(format!("写入剪贴板 HTML 失败: {}", e)))
+    }
+
+    /// 在一次剪贴板操作中写入纯文本和 HTML，像原生应用一样同时提供两种形式：
+    /// 只有纯文本时等同于 `set_text`，只有 HTML 时以 HTML 源码作为纯文本形式，都没有时清空剪贴板
+    pub fn set_rich(&self, text: Option<&str>, html: Option<&str>) -> SyncResult<()> {
+        match (text, html) {
+            (text, Some(html)) => self.set_html(html, text.unwrap_or(html)),
+            (Some(text), None) => self.set_text(text),
+            (None, None) => self.clear(),
+        }
+    }
+
+    /// 清空剪贴板
+    pub fn clear(&self) -> SyncResult<()> {
+        self.write(|clipboard| clipboard.clear())
+            .map_err(|e| SyncError::Clipboard(format!("清空剪贴板失败: {}", e)))
+    }
+
+    /// 获取剪贴板中的图片内容
+    #[cfg(feature = "image-support")]
+    pub fn get_image(&self) -> SyncResult<Option<(u32, u32, Vec<u8>)>> {
+        let cached = lock_recover(&self.image_cache).take();
+        let image_data = match cached {
+            Some(image_data) => Some(image_data),
+            None => self.access(|clipboard| clipboard.get_image()).ok().filter(|image| self.is_usable_image(image)),
+        };
+        match image_data {
+            Some(image_data) => {
+                // 将 RGBA 数据编码为发送的格式（默认 PNG）
+                let png_data = self.codec.encode(&image_data)?;
+                Ok(Some((image_data.width as u32, image_data.height as u32, png_data)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 剪贴板图片像素的指纹，没有图片时返回 `None`
+    ///
+    /// 优先使用类型检测时缓存的图片；读到的图片留在缓存中，内容有变化时随后的 `get_image` 无需再次读取，
+    /// 没有变化时调用 [`discard_cached_image`](Self::discard_cached_image) 释放
+    #[cfg(feature = "image-support")]
+    pub fn image_fingerprint(&self) -> Option<u64> {
+        let mut cache = lock_recover(&self.image_cache);
+        if cache.is_none() {
+            *cache = self.access(|clipboard| clipboard.get_image()).ok().filter(|image| self.is_usable_image(image));
+        }
+        let image = cache.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        (image.width, image.height, image.bytes.as_ref()).hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// 释放类型检测时缓存的图片
+    #[cfg(feature = "image-support")]
+    pub fn discard_cached_image(&self) {
+        lock_recover(&self.image_cache).take();
+    }
+
+    /// 图片是否可以当作剪贴板内容：没有数据或宽、高小于最小边长的图片视为没有图片
+    #[cfg(feature = "image-support")]
+    fn is_usable_image(&self, image: &ImageData) -> bool {
+        let min_size = (self.min_image_size as usize).max(1);
+        if image.bytes.is_empty() || image.width < min_size || image.height < min_size {
+            let size = (image.width, image.height);
+            if lock_recover(&self.last_small_image).replace(size) != Some(size) {
+                verbose!("🖼️ 忽略过小的剪贴板图片 {}x{}（最小边长 {}）", image.width, image.height, min_size);
+            }
+            return false;
+        }
+        true
+    }
+    
+    /// 设置剪贴板图片内容，图片格式（PNG、JPEG、WebP 等）由数据头自动识别
+    #[cfg(feature = "image-support")]
+    pub fn set_image(&self, width: u32, height: u32, data: &[u8]) -> SyncResult<()> {
+        // 将图片数据转换为 RGBA，解码期间不持有剪贴板锁
+        let image_data = image_to_rgba(self.codec.as_ref(), width, height, data, self.max_image_pixels)?;
         
-        Ok(ImageData {
-            width: width as usize,
-            height: height as usize,
-            bytes: bytes.into(),
+        self.write(|clipboard| {
+            // 每次尝试只借用像素数据，不复制
+            clipboard.set_image(ImageData {
+                width: image_data.width,
+                height: image_data.height,
+                bytes: std::borrow::Cow::Borrowed(&image_data.bytes),
+            })
         })
+            .map_err(|e| SyncError::Clipboard(format!("写入剪贴板图片失败: {}", e)))
+    }
+    
+    /// 检测剪贴板内容类型
+    pub fn get_content_type(&self) -> ClipboardContentType {
+        // 先检查文本，读取文本远比解码图片便宜
+        let has_text = matches!(self.read_text(), Ok(text) if !text.is_empty());
+        // 只在优先 HTML 时读取 HTML，其他策略下不多读一次剪贴板
+        let has_html = self.priority == ContentPriority::PreferHtml
+            && matches!(self.access(|clipboard| clipboard.get_html()), Ok(html) if !html.is_empty());
+        if (has_text && self.priority != ContentPriority::PreferImage) || has_html {
+            #[cfg(feature = "image-support")]
+            lock_recover(&self.image_cache).take();
+            return classify(has_text, has_html, false, self.priority);
+        }
+        self.detect_image(has_text, has_html)
+    }
+
+    /// 未编译图片支持时总是当作没有图片
+    #[cfg(not(feature = "image-support"))]
+    fn detect_image(&self, has_text: bool, has_html: bool) -> ClipboardContentType {
+        classify(has_text, has_html, false, self.priority)
     }
-}
 
+    /// 检查剪贴板中是否有图片
+    #[cfg(feature = "image-support")]
+    fn detect_image(&self, has_text: bool, has_html: bool) -> ClipboardContentType {
+        // 再检查图片，忽略空的、过小的或失效的图片句柄
+        let image = self
+            .access(|clipboard| clipboard.get_image())
+            .ok()
+            .filter(|image| self.is_usable_image(image));
+        let has_image = image.is_some();
+        
+        // 缓存读到的图片，随后的 get_image 无需再次读取
+        *lock_recover(&self.image_cache) = image;
+        
+        classify(has_text, has_html, has_image, self.priority)
+    }
+    
+    /// 检查剪贴板是否有内容
+    pub fn has_content(&self) -> bool {
+        !matches!(self.get_content_type(), ClipboardContentType::Empty)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_change_sequence() {
+        let mut sequence = ChangeSequence::default();
+        // 首次观察总是视为变化
+        assert!(sequence.observe(Some(1)));
+        assert!(!sequence.observe(Some(1)));
+        assert!(sequence.observe(Some(2)));
+        // 平台不支持序号时退回轮询
+        assert!(sequence.observe(None));
+        assert!(sequence.observe(None));
+    }
+
     #[test]
     fn test_clipboard_basic_operations() {
         let manager = ClipboardManager::new().expect("创建剪贴板管理器失败");
@@ -139,5 +1029,348 @@ mod tests {
         
         let result = manager.get_text().expect("读取失败");
         assert_eq!(result, test_text);
+        
+        // 写入空文本等同于清空
+        manager.set_text("").expect("写入空文本失败");
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Empty);
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_memory_backend() {
+        let memory = MemoryClipboard::new();
+        let manager = ClipboardManager::with_backend(memory.clone());
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Empty);
+
+        manager.set_text("hello").unwrap();
+        assert_eq!(memory.clone().get_text().unwrap(), "hello");
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Text);
+
+        // 另一个句柄模拟用户复制图片，变化序号随之推进
+        assert!(manager.poll_for_change());
+        assert!(!manager.poll_for_change());
+        memory.clone().set_image(ImageData { width: 12, height: 8, bytes: vec![0x80; 384].into() }).unwrap();
+        assert!(manager.poll_for_change());
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Image);
+        let (width, height, png_data) = manager.get_image().unwrap().unwrap();
+        assert_eq!((width, height), (12, 8));
+        manager.set_image(width, height, &png_data).unwrap();
+        assert_eq!(memory.clone().get_image().unwrap().bytes.as_ref(), &[0x80; 384][..]);
+
+        manager.set_text("").unwrap();
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Empty);
+    }
+
+    #[test]
+    fn test_fallback_text_encoding() {
+        let memory = MemoryClipboard::new();
+        // GBK 编码的“你好”
+        memory.set_text_bytes(vec![0xc4, 0xe3, 0xba, 0xc3]);
+
+        // 默认只接受 UTF-8
+        let strict = ClipboardManager::with_backend(memory.clone());
+        assert!(strict.get_text().is_err());
+        assert_eq!(strict.get_content_type(), ClipboardContentType::Empty);
+
+        let lossy = ClipboardManager::with_backend(memory.clone()).with_text_encoding(Some("gbk".parse().unwrap()));
+        assert_eq!(lossy.get_content_type(), ClipboardContentType::Text);
+        assert_eq!(lossy.get_text().unwrap(), "你好");
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_priority_with_multiple_representations() {
+        let memory = MemoryClipboard::new();
+        let image = ImageData { width: 8, height: 8, bytes: vec![0u8; 256].into() };
+        // 从浏览器复制：同时有纯文本、HTML 和图片
+        memory.set_contents(Some("标题"), Some("<b>标题</b>"));
+        memory.add_image(image);
+
+        let detect = |priority| ClipboardManager::with_backend(memory.clone()).with_priority(priority).get_content_type();
+        assert_eq!(detect(ContentPriority::PreferText), ClipboardContentType::Text);
+        assert_eq!(detect(ContentPriority::PreferImage), ClipboardContentType::Image);
+        assert_eq!(detect(ContentPriority::PreferHtml), ClipboardContentType::Html);
+
+        // 写入 HTML 后两种形式都可以读取，写入纯文本会去掉 HTML
+        let manager = ClipboardManager::with_backend(memory.clone()).with_priority(ContentPriority::PreferHtml);
+        manager.set_html("<i>斜体</i>", "斜体").unwrap();
+        assert_eq!(manager.get_html().unwrap(), "<i>斜体</i>");
+        assert_eq!(manager.get_text().unwrap(), "斜体");
+        manager.set_text("纯文本").unwrap();
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Text);
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_tiny_images_ignored() {
+        let memory = MemoryClipboard::new();
+        let manager = ClipboardManager::with_backend(memory.clone());
+        memory.clone().set_image(ImageData { width: 1, height: 1, bytes: vec![0xff; 4].into() }).unwrap();
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Empty);
+        assert_eq!(manager.get_image().unwrap(), None);
+
+        // 1xN 的图片同样忽略
+        memory.clone().set_image(ImageData { width: 1, height: 64, bytes: vec![0xff; 256].into() }).unwrap();
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Empty);
+
+        // 降低最小边长后照常读取
+        let manager = manager.with_min_image_size(1);
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Image);
+        assert_eq!(manager.get_image().unwrap().map(|(width, height, _)| (width, height)), Some((1, 64)));
+    }
+
+    #[test]
+    fn test_set_rich_writes_both_formats() {
+        let memory = MemoryClipboard::new();
+        let manager = ClipboardManager::with_backend(memory.clone()).with_priority(ContentPriority::PreferHtml);
+
+        manager.set_rich(Some("加粗"), Some("<b>加粗</b>")).unwrap();
+        assert_eq!(manager.get_html().unwrap(), "<b>加粗</b>");
+        assert_eq!(manager.get_text().unwrap(), "加粗");
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Html);
+
+        // 只有纯文本时去掉之前的 HTML
+        manager.set_rich(Some("纯文本"), None).unwrap();
+        assert!(manager.get_html().is_err());
+        assert_eq!(manager.get_text().unwrap(), "纯文本");
+
+        manager.set_rich(None, Some("<p>段落</p>")).unwrap();
+        assert_eq!(manager.get_text().unwrap(), "<p>段落</p>");
+
+        manager.set_rich(None, None).unwrap();
+        assert_eq!(manager.get_content_type(), ClipboardContentType::Empty);
+    }
+
+    #[cfg(feature = "image-support")]
+    fn sample_png(width: u32, height: u32) -> Vec<u8> {
+        let bytes = vec![0x80u8; (width * height * 4) as usize];
+        let image_data = ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: bytes.into(),
+        };
+        PngCodec.encode(&image_data).expect("PNG 编码失败")
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_png_round_trip() {
+        let png_data = sample_png(3, 2);
+        let image_data = image_to_rgba(&PngCodec, 3, 2, &png_data, DEFAULT_MAX_IMAGE_PIXELS).expect("PNG 解码失败");
+        assert_eq!((image_data.width, image_data.height), (3, 2));
+        assert_eq!(image_data.bytes.len(), 3 * 2 * 4);
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_image_to_rgba_rejects_wrong_dimensions() {
+        let png_data = sample_png(3, 2);
+        let err = image_to_rgba(&PngCodec, 300, 200, &png_data, DEFAULT_MAX_IMAGE_PIXELS).unwrap_err();
+        assert!(err.to_string().contains("尺寸不匹配"));
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_image_to_rgba_rejects_corrupt_data() {
+        assert!(image_to_rgba(&PngCodec, 3, 2, b"not a png", DEFAULT_MAX_IMAGE_PIXELS).is_err());
+    }
+
+    #[cfg(feature = "image-support")]
+    /// 测试用编解码器：数据的前两个字节为宽和高，像素全部为同一个值
+    struct MockCodec;
+
+    #[cfg(feature = "image-support")]
+    impl ImageCodec for MockCodec {
+        fn encode(&self, image: &ImageData) -> Result<Vec<u8>> {
+            Ok(vec![image.width as u8, image.height as u8, image.bytes[0]])
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<ImageData<'static>> {
+            let (width, height) = (data[0] as usize, data[1] as usize);
+            Ok(ImageData { width, height, bytes: vec![data[2]; width * height * 4].into() })
+        }
+
+        fn dimensions(&self, data: &[u8]) -> Result<(u32, u32)> {
+            match data {
+                [width, height, _] => Ok((*width as u32, *height as u32)),
+                _ => Err(anyhow::anyhow!("无法识别的图片格式")),
+            }
+        }
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_custom_codec() {
+        let image = ImageData { width: 3, height: 2, bytes: vec![7; 24].into() };
+        let data = MockCodec.encode(&image).unwrap();
+        let decoded = image_to_rgba(&MockCodec, 3, 2, &data, DEFAULT_MAX_IMAGE_PIXELS).unwrap();
+        assert_eq!(decoded.bytes.as_ref(), image.bytes.as_ref());
+        
+        // 尺寸检查和像素上限同样适用于自定义编解码器
+        assert!(image_to_rgba(&MockCodec, 3, 3, &data, DEFAULT_MAX_IMAGE_PIXELS).is_err());
+        assert!(image_to_rgba(&MockCodec, 3, 2, &data, 5).is_err());
+        assert!(image_to_rgba(&MockCodec, 3, 2, &sample_png(3, 2), DEFAULT_MAX_IMAGE_PIXELS).is_err());
+    }
+
+    #[test]
+    fn test_write_retries_transient_errors() {
+        let mut calls = 0;
+        let result = with_retry(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 { Err(arboard::Error::ClipboardOccupied) } else { Ok(calls) }
+        });
+        assert_eq!(result.unwrap(), 3);
+        
+        // 次数用尽后返回最后一次的错误
+        let mut calls = 0;
+        let result: std::result::Result<(), _> = with_retry(3, Duration::ZERO, || {
+            calls += 1;
+            Err(arboard::Error::ClipboardOccupied)
+        });
+        assert!(matches!(result, Err(arboard::Error::ClipboardOccupied)));
+        assert_eq!(calls, 3);
+        
+        // 不会因重试而恢复的错误立即返回
+        let mut calls = 0;
+        let result: std::result::Result<(), _> = with_retry(3, Duration::ZERO, || {
+            calls += 1;
+            Err(arboard::Error::ConversionFailure)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_recovery_bounds_reinitialization() {
+        let mut recovery = Recovery::default();
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::Wait);
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::Reinit(1));
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::Wait);
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::Reinit(2));
+
+        // 达到上限后只提示一次，不再重新初始化
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::Wait);
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::GiveUp);
+        assert_eq!(recovery.record_failure(2, 2), RecoveryAction::Wait);
+
+        // 恢复后重新计数
+        assert!(recovery.record_success());
+        assert!(!recovery.record_success());
+        assert_eq!(recovery.record_failure(1, 2), RecoveryAction::Reinit(1));
+
+        // 上限为 0 时不重新初始化
+        let mut recovery = Recovery::default();
+        assert_eq!(recovery.record_failure(1, 0), RecoveryAction::GiveUp);
+        assert!(indicates_stale_handle(&arboard::Error::ClipboardOccupied));
+        assert!(!indicates_stale_handle(&arboard::Error::ContentNotAvailable));
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_image_dimensions() {
+        assert_eq!(image_dimensions(&sample_png(3, 2)).unwrap(), (3, 2));
+        assert!(image_dimensions(b"not a png").is_err());
+    }
+
+    #[cfg(feature = "image-support")]
+    fn sample_rgba(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| image::Rgba([x as u8 * 40, y as u8 * 40, 0x80, 0xff]))
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_decode_jpeg() {
+        let rgb = image::DynamicImage::ImageRgba8(sample_rgba(4, 3)).to_rgb8();
+        let mut jpeg = Vec::new();
+        rgb.write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg).unwrap();
+        
+        let image_data = decode_image(&jpeg, DEFAULT_MAX_IMAGE_PIXELS).unwrap();
+        assert_eq!((image_data.width, image_data.height), (4, 3));
+        assert_eq!(image_data.bytes.len(), 4 * 3 * 4);
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_decode_webp() {
+        use image::ImageEncoder;
+        let rgba = sample_rgba(4, 3);
+        let mut webp = Vec::new();
+        image::codecs::webp::WebPEncoder::new_lossless(&mut webp)
+            .write_image(&rgba, 4, 3, image::ColorType::Rgba8)
+            .unwrap();
+        
+        let image_data = decode_image(&webp, DEFAULT_MAX_IMAGE_PIXELS).unwrap();
+        assert_eq!((image_data.width, image_data.height), (4, 3));
+        // 无损编码，像素应完全一致
+        assert_eq!(image_data.bytes.as_ref(), rgba.as_raw().as_slice());
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_image_to_rgba_rejects_huge_dimensions() {
+        // 声明的尺寸远超上限，无论数据内容如何都在分配前拒绝
+        let png_data = sample_png(3, 2);
+        let err = image_to_rgba(&PngCodec, u32::MAX, u32::MAX, &png_data, DEFAULT_MAX_IMAGE_PIXELS)
+            .unwrap_err();
+        assert!(err.to_string().contains("图片过大"));
+        
+        let png_data = sample_png(100, 100);
+        assert!(image_to_rgba(&PngCodec, 100, 100, &png_data, 9_999).is_err());
+        assert!(image_to_rgba(&PngCodec, 100, 100, &png_data, 10_000).is_ok());
+    }
+
+    #[test]
+    fn test_lock_recover_from_poisoned_mutex() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = mutex.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("持有锁时 panic");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+        
+        *lock_recover(&mutex) += 1;
+        assert_eq!(*lock_recover(&mutex), 2);
+        assert!(!mutex.is_poisoned());
+    }
+
+    #[test]
+    fn test_classify_text_and_image_both_present() {
+        assert_eq!(
+            classify(true, false, true, ContentPriority::PreferText),
+            ClipboardContentType::Text
+        );
+        assert_eq!(
+            classify(true, false, true, ContentPriority::PreferImage),
+            ClipboardContentType::Image
+        );
+        assert_eq!(
+            classify(true, true, true, ContentPriority::PreferHtml),
+            ClipboardContentType::Html
+        );
+        // 没有 HTML 时与优先文本相同
+        assert_eq!(
+            classify(true, false, true, ContentPriority::PreferHtml),
+            ClipboardContentType::Text
+        );
+    }
+
+    #[test]
+    fn test_classify_single_or_no_content() {
+        for priority in [ContentPriority::PreferText, ContentPriority::PreferImage, ContentPriority::PreferHtml] {
+            assert_eq!(classify(true, false, false, priority), ClipboardContentType::Text);
+            assert_eq!(classify(false, false, true, priority), ClipboardContentType::Image);
+            assert_eq!(classify(false, true, false, priority), ClipboardContentType::Html);
+            assert_eq!(classify(false, false, false, priority), ClipboardContentType::Empty);
+        }
+    }
+
+    #[test]
+    fn test_parse_content_priority() {
+        assert_eq!("text".parse::<ContentPriority>(), Ok(ContentPriority::PreferText));
+        assert_eq!("image".parse::<ContentPriority>(), Ok(ContentPriority::PreferImage));
+        assert_eq!("html".parse::<ContentPriority>(), Ok(ContentPriority::PreferHtml));
+        assert!("rtf".parse::<ContentPriority>().is_err());
     }
 }
\ No newline at end of file
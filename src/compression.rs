@@ -0,0 +1,226 @@
+use anyhow::Result;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use crate::network_alternative::ClipboardContent;
+
+/// 小于该大小的消息不压缩，压缩收益抵不上开销
+pub const COMPRESS_MIN_BYTES: usize = 1024;
+/// 解压后消息的最大大小，与分块重组的上限一致
+const DECOMPRESS_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// 消息的编码方式，连接建立时双方交换各自能解码的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// 不压缩，所有版本都支持
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    /// 本机支持的编码方式，按优先级排列
+    pub const ALL: [Encoding; 3] = [Encoding::Zstd, Encoding::Gzip, Encoding::None];
+
+    /// 在能力帧中的编号
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Encoding::None => 0,
+            Encoding::Gzip => 1,
+            Encoding::Zstd => 2,
+        }
+    }
+
+    /// 从能力帧中的编号还原，未知编号（对方版本更新）返回 `None`
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Encoding::None),
+            1 => Some(Encoding::Gzip),
+            2 => Some(Encoding::Zstd),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Encoding::None),
+            "gzip" => Ok(Encoding::Gzip),
+            "zstd" => Ok(Encoding::Zstd),
+            other => Err(anyhow::anyhow!("未知的压缩方式: {} (可选: zstd, gzip, none)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::None => "none",
+            Encoding::Gzip => "gzip",
+            Encoding::Zstd => "zstd",
+        })
+    }
+}
+
+/// 是否压缩要发送的消息，压缩时使用与对方协商出的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionPolicy {
+    /// 按内容类型决定：文本类内容和图片压缩，清空不压缩
+    ///
+    /// PNG 等图片数据本身已压缩，但在 JSON 消息中以数字数组表示，体积约为原图的 3.5 倍，
+    /// 压缩后才能接近原图大小，因此消息仍为 JSON 时图片也压缩
+    #[default]
+    Auto,
+    /// 不论内容类型都压缩
+    Always,
+    /// 从不压缩
+    Never,
+}
+
+impl CompressionPolicy {
+    /// 该内容是否值得压缩；小于 `COMPRESS_MIN_BYTES` 或压缩后没有变小时 `compress` 仍会原样发送
+    pub fn applies_to(self, content: &ClipboardContent) -> bool {
+        match self {
+            CompressionPolicy::Always => true,
+            CompressionPolicy::Never => false,
+            CompressionPolicy::Auto => match content {
+                ClipboardContent::Text(_)
+                | ClipboardContent::Color(_)
+                | ClipboardContent::Html { .. }
+                | ClipboardContent::Image { .. } => true,
+                ClipboardContent::Clear => false,
+            },
+        }
+    }
+}
+
+impl FromStr for CompressionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(CompressionPolicy::Auto),
+            "always" => Ok(CompressionPolicy::Always),
+            "never" => Ok(CompressionPolicy::Never),
+            other => Err(anyhow::anyhow!("未知的压缩策略: {} (可选: auto, always, never)", other)),
+        }
+    }
+}
+
+impl fmt::Display for CompressionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CompressionPolicy::Auto => "auto",
+            CompressionPolicy::Always => "always",
+            CompressionPolicy::Never => "never",
+        })
+    }
+}
+
+/// 按本机的优先级选择对方能解码的第一种编码方式，都不支持时不压缩
+pub fn select(local: &[Encoding], peer: &[Encoding]) -> Encoding {
+    local
+        .iter()
+        .copied()
+        .find(|encoding| peer.contains(encoding))
+        .unwrap_or(Encoding::None)
+}
+
+/// 压缩序列化后的消息，消息太小或压缩后没有变小时原样返回
+pub fn compress(encoding: Encoding, data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < COMPRESS_MIN_BYTES {
+        return Ok(data.to_vec());
+    }
+    let compressed = match encoding {
+        Encoding::None => return Ok(data.to_vec()),
+        Encoding::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?
+        }
+        Encoding::Zstd => zstd::encode_all(data, 0)?,
+    };
+    Ok(if compressed.len() < data.len() { compressed } else { data.to_vec() })
+}
+
+/// 按开头的魔数识别并解压消息
+///
+/// 序列化后的消息是以 `{` 开头的 JSON，与 gzip、zstd 的魔数不会冲突，旧版本发送的消息原样返回
+pub fn decompress(payload: Vec<u8>) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    if payload.starts_with(&ZSTD_MAGIC) {
+        zstd::Decoder::new(payload.as_slice())?
+            .take(DECOMPRESS_MAX_BYTES + 1)
+            .read_to_end(&mut data)?;
+    } else if payload.starts_with(&GZIP_MAGIC) {
+        flate2::read::GzDecoder::new(payload.as_slice())
+            .take(DECOMPRESS_MAX_BYTES + 1)
+            .read_to_end(&mut data)?;
+    } else {
+        return Ok(payload);
+    }
+    if data.len() as u64 > DECOMPRESS_MAX_BYTES {
+        return Err(anyhow::anyhow!("解压后的消息过大，上限 {} bytes", DECOMPRESS_MAX_BYTES));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_each_encoding() {
+        let data = format!("{{\"text\":\"{}\"}}", "剪贴板".repeat(1000)).into_bytes();
+        for encoding in Encoding::ALL {
+            let compressed = compress(encoding, &data).unwrap();
+            if encoding != Encoding::None {
+                assert!(compressed.len() < data.len(), "{} 应当压缩", encoding);
+            }
+            assert_eq!(decompress(compressed).unwrap(), data);
+        }
+
+        // 小消息不压缩
+        assert_eq!(compress(Encoding::Zstd, b"{}").unwrap(), b"{}");
+    }
+
+    #[test]
+    fn test_select_prefers_local_order() {
+        assert_eq!(select(&Encoding::ALL, &[Encoding::Gzip, Encoding::None]), Encoding::Gzip);
+        assert_eq!(select(&Encoding::ALL, &[Encoding::None]), Encoding::None);
+        assert_eq!(select(&[Encoding::Gzip, Encoding::Zstd], &Encoding::ALL), Encoding::Gzip);
+        assert_eq!(select(&[Encoding::Zstd], &[]), Encoding::None);
+        assert_eq!("zstd".parse::<Encoding>().unwrap(), Encoding::Zstd);
+        assert!("brotli".parse::<Encoding>().is_err());
+    }
+
+    #[test]
+    fn test_policy_by_content_type() {
+        let text = ClipboardContent::Text("剪贴板".repeat(1000));
+        let html = ClipboardContent::Html { html: "<b>粗体</b>".to_string(), text: "粗体".to_string() };
+        let color = ClipboardContent::from_text("#ff8800".to_string());
+        let image = ClipboardContent::Image { width: 1, height: 1, data: vec![0; 4] };
+
+        let auto = CompressionPolicy::Auto;
+        assert!(auto.applies_to(&text));
+        assert!(auto.applies_to(&html));
+        assert!(matches!(color, ClipboardContent::Color(_)) && auto.applies_to(&color));
+        // 图片在 JSON 中以数字数组表示，同样压缩
+        assert!(auto.applies_to(&image));
+        assert!(!auto.applies_to(&ClipboardContent::Clear));
+
+        // 强制压缩或不压缩时忽略内容类型
+        assert!(CompressionPolicy::Always.applies_to(&ClipboardContent::Clear));
+        assert!(!CompressionPolicy::Never.applies_to(&image));
+        assert!(!CompressionPolicy::Never.applies_to(&text));
+        assert_eq!("never".parse::<CompressionPolicy>().unwrap(), CompressionPolicy::Never);
+        assert_eq!(CompressionPolicy::default().to_string(), "auto");
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "clipboard-sync-alt";
+const DEVICE_ID_FILE: &str = "device_id";
+
+/// 无法获取主机名时使用的设备名称
+pub const DEFAULT_DEVICE_NAME: &str = "我的设备";
+
+/// 提供预共享密钥的环境变量
+pub const SECRET_ENV: &str = "CLIPSYNC_SECRET";
+
+/// 应用配置目录: <系统配置目录>/clipboard-sync-alt
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR))
+}
+
+/// 应用缓存目录: <系统缓存目录>/clipboard-sync-alt
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(APP_DIR))
+}
+
+/// 未指定 `--name` 时的设备名称：本机的主机名，获取失败或为空时使用“我的设备”
+pub fn default_device_name() -> String {
+    device_name_or_default(gethostname::gethostname().to_str())
+}
+
+fn device_name_or_default(hostname: Option<&str>) -> String {
+    match hostname.map(str::trim) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => DEFAULT_DEVICE_NAME.to_string(),
+    }
+}
+
+/// 生成新的设备标识
+pub fn generate_device_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// 读取配置目录中保存的设备标识，不存在时生成并保存，保证每次启动使用同一个标识
+pub fn load_or_create_device_id() -> Result<String> {
+    let dir = config_dir().ok_or_else(|| anyhow::anyhow!("无法确定配置目录"))?;
+    load_or_create_device_id_in(&dir)
+}
+
+fn load_or_create_device_id_in(dir: &Path) -> Result<String> {
+    let path = dir.join(DEVICE_ID_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let id = generate_device_id();
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, &id)?;
+    Ok(id)
+}
+
+/// 确定预共享密钥，优先级: 命令行参数 > 密钥文件 > 环境变量
+pub fn resolve_secret(flag: Option<String>, file: Option<&Path>) -> Result<Option<String>> {
+    resolve_secret_from(flag, file, std::env::var(SECRET_ENV).ok())
+}
+
+fn resolve_secret_from(flag: Option<String>, file: Option<&Path>, env: Option<String>) -> Result<Option<String>> {
+    if let Some(secret) = flag {
+        return Ok(Some(secret));
+    }
+    if let Some(path) = file {
+        return read_secret_file(path).map(Some);
+    }
+    Ok(env.filter(|secret| !secret.is_empty()))
+}
+
+/// 读取密钥文件，去掉末尾的一个换行；Unix 上文件对所有用户可读时给出警告
+pub fn read_secret_file(path: &Path) -> Result<String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("无法读取密钥文件 {}: {}", path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o004 != 0 {
+            eprintln!("⚠️ 密钥文件 {} 对所有用户可读，建议执行 chmod 600", path.display());
+        }
+    }
+
+    let secret = content
+        .strip_suffix('\n')
+        .map(|rest| rest.strip_suffix('\r').unwrap_or(rest))
+        .unwrap_or(&content);
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("密钥文件 {} 为空", path.display()));
+    }
+    Ok(secret.to_string())
+}
+
+/// 日志中显示的短设备标识
+pub fn short_id(device_id: &str) -> &str {
+    match device_id.char_indices().nth(8) {
+        Some((end, _)) => &device_id[..end],
+        None => device_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_name_falls_back_without_hostname() {
+        assert_eq!(device_name_or_default(Some(" laptop\n")), "laptop");
+        assert_eq!(device_name_or_default(Some("  ")), DEFAULT_DEVICE_NAME);
+        assert_eq!(device_name_or_default(None), DEFAULT_DEVICE_NAME);
+    }
+
+    #[test]
+    fn test_device_id_is_stable() {
+        let dir = std::env::temp_dir()
+            .join(format!("clipboard-sync-alt-test-{}-device-id", std::process::id()));
+
+        let first = load_or_create_device_id_in(&dir).unwrap();
+        let second = load_or_create_device_id_in(&dir).unwrap();
+        assert_eq!(first, second);
+        assert!(uuid::Uuid::parse_str(&first).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_secret_precedence() {
+        let dir = std::env::temp_dir()
+            .join(format!("clipboard-sync-alt-test-{}-secret", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("secret");
+        std::fs::write(&file, "from-file\r\n").unwrap();
+
+        let env = || Some("from-env".to_string());
+        let flag = || Some("from-flag".to_string());
+        assert_eq!(resolve_secret_from(flag(), Some(&file), env()).unwrap().as_deref(), Some("from-flag"));
+        assert_eq!(resolve_secret_from(None, Some(&file), env()).unwrap().as_deref(), Some("from-file"));
+        assert_eq!(resolve_secret_from(None, None, env()).unwrap().as_deref(), Some("from-env"));
+        assert_eq!(resolve_secret_from(None, None, Some(String::new())).unwrap(), None);
+
+        // 只去掉一个换行
+        std::fs::write(&file, "key\n\n").unwrap();
+        assert_eq!(read_secret_file(&file).unwrap(), "key\n");
+        std::fs::write(&file, "\n").unwrap();
+        assert!(read_secret_file(&file).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_short_id() {
+        assert_eq!(short_id("0f8fad5b-d9cb-469f-a165-70867728950e"), "0f8fad5b");
+        assert_eq!(short_id("abc"), "abc");
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(feature = "image-support")]
+use anyhow::Result;
+
+use crate::clipboard::ClipboardManager;
+#[cfg(feature = "image-support")]
+use crate::clipboard::ClipboardContentType;
+use crate::notification::NotificationManager;
+
+/// 一项诊断检查的结果
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// 通过时为说明，失败时为错误原因
+    pub outcome: std::result::Result<String, String>,
+    /// 失败时的解决建议
+    pub hint: Option<&'static str>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, outcome: Ok(detail.into()), hint: None }
+    }
+
+    fn fail(name: &'static str, error: impl ToString, hint: &'static str) -> Self {
+        Self { name, outcome: Err(error.to_string()), hint: Some(hint) }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+/// 依次检查剪贴板读写、图片读写（编译了图片支持时）和系统通知，不进行任何网络通信
+///
+/// 检查会改写剪贴板，结束后尽量恢复原来的文本
+pub fn run_checks() -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    match ClipboardManager::new() {
+        Ok(clipboard) => {
+            results.push(CheckResult::pass("剪贴板初始化", "已连接系统剪贴板"));
+            let original = clipboard.get_text().ok();
+            results.push(check_text(&clipboard));
+            #[cfg(feature = "image-support")]
+            results.push(check_image(&clipboard));
+            if let Some(text) = original {
+                let _ = clipboard.set_text(&text);
+            }
+        }
+        Err(e) => results.push(CheckResult::fail("剪贴板初始化", e, clipboard_hint())),
+    }
+    results.push(check_notification());
+    results
+}
+
+/// 写入一段文本并读回比较
+fn check_text(clipboard: &ClipboardManager) -> CheckResult {
+    const NAME: &str = "文本读写";
+    let expected = format!("clipboard-sync-alt doctor {}", uuid::Uuid::new_v4());
+    if let Err(e) = clipboard.set_text(&expected) {
+        return CheckResult::fail(NAME, e, clipboard_hint());
+    }
+    match clipboard.get_text() {
+        Ok(text) if text == expected => CheckResult::pass(NAME, "写入的文本已原样读回"),
+        Ok(text) => CheckResult::fail(
+            NAME,
+            format!("读回的文本与写入的不一致 ({} 字节)", text.len()),
+            "可能有剪贴板管理器改写了内容，暂时关闭剪贴板管理器后重试",
+        ),
+        Err(e) => CheckResult::fail(NAME, e, clipboard_hint()),
+    }
+}
+
+#[cfg(feature = "image-support")]
+/// 写入一张 2x2 的图片并读回比较尺寸
+fn check_image(clipboard: &ClipboardManager) -> CheckResult {
+    const NAME: &str = "图片读写";
+    if let Err(e) = tiny_png().and_then(|png| Ok(clipboard.set_image(2, 2, &png)?)) {
+        return CheckResult::fail(NAME, e, clipboard_hint());
+    }
+    if clipboard.get_content_type() != ClipboardContentType::Image {
+        return CheckResult::fail(NAME, "写入图片后剪贴板中没有图片", IMAGE_HINT);
+    }
+    match clipboard.get_image() {
+        Ok(Some((2, 2, _))) => CheckResult::pass(NAME, "2x2 图片已原样读回"),
+        Ok(Some((width, height, _))) => CheckResult::fail(NAME, format!("读回的图片尺寸为 {}x{}", width, height), IMAGE_HINT),
+        Ok(None) => CheckResult::fail(NAME, "写入图片后剪贴板中没有图片", IMAGE_HINT),
+        Err(e) => CheckResult::fail(NAME, e, IMAGE_HINT),
+    }
+}
+
+#[cfg(feature = "image-support")]
+const IMAGE_HINT: &str = "当前剪贴板后端可能不支持图片，文本同步不受影响";
+
+#[cfg(feature = "image-support")]
+fn tiny_png() -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    image::RgbaImage::from_pixel(2, 2, image::Rgba([0x20, 0x80, 0xe0, 0xff]))
+        .write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Png)?;
+    Ok(data)
+}
+
+/// 发送一条测试通知，检查系统通知服务是否可用
+fn check_notification() -> CheckResult {
+    const NAME: &str = "系统通知";
+    let notifier = NotificationManager::new();
+    let _ = notifier.send("剪贴板同步", "诊断测试通知");
+    if notifier.is_backend_available() {
+        CheckResult::pass(NAME, "测试通知已发送，请确认桌面上出现了通知")
+    } else {
+        CheckResult::fail(NAME, "系统通知发送失败", notification_hint())
+    }
+}
+
+/// 无法访问剪贴板时的建议
+fn clipboard_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "在“系统设置 > 隐私与安全性 > 辅助功能”中为运行本程序的终端授予权限后重试"
+    } else if cfg!(windows) {
+        "剪贴板可能被其他程序占用，关闭剪贴板增强工具或远程桌面的剪贴板共享后重试"
+    } else {
+        let has_x11 = std::env::var_os("DISPLAY").is_some();
+        let has_wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        linux_clipboard_hint(has_x11, has_wayland)
+    }
+}
+
+/// Linux 上按图形会话类型给出建议，本程序通过 X11（Wayland 下为 XWayland）访问剪贴板
+fn linux_clipboard_hint(has_x11: bool, has_wayland: bool) -> &'static str {
+    match (has_x11, has_wayland) {
+        (false, false) => "没有检测到图形会话（DISPLAY 和 WAYLAND_DISPLAY 均未设置），请在桌面会话中运行；无桌面的服务器请使用 --no-clipboard 作为中继",
+        (false, true) => "当前为 Wayland 会话但 DISPLAY 未设置，本程序通过 XWayland 访问剪贴板，请启用 XWayland",
+        _ => "无法连接 X11 剪贴板，检查 DISPLAY 是否指向当前会话，通过 SSH 运行时需要转发 X11",
+    }
+}
+
+/// 系统通知不可用时的建议
+fn notification_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "在“系统设置 > 通知”中允许终端发送通知"
+    } else if cfg!(windows) {
+        "在“设置 > 系统 > 通知”中开启通知，并关闭专注助手"
+    } else {
+        "没有可用的通知服务，安装并启动 dunst、mako 等通知守护进程；不需要通知时可使用 --no-notify"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linux_clipboard_hint() {
+        assert!(linux_clipboard_hint(false, false).contains("--no-clipboard"));
+        assert!(linux_clipboard_hint(false, true).contains("XWayland"));
+        assert!(linux_clipboard_hint(true, true).contains("X11"));
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_tiny_png() {
+        let png = tiny_png().unwrap();
+        assert_eq!(crate::clipboard::image_dimensions(&png).unwrap(), (2, 2));
+    }
+}
@@ -0,0 +1,79 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use thiserror::Error;
+
+/// `NetworkManager` 和 `ClipboardManager` 公开方法返回的错误
+///
+/// 调用方可以按类型区分处理，`Display` 仍是面向用户的中文说明
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SyncError {
+    /// 在连接超时时间内未能建立 TCP 连接
+    #[error("连接超时 ({} 秒)", .timeout.as_secs_f32())]
+    ConnectTimeout { addr: SocketAddr, timeout: Duration },
+    /// 对方拒绝连接或网络不可达
+    #[error("连接失败: {source}")]
+    Connect {
+        addr: SocketAddr,
+        #[source]
+        source: std::io::Error,
+    },
+    /// 序列化后的消息超过允许的最大大小
+    #[error("消息过大: {size} bytes，上限 {max} bytes")]
+    MessageTooLarge { size: usize, max: usize },
+    /// 预共享密钥认证失败（密钥不一致、对方未设置密钥或认证超时）
+    #[error("{0}")]
+    AuthFailed(String),
+    /// 指定的设备当前没有连接
+    #[error("设备 {0} 未连接")]
+    PeerNotConnected(String),
+    /// 消息序列化或反序列化失败
+    #[error("消息序列化失败: {0}")]
+    Serialization(#[from] serde_json::Error),
+    /// 无法访问系统剪贴板（如没有桌面环境）
+    #[error("无法初始化剪贴板: {0}")]
+    ClipboardUnavailable(String),
+    /// 读写剪贴板失败
+    #[error("{0}")]
+    Clipboard(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// 其他错误
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl SyncError {
+    /// 消息本身无法编码（序列化失败或过大），而不是发送到设备时出错；重试或换一个设备都不会成功
+    pub fn is_encode_error(&self) -> bool {
+        matches!(self, SyncError::Serialization(_) | SyncError::MessageTooLarge { .. })
+    }
+}
+
+/// 库公开方法的返回类型
+pub type SyncResult<T> = std::result::Result<T, SyncError>;
+
+/// 内部以 `anyhow::Error` 传递的 `SyncError` 在公开接口处还原为原来的类型
+impl From<anyhow::Error> for SyncError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<SyncError>() {
+            Ok(error) => error,
+            Err(error) => SyncError::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anyhow_round_trip_keeps_variant() {
+        let error: anyhow::Error = SyncError::MessageTooLarge { size: 300, max: 200 }.into();
+        assert!(matches!(SyncError::from(error), SyncError::MessageTooLarge { size: 300, max: 200 }));
+
+        let error = SyncError::from(anyhow::anyhow!("其他错误"));
+        assert!(matches!(error, SyncError::Other(_)));
+        assert_eq!(error.to_string(), "其他错误");
+    }
+}
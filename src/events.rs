@@ -0,0 +1,269 @@
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::network_alternative::ClipboardContent;
+
+/// 事件中内容预览的最大长度
+const PREVIEW_LENGTH: usize = 50;
+
+/// 是否以 JSON Lines 格式输出事件，开启后不再输出带 emoji 的日志
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// 切换 JSON Lines 事件输出，对整个进程生效
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// 是否处于 JSON Lines 事件输出模式
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// 日志的详细程度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// 只输出面向用户的日志
+    #[default]
+    Info,
+    /// 另外输出调试日志，如各连接的心跳延迟
+    Debug,
+    /// 另外输出帧层的跟踪日志：每一帧的长度、开头的字节和解码结果
+    Trace,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            other => Err(format!("未知的日志级别: {} (可选: info, debug, trace)", other)),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        })
+    }
+}
+
+/// 当前的日志级别
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// 设置日志级别，对整个进程生效
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// 当前的日志级别
+pub fn log_level() -> LogLevel {
+    match LOG_LEVEL.load(Ordering::Relaxed) {
+        0 => LogLevel::Info,
+        1 => LogLevel::Debug,
+        _ => LogLevel::Trace,
+    }
+}
+
+/// 切换调试日志（如各连接的心跳延迟），对整个进程生效，等同于设置为 `Debug` 或 `Info` 级别
+pub fn set_verbose(enabled: bool) {
+    set_log_level(if enabled { LogLevel::Debug } else { LogLevel::Info });
+}
+
+/// 是否输出调试日志
+pub fn verbose() -> bool {
+    log_level() >= LogLevel::Debug
+}
+
+/// 是否输出帧层的跟踪日志
+pub fn trace() -> bool {
+    log_level() >= LogLevel::Trace
+}
+
+/// 帧跟踪日志中是否显示消息内容的字节
+static TRACE_PAYLOAD: AtomicBool = AtomicBool::new(false);
+
+/// 切换帧跟踪日志中的内容显示，对整个进程生效；内容可能包含剪贴板中的敏感信息
+pub fn set_trace_payload(enabled: bool) {
+    TRACE_PAYLOAD.store(enabled, Ordering::Relaxed);
+}
+
+/// 帧跟踪日志中是否显示消息内容的字节，默认只显示长度和帧类型
+pub fn trace_payload() -> bool {
+    TRACE_PAYLOAD.load(Ordering::Relaxed)
+}
+
+/// 日志中是否隐藏剪贴板的完整文本
+static REDACT_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// 切换日志中的文本隐藏，对整个进程生效
+pub fn set_redact_logs(enabled: bool) {
+    REDACT_LOGS.store(enabled, Ordering::Relaxed);
+}
+
+/// 日志中是否只输出文本的预览和长度
+pub fn redact_logs() -> bool {
+    REDACT_LOGS.load(Ordering::Relaxed)
+}
+
+/// 日志中显示的剪贴板文本：隐藏完整文本时只显示开头的预览和字符数
+pub fn loggable_text(text: &str) -> Cow<'_, str> {
+    if !redact_logs() {
+        return Cow::Borrowed(text);
+    }
+    let chars = text.chars().count();
+    Cow::Owned(match text.grapheme_indices(true).nth(PREVIEW_LENGTH) {
+        Some((end, _)) => format!("{}... ({} 字符)", &text[..end], chars),
+        None => format!("{} ({} 字符)", text, chars),
+    })
+}
+
+/// 供其他程序订阅的同步事件，序列化后 `event` 字段为事件类型
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// 服务器开始监听
+    Listening { port: u16 },
+    /// 与设备建立连接（`peer` 为连接标识）
+    PeerConnected { peer: &'a str },
+    /// 与设备的连接断开
+    PeerDisconnected { peer: &'a str },
+    /// 本机内容已广播
+    ContentSent {
+        content_type: &'static str,
+        preview: String,
+        delivered: usize,
+        failed: usize,
+    },
+    /// 收到其他设备的内容（`peer` 为发送方的设备标识）
+    ContentReceived {
+        peer: &'a str,
+        sender_name: &'a str,
+        content_type: &'static str,
+        preview: String,
+    },
+    /// 运行中出现的错误
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        peer: Option<&'a str>,
+        message: String,
+    },
+}
+
+impl<'a> Event<'a> {
+    /// 发送内容事件
+    pub fn content_sent(content: &ClipboardContent, delivered: usize, failed: usize) -> Self {
+        Event::ContentSent {
+            content_type: content.kind(),
+            preview: content.preview(PREVIEW_LENGTH),
+            delivered,
+            failed,
+        }
+    }
+
+    /// 收到内容事件
+    pub fn content_received(content: &ClipboardContent, peer: &'a str, sender_name: &'a str) -> Self {
+        Event::ContentReceived {
+            peer,
+            sender_name,
+            content_type: content.kind(),
+            preview: content.preview(PREVIEW_LENGTH),
+        }
+    }
+
+    /// 错误事件
+    pub fn error(peer: Option<&'a str>, message: impl ToString) -> Self {
+        Event::Error { peer, message: message.to_string() }
+    }
+}
+
+/// 带时间戳的一行输出
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Unix 毫秒时间戳
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// 将事件序列化为一行 JSON（不含换行）
+pub fn to_json_line(event: &Event) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    serde_json::to_string(&Line { timestamp, event }).expect("事件总能序列化为 JSON")
+}
+
+/// 在 JSON Lines 模式下向标准输出写入一个事件，否则什么也不做
+pub fn emit(event: Event) {
+    if !json_output() {
+        return;
+    }
+    // 每行立即刷新，管道另一端可以实时读取
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", to_json_line(&event));
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_level() {
+        assert_eq!("TRACE".parse::<LogLevel>(), Ok(LogLevel::Trace));
+        assert_eq!(LogLevel::Debug.to_string(), "debug");
+        assert!(LogLevel::Trace > LogLevel::Debug);
+        assert!("verbose".parse::<LogLevel>().is_err());
+    }
+
+    #[test]
+    fn test_event_json_line() {
+        let content = ClipboardContent::Text("你好\nworld".to_string());
+        let line = to_json_line(&Event::content_received(&content, "abc", "笔记本"));
+        assert!(!line.contains('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["event"], "content_received");
+        assert_eq!(value["peer"], "abc");
+        assert_eq!(value["sender_name"], "笔记本");
+        assert_eq!(value["content_type"], "text");
+        assert_eq!(value["preview"], "你好\nworld");
+        assert!(value["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_error_event_omits_missing_peer() {
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json_line(&Event::error(None, "连接失败"))).unwrap();
+        assert_eq!(value["event"], "error");
+        assert_eq!(value["message"], "连接失败");
+        assert!(value.get("peer").is_none());
+    }
+
+    #[test]
+    fn test_loggable_text_redacts_long_text() {
+        set_redact_logs(true);
+        let secret = format!("{}密码", "a".repeat(PREVIEW_LENGTH));
+        let logged = loggable_text(&secret).into_owned();
+        let short = loggable_text("你好").into_owned();
+        set_redact_logs(false);
+
+        assert_eq!(logged, format!("{}... ({} 字符)", "a".repeat(PREVIEW_LENGTH), PREVIEW_LENGTH + 2));
+        assert_eq!(short, "你好 (2 字符)");
+        assert_eq!(loggable_text(&secret), secret);
+    }
+}
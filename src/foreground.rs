@@ -0,0 +1,160 @@
+use std::fmt;
+
+/// 当前的前台应用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForegroundApp {
+    /// 可用于匹配的标识：macOS 上为 Bundle ID 和应用名称，Windows 上为可执行文件名
+    pub identifiers: Vec<String>,
+}
+
+impl fmt::Display for ForegroundApp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.identifiers.first().map(String::as_str).unwrap_or("未知应用"))
+    }
+}
+
+/// 是否能在当前平台查询前台应用
+pub fn is_supported() -> bool {
+    cfg!(all(feature = "app-filter", any(windows, target_os = "macos")))
+}
+
+/// 查询当前的前台应用，不支持的平台或查询失败时返回 `None`
+pub fn foreground_app() -> Option<ForegroundApp> {
+    platform::foreground_app()
+}
+
+#[cfg(all(feature = "app-filter", windows))]
+mod platform {
+    use super::ForegroundApp;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    pub fn foreground_app() -> Option<ForegroundApp> {
+        // SAFETY: 只调用查询类的 Win32 函数，缓冲区长度与传入的大小一致，进程句柄用完即关闭
+        let path = unsafe {
+            let window = GetForegroundWindow();
+            if window.is_null() {
+                return None;
+            }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(window, &mut pid);
+            if pid == 0 {
+                return None;
+            }
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let mut buffer = [0u16; 1024];
+            let mut len = buffer.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut len);
+            CloseHandle(process);
+            if ok == 0 {
+                return None;
+            }
+            String::from_utf16_lossy(&buffer[..len as usize])
+        };
+        let name = Path::new(&path).file_name()?.to_string_lossy().into_owned();
+        Some(ForegroundApp { identifiers: vec![name] })
+    }
+}
+
+#[cfg(all(feature = "app-filter", target_os = "macos"))]
+mod platform {
+    use super::ForegroundApp;
+    use objc2_app_kit::NSWorkspace;
+
+    pub fn foreground_app() -> Option<ForegroundApp> {
+        // SAFETY: 只读取共享工作区的前台应用及其标识，返回的对象由 `Retained` 管理引用计数
+        let names = unsafe {
+            let app = NSWorkspace::sharedWorkspace().frontmostApplication()?;
+            [app.bundleIdentifier(), app.localizedName()]
+        };
+        let identifiers: Vec<String> = names
+            .into_iter()
+            .flatten()
+            .map(|id| id.to_string())
+            .collect();
+        (!identifiers.is_empty()).then_some(ForegroundApp { identifiers })
+    }
+}
+
+#[cfg(not(all(feature = "app-filter", any(windows, target_os = "macos"))))]
+mod platform {
+    use super::ForegroundApp;
+
+    pub fn foreground_app() -> Option<ForegroundApp> {
+        None
+    }
+}
+
+/// 只在指定的前台应用中应用收到的内容，列表为空时不限制
+#[derive(Debug, Clone, Default)]
+pub struct AppFilter {
+    apps: Vec<String>,
+}
+
+impl AppFilter {
+    /// 应用标识不区分大小写，Windows 可执行文件名可以省略 `.exe`
+    pub fn new(apps: Vec<String>) -> Self {
+        Self { apps }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+    }
+
+    /// 前台应用是否在列表中
+    pub fn matches(&self, app: &ForegroundApp) -> bool {
+        app.identifiers.iter().any(|id| {
+            let id = id.to_lowercase();
+            let stem = id.strip_suffix(".exe").unwrap_or(&id);
+            self.apps.iter().any(|allowed| {
+                let allowed = allowed.to_lowercase();
+                allowed == id || allowed.strip_suffix(".exe").unwrap_or(&allowed) == stem
+            })
+        })
+    }
+
+    /// 检查当前前台应用，不在列表中时返回该应用；无法查询前台应用时不限制
+    pub fn blocked_by(&self) -> Option<ForegroundApp> {
+        if self.is_empty() {
+            return None;
+        }
+        foreground_app().filter(|app| !self.matches(app))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(ids: &[&str]) -> ForegroundApp {
+        ForegroundApp { identifiers: ids.iter().map(|id| id.to_string()).collect() }
+    }
+
+    #[test]
+    fn test_app_filter_matches() {
+        let filter = AppFilter::new(vec!["Code".to_string(), "com.apple.Terminal".to_string()]);
+        assert!(filter.matches(&app(&["Code.exe"])));
+        assert!(filter.matches(&app(&["com.apple.terminal", "终端"])));
+        assert!(!filter.matches(&app(&["notepad.exe"])));
+        assert!(!filter.matches(&app(&["com.apple.Safari", "Safari"])));
+
+        let filter = AppFilter::new(vec!["notepad.exe".to_string()]);
+        assert!(filter.matches(&app(&["NOTEPAD.EXE"])));
+    }
+
+    #[test]
+    fn test_unsupported_platform_never_blocks() {
+        let filter = AppFilter::new(vec!["Code".to_string()]);
+        if !is_supported() {
+            assert!(filter.blocked_by().is_none());
+        }
+        assert!(AppFilter::default().blocked_by().is_none());
+    }
+}
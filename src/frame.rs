@@ -0,0 +1,644 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::SyncError;
+use crate::events;
+
+// 帧格式: 4字节长度(大端) + 1字节帧类型 + 帧内容
+// 完整消息帧内容: 序列化后的消息
+// 分块帧内容: 8字节消息ID + 4字节分块序号 + 4字节分块总数 + 分块数据
+// 握手帧内容: 连接建立后、传输消息前交换的认证数据
+// 版本帧内容: 4字节最低支持版本 + 4字节最高支持版本，连接建立后双方首先交换
+// 能力帧内容: 每字节一个本机能解码的编码方式编号，协商出 v3 及以上版本后紧接着交换
+// 心跳帧内容: 8字节标识，收到 ping 后以相同标识回复 pong，协商出 v5 及以上版本后才会发送
+// 请求当前内容帧: 没有内容，对方以一条普通消息回复它当前的剪贴板内容，协商出 v7 及以上版本后才会发送
+// 身份帧内容: 2字节监听端口（0 表示不监听）+ 设备标识，协商出 v8 及以上版本后在认证之后交换
+pub const PROTOCOL_VERSION: u32 = 8; // 线路协议版本，帧格式或消息格式不兼容地变化时递增
+pub const MIN_PROTOCOL_VERSION: u32 = 2; // 仍兼容的最低协议版本（版本 1 没有版本帧）
+pub const MESSAGE_MAX_SIZE: usize = 10 * 1024 * 1024; // 10MB最大帧大小
+const CHUNK_HEADER_LEN: usize = 8 + 4 + 4;
+const CHUNK_SIZE: usize = 4 * 1024 * 1024; // 超过单帧上限的消息按 4MB 分块
+const REASSEMBLY_MAX_SIZE: usize = 256 * 1024 * 1024; // 重组后消息的最大大小
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30); // 未完成的重组超时丢弃
+const REASSEMBLY_MAX_PENDING: usize = 4; // 每个连接同时重组的消息数上限
+const REASSEMBLY_MAX_PENDING_BYTES: usize = REASSEMBLY_MAX_SIZE; // 每个连接所有未完成重组的分块总大小上限
+const FRAME_READ_TIMEOUT: Duration = Duration::from_secs(30); // 收到长度后，帧的其余部分必须在此时间内到达
+const READ_BUFFER_INITIAL: usize = 64 * 1024; // 帧缓冲的初始容量，随实际收到的数据增长
+const TRACE_PREVIEW_LEN: usize = 16; // 帧跟踪日志中最多显示的内容字节数
+
+const FRAME_MESSAGE: u8 = 0;
+const FRAME_CHUNK: u8 = 1;
+const FRAME_HANDSHAKE: u8 = 2;
+const FRAME_HELLO: u8 = 3;
+const FRAME_CAPABILITIES: u8 = 4;
+const FRAME_JOIN: u8 = 5;
+const FRAME_PING: u8 = 6;
+const FRAME_PONG: u8 = 7;
+const FRAME_REQUEST_CURRENT: u8 = 8;
+const FRAME_IDENTITY: u8 = 9;
+
+// 分块消息ID，重组缓冲按连接隔离，进程内递增即可保证唯一
+static NEXT_MESSAGE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 从数据流中读取的一帧
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    /// 完整的消息
+    Message(Vec<u8>),
+    /// 大消息的一个分块
+    Chunk {
+        message_id: u64,
+        index: u32,
+        total: u32,
+        data: Vec<u8>,
+    },
+    /// 握手数据
+    Handshake(Vec<u8>),
+    /// 对方支持的协议版本范围
+    Hello { min_version: u32, max_version: u32 },
+    /// 对方能解码的编码方式编号
+    Capabilities(Vec<u8>),
+    /// 通过中继连接时要加入的房间
+    Join(Vec<u8>),
+    /// 心跳请求，对方以相同标识回复 `Pong`
+    Ping(u64),
+    /// 心跳回复
+    Pong(u64),
+    /// 请求对方发送当前的剪贴板内容
+    RequestCurrent,
+    /// 对方的设备标识和监听端口（0 表示不监听）
+    Identity { listen_port: u16, device_id: Vec<u8> },
+}
+
+impl Frame {
+    /// 帧跟踪日志中的简要说明，不包含内容
+    fn summary(&self) -> String {
+        match self {
+            Frame::Message(data) => format!("消息 ({} 字节)", data.len()),
+            Frame::Chunk { message_id, index, total, data } => {
+                format!("消息 {} 的分块 {}/{} ({} 字节)", message_id, index + 1, total, data.len())
+            }
+            Frame::Handshake(data) => format!("握手 ({} 字节)", data.len()),
+            Frame::Hello { min_version, max_version } => format!("版本 v{}-v{}", min_version, max_version),
+            Frame::Capabilities(ids) => format!("能力 ({} 种编码)", ids.len()),
+            Frame::Join(room) => format!("加入房间 ({} 字节)", room.len()),
+            Frame::Ping(token) => format!("心跳请求 #{}", token),
+            Frame::Pong(token) => format!("心跳回复 #{}", token),
+            Frame::RequestCurrent => "请求当前剪贴板".to_string(),
+            Frame::Identity { listen_port, device_id } => format!("身份 ({} 字节标识，监听端口 {})", device_id.len(), listen_port),
+        }
+    }
+}
+
+/// 将序列化后的消息编码为待发送的帧，超过单帧上限时自动分块
+pub fn encode_frames(payload: &[u8]) -> Result<Vec<Vec<u8>>> {
+    if payload.len() < MESSAGE_MAX_SIZE {
+        return Ok(vec![encode_frame(FRAME_MESSAGE, &[], payload)]);
+    }
+
+    if payload.len() > REASSEMBLY_MAX_SIZE {
+        return Err(SyncError::MessageTooLarge { size: payload.len(), max: REASSEMBLY_MAX_SIZE }.into());
+    }
+
+    let message_id = next_message_id();
+    let total = payload.len().div_ceil(CHUNK_SIZE) as u32;
+    let frames = payload
+        .chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, data)| {
+            let mut header = Vec::with_capacity(CHUNK_HEADER_LEN);
+            header.extend_from_slice(&message_id.to_be_bytes());
+            header.extend_from_slice(&(index as u32).to_be_bytes());
+            header.extend_from_slice(&total.to_be_bytes());
+            encode_frame(FRAME_CHUNK, &header, data)
+        })
+        .collect();
+
+    Ok(frames)
+}
+
+/// 编码一个握手帧
+pub fn encode_handshake(data: &[u8]) -> Vec<u8> {
+    encode_frame(FRAME_HANDSHAKE, &[], data)
+}
+
+/// 编码一个声明协议版本范围的版本帧
+pub fn encode_hello(min_version: u32, max_version: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8);
+    data.extend_from_slice(&min_version.to_be_bytes());
+    data.extend_from_slice(&max_version.to_be_bytes());
+    encode_frame(FRAME_HELLO, &[], &data)
+}
+
+/// 编码一个声明本机能解码的编码方式的能力帧
+pub fn encode_capabilities(encodings: &[u8]) -> Vec<u8> {
+    encode_frame(FRAME_CAPABILITIES, &[], encodings)
+}
+
+/// 编码加入房间帧
+pub fn encode_join(room: &str) -> Vec<u8> {
+    encode_frame(FRAME_JOIN, &[], room.as_bytes())
+}
+
+/// 编码心跳请求帧
+pub fn encode_ping(token: u64) -> Vec<u8> {
+    encode_frame(FRAME_PING, &[], &token.to_be_bytes())
+}
+
+/// 编码心跳回复帧
+pub fn encode_pong(token: u64) -> Vec<u8> {
+    encode_frame(FRAME_PONG, &[], &token.to_be_bytes())
+}
+
+/// 编码请求当前剪贴板内容的帧
+pub fn encode_request_current() -> Vec<u8> {
+    encode_frame(FRAME_REQUEST_CURRENT, &[], &[])
+}
+
+/// 编码身份帧，`listen_port` 为 0 表示本机不监听连接
+pub fn encode_identity(device_id: &str, listen_port: u16) -> Vec<u8> {
+    encode_frame(FRAME_IDENTITY, &listen_port.to_be_bytes(), device_id.as_bytes())
+}
+
+fn encode_frame(kind: u8, header: &[u8], data: &[u8]) -> Vec<u8> {
+    let frame_len = (1 + header.len() + data.len()) as u32;
+    let mut frame = Vec::with_capacity(4 + frame_len as usize);
+    frame.extend_from_slice(&frame_len.to_be_bytes());
+    frame.push(kind);
+    frame.extend_from_slice(header);
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// 以十六进制显示字节，如 `00 00 01 2a`
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// 帧跟踪日志中的内容预览（`body` 以帧类型字节开头）：默认隐藏，`--unsafe-trace-payload` 时显示开头的字节
+fn trace_payload(body: &[u8]) -> String {
+    let content = body.get(1..).unwrap_or_default();
+    if content.is_empty() {
+        return String::new();
+    }
+    if !events::trace_payload() {
+        return "，内容已隐藏".to_string();
+    }
+    let preview = &content[..content.len().min(TRACE_PREVIEW_LEN)];
+    let more = if content.len() > preview.len() { " ..." } else { "" };
+    format!("，内容开头: {}{}", hex(preview), more)
+}
+
+/// 输出一个写入的帧的跟踪日志，只在 `--log-level trace` 时输出
+pub(crate) fn trace_written(frame: &[u8]) {
+    if frame.len() < 5 {
+        return;
+    }
+    trace!(
+        "🔬 → 写入帧: 长度 {}（{}），类型 {}，共 {} 字节{}",
+        frame.len() - 4,
+        hex(&frame[..4]),
+        frame[4],
+        frame.len(),
+        trace_payload(&frame[4..])
+    );
+}
+
+fn next_message_id() -> u64 {
+    NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 读取一帧，连接在帧之间正常关闭时返回 `None`
+///
+/// 等待下一帧时不限时间；一旦开始收到一帧，其余部分必须在超时时间内到达，
+/// 否则视为对方异常，避免连接一直卡在读取中
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Frame>> {
+    read_frame_within(reader, FRAME_READ_TIMEOUT).await
+}
+
+async fn read_frame_within<R: AsyncRead + Unpin>(reader: &mut R, timeout: Duration) -> Result<Option<Frame>> {
+    // 第一个字节决定是连接正常关闭还是开始了一个新帧
+    let mut len_buf = [0u8; 4];
+    if reader.read(&mut len_buf[..1]).await? == 0 {
+        return Ok(None); // 连接断开
+    }
+
+    match tokio::time::timeout(timeout, read_frame_body(reader, len_buf)).await {
+        Ok(result) => result.map(Some),
+        Err(_) => Err(anyhow::anyhow!("读取帧超时（{} 秒内未收到完整的帧）", timeout.as_secs_f32())),
+    }
+}
+
+/// 读取长度的其余字节和帧内容，`len_buf` 的第一个字节已经读取
+async fn read_frame_body<R: AsyncRead + Unpin>(reader: &mut R, mut len_buf: [u8; 4]) -> Result<Frame> {
+    reader
+        .read_exact(&mut len_buf[1..])
+        .await
+        .map_err(|e| truncated_or(e, "帧长度不完整"))?;
+
+    let frame_len = u32::from_be_bytes(len_buf) as usize;
+    trace!("🔬 ← 读取帧: 长度 {}（{}）", frame_len, hex(&len_buf));
+    if frame_len > MESSAGE_MAX_SIZE {
+        return Err(anyhow::anyhow!("消息过大: {} bytes", frame_len));
+    }
+    if frame_len == 0 {
+        return Err(anyhow::anyhow!("无效的空帧"));
+    }
+
+    // 按实际收到的数据增长缓冲，对方声明了很大的长度却不发送内容时不会预先占用内存
+    let mut buffer = Vec::with_capacity(frame_len.min(READ_BUFFER_INITIAL));
+    reader.take(frame_len as u64).read_to_end(&mut buffer).await?;
+    if buffer.len() < frame_len {
+        trace!("🔬 ← 帧不完整: 收到 {}/{} 字节", buffer.len(), frame_len);
+        return Err(anyhow::anyhow!("帧不完整: 声明 {} bytes，连接断开前只收到 {} bytes", frame_len, buffer.len()));
+    }
+    trace!("🔬 ← 已完整收到 {} 字节，类型 {}{}", frame_len, buffer[0], trace_payload(&buffer));
+
+    let result = decode_frame(buffer);
+    match &result {
+        Ok(frame) => trace!("🔬 ← 解码为 {}", frame.summary()),
+        Err(e) => trace!("🔬 ← 解码失败: {}", e),
+    }
+    result
+}
+
+/// 按帧类型解析完整的帧内容（以帧类型字节开头）
+fn decode_frame(mut buffer: Vec<u8>) -> Result<Frame> {
+    match buffer[0] {
+        FRAME_MESSAGE => {
+            buffer.remove(0);
+            Ok(Frame::Message(buffer))
+        }
+        FRAME_CHUNK => {
+            if buffer.len() < 1 + CHUNK_HEADER_LEN {
+                return Err(anyhow::anyhow!("分块帧头不完整"));
+            }
+            let message_id = u64::from_be_bytes(buffer[1..9].try_into().unwrap());
+            let index = u32::from_be_bytes(buffer[9..13].try_into().unwrap());
+            let total = u32::from_be_bytes(buffer[13..17].try_into().unwrap());
+            let data = buffer.split_off(1 + CHUNK_HEADER_LEN);
+            Ok(Frame::Chunk { message_id, index, total, data })
+        }
+        FRAME_HANDSHAKE => {
+            buffer.remove(0);
+            Ok(Frame::Handshake(buffer))
+        }
+        FRAME_HELLO => {
+            if buffer.len() != 1 + 8 {
+                return Err(anyhow::anyhow!("版本帧长度错误"));
+            }
+            let min_version = u32::from_be_bytes(buffer[1..5].try_into().unwrap());
+            let max_version = u32::from_be_bytes(buffer[5..9].try_into().unwrap());
+            Ok(Frame::Hello { min_version, max_version })
+        }
+        FRAME_CAPABILITIES => {
+            buffer.remove(0);
+            Ok(Frame::Capabilities(buffer))
+        }
+        FRAME_JOIN => {
+            buffer.remove(0);
+            Ok(Frame::Join(buffer))
+        }
+        FRAME_PING | FRAME_PONG => {
+            if buffer.len() != 1 + 8 {
+                return Err(anyhow::anyhow!("心跳帧长度错误"));
+            }
+            let token = u64::from_be_bytes(buffer[1..9].try_into().unwrap());
+            Ok(if buffer[0] == FRAME_PING { Frame::Ping(token) } else { Frame::Pong(token) })
+        }
+        FRAME_REQUEST_CURRENT => {
+            if buffer.len() != 1 {
+                return Err(anyhow::anyhow!("请求当前内容帧不应有内容"));
+            }
+            Ok(Frame::RequestCurrent)
+        }
+        FRAME_IDENTITY => {
+            if buffer.len() < 1 + 2 {
+                return Err(anyhow::anyhow!("身份帧长度错误"));
+            }
+            let listen_port = u16::from_be_bytes(buffer[1..3].try_into().unwrap());
+            Ok(Frame::Identity { listen_port, device_id: buffer.split_off(3) })
+        }
+        kind => Err(anyhow::anyhow!("未知的帧类型: {}", kind)),
+    }
+}
+
+/// 帧中途连接断开时给出可读的错误
+fn truncated_or(e: std::io::Error, message: &str) -> anyhow::Error {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        anyhow::anyhow!("{}", message)
+    } else {
+        e.into()
+    }
+}
+
+/// 正在重组的分块消息
+struct PartialMessage {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    size: usize,
+    started: Instant,
+}
+
+/// 分块重组器 - 每个连接一个，收齐所有分块后还原完整消息
+///
+/// 同时重组的消息数和未完成分块的总大小都有上限，避免对方开始大量分块消息却不发完而耗尽内存
+pub struct Reassembler {
+    pending: HashMap<u64, PartialMessage>,
+    /// 所有未完成重组的分块总大小
+    pending_bytes: usize,
+    max_pending: usize,
+    max_pending_bytes: usize,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+            pending_bytes: 0,
+            max_pending: REASSEMBLY_MAX_PENDING,
+            max_pending_bytes: REASSEMBLY_MAX_PENDING_BYTES,
+        }
+    }
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用较小的上限，便于测试
+    #[cfg(test)]
+    fn with_limits(max_pending: usize, max_pending_bytes: usize) -> Self {
+        Self { max_pending, max_pending_bytes, ..Self::default() }
+    }
+
+    /// 处理一帧，消息完整时返回消息内容
+    pub fn push(&mut self, frame: Frame, now: Instant) -> Result<Option<Vec<u8>>> {
+        self.expire(now);
+
+        let (message_id, index, total, data) = match frame {
+            Frame::Message(payload) => return Ok(Some(payload)),
+            Frame::Handshake(_) | Frame::Hello { .. } | Frame::Capabilities(_) | Frame::Join(_) | Frame::Identity { .. } => {
+                return Err(anyhow::anyhow!("握手完成后收到意外的握手帧"))
+            }
+            Frame::Ping(_) | Frame::Pong(_) => return Err(anyhow::anyhow!("心跳帧不属于任何消息")),
+            Frame::RequestCurrent => return Err(anyhow::anyhow!("请求当前内容帧不属于任何消息")),
+            Frame::Chunk { message_id, index, total, data } => (message_id, index, total, data),
+        };
+
+        if total == 0 || index >= total {
+            return Err(anyhow::anyhow!("无效的分块序号: {}/{}", index, total));
+        }
+        if total as usize > REASSEMBLY_MAX_SIZE.div_ceil(CHUNK_SIZE) {
+            return Err(anyhow::anyhow!("分块数量过多: {}", total));
+        }
+
+        if !self.pending.contains_key(&message_id) && self.pending.len() >= self.max_pending {
+            return Err(anyhow::anyhow!("同时重组的消息过多（上限 {}），丢弃消息 {}", self.max_pending, message_id));
+        }
+        let partial = self.pending.entry(message_id).or_insert_with(|| PartialMessage {
+            chunks: vec![None; total as usize],
+            received: 0,
+            size: 0,
+            started: now,
+        });
+
+        if partial.chunks.len() != total as usize {
+            self.discard(message_id);
+            return Err(anyhow::anyhow!("分块总数不一致，丢弃消息 {}", message_id));
+        }
+
+        let slot = &mut partial.chunks[index as usize];
+        if slot.is_none() {
+            if self.pending_bytes + data.len() > self.max_pending_bytes {
+                self.discard(message_id);
+                return Err(anyhow::anyhow!("未完成重组的分块过多（上限 {} 字节），丢弃消息 {}", self.max_pending_bytes, message_id));
+            }
+            partial.size += data.len();
+            partial.received += 1;
+            self.pending_bytes += data.len();
+            *slot = Some(data);
+        }
+
+        if partial.size > REASSEMBLY_MAX_SIZE {
+            self.discard(message_id);
+            return Err(anyhow::anyhow!("重组消息过大，丢弃消息 {}", message_id));
+        }
+
+        if partial.received < partial.chunks.len() {
+            return Ok(None);
+        }
+
+        let partial = self.discard(message_id).unwrap();
+        let mut payload = Vec::with_capacity(partial.size);
+        for chunk in partial.chunks.into_iter().flatten() {
+            payload.extend_from_slice(&chunk);
+        }
+        Ok(Some(payload))
+    }
+
+    /// 移出一条正在重组的消息，并从未完成的总大小中扣除
+    fn discard(&mut self, message_id: u64) -> Option<PartialMessage> {
+        let partial = self.pending.remove(&message_id)?;
+        self.pending_bytes -= partial.size;
+        Some(partial)
+    }
+
+    /// 丢弃超时未完成的重组缓冲
+    fn expire(&mut self, now: Instant) {
+        let pending_bytes = &mut self.pending_bytes;
+        self.pending.retain(|message_id, partial| {
+            let alive = now.duration_since(partial.started) < REASSEMBLY_TIMEOUT;
+            if !alive {
+                eprintln!("⚠️ 分块消息 {} 重组超时，已丢弃", message_id);
+                *pending_bytes -= partial.size;
+            }
+            alive
+        });
+    }
+
+    /// 正在重组的消息数量
+    #[cfg(test)]
+    fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn decode_all(frames: Vec<Vec<u8>>) -> Vec<Frame> {
+        let bytes: Vec<u8> = frames.concat();
+        let mut reader = bytes.as_slice();
+        let mut decoded = Vec::new();
+        while let Some(frame) = read_frame(&mut reader).await.unwrap() {
+            decoded.push(frame);
+        }
+        decoded
+    }
+
+    #[test]
+    fn test_trace_redacts_payload() {
+        let frame = encode_frames(b"secret").unwrap().remove(0);
+        assert_eq!(hex(&frame[..4]), "00 00 00 07");
+        assert_eq!(trace_payload(&frame[4..]), "，内容已隐藏");
+        assert_eq!(trace_payload(&encode_hello(2, 6)[4..5]), "");
+
+        events::set_trace_payload(true);
+        let preview = trace_payload(&encode_frames(&[0xab; 20]).unwrap()[0][4..]);
+        events::set_trace_payload(false);
+        assert_eq!(preview, format!("，内容开头: {} ...", ["ab"; TRACE_PREVIEW_LEN].join(" ")));
+
+        assert_eq!(Frame::Chunk { message_id: 3, index: 0, total: 2, data: vec![0; 4] }.summary(), "消息 3 的分块 1/2 (4 字节)");
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frames_rejected() {
+        let frame = encode_frames(b"hello").unwrap().remove(0);
+
+        // 长度只有一部分
+        assert!(read_frame(&mut &frame[..2]).await.is_err());
+        // 内容只有一部分
+        let err = read_frame(&mut &frame[..frame.len() - 1]).await.unwrap_err();
+        assert!(err.to_string().contains("帧不完整"));
+        // 空帧
+        assert!(read_frame(&mut &[0u8, 0, 0, 0][..]).await.is_err());
+        // 帧之间断开是正常关闭
+        assert!(read_frame(&mut &[][..]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_frame_times_out() {
+        // 声明了接近上限的长度，但只发送几个字节后不再发送也不关闭连接
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let declared = (MESSAGE_MAX_SIZE as u32 - 1).to_be_bytes();
+        tokio::io::AsyncWriteExt::write_all(&mut client, &declared).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut client, &[FRAME_MESSAGE, 1, 2, 3]).await.unwrap();
+
+        let err = read_frame_within(&mut server, Duration::from_millis(50)).await.unwrap_err();
+        assert!(err.to_string().contains("超时"));
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_frames() {
+        let decoded = decode_all(vec![encode_ping(7), encode_pong(u64::MAX)]).await;
+        assert_eq!(decoded, vec![Frame::Ping(7), Frame::Pong(u64::MAX)]);
+
+        // 标识长度不对
+        let short = encode_frame(FRAME_PING, &[], &[1, 2]);
+        assert!(read_frame(&mut &short[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_current_frame() {
+        assert_eq!(decode_all(vec![encode_request_current()]).await, vec![Frame::RequestCurrent]);
+        let with_payload = encode_frame(FRAME_REQUEST_CURRENT, &[], &[1]);
+        assert!(read_frame(&mut &with_payload[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_identity_frame() {
+        let decoded = decode_all(vec![encode_identity("a1b2c3d4", 8765), encode_identity("e5f6", 0)]).await;
+        assert_eq!(
+            decoded,
+            vec![
+                Frame::Identity { listen_port: 8765, device_id: b"a1b2c3d4".to_vec() },
+                Frame::Identity { listen_port: 0, device_id: b"e5f6".to_vec() },
+            ]
+        );
+        let short = encode_frame(FRAME_IDENTITY, &[], &[1]);
+        assert!(read_frame(&mut &short[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_small_payload_single_frame() {
+        let frames = encode_frames(b"hello").unwrap();
+        assert_eq!(frames.len(), 1);
+
+        let decoded = decode_all(frames).await;
+        assert_eq!(decoded, vec![Frame::Message(b"hello".to_vec())]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_payload_chunked_and_reassembled() {
+        let payload: Vec<u8> = (0..MESSAGE_MAX_SIZE + CHUNK_SIZE / 2)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let frames = encode_frames(&payload).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|frame| frame.len() - 4 <= MESSAGE_MAX_SIZE));
+
+        // 乱序到达也能正确重组
+        let mut decoded = decode_all(frames).await;
+        decoded.reverse();
+
+        let mut reassembler = Reassembler::new();
+        let now = Instant::now();
+        let mut result = None;
+        for frame in decoded {
+            result = reassembler.push(frame, now).unwrap();
+        }
+        assert_eq!(result, Some(payload));
+        assert_eq!(reassembler.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_incomplete_reassembly_expires() {
+        let mut reassembler = Reassembler::new();
+        let start = Instant::now();
+        let chunk = Frame::Chunk { message_id: 7, index: 0, total: 2, data: vec![1, 2, 3] };
+        assert_eq!(reassembler.push(chunk, start).unwrap(), None);
+        assert_eq!(reassembler.pending_count(), 1);
+
+        // 超时后的任意一帧都会触发清理
+        let later = start + REASSEMBLY_TIMEOUT + Duration::from_secs(1);
+        let message = Frame::Message(b"x".to_vec());
+        assert_eq!(reassembler.push(message, later).unwrap(), Some(b"x".to_vec()));
+        assert_eq!(reassembler.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_pending_reassembly_limited() {
+        let now = Instant::now();
+        let chunk = |message_id, index, data: &[u8]| Frame::Chunk { message_id, index, total: 2, data: data.to_vec() };
+
+        // 同时重组的消息数达到上限后拒绝新的消息，已开始的消息照常重组
+        let mut reassembler = Reassembler::with_limits(2, 1024);
+        assert_eq!(reassembler.push(chunk(1, 0, b"a"), now).unwrap(), None);
+        assert_eq!(reassembler.push(chunk(2, 0, b"b"), now).unwrap(), None);
+        assert!(reassembler.push(chunk(3, 0, b"c"), now).is_err());
+        assert_eq!(reassembler.pending_count(), 2);
+        assert_eq!(reassembler.push(chunk(1, 1, b"a"), now).unwrap(), Some(b"aa".to_vec()));
+        assert_eq!(reassembler.push(chunk(3, 0, b"c"), now).unwrap(), None);
+
+        // 未完成分块的总大小超出上限时丢弃超出的消息，并释放它占用的额度
+        let mut reassembler = Reassembler::with_limits(4, 10);
+        assert_eq!(reassembler.push(chunk(1, 0, &[0; 6]), now).unwrap(), None);
+        assert_eq!(reassembler.push(chunk(2, 0, &[0; 3]), now).unwrap(), None);
+        assert!(reassembler.push(chunk(2, 1, &[0; 3]), now).is_err());
+        assert_eq!(reassembler.pending_count(), 1);
+        assert_eq!(reassembler.push(chunk(1, 1, &[0; 4]), now).unwrap(), Some(vec![0; 10]));
+        assert_eq!(reassembler.pending_count(), 0);
+        assert_eq!(reassembler.pending_bytes, 0);
+    }
+
+    #[test]
+    fn test_invalid_chunk_index_rejected() {
+        let mut reassembler = Reassembler::new();
+        let chunk = Frame::Chunk { message_id: 1, index: 2, total: 2, data: vec![] };
+        assert!(reassembler.push(chunk, Instant::now()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_length_rejected() {
+        let bytes = ((MESSAGE_MAX_SIZE + 1) as u32).to_be_bytes();
+        let mut reader = bytes.as_slice();
+        assert!(read_frame(&mut reader).await.is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::auth::HANDSHAKE_TIMEOUT;
+use crate::compression::Encoding;
+use crate::frame::{self, Frame};
+use anyhow::Result;
+use std::ops::RangeInclusive;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+/// 从该版本起，版本协商后双方交换能解码的编码方式
+pub const CAPABILITIES_VERSION: u32 = 3;
+/// 从该版本起，消息中可以包含颜色等结构化内容
+pub const STRUCTURED_CONTENT_VERSION: u32 = 4;
+/// 从该版本起，连接建立后双方定期交换心跳帧
+pub const HEARTBEAT_VERSION: u32 = 5;
+/// 从该版本起，消息中可以包含带格式的 HTML
+pub const HTML_CONTENT_VERSION: u32 = 6;
+/// 从该版本起，连接后可以请求对方发送当前的剪贴板内容
+pub const REQUEST_CURRENT_VERSION: u32 = 7;
+/// 从该版本起，认证后双方交换设备标识和监听端口
+pub const IDENTITY_VERSION: u32 = 8;
+/// 设备标识的最大长度（字节）
+const MAX_DEVICE_ID_LEN: usize = 128;
+/// 房间标识的最大长度（字节）
+const MAX_ROOM_LEN: usize = 128;
+
+/// 本机支持的协议版本范围
+pub fn supported_versions() -> RangeInclusive<u32> {
+    frame::MIN_PROTOCOL_VERSION..=frame::PROTOCOL_VERSION
+}
+
+/// 协商协议版本：双方首先交换各自支持的版本范围，取两者都支持的最高版本
+///
+/// 协商是对称的，双方得到相同的结果；范围没有交集时两端都会拒绝连接。
+pub async fn negotiate<R, W>(reader: &mut R, writer: &mut W, supported: RangeInclusive<u32>) -> Result<u32>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange(reader, writer, supported))
+        .await
+        .map_err(|_| anyhow::anyhow!("协商协议版本超时，对方可能是不兼容的旧版本"))?
+}
+
+async fn exchange<R, W>(reader: &mut R, writer: &mut W, supported: RangeInclusive<u32>) -> Result<u32>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    writer
+        .write_all(&frame::encode_hello(*supported.start(), *supported.end()))
+        .await?;
+    let peer = match frame::read_frame(reader).await? {
+        Some(Frame::Hello { min_version, max_version }) => min_version..=max_version,
+        Some(_) => return Err(anyhow::anyhow!("对方没有发送协议版本，可能是不兼容的旧版本")),
+        None => return Err(anyhow::anyhow!("协商协议版本时连接已断开")),
+    };
+    select_version(&supported, &peer)
+}
+
+/// 交换双方能解码的编码方式，返回对方支持的列表，只在协商出的版本不低于 `CAPABILITIES_VERSION` 时调用
+///
+/// 对方列出的未知编码方式（来自更新的版本）会被忽略
+pub async fn exchange_encodings<R, W>(reader: &mut R, writer: &mut W, local: &[Encoding]) -> Result<Vec<Encoding>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let exchange = async {
+        let ids: Vec<u8> = local.iter().map(|encoding| encoding.id()).collect();
+        writer.write_all(&frame::encode_capabilities(&ids)).await?;
+        match frame::read_frame(reader).await? {
+            Some(Frame::Capabilities(ids)) => Ok(ids.into_iter().filter_map(Encoding::from_id).collect()),
+            Some(_) => Err(anyhow::anyhow!("对方没有发送支持的编码方式")),
+            None => Err(anyhow::anyhow!("交换编码方式时连接已断开")),
+        }
+    };
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("交换编码方式超时"))?
+}
+
+/// 对方在握手中告知的身份
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerIdentity {
+    /// 对方稳定的设备标识，与它发出的消息中的发送方标识相同
+    pub device_id: String,
+    /// 对方监听连接的端口，不监听时为 `None`
+    pub listen_port: Option<u16>,
+}
+
+/// 交换设备标识和监听端口（`None` 表示不监听），只在协商出的版本不低于 `IDENTITY_VERSION` 时、认证之后调用
+pub async fn exchange_identity<R, W>(reader: &mut R, writer: &mut W, device_id: &str, listen_port: Option<u16>) -> Result<PeerIdentity>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let exchange = async {
+        writer.write_all(&frame::encode_identity(device_id, listen_port.unwrap_or(0))).await?;
+        let (listen_port, device_id) = match frame::read_frame(reader).await? {
+            Some(Frame::Identity { listen_port, device_id }) => (listen_port, device_id),
+            Some(_) => return Err(anyhow::anyhow!("对方没有发送设备标识")),
+            None => return Err(anyhow::anyhow!("交换设备标识时连接已断开")),
+        };
+        let device_id = String::from_utf8(device_id).map_err(|_| anyhow::anyhow!("设备标识不是有效的 UTF-8"))?;
+        if device_id.is_empty() || device_id.len() > MAX_DEVICE_ID_LEN {
+            return Err(anyhow::anyhow!("设备标识长度应为 1-{} 字节", MAX_DEVICE_ID_LEN));
+        }
+        Ok(PeerIdentity { device_id, listen_port: (listen_port != 0).then_some(listen_port) })
+    };
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("交换设备标识超时"))?
+}
+
+/// 通过中继连接时，在版本协商之后告诉中继要加入的房间
+pub async fn send_join<W: AsyncWrite + Unpin>(writer: &mut W, room: &str) -> Result<()> {
+    check_room(room)?;
+    writer.write_all(&frame::encode_join(room)).await?;
+    Ok(())
+}
+
+/// 中继读取对方要加入的房间，对方没有加入房间时返回错误
+pub async fn read_join<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String> {
+    let frame = tokio::time::timeout(HANDSHAKE_TIMEOUT, frame::read_frame(reader))
+        .await
+        .map_err(|_| anyhow::anyhow!("等待加入房间超时，对方需要使用 --via 和 --room 连接中继"))??;
+    let room = match frame {
+        Some(Frame::Join(room)) => String::from_utf8(room).map_err(|_| anyhow::anyhow!("房间标识不是有效的 UTF-8"))?,
+        Some(_) => return Err(anyhow::anyhow!("对方没有加入房间，需要使用 --via 和 --room 连接中继")),
+        None => return Err(anyhow::anyhow!("加入房间时连接已断开")),
+    };
+    check_room(&room)?;
+    Ok(room)
+}
+
+/// 房间标识不能为空，也不能过长
+fn check_room(room: &str) -> Result<()> {
+    if room.is_empty() || room.len() > MAX_ROOM_LEN {
+        return Err(anyhow::anyhow!("房间标识长度应为 1-{} 字节", MAX_ROOM_LEN));
+    }
+    Ok(())
+}
+
+/// 选择双方都支持的最高版本
+fn select_version(local: &RangeInclusive<u32>, peer: &RangeInclusive<u32>) -> Result<u32> {
+    let version = (*local.end()).min(*peer.end());
+    if version < *local.start() || version < *peer.start() {
+        return Err(anyhow::anyhow!(
+            "协议版本不兼容：本机支持 v{}-v{}，对方支持 v{}-v{}，请升级较旧的一端",
+            local.start(),
+            local.end(),
+            peer.start(),
+            peer.end()
+        ));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn negotiate_pair(a: RangeInclusive<u32>, b: RangeInclusive<u32>) -> (Result<u32>, Result<u32>) {
+        let (left, right) = tokio::io::duplex(1024);
+        let (mut left_reader, mut left_writer) = tokio::io::split(left);
+        let (mut right_reader, mut right_writer) = tokio::io::split(right);
+        tokio::join!(
+            negotiate(&mut left_reader, &mut left_writer, a),
+            negotiate(&mut right_reader, &mut right_writer, b),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_highest_common_version_selected() {
+        let (left, right) = negotiate_pair(2..=4, 3..=5).await;
+        assert_eq!(left.unwrap(), 4);
+        assert_eq!(right.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_versions_refused_on_both_ends() {
+        let (left, right) = negotiate_pair(2..=2, 3..=4).await;
+        assert!(left.unwrap_err().to_string().contains("协议版本不兼容"));
+        assert!(right.unwrap_err().to_string().contains("协议版本不兼容"));
+    }
+
+    #[tokio::test]
+    async fn test_encodings_exchanged_both_ways() {
+        let (left, right) = tokio::io::duplex(1024);
+        let (mut left_reader, mut left_writer) = tokio::io::split(left);
+        let (mut right_reader, mut right_writer) = tokio::io::split(right);
+        let (left, right) = tokio::join!(
+            exchange_encodings(&mut left_reader, &mut left_writer, &Encoding::ALL),
+            exchange_encodings(&mut right_reader, &mut right_writer, &[Encoding::None]),
+        );
+        assert_eq!(left.unwrap(), vec![Encoding::None]);
+        assert_eq!(right.unwrap(), Encoding::ALL.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_encodings_ignored() {
+        let (left, mut right) = tokio::io::duplex(1024);
+        let (mut reader, mut writer) = tokio::io::split(left);
+        right.write_all(&frame::encode_capabilities(&[9, 1, 0])).await.unwrap();
+        let peer = exchange_encodings(&mut reader, &mut writer, &Encoding::ALL).await.unwrap();
+        assert_eq!(peer, vec![Encoding::Gzip, Encoding::None]);
+    }
+
+    #[tokio::test]
+    async fn test_join_room() {
+        let (left, right) = tokio::io::duplex(1024);
+        let (_, mut writer) = tokio::io::split(left);
+        let (mut reader, _) = tokio::io::split(right);
+        send_join(&mut writer, "家里").await.unwrap();
+        assert_eq!(read_join(&mut reader).await.unwrap(), "家里");
+
+        // 没有加入房间就开始认证的连接被拒绝
+        writer.write_all(&frame::encode_handshake(b"nonce")).await.unwrap();
+        assert!(read_join(&mut reader).await.is_err());
+        assert!(send_join(&mut writer, "").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peer_without_hello_refused() {
+        let (left, mut right) = tokio::io::duplex(1024);
+        let (mut reader, mut writer) = tokio::io::split(left);
+        right.write_all(&frame::encode_frames(b"{}").unwrap()[0]).await.unwrap();
+        assert!(negotiate(&mut reader, &mut writer, supported_versions()).await.is_err());
+    }
+}
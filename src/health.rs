@@ -0,0 +1,218 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::access::ContentTypes;
+
+/// 默认的心跳间隔
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// 连续多少次心跳没有回复时提示连接可能不稳定
+const MISSED_WARN: u32 = 2;
+/// 延迟超过平均值的多少倍视为突增
+const SPIKE_FACTOR: u32 = 3;
+/// 延迟至少比平均值高出这么多才视为突增，避免本机或局域网中亚毫秒级的波动被误报
+const SPIKE_MIN_INCREASE: Duration = Duration::from_millis(50);
+/// 平均延迟的平滑系数的倒数，新的测量值占 1/8
+const AVERAGE_WEIGHT: u32 = 8;
+
+/// 收到心跳回复后对连接状况的判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatOutcome {
+    /// 延迟正常
+    Normal(Duration),
+    /// 延迟相对平均值突增
+    Spike { rtt: Duration, average: Duration },
+    /// 之前连续未回复心跳，现在恢复
+    Recovered { rtt: Duration, missed: u32 },
+}
+
+/// 一个连接的健康状况：心跳往返延迟、最近一次收到数据的时间和未回复的心跳数
+#[derive(Debug)]
+pub struct PeerHealth {
+    /// 对方是否支持心跳（协议 v5 及以上）
+    supports_heartbeat: bool,
+    last_seen: Instant,
+    rtt: Option<Duration>,
+    average: Option<Duration>,
+    /// 已发出但尚未收到回复的心跳标识和发送时间
+    pending: Option<(u64, Instant)>,
+    next_token: u64,
+    /// 连续未回复的心跳数
+    missed: u32,
+}
+
+impl PeerHealth {
+    pub fn new(supports_heartbeat: bool, now: Instant) -> Self {
+        Self { supports_heartbeat, last_seen: now, rtt: None, average: None, pending: None, next_token: 0, missed: 0 }
+    }
+
+    pub fn supports_heartbeat(&self) -> bool {
+        self.supports_heartbeat
+    }
+
+    /// 收到对方的任何数据
+    pub fn record_seen(&mut self, now: Instant) {
+        self.last_seen = now;
+    }
+
+    /// 准备发送下一次心跳，返回心跳标识；上一次心跳仍未回复时计为一次未回复，
+    /// 连续未回复的次数达到提示阈值时一并返回
+    pub fn next_ping(&mut self, now: Instant) -> (u64, Option<u32>) {
+        let mut warn = None;
+        if self.pending.is_some() {
+            self.missed += 1;
+            if self.missed == MISSED_WARN {
+                warn = Some(self.missed);
+            }
+        }
+        let token = self.next_token;
+        self.next_token += 1;
+        self.pending = Some((token, now));
+        (token, warn)
+    }
+
+    /// 收到心跳回复，标识与最近一次发出的心跳不符时（迟到的旧回复）忽略并返回 `None`
+    pub fn record_pong(&mut self, token: u64, now: Instant) -> Option<HeartbeatOutcome> {
+        self.last_seen = now;
+        let (sent_token, sent) = self.pending?;
+        if sent_token != token {
+            return None;
+        }
+        self.pending = None;
+        let rtt = now.saturating_duration_since(sent);
+        self.rtt = Some(rtt);
+
+        let missed = std::mem::take(&mut self.missed);
+        let outcome = match self.average {
+            _ if missed >= MISSED_WARN => HeartbeatOutcome::Recovered { rtt, missed },
+            Some(average) if rtt > average * SPIKE_FACTOR && rtt > average + SPIKE_MIN_INCREASE => {
+                HeartbeatOutcome::Spike { rtt, average }
+            }
+            _ => HeartbeatOutcome::Normal(rtt),
+        };
+        self.average = Some(match self.average {
+            Some(average) => (average * (AVERAGE_WEIGHT - 1) + rtt) / AVERAGE_WEIGHT,
+            None => rtt,
+        });
+        Some(outcome)
+    }
+
+    /// 当前状况的快照，`accept` 为允许该连接发送的内容类型
+    pub fn snapshot(&self, peer: &str, accept: ContentTypes, now: Instant) -> HealthSnapshot {
+        HealthSnapshot {
+            peer: peer.to_string(),
+            accept,
+            supports_heartbeat: self.supports_heartbeat,
+            rtt: self.rtt,
+            average_rtt: self.average,
+            since_last_seen: now.saturating_duration_since(self.last_seen),
+            missed: self.missed,
+        }
+    }
+}
+
+/// 某一时刻一个连接的健康状况
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthSnapshot {
+    /// 连接标识
+    pub peer: String,
+    /// 允许对方发送的内容类型
+    pub accept: ContentTypes,
+    pub supports_heartbeat: bool,
+    /// 最近一次心跳的往返延迟，尚未测量时为 `None`
+    pub rtt: Option<Duration>,
+    /// 平滑后的平均往返延迟
+    pub average_rtt: Option<Duration>,
+    /// 距离最近一次收到对方数据的时间
+    pub since_last_seen: Duration,
+    /// 连续未回复的心跳数
+    pub missed: u32,
+}
+
+impl HealthSnapshot {
+    /// 连续多次未回复心跳，连接可能不稳定
+    pub fn is_unresponsive(&self) -> bool {
+        self.missed >= MISSED_WARN
+    }
+}
+
+impl fmt::Display for HealthSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.peer)?;
+        match (self.supports_heartbeat, self.rtt, self.average_rtt) {
+            (false, _, _) => write!(f, "延迟未知（对方版本不支持心跳）")?,
+            (true, Some(rtt), Some(average)) => write!(f, "延迟 {} ms（平均 {} ms）", rtt.as_millis(), average.as_millis())?,
+            (true, _, _) => write!(f, "延迟尚未测量")?,
+        }
+        write!(f, "，{} 秒前收到数据", self.since_last_seen.as_secs())?;
+        if self.missed > 0 {
+            write!(f, "，{} 次心跳未回复", self.missed)?;
+        }
+        write!(f, "，接受: {}", self.accept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// 发送一次心跳并在 `rtt` 之后收到回复
+    fn round_trip(health: &mut PeerHealth, now: &mut Instant, rtt: Duration) -> Option<HeartbeatOutcome> {
+        let (token, _) = health.next_ping(*now);
+        *now += rtt;
+        health.record_pong(token, *now)
+    }
+
+    #[test]
+    fn test_latency_spike_detected() {
+        let mut now = Instant::now();
+        let mut health = PeerHealth::new(true, now);
+        for _ in 0..5 {
+            assert_eq!(round_trip(&mut health, &mut now, ms(20)), Some(HeartbeatOutcome::Normal(ms(20))));
+        }
+        assert_eq!(
+            round_trip(&mut health, &mut now, ms(300)),
+            Some(HeartbeatOutcome::Spike { rtt: ms(300), average: ms(20) })
+        );
+        // 亚毫秒级的波动不算突增
+        let mut health = PeerHealth::new(true, now);
+        round_trip(&mut health, &mut now, Duration::from_micros(100));
+        assert!(matches!(round_trip(&mut health, &mut now, ms(5)), Some(HeartbeatOutcome::Normal(_))));
+    }
+
+    #[test]
+    fn test_missed_heartbeats_flagged_once() {
+        let mut now = Instant::now();
+        let mut health = PeerHealth::new(true, now);
+        let (first, warn) = health.next_ping(now);
+        assert_eq!(warn, None);
+        assert_eq!(health.next_ping(now).1, None);
+        let (last, warn) = health.next_ping(now);
+        assert_eq!(warn, Some(2));
+        assert!(health.snapshot("a", ContentTypes::ALL, now).is_unresponsive());
+        assert_eq!(health.next_ping(now).1, None);
+
+        // 迟到的旧回复被忽略
+        now += ms(10);
+        assert_eq!(health.record_pong(first, now), None);
+        assert_eq!(health.record_pong(last, now), None);
+        let (token, _) = health.next_ping(now);
+        assert_eq!(health.record_pong(token, now + ms(10)), Some(HeartbeatOutcome::Recovered { rtt: ms(10), missed: 4 }));
+        assert!(!health.snapshot("a", ContentTypes::ALL, now).is_unresponsive());
+    }
+
+    #[test]
+    fn test_snapshot_shows_accepted_content() {
+        let now = Instant::now();
+        let health = PeerHealth::new(false, now);
+        let text_only = ContentTypes { text: true, image: false, clear: false };
+        assert_eq!(
+            health.snapshot("a", text_only, now).to_string(),
+            "a: 延迟未知（对方版本不支持心跳），0 秒前收到数据，接受: text"
+        );
+        assert!(health.snapshot("a", ContentTypes::ALL, now).to_string().ends_with("接受: all"));
+    }
+}
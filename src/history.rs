@@ -0,0 +1,232 @@
+//! 同步过的剪贴板内容，每条记录来自本机还是哪一台设备，便于在多设备之间追查内容的来源
+//!
+//! 默认只保存在内存中，启用 history-sqlite 特性后可以保存到 SQLite 数据库，重启后仍可查看和搜索
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::network_alternative::{ClipboardContent, ClipboardMessage};
+
+/// 内存中最多保留的记录数，超出时丢弃最旧的；也是查看和搜索时最多列出的条数
+pub const HISTORY_CAPACITY: usize = 50;
+/// 保存到数据库时默认最多保留的记录数
+pub const DEFAULT_MAX_STORED: usize = 10_000;
+/// 记录中内容预览的最大长度
+const PREVIEW_LENGTH: usize = 50;
+
+/// 内容的来源
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "from")]
+pub enum HistorySource {
+    /// 本机复制后广播的内容
+    Local,
+    /// 从其他设备收到并写入剪贴板的内容
+    Peer { device_id: String, name: String },
+}
+
+impl HistorySource {
+    /// 收到的消息的发送方
+    pub fn peer(message: &ClipboardMessage) -> Self {
+        HistorySource::Peer { device_id: message.sender_id.clone(), name: message.sender_name.clone() }
+    }
+}
+
+impl fmt::Display for HistorySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistorySource::Local => f.write_str("本机"),
+            HistorySource::Peer { device_id, name } => write!(f, "{} [{}]", name, config::short_id(device_id)),
+        }
+    }
+}
+
+/// 一条同步记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// 记录时间（Unix 时间戳，秒）
+    pub timestamp: u64,
+    pub source: HistorySource,
+    /// 内容类型: text、image、html 等
+    pub content_type: String,
+    pub preview: String,
+    /// 完整的文本，用于搜索；图片没有文本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// 图片保存在图片缓存中的路径，未启用 `--image-cache` 时为 `None`；缓存清理旧文件后路径可能已不存在
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_path: Option<PathBuf>,
+}
+
+impl HistoryEntry {
+    /// 为同步过的内容创建记录，清空剪贴板不记录，返回 `None`
+    pub fn new(source: HistorySource, content: &ClipboardContent) -> Option<Self> {
+        let text = match content {
+            ClipboardContent::Clear => return None,
+            ClipboardContent::Text(text) => Some(text.clone()),
+            ClipboardContent::Image { .. } => None,
+            _ => content.structured_text().map(str::to_string),
+        };
+        Some(Self {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            source,
+            content_type: content.kind().to_string(),
+            preview: content.preview(PREVIEW_LENGTH),
+            text,
+            image_path: None,
+        })
+    }
+
+    /// 文本（没有文本时为预览）是否包含关键字，不区分大小写
+    pub fn matches(&self, query: &str) -> bool {
+        let haystack = self.text.as_deref().unwrap_or(&self.preview);
+        haystack.to_lowercase().contains(&query.to_lowercase())
+    }
+}
+
+/// 保存历史记录的后端
+pub trait HistoryStore: Send + Sync {
+    /// 保存一条记录
+    fn push(&self, entry: HistoryEntry) -> Result<()>;
+
+    /// 最近的 `limit` 条记录，从旧到新
+    fn recent(&self, limit: usize) -> Result<Vec<HistoryEntry>>;
+
+    /// 文本包含关键字的最近 `limit` 条记录，从旧到新
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<HistoryEntry>>;
+}
+
+/// 只保存在内存中的历史记录，最多 `HISTORY_CAPACITY` 条，退出后丢失
+#[derive(Debug, Default)]
+pub struct MemoryHistory {
+    entries: Mutex<VecDeque<HistoryEntry>>,
+}
+
+impl MemoryHistory {
+    fn entries(&self) -> std::sync::MutexGuard<'_, VecDeque<HistoryEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HistoryStore for MemoryHistory {
+    fn push(&self, entry: HistoryEntry) -> Result<()> {
+        let mut entries = self.entries();
+        if entries.len() >= HISTORY_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+        Ok(())
+    }
+
+    fn recent(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let entries = self.entries();
+        Ok(entries.iter().skip(entries.len().saturating_sub(limit)).cloned().collect())
+    }
+
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let mut found: Vec<_> = self.entries().iter().rev().filter(|entry| entry.matches(query)).take(limit).cloned().collect();
+        found.reverse();
+        Ok(found)
+    }
+}
+
+/// 同步过的内容，克隆出的句柄共享同一个后端
+#[derive(Clone)]
+pub struct History {
+    store: Arc<dyn HistoryStore>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_store(MemoryHistory::default())
+    }
+}
+
+impl fmt::Debug for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("History").finish_non_exhaustive()
+    }
+}
+
+impl History {
+    /// 只保存在内存中的历史记录
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用指定的后端保存记录，如 `SqliteHistory`
+    pub fn with_store(store: impl HistoryStore + 'static) -> Self {
+        Self { store: Arc::new(store) }
+    }
+
+    /// 记录一条同步过的内容，清空剪贴板不记录
+    pub fn record(&self, source: HistorySource, content: &ClipboardContent) {
+        if let Some(entry) = HistoryEntry::new(source, content) {
+            self.push(entry);
+        }
+    }
+
+    /// 保存一条已创建的记录，保存失败时只输出警告，不影响同步
+    pub fn push(&self, entry: HistoryEntry) {
+        if let Err(e) = self.store.push(entry) {
+            eprintln!("⚠️ 保存同步历史失败: {}", e);
+        }
+    }
+
+    /// 最近的记录，从旧到新
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>> {
+        self.store.recent(HISTORY_CAPACITY)
+    }
+
+    /// 文本包含关键字的最近记录，从旧到新
+    pub fn search(&self, query: &str) -> Result<Vec<HistoryEntry>> {
+        self.store.search(query, HISTORY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_source_and_drops_oldest() {
+        let history = History::new();
+        let message = ClipboardMessage::new_text("来自笔记本".to_string(), "a1b2c3d4e5f6".to_string(), "笔记本".to_string());
+        history.record(HistorySource::peer(&message), &message.content);
+        history.record(HistorySource::Local, &ClipboardContent::Clear);
+        history.record(HistorySource::Local, &ClipboardContent::Text("本机复制".to_string()));
+
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].source.to_string(), "笔记本 [a1b2c3d4]");
+        assert_eq!(entries[0].preview, "来自笔记本");
+        assert_eq!(entries[1].source, HistorySource::Local);
+
+        for i in 0..HISTORY_CAPACITY {
+            history.record(HistorySource::Local, &ClipboardContent::Text(i.to_string()));
+        }
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), HISTORY_CAPACITY);
+        assert_eq!(entries[0].preview, "0");
+    }
+
+    #[test]
+    fn test_search_matches_full_text() {
+        let history = History::new();
+        let long = format!("{}Needle", "前缀".repeat(PREVIEW_LENGTH));
+        history.record(HistorySource::Local, &ClipboardContent::Text(long.clone()));
+        history.record(HistorySource::Local, &ClipboardContent::Text("无关".to_string()));
+        history.record(HistorySource::Local, &ClipboardContent::Image { width: 1, height: 1, data: vec![0; 4] });
+
+        let found = history.search("needle").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].text.as_deref(), Some(long.as_str()));
+        assert_eq!(history.search("图片 1x1").unwrap().len(), 1);
+        assert!(history.search("不存在").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+//! 把同步历史保存到 SQLite 数据库（`--history-db`），重启后仍可查看和搜索，记录数超出上限时删除最旧的
+
+use anyhow::Result;
+use rusqlite::{params, Connection, Row};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::history::{HistoryEntry, HistorySource, HistoryStore, DEFAULT_MAX_STORED};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    device_id TEXT,
+    device_name TEXT,
+    content_type TEXT NOT NULL,
+    preview TEXT NOT NULL,
+    text TEXT,
+    image_path TEXT
+)";
+
+const COLUMNS: &str = "timestamp, device_id, device_name, content_type, preview, text, image_path";
+
+/// 保存在 SQLite 数据库中的历史记录，本机的记录 device_id 为空
+pub struct SqliteHistory {
+    connection: Mutex<Connection>,
+    max_entries: usize,
+}
+
+impl SqliteHistory {
+    /// 打开数据库，不存在时创建
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(path)
+            .map_err(|e| anyhow::anyhow!("无法打开历史数据库 {}: {}", path.display(), e))?;
+        connection.execute(SCHEMA, [])?;
+        Ok(Self { connection: Mutex::new(connection), max_entries: DEFAULT_MAX_STORED })
+    }
+
+    /// 设置最多保留的记录数，超出时删除最旧的，默认为 `DEFAULT_MAX_STORED`
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 执行查询，结果按从旧到新排列
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<HistoryEntry>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(sql)?;
+        let mut entries = statement.query_map(params, entry_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+        entries.reverse();
+        Ok(entries)
+    }
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<HistoryEntry> {
+    let device_id: Option<String> = row.get(1)?;
+    let source = match device_id {
+        Some(device_id) => HistorySource::Peer { device_id, name: row.get::<_, Option<String>>(2)?.unwrap_or_default() },
+        None => HistorySource::Local,
+    };
+    Ok(HistoryEntry {
+        timestamp: row.get::<_, i64>(0)? as u64,
+        source,
+        content_type: row.get(3)?,
+        preview: row.get(4)?,
+        text: row.get(5)?,
+        image_path: row.get::<_, Option<String>>(6)?.map(PathBuf::from),
+    })
+}
+
+/// 转义 LIKE 中的通配符，按字面匹配关键字
+fn like_pattern(query: &str) -> String {
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+impl HistoryStore for SqliteHistory {
+    fn push(&self, entry: HistoryEntry) -> Result<()> {
+        let (device_id, device_name) = match &entry.source {
+            HistorySource::Local => (None, None),
+            HistorySource::Peer { device_id, name } => (Some(device_id), Some(name)),
+        };
+        let connection = self.connection();
+        connection.execute(
+            &format!("INSERT INTO history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", COLUMNS),
+            params![
+                entry.timestamp as i64,
+                device_id,
+                device_name,
+                entry.content_type,
+                entry.preview,
+                entry.text,
+                entry.image_path.as_ref().map(|path| path.display().to_string()),
+            ],
+        )?;
+        // 自增的 id 按插入顺序递增，保留最新的 max_entries 条
+        connection.execute(
+            "DELETE FROM history WHERE id <= (SELECT MAX(id) FROM history) - ?1",
+            params![self.max_entries as i64],
+        )?;
+        Ok(())
+    }
+
+    fn recent(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        self.query(&format!("SELECT {} FROM history ORDER BY id DESC LIMIT ?1", COLUMNS), params![limit as i64])
+    }
+
+    /// 按完整文本（没有文本时按预览）匹配，ASCII 字母不区分大小写
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<HistoryEntry>> {
+        self.query(
+            &format!(
+                "SELECT {} FROM history WHERE COALESCE(text, preview) LIKE ?1 ESCAPE '\\' ORDER BY id DESC LIMIT ?2",
+                COLUMNS
+            ),
+            params![like_pattern(query), limit as i64],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_alternative::ClipboardContent;
+
+    fn entry(source: HistorySource, text: &str) -> HistoryEntry {
+        HistoryEntry::new(source, &ClipboardContent::Text(text.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_persists_searches_and_prunes() {
+        let path = std::env::temp_dir().join(format!("clipboard-sync-alt-test-{}-history.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let store = SqliteHistory::open(&path).unwrap().with_max_entries(3);
+        let peer = HistorySource::Peer { device_id: "a1b2c3d4e5f6".to_string(), name: "笔记本".to_string() };
+        store.push(entry(peer.clone(), "Hello 世界")).unwrap();
+        store.push(entry(HistorySource::Local, "100% 完成")).unwrap();
+        let mut image = HistoryEntry::new(HistorySource::Local, &ClipboardContent::Image { width: 2, height: 3, data: vec![0; 24] }).unwrap();
+        image.image_path = Some(PathBuf::from("/tmp/image-1.png"));
+        store.push(image.clone()).unwrap();
+        drop(store);
+
+        // 重新打开后记录仍在
+        let store = SqliteHistory::open(&path).unwrap().with_max_entries(3);
+        let entries = store.recent(10).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].source, peer);
+        assert_eq!(entries[2], image);
+
+        assert_eq!(store.search("hello", 10).unwrap()[0].text.as_deref(), Some("Hello 世界"));
+        assert_eq!(store.search("%", 10).unwrap().len(), 1);
+        assert_eq!(store.search("图片 2x3", 10).unwrap().len(), 1);
+
+        // 超出上限时删除最旧的
+        store.push(entry(HistorySource::Local, "最新")).unwrap();
+        let entries = store.recent(10).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].preview, "100% 完成");
+        assert_eq!(store.recent(1).unwrap()[0].preview, "最新");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
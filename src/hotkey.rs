@@ -0,0 +1,120 @@
+use anyhow::Result;
+use global_hotkey::hotkey::HotKey;
+use std::str::FromStr;
+
+use crate::monitor::SyncControl;
+
+/// 快捷键控制同步的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HotkeyMode {
+    /// 每按一次切换开启/暂停
+    #[default]
+    Toggle,
+    /// 按住时开启，松开后暂停
+    Hold,
+}
+
+impl FromStr for HotkeyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "toggle" => Ok(HotkeyMode::Toggle),
+            "hold" => Ok(HotkeyMode::Hold),
+            other => Err(anyhow::anyhow!("无效的快捷键模式 {:?}: 应为 toggle 或 hold", other)),
+        }
+    }
+}
+
+/// 解析快捷键组合，如 `ctrl+shift+KeyS`、`alt+F9`
+pub fn parse_chord(chord: &str) -> Result<HotKey> {
+    HotKey::from_str(chord).map_err(|e| anyhow::anyhow!("无效的快捷键 {:?}: {}", chord, e))
+}
+
+/// 按下或松开快捷键后是否应暂停同步，`None` 表示保持当前状态
+fn next_paused(mode: HotkeyMode, pressed: bool, paused: bool) -> Option<bool> {
+    match (mode, pressed) {
+        (HotkeyMode::Toggle, true) => Some(!paused),
+        (HotkeyMode::Toggle, false) => None,
+        (HotkeyMode::Hold, pressed) => Some(!pressed),
+    }
+}
+
+/// 注册全局快捷键，同步在按下快捷键之前保持暂停
+///
+/// 快捷键只在 Linux（X11）上可用，其他平台或注册失败时只输出警告，同步保持开启
+pub fn spawn(chord: &str, mode: HotkeyMode, control: SyncControl) -> Result<()> {
+    let hotkey = parse_chord(chord)?;
+    if !cfg!(target_os = "linux") {
+        eprintln!("⚠️ 当前平台暂不支持全局快捷键，已忽略 --hotkey");
+        return Ok(());
+    }
+
+    // 快捷键管理器在独立线程中创建并一直保留，线程退出前快捷键保持注册
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let manager = match global_hotkey::GlobalHotKeyManager::new() {
+            Ok(manager) => manager,
+            Err(e) => {
+                let _ = ready_tx.send(Err(e.to_string()));
+                return;
+            }
+        };
+        if let Err(e) = manager.register(hotkey) {
+            let _ = ready_tx.send(Err(e.to_string()));
+            return;
+        }
+        control.set_paused(true);
+        let _ = ready_tx.send(Ok(()));
+
+        let receiver = global_hotkey::GlobalHotKeyEvent::receiver();
+        while let Ok(event) = receiver.recv() {
+            if event.id != hotkey.id() {
+                continue;
+            }
+            let pressed = event.state == global_hotkey::HotKeyState::Pressed;
+            let Some(paused) = next_paused(mode, pressed, control.is_paused()) else {
+                continue;
+            };
+            if paused != control.is_paused() {
+                control.set_paused(paused);
+                status!("{}", if paused { "⏸️ 同步已暂停" } else { "▶️ 同步已开启" });
+            }
+        }
+        drop(manager);
+    });
+
+    match ready_rx.recv() {
+        Ok(Ok(())) => match mode {
+            HotkeyMode::Toggle => status!("⌨️ 同步已暂停，按 {} 开启或暂停同步", chord),
+            HotkeyMode::Hold => status!("⌨️ 同步已暂停，按住 {} 时同步", chord),
+        },
+        Ok(Err(e)) => eprintln!("⚠️ 无法注册全局快捷键 {}: {}，同步保持开启", chord, e),
+        Err(_) => eprintln!("⚠️ 无法注册全局快捷键 {}，同步保持开启", chord),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chord() {
+        assert!(parse_chord("ctrl+shift+KeyS").is_ok());
+        assert!(parse_chord("ctrl+nosuchkey").is_err());
+        assert_eq!("hold".parse::<HotkeyMode>().unwrap(), HotkeyMode::Hold);
+        assert!("press".parse::<HotkeyMode>().is_err());
+    }
+
+    #[test]
+    fn test_next_paused() {
+        // 切换模式只响应按下
+        assert_eq!(next_paused(HotkeyMode::Toggle, true, true), Some(false));
+        assert_eq!(next_paused(HotkeyMode::Toggle, true, false), Some(true));
+        assert_eq!(next_paused(HotkeyMode::Toggle, false, false), None);
+        // 按住模式跟随按键状态
+        assert_eq!(next_paused(HotkeyMode::Hold, true, true), Some(false));
+        assert_eq!(next_paused(HotkeyMode::Hold, false, false), Some(true));
+    }
+}
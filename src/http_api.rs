@@ -0,0 +1,347 @@
+use anyhow::Result;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+use crate::clipboard::{ClipboardContentType, ClipboardManager};
+
+/// 请求行和请求头的最大字节数
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+/// 请求体的最大字节数
+const MAX_BODY_BYTES: usize = crate::frame::MESSAGE_MAX_SIZE;
+
+/// 解析后的 HTTP 请求
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    content_type: Option<String>,
+    authorization: Option<String>,
+    /// 浏览器发出的跨站请求带有的来源
+    origin: Option<String>,
+    host: Option<String>,
+    body: Vec<u8>,
+}
+
+/// 待发送的 HTTP 响应
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, content_type, body: body.into() }
+    }
+
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", body.into())
+    }
+
+    fn no_content() -> Self {
+        Self::new(204, "text/plain; charset=utf-8", Vec::new())
+    }
+}
+
+/// 启动本地 HTTP 接口，返回实际监听的地址
+///
+/// - `GET /clipboard`: 返回当前剪贴板内容（文本为 `text/plain`，图片为 `image/png`，为空时返回 204）
+/// - `POST /clipboard`: 设置剪贴板内容，`Content-Type: image/*`（PNG、JPEG、WebP 等）时作为图片，否则作为 UTF-8 文本；
+///   写入后由监控循环检测到变化并广播给其他设备
+///
+/// 设置了 `token` 时，请求需要携带 `Authorization: Bearer <token>`。
+/// 网页中的脚本无需预检即可向本机地址发送简单的 POST 请求，因此拒绝带有 `Origin` 的请求，
+/// 以及 `Host` 不是 `localhost` 或 IP 地址加本接口端口的请求（DNS 重绑定）
+pub async fn start(addr: SocketAddr, clipboard: ClipboardManager, token: Option<String>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("无法启动 HTTP 接口 {}: {}", addr, e))?;
+    let local_addr = listener.local_addr()?;
+    let token: Option<Arc<str>> = token.map(Arc::from);
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("❌ HTTP 接口接受连接失败: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let clipboard = clipboard.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.split();
+                let response = match read_request(reader).await {
+                    Ok(request) if !same_machine(&request, local_addr.port()) => Response::text(403, "拒绝来自浏览器的请求\n"),
+                    Ok(request) if !authorized(&request, token.as_deref()) => Response::text(401, "未授权\n"),
+                    Ok(request) => handle(&request, &clipboard),
+                    Err(e) => Response::text(400, format!("无效的请求: {}\n", e)),
+                };
+                if let Err(e) = write_response(&mut writer, &response).await {
+                    eprintln!("⚠️ HTTP 接口响应 {} 失败: {}", peer, e);
+                }
+            });
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// 检查请求是否携带了正确的令牌，未设置令牌时允许所有请求
+fn authorized(request: &Request, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| provided.trim() == token)
+}
+
+/// 请求是否由本机的程序直接发出，而不是浏览器中的网页
+///
+/// 浏览器发出的跨站请求总是带有 `Origin`；DNS 重绑定时 `Host` 是攻击者的域名。
+/// 程序和脚本（如 curl）不发送 `Origin`，`Host` 为 `localhost` 或 IP 地址，没有 `Host` 的请求也允许
+fn same_machine(request: &Request, port: u16) -> bool {
+    if request.origin.is_some() {
+        return false;
+    }
+    let Some(host) = request.host.as_deref() else {
+        return true;
+    };
+    let (name, host_port) = match host.rsplit_once(':') {
+        Some((name, host_port)) if !host_port.contains(']') => (name, Some(host_port)),
+        _ => (host, None),
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let local_name = name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok();
+    local_name && host_port.is_none_or(|host_port| host_port.parse() == Ok(port))
+}
+
+/// 处理已通过认证的请求
+fn handle(request: &Request, clipboard: &ClipboardManager) -> Response {
+    if request.path != "/clipboard" {
+        return Response::text(404, "未找到\n");
+    }
+    match request.method.as_str() {
+        "GET" => get_clipboard(clipboard),
+        "POST" => set_clipboard(request, clipboard),
+        _ => Response::text(405, "仅支持 GET 和 POST\n"),
+    }
+}
+
+fn get_clipboard(clipboard: &ClipboardManager) -> Response {
+    let result = match clipboard.get_content_type() {
+        ClipboardContentType::Text => clipboard
+            .get_text()
+            .map(|text| Response::new(200, "text/plain; charset=utf-8", text)),
+        #[cfg(feature = "image-support")]
+        ClipboardContentType::Image => clipboard.get_image().map(|image| match image {
+            Some((_, _, png_data)) => Response::new(200, "image/png", png_data),
+            None => Response::no_content(),
+        }),
+        #[cfg(not(feature = "image-support"))]
+        ClipboardContentType::Image => Ok(Response::no_content()),
+        ClipboardContentType::Html => clipboard
+            .get_html()
+            .map(|html| Response::new(200, "text/html; charset=utf-8", html)),
+        ClipboardContentType::Empty => Ok(Response::no_content()),
+    };
+    result.unwrap_or_else(|e| Response::text(500, format!("{}\n", e)))
+}
+
+fn set_clipboard(request: &Request, clipboard: &ClipboardManager) -> Response {
+    let is_image = request
+        .content_type
+        .as_deref()
+        .is_some_and(|value| value.trim().to_ascii_lowercase().starts_with("image/"));
+    let result = if is_image {
+        set_image(clipboard, &request.body)
+    } else {
+        match std::str::from_utf8(&request.body) {
+            Ok(text) => clipboard.set_text(text).map_err(Into::into),
+            Err(_) => return Response::text(400, "请求体不是有效的 UTF-8 文本\n"),
+        }
+    };
+    match result {
+        Ok(()) => Response::no_content(),
+        Err(e) => Response::text(500, format!("{}\n", e)),
+    }
+}
+
+/// 写入图片，尺寸从图片头读取
+#[cfg(feature = "image-support")]
+fn set_image(clipboard: &ClipboardManager, data: &[u8]) -> anyhow::Result<()> {
+    let (width, height) = crate::clipboard::image_dimensions(data)?;
+    Ok(clipboard.set_image(width, height, data)?)
+}
+
+#[cfg(not(feature = "image-support"))]
+fn set_image(_clipboard: &ClipboardManager, _data: &[u8]) -> anyhow::Result<()> {
+    anyhow::bail!("未编译图片支持（image-support 特性），只能设置文本")
+}
+
+/// 读取一个 HTTP/1.1 请求，只支持 `Content-Length` 指定长度的请求体
+async fn read_request<R: AsyncRead + Unpin>(reader: R) -> Result<Request> {
+    let mut reader = BufReader::new(reader.take(MAX_HEADER_BYTES as u64));
+
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(anyhow::anyhow!("请求行格式错误"));
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        content_type: None,
+        authorization: None,
+        origin: None,
+        host: None,
+        body: Vec::new(),
+    };
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(anyhow::anyhow!("请求头不完整或过大"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(anyhow::anyhow!("请求头格式错误: {}", header));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.parse().map_err(|_| anyhow::anyhow!("无效的 Content-Length"))?,
+            "content-type" => request.content_type = Some(value.to_string()),
+            "authorization" => request.authorization = Some(value.to_string()),
+            "origin" => request.origin = Some(value.to_string()),
+            "host" => request.host = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(anyhow::anyhow!("请求体过大 ({} bytes)", content_length));
+    }
+    // 请求头读取完毕后按请求体长度放开限制（缓冲区中可能已有部分请求体）
+    reader.get_mut().set_limit(content_length as u64);
+    request.body.resize(content_length, 0);
+    reader.read_exact(&mut request.body).await?;
+    Ok(request)
+}
+
+async fn write_response<W: AsyncWrite + Unpin>(writer: &mut W, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let mut head = format!("HTTP/1.1 {} {}\r\nConnection: close\r\n", response.status, reason);
+    // 204 响应不能带有内容相关的头
+    if response.status != 204 {
+        head.push_str(&format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n",
+            response.content_type, response.body.len()
+        ));
+    }
+    if response.status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    head.push_str("\r\n");
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(&response.body).await?;
+    writer.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn parse(raw: &[u8]) -> Result<Request> {
+        read_request(raw).await
+    }
+
+    #[tokio::test]
+    async fn test_read_request_with_body() {
+        let request = parse(
+            b"POST /clipboard HTTP/1.1\r\nHost: localhost\r\ncontent-type: text/plain\r\nAuthorization: Bearer abc\r\nContent-Length: 6\r\n\r\nhello!",
+        )
+        .await
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/clipboard");
+        assert_eq!(request.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(request.body, b"hello!");
+        assert!(authorized(&request, Some("abc")));
+        assert!(!authorized(&request, Some("other")));
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_invalid() {
+        assert!(parse(b"").await.is_err());
+        assert!(parse(b"GET /clipboard HTTP/1.1\r\nHost").await.is_err());
+        let oversized = format!("POST /clipboard HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1);
+        assert!(parse(oversized.as_bytes()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_token_rejected() {
+        let request = parse(b"GET /clipboard HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(authorized(&request, None));
+        assert!(!authorized(&request, Some("abc")));
+    }
+
+    #[tokio::test]
+    async fn test_browser_request_with_origin_rejected() {
+        let request = parse(b"POST /clipboard HTTP/1.1\r\nHost: 127.0.0.1:8780\r\nOrigin: https://example.com\r\nContent-Length: 0\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(!same_machine(&request, 8780));
+        // 同源的页面也会带上 Origin
+        let request = parse(b"POST /clipboard HTTP/1.1\r\nHost: localhost:8780\r\nOrigin: null\r\n\r\n").await.unwrap();
+        assert!(!same_machine(&request, 8780));
+    }
+
+    #[tokio::test]
+    async fn test_rebound_host_rejected() {
+        let request = parse(b"POST /clipboard HTTP/1.1\r\nHost: attacker.example:8780\r\n\r\n").await.unwrap();
+        assert!(!same_machine(&request, 8780));
+        let request = parse(b"GET /clipboard HTTP/1.1\r\nHost: localhost:9999\r\n\r\n").await.unwrap();
+        assert!(!same_machine(&request, 8780));
+
+        for host in ["127.0.0.1:8780", "localhost:8780", "LOCALHOST", "[::1]:8780", "192.168.1.5:8780"] {
+            let raw = format!("GET /clipboard HTTP/1.1\r\nHost: {}\r\n\r\n", host);
+            let request = parse(raw.as_bytes()).await.unwrap();
+            assert!(same_machine(&request, 8780), "{}", host);
+        }
+        let request = parse(b"GET /clipboard HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(same_machine(&request, 8780));
+    }
+
+    #[tokio::test]
+    async fn test_write_response() {
+        let mut output = Vec::new();
+        write_response(&mut output, &Response::text(401, "未授权\n")).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(output.contains("Content-Length: 10\r\n"));
+        assert!(output.contains("WWW-Authenticate: Bearer\r\n"));
+        assert!(output.ends_with("\r\n\r\n未授权\n"));
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::Result;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+
+/// 缓存目录下存放图片的子目录
+const IMAGES_DIR: &str = "images";
+
+/// 图片缓存的默认目录: <系统缓存目录>/clipboard-sync-alt/images
+pub fn default_dir() -> Option<PathBuf> {
+    config::cache_dir().map(|dir| dir.join(IMAGES_DIR))
+}
+
+/// 缓存中的一个图片文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedImage {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// 把同步过的图片保存到磁盘，总大小超出上限时删除最旧的文件
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ImageCache {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self { dir: dir.into(), max_bytes }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 以时间戳命名保存一张图片（扩展名按图片格式确定），然后按上限清理旧文件
+    pub fn save(&self, data: &[u8]) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let extension = extension(data);
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        // 同一毫秒内保存多张时追加序号，不覆盖已有文件
+        let mut suffix = 0;
+        let (path, mut file) = loop {
+            let name = match suffix {
+                0 => format!("image-{}.{}", millis, extension),
+                n => format!("image-{}-{}.{}", millis, n, extension),
+            };
+            let path = self.dir.join(name);
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => suffix += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        file.write_all(data)?;
+
+        self.evict()?;
+        Ok(path)
+    }
+
+    /// 列出缓存中的图片，按时间从旧到新排序；目录不存在时返回空列表
+    pub fn list(&self) -> Result<Vec<CachedImage>> {
+        list_images(&self.dir)
+    }
+
+    /// 删除最旧的文件，直到总大小不超过上限
+    fn evict(&self) -> Result<()> {
+        let images = self.list()?;
+        let mut total: u64 = images.iter().map(|image| image.size).sum();
+        for image in images {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(&image.path)?;
+            total -= image.size;
+        }
+        Ok(())
+    }
+}
+
+/// 列出目录中的图片文件，按修改时间和文件名从旧到新排序
+pub fn list_images(dir: &Path) -> Result<Vec<CachedImage>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut images = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        // 只管理本程序保存的文件
+        let is_cached = entry.file_name().to_string_lossy().starts_with("image-");
+        if metadata.is_file() && is_cached {
+            images.push(CachedImage {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+    images.sort_by(|a, b| (a.modified, &a.path).cmp(&(b.modified, &b.path)));
+    Ok(images)
+}
+
+/// 按文件头确定扩展名，无法识别时为 bin
+#[cfg(feature = "image-support")]
+fn extension(data: &[u8]) -> &'static str {
+    image::guess_format(data)
+        .ok()
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("bin")
+}
+
+/// 未编译图片支持时不识别格式
+#[cfg(not(feature = "image-support"))]
+fn extension(_data: &[u8]) -> &'static str {
+    "bin"
+}
+
+#[cfg(all(test, feature = "image-support"))]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("clipboard-sync-alt-test-{}-{}", std::process::id(), name))
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::RgbaImage::new(width, height)
+            .write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_save_and_list() {
+        let dir = temp_dir("image-cache-save");
+        let cache = ImageCache::new(&dir, 1024 * 1024);
+        assert!(cache.list().unwrap().is_empty());
+
+        let first = cache.save(&png(2, 2)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let second = cache.save(&png(3, 3)).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.extension().unwrap(), "png");
+
+        let images = cache.list().unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(fs::read(&images[0].path).unwrap(), png(2, 2));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_over_limit() {
+        let dir = temp_dir("image-cache-evict");
+        let size = png(4, 4).len() as u64;
+        // 只能容纳两张
+        let cache = ImageCache::new(&dir, size * 2);
+
+        let first = cache.save(&png(4, 4)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let second = cache.save(&png(4, 4)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let third = cache.save(&png(4, 4)).unwrap();
+
+        let paths: Vec<_> = cache.list().unwrap().into_iter().map(|image| image.path).collect();
+        assert_eq!(paths, vec![second, third]);
+        assert!(!first.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
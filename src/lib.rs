@@ -0,0 +1,125 @@
+//! 跨平台剪贴板同步引擎 (TCP直连版本)
+//!
+//! 可以直接嵌入到其他 Rust 程序中使用：
+//!
+//! ```no_run
+//! use clipboard_sync_alt::{ClipboardContent, NetworkManager};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let network = NetworkManager::new("我的设备".to_string());
+//! network.start_server(8765).await?;
+//!
+//! // 订阅其他设备发来的剪贴板消息
+//! let mut receiver = network.setup_message_handler().await;
+//! tokio::spawn(async move {
+//!     while let Some(message) = receiver.recv().await {
+//!         println!("{}: {}", message.sender_name, message.content.preview(50));
+//!     }
+//! });
+//!
+//! // 推送本地内容
+//! network.broadcast_content(ClipboardContent::Text("hello".to_string())).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+/// 输出面向用户的日志，JSON Lines 事件模式下不输出
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::events::json_output() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 输出调试日志，只在开启详细日志（`--verbose`）且不处于 JSON Lines 事件模式时输出
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::events::verbose() && !$crate::events::json_output() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 输出帧层的跟踪日志，只在日志级别为 trace（`--log-level trace`）且不处于 JSON Lines 事件模式时输出
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::events::trace() && !$crate::events::json_output() {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub mod access;
+pub(crate) mod auth;
+pub mod clipboard;
+pub mod compression;
+pub mod config;
+pub mod doctor;
+pub mod error;
+pub mod events;
+pub mod foreground;
+pub(crate) mod frame;
+pub(crate) mod handshake;
+pub mod health;
+pub mod history;
+#[cfg(feature = "history-sqlite")]
+pub mod history_sqlite;
+#[cfg(feature = "hotkey")]
+pub mod hotkey;
+pub mod http_api;
+pub mod image_cache;
+pub mod monitor;
+pub mod network_alternative;
+#[cfg(feature = "noise")]
+pub(crate) mod noise;
+pub mod notification;
+pub mod observe;
+pub mod os_history;
+pub mod pairing;
+pub mod peers;
+pub mod profile;
+pub mod queue;
+pub mod selection;
+pub mod session;
+pub mod structured;
+pub mod text_encoding;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transform;
+pub mod transport;
+#[cfg(feature = "websocket")]
+pub(crate) mod websocket;
+
+pub use access::{AccessList, ContentPolicy, ContentTypes};
+pub use clipboard::{ArboardBackend, ClipboardBackend, ClipboardContentType, ClipboardManager, ContentPriority, MemoryClipboard};
+#[cfg(feature = "image-support")]
+pub use clipboard::{ImageCodec, PngCodec};
+pub use compression::{CompressionPolicy, Encoding};
+pub use error::{SyncError, SyncResult};
+pub use events::Event;
+pub use frame::PROTOCOL_VERSION;
+pub use health::HealthSnapshot;
+pub use image_cache::ImageCache;
+pub use monitor::{run_clipboard_monitor, run_relay, send_text, sync_once, ConflictPolicy, MonitorOptions, SyncControl};
+pub use network_alternative::{
+    BroadcastResult, ClipboardContent, ClipboardMessage, MessageOrdering, NameCollision, NetworkManager, SocketOptions,
+    SyncDirection, SyncEvent,
+};
+pub use notification::{NotificationManager, NotifyKind};
+pub use pairing::ConnectTarget;
+pub use peers::PeerStore;
+pub use queue::{MessageQueue, QueueFullPolicy};
+pub use selection::Selection;
+pub use session::SessionLimits;
+pub use structured::Color;
+pub use text_encoding::FallbackEncoding;
+#[cfg(feature = "tls")]
+pub use tls::TlsSettings;
+pub use transform::TextTransform;
+pub use transport::{Encryption, FramedConnection, FramedReader, FramedWriter, TcpTransport, Transport, TransportKind};
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketTransport;
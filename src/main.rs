@@ -1,12 +1,32 @@
-mod clipboard;
-mod network_alternative;
-mod notification;
+#[cfg(feature = "tray")]
+mod tray;
 
-use clipboard::ClipboardManager;
-use network_alternative::NetworkManager;
-use notification::NotificationManager;
+use clipboard_sync_alt::monitor::{DEFAULT_APPLY_SUPPRESSION, DEFAULT_MAX_UPDATES_PER_SEC};
+use clipboard_sync_alt::events::LogLevel;
+use clipboard_sync_alt::{access, clipboard, config, doctor, events, foreground, health, history, http_api, image_cache, notification, observe, os_history, pairing, profile, status, Event};
+use clipboard_sync_alt::history::History;
+#[cfg(feature = "history-sqlite")]
+use clipboard_sync_alt::history_sqlite::SqliteHistory;
+use clipboard_sync_alt::profile::{Profile, SecretRef};
+use clipboard_sync_alt::{
+    run_clipboard_monitor, run_relay, send_text, sync_once, AccessList, ClipboardManager, CompressionPolicy, ConflictPolicy, ConnectTarget, ContentPolicy,
+    ContentPriority, ContentTypes, Encoding, Encryption, FallbackEncoding, ImageCache, MessageOrdering, MonitorOptions, NameCollision, NetworkManager, NotificationManager,
+    PeerStore, QueueFullPolicy, Selection, SessionLimits, SocketOptions, SyncDirection, TextTransform, Transport, TransportKind, PROTOCOL_VERSION,
+};
+use clipboard_sync_alt::foreground::AppFilter;
+use clipboard_sync_alt::queue::DEFAULT_QUEUE_CAPACITY;
+#[cfg(feature = "hotkey")]
+use clipboard_sync_alt::hotkey::{self, HotkeyMode};
+#[cfg(feature = "tls")]
+use clipboard_sync_alt::TlsSettings;
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use ipnet::IpNet;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Parser)]
@@ -17,290 +37,1351 @@ struct Cli {
     command: Commands,
 }
 
+/// `start --help` 末尾显示的 systemd 服务示例
+const SYSTEMD_UNIT_EXAMPLE: &str = "\
+作为 systemd 用户服务运行（保存为 ~/.config/systemd/user/clipboard-sync.service，
+然后执行 systemctl --user enable --now clipboard-sync）:
+
+  [Unit]
+  Description=剪贴板同步服务
+  After=graphical-session.target
+  PartOf=graphical-session.target
+
+  [Service]
+  # 以 --features systemd 编译时开始监听后才通知就绪，否则改为 Type=simple
+  Type=notify
+  ExecStart=/usr/local/bin/clipboard-sync-alt start --port 8765
+  Restart=on-failure
+
+  [Install]
+  WantedBy=graphical-session.target
+
+systemctl stop 发送的 SIGTERM 与 Ctrl+C 一样会正常关闭连接后退出。";
+
 #[derive(Subcommand)]
 enum Commands {
     /// 启动同步服务（作为服务器）
+    #[command(after_long_help = SYSTEMD_UNIT_EXAMPLE)]
     Start {
-        /// 设备名称
-        #[arg(short, long, default_value = "我的设备")]
+        /// 设备名称，默认使用本机的主机名
+        #[arg(short, long, default_value_t = config::default_device_name())]
         name: String,
-        /// 监听端口
+        /// 监听端口，0 表示由系统分配空闲端口
         #[arg(short, long, default_value_t = 8765)]
         port: u16,
+        /// 监听端口被占用时依次尝试后续的 N 个端口
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        port_retry: u16,
+        /// 在终端显示配对二维码，手机扫码即可获得连接链接
+        #[arg(long, conflicts_with = "events_json")]
+        qr: bool,
+        /// 二维码中使用的本机地址，存在多个网络接口时用于选择，默认与 --advertise 相同
+        #[arg(long, value_name = "IP", requires = "qr")]
+        qr_addr: Option<IpAddr>,
+        /// 启动信息和配对二维码/链接中告诉其他设备的本机地址，与监听的地址无关；
+        /// 有 VPN 或虚拟网卡时自动选择的地址可能无法从其他设备访问，可以用它指定
+        #[arg(long, value_name = "IP")]
+        advertise: Option<IpAddr>,
+        /// 同时保持的最大连接数，达到上限后拒绝新的连接，默认不限制
+        #[arg(long, value_name = "N")]
+        max_connections: Option<usize>,
+        /// 只允许来自这些地址的连接（IP 或 CIDR，如 192.168.1.0/24），可重复指定，默认允许所有地址
+        #[arg(long, value_name = "CIDR", value_parser = access::parse_net)]
+        allow: Vec<IpNet>,
+        /// 拒绝来自这些地址的连接（IP 或 CIDR），可重复指定，优先于 --allow
+        #[arg(long, value_name = "CIDR", value_parser = access::parse_net)]
+        deny: Vec<IpNet>,
+        /// 同时主动连接的设备（ip:port 或 clipsync:// 链接），与接受的连接一起同步，可重复指定
+        #[arg(long = "connect", value_name = "ADDR")]
+        connect: Vec<String>,
+        /// 作为公网中继运行：设备用 --via 和 --room 连接进来，消息只在同一房间的设备之间转发（需要 --no-clipboard）
+        #[arg(long, requires = "no_clipboard")]
+        relay: bool,
+        /// 中继中某个房间的预共享密钥，格式为 <房间>=<密钥>，可重复指定；未列出的房间使用 --secret
+        #[arg(long = "room-secret", value_name = "ROOM=KEY", requires = "relay", value_parser = parse_room_secret)]
+        room_secrets: Vec<(String, String)>,
+        #[command(flatten)]
+        sync: SyncArgs,
     },
     /// 连接到指定设备
     Connect {
+        /// 设备名称，默认使用本机的主机名
+        #[arg(short, long, default_value_t = config::default_device_name())]
+        name: String,
+        /// 目标设备IP地址或 clipsync:// 配对链接，未指定任何目标时连接之前配对过的设备
+        ip: Option<String>,
+        /// 目标设备端口
+        #[arg(short, long, default_value_t = 8765)]
+        port: u16,
+        /// 额外连接的设备，格式为 ip:port[:send|recv|both]（省略端口时使用 --port），可重复指定；
+        /// send 表示只向该设备发送，recv 表示只接收该设备的内容，默认双向
+        #[arg(long = "peer", value_name = "ADDR")]
+        peers: Vec<String>,
+        /// 连接超时时间（秒）
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        connect_timeout: u64,
+        /// 清除已保存的配对设备
+        #[arg(long)]
+        forget_peers: bool,
+        /// 发送当前剪贴板内容后立即退出，不进入监控循环
+        #[arg(long, conflicts_with = "no_clipboard")]
+        once: bool,
+        /// 通过中继连接（ip:port），无需端口转发即可与其他网络中的设备同步，需要同时指定 --room
+        #[arg(long, value_name = "ADDR", conflicts_with_all = ["ip", "peers"], requires = "room")]
+        via: Option<String>,
+        /// 通过中继连接时加入的房间，同一房间的设备互相同步
+        #[arg(long, value_name = "ID", requires = "via")]
+        room: Option<String>,
+        #[command(flatten)]
+        sync: SyncArgs,
+    },
+    /// 观察模式：连接设备并记录收到的每条剪贴板内容，不访问本地剪贴板，也不发送任何内容
+    Observe {
         /// 设备名称
-        #[arg(short, long, default_value = "我的设备")]
+        #[arg(short, long, default_value = "观察者")]
         name: String,
-        /// 目标设备IP地址
+        /// 目标设备IP地址或 clipsync:// 配对链接
         ip: String,
         /// 目标设备端口
         #[arg(short, long, default_value_t = 8765)]
         port: u16,
+        /// 额外观察的设备，格式为 ip:port（省略端口时使用 --port），可重复指定
+        #[arg(long = "peer", value_name = "ADDR")]
+        peers: Vec<String>,
+        /// 连接超时时间（秒）
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        connect_timeout: u64,
+        /// 输出完整文本，默认只显示预览
+        #[arg(long)]
+        full: bool,
+        /// 把收到的每条消息以 JSON Lines 格式追加写入该文件（文本完整记录，图片只记录尺寸和大小）
+        #[arg(long, value_name = "PATH")]
+        transcript: Option<PathBuf>,
+        /// 预共享密钥，双方一致才能连接
+        #[arg(long, value_name = "KEY")]
+        secret: Option<String>,
+        /// 从文件读取预共享密钥，优先级: --secret > --secret-file > CLIPSYNC_SECRET
+        #[arg(long, value_name = "PATH")]
+        secret_file: Option<PathBuf>,
+        /// 以 JSON Lines 格式向标准输出打印事件，代替带 emoji 的日志
+        #[arg(long)]
+        events_json: bool,
+        /// 连接后请求对方发送它当前的剪贴板内容，不必等到对方下一次复制
+        #[arg(long)]
+        request_current: bool,
+        #[cfg(feature = "tls")]
+        #[command(flatten)]
+        tls: TlsArgs,
+    },
+    /// 把指定的文本发送给设备后退出，不读取也不修改本地剪贴板
+    SendText {
+        /// 要发送的文本，为 - 时从标准输入读取（去掉末尾的一个换行）
+        text: String,
+        /// 设备名称，默认使用本机的主机名
+        #[arg(short, long, default_value_t = config::default_device_name())]
+        name: String,
+        /// 目标设备IP地址或 clipsync:// 配对链接，未指定任何目标时发送给之前配对过的设备
+        ip: Option<String>,
+        /// 目标设备端口
+        #[arg(short, long, default_value_t = 8765)]
+        port: u16,
+        /// 额外发送的设备，格式为 ip:port（省略端口时使用 --port），可重复指定
+        #[arg(long = "peer", value_name = "ADDR")]
+        peers: Vec<String>,
+        /// 连接超时时间（秒）
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        connect_timeout: u64,
+        /// 预共享密钥，双方一致才能连接
+        #[arg(long, value_name = "KEY")]
+        secret: Option<String>,
+        /// 从文件读取预共享密钥，优先级: --secret > --secret-file > CLIPSYNC_SECRET
+        #[arg(long, value_name = "PATH")]
+        secret_file: Option<PathBuf>,
+        #[cfg(feature = "tls")]
+        #[command(flatten)]
+        tls: TlsArgs,
+    },
+    /// 显示版本、协议版本、编译目标和已启用的可选功能，便于反馈问题
+    Info {
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
     },
+    /// 诊断当前平台的剪贴板读写和系统通知，给出失败项的解决建议，不进行网络通信
+    Doctor,
+    /// 列出图片缓存中保存的图片
+    Cache {
+        /// 图片缓存目录，默认为系统缓存目录下的 clipboard-sync-alt/images
+        #[arg(long, value_name = "PATH")]
+        dir: Option<PathBuf>,
+        /// 在文件管理器中打开缓存目录
+        #[arg(long)]
+        open: bool,
+    },
+    /// 把配置（设备名称、端口、选项、密钥来源）和已配对设备导出到一个 JSON 文件，用于在新设备上导入
+    ExportConfig {
+        /// 导出文件的路径
+        path: PathBuf,
+        /// 在导出文件中包含密钥本身，默认只记录密钥来源（密钥文件路径或环境变量）
+        #[arg(long)]
+        include_secret: bool,
+        /// 覆盖已存在的文件
+        #[arg(long)]
+        force: bool,
+        /// 平时运行的命令（如 -- start --name 笔记本 --port 9000），省略时导出已导入的配置
+        #[arg(last = true, value_name = "COMMAND")]
+        args: Vec<String>,
+    },
+    /// 导入 export-config 导出的配置，之后不带参数运行即按导入的配置启动
+    ImportConfig {
+        /// 导出文件的路径
+        path: PathBuf,
+        /// 覆盖之前导入的配置
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// 服务器和客户端共用的同步选项
+#[derive(Args)]
+struct SyncArgs {
+    /// 剪贴板同时包含多种形式时优先同步哪一种 (text, image, html)：html 同步浏览器等程序复制的带格式内容，并附带纯文本
+    #[arg(long, default_value = "text")]
+    prefer: ContentPriority,
+    /// 同步的选区 (clipboard, primary, both)：primary 为 Linux 上选中即复制、中键粘贴的 PRIMARY 选区，both 同步两者中最近变化的一个；其他平台只有一个剪贴板，忽略此选项
+    #[arg(long, value_name = "SELECTION", default_value = "clipboard", conflicts_with = "no_clipboard")]
+    selection: Selection,
+    /// 剪贴板文本不是有效的 UTF-8 时（多为使用旧编码的程序复制的）按该编码解码（如 gbk、shift_jis、windows-1252），无法解码的部分替换为 U+FFFD；默认只接受 UTF-8，这样的文本不会同步
+    #[arg(long, value_name = "ENCODING", conflicts_with = "no_clipboard")]
+    text_encoding: Option<FallbackEncoding>,
+    /// 同步通知的合并窗口（毫秒），窗口内的多次同步合并为一条通知，0 表示不合并
+    #[arg(long, value_name = "MS", default_value_t = 2000)]
+    notify_throttle: u64,
+    /// 系统通知的显示时长（毫秒），0 表示一直显示直到手动关闭
+    #[arg(long, value_name = "MS", default_value_t = 3000)]
+    notify_timeout: u64,
+    /// 通知中显示的应用名称，Linux 上通知按应用名称分组
+    #[arg(long, value_name = "NAME", default_value = notification::DEFAULT_APP_NAME)]
+    notify_app_name: String,
+    /// 通知使用的图标文件，默认按内容类型使用系统图标
+    #[arg(long, value_name = "PATH")]
+    notify_icon: Option<PathBuf>,
+    /// 与设备建立连接和连接意外断开时也发送通知，与同步通知使用相同的合并窗口
+    #[arg(long, conflicts_with = "no_notify")]
+    notify_connections: bool,
+    /// 禁用所有通知（包括控制台中的 🔔 提示），适用于无桌面环境的服务器
+    #[arg(long)]
+    no_notify: bool,
+    /// 同步清空操作：本地剪贴板被清空时通知其他设备清空，并响应其他设备的清空
+    #[arg(long)]
+    sync_clears: bool,
+    /// 同步前规范化文本：CRLF 转为 LF，并去掉末尾的一个换行
+    #[arg(long)]
+    normalize_text: bool,
+    /// 广播文本的最大字节数，超出部分会被截断（不会拆开多字节字符）
+    #[arg(long, value_name = "BYTES")]
+    max_text_bytes: Option<usize>,
+    /// 收到的图片允许的最大像素数（百万像素），超出时拒绝写入剪贴板
+    #[arg(long, value_name = "MP", default_value_t = 64)]
+    max_image_megapixels: u64,
+    /// 剪贴板图片的最小边长（像素），宽或高更小的图片（如部分程序临时放入的 1x1 占位图）不会同步
+    #[arg(long, value_name = "PX", default_value_t = clipboard::DEFAULT_MIN_IMAGE_SIZE)]
+    min_image_size: u32,
+    /// 剪贴板连续访问失败时（如锁屏解锁后句柄失效）最多重新初始化的次数，访问恢复后重新计数；0 表示不重新初始化
+    #[arg(long, value_name = "N", default_value_t = clipboard::DEFAULT_MAX_REINITS)]
+    clipboard_reinit: u32,
+    /// 把同步过的图片另存到缓存目录，总大小超过该值（MB）时删除最旧的图片
+    #[arg(long, value_name = "MB", conflicts_with = "no_clipboard")]
+    image_cache_mb: Option<u64>,
+    /// 图片缓存目录，默认为系统缓存目录下的 clipboard-sync-alt/images
+    #[arg(long, value_name = "PATH", requires = "image_cache_mb")]
+    image_cache_dir: Option<PathBuf>,
+    /// 把同步历史保存到该 SQLite 数据库，重启后仍可用交互命令 history 查看和搜索；需要 history-sqlite 特性
+    #[arg(long, value_name = "PATH", conflicts_with = "no_clipboard")]
+    history_db: Option<PathBuf>,
+    /// 历史数据库最多保留的记录数，超出时删除最旧的
+    #[arg(long, value_name = "N", default_value_t = history::DEFAULT_MAX_STORED, requires = "history_db")]
+    history_max_entries: usize,
+    /// 每个设备每秒最多应用的更新数，超出的更新合并为最新的一条，0 表示不限制
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_UPDATES_PER_SEC)]
+    max_updates_per_sec: u32,
+    /// 写入收到的内容后暂停检测本地剪贴板变化的时长（毫秒），等写入完成后再比较，避免把刚写入的内容当作本地变化；窗口内的复制在窗口结束后照常同步，0 表示不暂停
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_APPLY_SUPPRESSION.as_millis() as u64, conflicts_with = "no_clipboard")]
+    apply_suppression: u64,
+    /// 广播前对文本应用的替换规则，格式为 regex=>replacement，可重复指定，按顺序应用
+    #[arg(long = "transform", value_name = "RULE")]
+    transforms: Vec<String>,
+    /// 对收到的文本也应用 --transform 规则
+    #[arg(long, requires = "transforms")]
+    transform_incoming: bool,
+    /// 预共享密钥，双方一致才能连接（会出现在 shell 历史和进程列表中，建议使用 --secret-file 或环境变量 CLIPSYNC_SECRET）
+    #[arg(long, value_name = "KEY")]
+    secret: Option<String>,
+    /// 从文件读取预共享密钥，优先级: --secret > --secret-file > CLIPSYNC_SECRET
+    #[arg(long, value_name = "PATH")]
+    secret_file: Option<PathBuf>,
+    /// 不设置 TCP_NODELAY，允许系统合并小数据包发送
+    #[arg(long)]
+    no_nodelay: bool,
+    /// 连接空闲多久后开始发送 TCP 保活探测（秒）
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    keepalive_idle: u64,
+    /// TCP 保活探测的间隔（秒）
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    keepalive_interval: u64,
+    /// 不访问本地剪贴板，只在已连接的设备之间转发消息，适用于没有桌面环境的常驻中继服务器
+    #[arg(long)]
+    no_clipboard: bool,
+    /// 启动本地 HTTP 接口的端口，可通过 GET/POST /clipboard 读取和设置剪贴板
+    #[arg(long, value_name = "PORT", conflicts_with = "no_clipboard")]
+    http_port: Option<u16>,
+    /// HTTP 接口监听的地址，默认只允许本机访问
+    #[arg(long, value_name = "IP", default_value = "127.0.0.1", requires = "http_port")]
+    http_bind: IpAddr,
+    /// HTTP 接口的访问令牌，设置后请求需要携带 Authorization: Bearer <TOKEN>
+    #[arg(long, value_name = "TOKEN", requires = "http_port")]
+    http_token: Option<String>,
+    /// 发现其他设备与本机同名时的处理方式：warn 只输出警告，suffix 在本机名称后追加设备标识前几位
+    #[arg(long, value_name = "POLICY", default_value = "warn")]
+    name_collision: NameCollision,
+    /// 判断同一设备消息先后顺序的依据：time 按发送方的系统时间，sequence 按消息序号（设备之间时钟不同步时使用）
+    #[arg(long, value_name = "MODE", default_value = "time")]
+    ordering: MessageOrdering,
+    /// 连接使用的传输方式：tcp 直接使用 TCP，ws 使用 WebSocket（便于穿过 HTTP 代理或接入浏览器客户端，需要 websocket 特性）；所有设备必须一致
+    #[arg(long, value_name = "TRANSPORT", default_value = "tcp")]
+    transport: TransportKind,
+    /// 连接的加密方式：none 不额外加密，noise 使用 Noise 协议加密所有数据（前向保密，设置 --secret 时以它认证双方，不需要证书，需要 noise 特性）；所有设备必须一致
+    #[arg(long, value_name = "MODE", default_value = "none")]
+    encrypt: Encryption,
+    /// 连接时声明本机能解码的压缩方式，按优先级排列，用逗号分隔（zstd、gzip、none）；发送时使用对方也支持的第一种
+    #[arg(long, value_name = "LIST", value_delimiter = ',', default_value = "zstd,gzip,none")]
+    compression: Vec<Encoding>,
+    /// 何时压缩发送的消息：auto 按内容类型决定（文本和图片压缩，图片在消息中以数字数组表示，压缩后才接近原图大小；清空不压缩），always 总是压缩，never 从不压缩
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    compress: CompressionPolicy,
+    /// 限制某些设备可以发送的内容类型，格式为 <IP 或 CIDR>=<类型>（类型为 text、image、clear 的组合，或 all、none），如 192.168.1.20=text,clear；可重复指定，第一条匹配的规则生效，默认全部允许
+    #[arg(long = "peer-accept", value_name = "RULE", value_parser = access::parse_content_rule)]
+    peer_accept: Vec<(IpNet, ContentTypes)>,
+    /// 接收队列最多暂存的消息数，写入剪贴板跟不上接收速度时按 --queue-full 处理
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_QUEUE_CAPACITY)]
+    queue_capacity: usize,
+    /// 接收队列已满时的处理方式：drop-oldest 丢弃最早的消息，drop-newest 丢弃新消息，block 暂停读取连接（由 TCP 向发送方施加背压）
+    #[arg(long, value_name = "POLICY", default_value = "drop-oldest")]
+    queue_full: QueueFullPolicy,
+    /// 本地刚复制的内容与收到的内容冲突时保留哪一方：remote 总是应用收到的内容，latest 比较时间保留较新的一方，local 本地变化稍早于收到的内容时也保留本地
+    #[arg(long, value_name = "POLICY", default_value = "remote", conflicts_with = "no_clipboard")]
+    conflict: ConflictPolicy,
+    /// 只在指定的前台应用（macOS 为 Bundle ID 或应用名称，Windows 为可执行文件名）中应用收到的内容，可重复指定；其他应用在前台时暂缓，切换过去后应用最新的一条。需要 app-filter 特性，目前仅支持 Windows 和 macOS
+    #[arg(long = "apply-only-in", value_name = "APP", conflicts_with = "no_clipboard")]
+    apply_only_in: Vec<String>,
+    /// 把收到的文本推入系统剪贴板历史（Windows 上按 Win+V 查看），之前同步来的内容不会因被覆盖而找不回来。需要 os-history 特性，目前仅支持 Windows 10 及以上，其他平台照常写入
+    #[arg(long = "os-history", conflicts_with = "no_clipboard")]
+    os_history: bool,
+    /// 从标准输入读取控制命令，如 peers、send <设备或文本>、pause/resume、kick <设备>、history [--search <关键字>]、quit，输入 help 查看全部命令
+    #[arg(long, conflicts_with = "no_clipboard")]
+    interactive: bool,
+    /// 以 JSON Lines 格式向标准输出打印事件（连接、断开、发送、接收、错误），代替带 emoji 的日志，便于其他程序解析
+    #[arg(long)]
+    events_json: bool,
+    /// 日志中不输出剪贴板的完整文本，只输出开头的预览和字符数，适合在共享终端中运行或保存日志文件时使用
+    #[arg(long)]
+    redact_logs: bool,
+    /// 输出调试日志，如每次心跳后各连接的延迟和最近收到数据的时间，等同于 --log-level debug
+    #[arg(long)]
+    verbose: bool,
+    /// 日志级别 (info, debug, trace)：trace 另外输出每一帧的长度、开头的字节和解码结果，用于排查协议问题
+    #[arg(long, value_name = "LEVEL", default_value_t = LogLevel::Info)]
+    log_level: LogLevel,
+    /// 帧跟踪日志中显示消息内容开头的字节（默认隐藏）；内容可能包含剪贴板中的密码等敏感信息，只在排查问题时使用
+    #[arg(long)]
+    unsafe_trace_payload: bool,
+    /// 连接设备后请求对方发送它当前的剪贴板内容，不必等到对方下一次复制（对方需要协议 v7 及以上）
+    #[arg(long)]
+    request_current: bool,
+    /// 没有已连接的设备时保留最近复制的内容（每种类型只保留最新的一条），设备连接后立即发送给它，晚连接的设备也能拿到当前的剪贴板内容
+    #[arg(long, conflicts_with = "no_clipboard")]
+    send_on_connect: bool,
+    /// 心跳间隔（秒），定期测量各连接的往返延迟，延迟突增或连续未回复心跳时给出提示；0 表示不发送心跳
+    #[arg(long, value_name = "SECS", default_value_t = health::DEFAULT_HEARTBEAT_INTERVAL.as_secs())]
+    heartbeat: u64,
+    /// 超过指定分钟数没有剪贴板变化、收到消息或交互命令时自动退出，避免忘记关闭的同步会话一直开着
+    #[arg(long, value_name = "MINS", value_parser = clap::value_parser!(u64).range(1..))]
+    idle_timeout: Option<u64>,
+    /// 运行指定分钟数后自动退出，无论是否有活动
+    #[arg(long, value_name = "MINS", value_parser = clap::value_parser!(u64).range(1..))]
+    max_session: Option<u64>,
+    /// 指定设备标识，默认使用配置目录中保存的固定标识（首次运行时生成）
+    #[arg(long, value_name = "ID")]
+    device_id: Option<String>,
+    /// 显示系统托盘图标，可在菜单中暂停/恢复同步、切换通知和退出
+    #[cfg(feature = "tray")]
+    #[arg(long)]
+    tray: bool,
+    /// 全局快捷键（如 ctrl+shift+KeyS），启动后同步保持暂停，按快捷键开启
+    #[cfg(feature = "hotkey")]
+    #[arg(long, value_name = "CHORD")]
+    hotkey: Option<String>,
+    /// 快捷键的工作方式：toggle 每按一次切换开启/暂停，hold 按住时同步
+    #[cfg(feature = "hotkey")]
+    #[arg(long, value_name = "MODE", default_value = "toggle", requires = "hotkey")]
+    hotkey_mode: HotkeyMode,
+    #[cfg(feature = "tls")]
+    #[command(flatten)]
+    tls: TlsArgs,
+}
+
+/// TLS 和证书认证选项
+#[cfg(feature = "tls")]
+#[derive(Args)]
+struct TlsArgs {
+    /// 接受的连接使用 TLS，指定服务器证书（PEM）
+    #[arg(long, value_name = "PATH", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// 服务器证书的私钥（PEM）
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// 主动发起的连接使用 TLS，需要同时指定 --ca-cert 用于校验服务器证书
+    #[arg(long, requires = "ca_cert")]
+    tls: bool,
+    /// 受信任的 CA 证书（PEM），用于校验服务器证书和客户端证书
+    #[arg(long, value_name = "PATH")]
+    ca_cert: Option<PathBuf>,
+    /// 只接受出示了由 --ca-cert 签发的客户端证书的连接
+    #[arg(long, requires_all = ["tls_cert", "ca_cert"])]
+    require_client_cert: bool,
+    /// 主动连接时出示的客户端证书（PEM）
+    #[arg(long, value_name = "PATH", requires_all = ["client_key", "tls"])]
+    client_cert: Option<PathBuf>,
+    /// 客户端证书的私钥（PEM）
+    #[arg(long, value_name = "PATH", requires = "client_cert")]
+    client_key: Option<PathBuf>,
+    /// 校验服务器证书时使用的名称，默认使用连接的 IP 地址
+    #[arg(long, value_name = "NAME", requires = "tls")]
+    tls_server_name: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(command_line());
 
-    // 初始化剪贴板管理器
-    let clipboard = ClipboardManager::new()?;
-    
     match cli.command {
-        Commands::Start { name, port } => {
-            let network = NetworkManager::new(name);
-            run_server(clipboard, network, port).await?;
+        Commands::Start { name, port, port_retry, max_connections, allow, deny, qr, qr_addr, advertise, connect, relay, room_secrets, sync } => {
+            init_logging(&sync);
+            let outbound = connect_targets(None, &connect, port)?;
+            let clipboard = init_optional_clipboard(&sync)?;
+            let notifier = init_notifier(&sync);
+            let mut network = NetworkManager::new(name)
+                .with_device_id(device_id(sync.device_id.as_deref()))
+                .with_name_collision(sync.name_collision)
+                .with_ordering(sync.ordering)
+                .with_encodings(sync.compression.clone())
+                .with_compression_policy(sync.compress)
+                .with_transport(transport(&sync)?)
+                .with_encryption(encryption(&sync)?)
+                .with_content_policy(ContentPolicy::new(sync.peer_accept.clone()))
+                .with_message_queue(sync.queue_capacity, sync.queue_full)
+                .with_socket_options(socket_options(&sync))
+                .with_heartbeat(Some(Duration::from_secs(sync.heartbeat)))
+                .with_send_on_connect(sync.send_on_connect)
+                .with_request_current(sync.request_current)
+                .with_relay(sync.no_clipboard)
+                .with_port_retry(port_retry)
+                .with_access_list(AccessList::new(allow, deny));
+            if sync.notify_connections {
+                network = network.with_connection_notifications(notifier.clone());
+            }
+            #[cfg(feature = "tls")]
+            {
+                network = network.with_tls(tls_settings(&sync.tls)?);
+            }
+            if let Some(max_connections) = max_connections {
+                network = network.with_max_connections(max_connections);
+            }
+            if relay {
+                network = network.with_rooms(room_secrets.into_iter().collect());
+            } else if let Some(store) = load_peer_store() {
+                // 记录主动连接和连接进来的设备，下次以 connect 启动时可自动重连
+                network = network.with_peer_store(store);
+            }
+            let secret = config::resolve_secret(sync.secret.clone(), sync.secret_file.as_deref())?;
+            if let Some(secret) = &secret {
+                network = network.with_secret(secret);
+            }
+            let opts = monitor_options(&sync)?;
+            #[cfg(feature = "hotkey")]
+            if let Some(chord) = &sync.hotkey {
+                hotkey::spawn(chord, sync.hotkey_mode, opts.control.clone())?;
+            }
+            #[cfg(feature = "tray")]
+            if sync.tray {
+                tray::spawn(network.clone(), notifier.clone(), opts.control.clone());
+            }
+            let pairing = Pairing { advertise, qr: qr.then_some(PairingQr { addr: qr_addr, secret }) };
+            start_http_api(&sync, clipboard.as_ref()).await?;
+            run_server(clipboard, network, notifier, opts, port, pairing, &outbound).await?;
+        }
+        Commands::Connect { name, ip, port, peers, connect_timeout, forget_peers, once, via, room, sync } => {
+            init_logging(&sync);
+            let mut store = load_peer_store();
+            if forget_peers {
+                if let Some(store) = &mut store {
+                    store.clear()?;
+                    status!("🗑️ 已清除保存的配对设备");
+                }
+            }
+            
+            let mut targets = connect_targets(via.as_deref().or(ip.as_deref()), &peers, port)?;
+            if targets.is_empty() {
+                if forget_peers {
+                    return Ok(());
+                }
+                targets = store
+                    .as_ref()
+                    .map(|store| store.addresses().into_iter().map(ConnectTarget::from).collect())
+                    .unwrap_or_default();
+                if targets.is_empty() {
+                    return Err(anyhow::anyhow!("未指定目标设备，也没有之前配对过的设备"));
+                }
+                status!("📒 连接之前配对过的 {} 个设备", targets.len());
+            }
+            
+            let clipboard = init_optional_clipboard(&sync)?;
+            let notifier = init_notifier(&sync);
+            let mut network = NetworkManager::new(name)
+                .with_device_id(device_id(sync.device_id.as_deref()))
+                .with_name_collision(sync.name_collision)
+                .with_ordering(sync.ordering)
+                .with_encodings(sync.compression.clone())
+                .with_compression_policy(sync.compress)
+                .with_transport(transport(&sync)?)
+                .with_encryption(encryption(&sync)?)
+                .with_content_policy(ContentPolicy::new(sync.peer_accept.clone()))
+                .with_message_queue(sync.queue_capacity, sync.queue_full)
+                .with_socket_options(socket_options(&sync))
+                .with_heartbeat(Some(Duration::from_secs(sync.heartbeat)))
+                .with_send_on_connect(sync.send_on_connect)
+                .with_request_current(sync.request_current)
+                .with_relay(sync.no_clipboard)
+                .with_connect_timeout(Duration::from_secs(connect_timeout));
+            #[cfg(feature = "tls")]
+            {
+                network = network.with_tls(tls_settings(&sync.tls)?);
+            }
+            if sync.notify_connections {
+                network = network.with_connection_notifications(notifier.clone());
+            }
+            if let Some(store) = store {
+                network = network.with_peer_store(store);
+            }
+            if let Some(room) = room {
+                network = network.with_room(room);
+            }
+            if let Some(secret) = config::resolve_secret(sync.secret.clone(), sync.secret_file.as_deref())? {
+                network = network.with_secret(secret);
+            }
+            let opts = monitor_options(&sync)?;
+            if once {
+                let clipboard = clipboard.ok_or_else(|| anyhow::anyhow!("--once 需要访问剪贴板"))?;
+                return push_once(clipboard, network, opts, &targets).await;
+            }
+            #[cfg(feature = "hotkey")]
+            if let Some(chord) = &sync.hotkey {
+                hotkey::spawn(chord, sync.hotkey_mode, opts.control.clone())?;
+            }
+            #[cfg(feature = "tray")]
+            if sync.tray {
+                tray::spawn(network.clone(), notifier.clone(), opts.control.clone());
+            }
+            start_http_api(&sync, clipboard.as_ref()).await?;
+            connect_to_server(clipboard, network, notifier, opts, &targets).await?;
         }
-        Commands::Connect { name, ip, port } => {
-            let network = NetworkManager::new(name);
-            connect_to_server(clipboard, network, &ip, port).await?;
+        Commands::Observe {
+            name,
+            ip,
+            port,
+            peers,
+            connect_timeout,
+            full,
+            transcript,
+            secret,
+            secret_file,
+            events_json,
+            request_current,
+            #[cfg(feature = "tls")]
+            tls,
+        } => {
+            events::set_json_output(events_json);
+            // 观察者只接收，不向任何设备发送
+            let targets: Vec<ConnectTarget> = connect_targets(Some(&ip), &peers, port)?
+                .into_iter()
+                .map(|target| ConnectTarget { direction: SyncDirection::Receive, ..target })
+                .collect();
+            let transcript = transcript.as_deref().map(observe::Transcript::open).transpose()?;
+            let mut network = NetworkManager::new(name)
+                .with_device_id(device_id(None))
+                .with_connect_timeout(Duration::from_secs(connect_timeout))
+                .with_request_current(request_current);
+            #[cfg(feature = "tls")]
+            {
+                network = network.with_tls(tls_settings(&tls)?);
+            }
+            if let Some(secret) = config::resolve_secret(secret, secret_file.as_deref())? {
+                network = network.with_secret(secret);
+            }
+            observe_devices(network, &targets, full, transcript).await?;
         }
+        Commands::SendText {
+            text,
+            name,
+            ip,
+            port,
+            peers,
+            connect_timeout,
+            secret,
+            secret_file,
+            #[cfg(feature = "tls")]
+            tls,
+        } => {
+            let text = read_text_arg(text)?;
+            if text.is_empty() {
+                return Err(anyhow::anyhow!("要发送的文本为空"));
+            }
+            let mut targets = connect_targets(ip.as_deref(), &peers, port)?;
+            if targets.is_empty() {
+                targets = load_peer_store()
+                    .map(|store| store.addresses().into_iter().map(ConnectTarget::from).collect())
+                    .unwrap_or_default();
+                if targets.is_empty() {
+                    return Err(anyhow::anyhow!("未指定目标设备，也没有之前配对过的设备"));
+                }
+            }
+            let mut network = NetworkManager::new(name)
+                .with_device_id(device_id(None))
+                .with_connect_timeout(Duration::from_secs(connect_timeout));
+            #[cfg(feature = "tls")]
+            {
+                network = network.with_tls(tls_settings(&tls)?);
+            }
+            if let Some(secret) = config::resolve_secret(secret, secret_file.as_deref())? {
+                network = network.with_secret(secret);
+            }
+            push_text(network, &text, &targets).await?;
+        }
+        Commands::Info { json } => print_info(json)?,
+        Commands::Doctor => run_doctor()?,
+        Commands::Cache { dir, open } => show_image_cache(image_cache_dir(dir)?, open)?,
+        Commands::ExportConfig { path, include_secret, force, args } => export_config(&path, &args, include_secret, force)?,
+        Commands::ImportConfig { path, force } => import_config(&path, force)?,
     }
 
     Ok(())
 }
 
-/// 运行服务器模式
-async fn run_server(clipboard: ClipboardManager, network: NetworkManager, port: u16) -> Result<()> {
-    let notifier = NotificationManager::new();
+/// 编译信息，不依赖网络和剪贴板
+#[derive(serde::Serialize)]
+struct BuildInfo {
+    version: &'static str,
+    protocol_version: u32,
+    target: &'static str,
+    /// 可选功能及是否已编译
+    features: BTreeMap<&'static str, bool>,
+}
+
+impl BuildInfo {
+    fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            protocol_version: PROTOCOL_VERSION,
+            target: env!("BUILD_TARGET"),
+            features: BTreeMap::from([
+                ("app-filter", cfg!(feature = "app-filter")),
+                ("history-sqlite", cfg!(feature = "history-sqlite")),
+                ("hotkey", cfg!(feature = "hotkey")),
+                ("image-support", cfg!(feature = "image-support")),
+                ("noise", cfg!(feature = "noise")),
+                ("os-history", cfg!(feature = "os-history")),
+                ("systemd", cfg!(feature = "systemd")),
+                ("tls", cfg!(feature = "tls")),
+                ("tray", cfg!(feature = "tray")),
+                ("websocket", cfg!(feature = "websocket")),
+            ]),
+        }
+    }
+}
+
+/// 输出编译信息
+fn print_info(json: bool) -> Result<()> {
+    let info = BuildInfo::current();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
     
-    println!("🚀 启动剪贴板同步服务...");
+    println!("📦 版本: {} {}", env!("CARGO_PKG_NAME"), info.version);
+    println!("🔗 协议版本: {}", info.protocol_version);
+    println!("🎯 编译目标: {}", info.target);
+    println!("🧩 可选功能:");
+    for (feature, enabled) in &info.features {
+        println!("   {} {}", if *enabled { "✅" } else { "❌" }, feature);
+    }
+    Ok(())
+}
+
+/// 运行诊断检查并输出结果，有检查失败时返回错误
+fn run_doctor() -> Result<()> {
+    println!("🩺 正在检查剪贴板和系统通知（会临时改写剪贴板，结束后恢复原来的文本）...");
+    let results = doctor::run_checks();
+    for result in &results {
+        match &result.outcome {
+            Ok(detail) => println!("✅ {}: {}", result.name, detail),
+            Err(error) => {
+                println!("❌ {}: {}", result.name, error);
+                if let Some(hint) = result.hint {
+                    println!("   💡 {}", hint);
+                }
+            }
+        }
+    }
     
-    // 启动网络服务
-    network.start_server(port).await?;
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{}/{} 项检查失败", failed, results.len()));
+    }
+    println!("🎉 全部 {} 项检查通过", results.len());
+    Ok(())
+}
+
+/// 图片缓存目录，未指定时使用默认目录
+fn image_cache_dir(dir: Option<PathBuf>) -> Result<PathBuf> {
+    dir.or_else(image_cache::default_dir)
+        .ok_or_else(|| anyhow::anyhow!("无法确定缓存目录，请使用 --dir 指定"))
+}
+
+/// 列出缓存中的图片，`open` 时在文件管理器中打开缓存目录
+fn show_image_cache(dir: PathBuf, open: bool) -> Result<()> {
+    let images = image_cache::list_images(&dir)?;
+    println!("📁 图片缓存目录: {}", dir.display());
+    if images.is_empty() {
+        println!("   （暂无缓存的图片）");
+    }
+    for image in &images {
+        println!("   {} ({} KB)", image.path.display(), image.size.div_ceil(1024));
+    }
+    let total: u64 = images.iter().map(|image| image.size).sum();
+    println!("共 {} 张图片，{:.1} MB", images.len(), total as f64 / (1024.0 * 1024.0));
+
+    if open {
+        std::fs::create_dir_all(&dir)?;
+        let opener = if cfg!(target_os = "windows") {
+            "explorer"
+        } else if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        std::process::Command::new(opener)
+            .arg(&dir)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("无法打开缓存目录（{}）: {}", opener, e))?;
+    }
+    Ok(())
+}
+
+/// 命令行参数，不带任何参数运行时使用已导入的配置
+fn command_line() -> Vec<OsString> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    if args.len() > 1 {
+        return args;
+    }
+    match Profile::load_saved() {
+        Ok(Some(profile)) => {
+            status!("📄 使用已导入的配置: {}", profile.to_args().join(" "));
+            args.into_iter().chain(profile.to_args().into_iter().map(OsString::from)).collect()
+        }
+        Ok(None) => args,
+        Err(e) => {
+            eprintln!("⚠️ 无法读取已导入的配置: {}", e);
+            args
+        }
+    }
+}
+
+/// 检查配置的版本和子命令，并按命令行的规则检查参数
+fn validate_profile(profile: &Profile) -> Result<()> {
+    profile.validate()?;
+    let args = std::iter::once("clipboard-sync-alt".to_string()).chain(profile.to_args());
+    Cli::try_parse_from(args).map_err(|e| anyhow::anyhow!("配置中的参数无效: {}", e.render()))?;
+    Ok(())
+}
+
+/// 导出配置和已配对设备
+fn export_config(path: &std::path::Path, args: &[String], include_secret: bool, force: bool) -> Result<()> {
+    let mut profile = if args.is_empty() {
+        Profile::load_saved()?.ok_or_else(|| {
+            anyhow::anyhow!("没有已导入的配置，请在 -- 之后给出平时运行的命令，如 export-config {} -- start --name 笔记本", path.display())
+        })?
+    } else {
+        Profile::from_args(args)?
+    };
+    validate_profile(&profile)?;
+    if include_secret {
+        profile.embed_secret()?;
+    } else if let Some(SecretRef::Inline { .. }) = profile.secret {
+        // --secret 直接给出的密钥没有可引用的来源
+        eprintln!("⚠️ 未导出 --secret 给出的密钥，在新设备上请通过 {} 提供，或加上 --include-secret", config::SECRET_ENV);
+        profile.secret = Some(SecretRef::Env);
+    }
+    profile.peers = PeerStore::load_default()?.peers().clone();
+    profile.save(path, force)?;
+
+    status!("📦 已导出配置到 {}（{} 个已配对设备）", path.display(), profile.peers.len());
+    match &profile.secret {
+        Some(SecretRef::Inline { .. }) => eprintln!("⚠️ 导出文件中包含密钥，请妥善保管"),
+        Some(SecretRef::File { path }) => status!("🔑 密钥未包含在导出文件中，新设备上需要有密钥文件 {}", path.display()),
+        Some(SecretRef::Env) => status!("🔑 密钥未包含在导出文件中，新设备上需要设置环境变量 {}", config::SECRET_ENV),
+        None => {}
+    }
+    Ok(())
+}
+
+/// 导入配置：已配对设备合并到本机，其余保存为不带参数运行时使用的配置
+fn import_config(path: &std::path::Path, force: bool) -> Result<()> {
+    let mut profile = Profile::load(path)?;
+    validate_profile(&profile)?;
+    let target = Profile::default_path().ok_or_else(|| anyhow::anyhow!("无法确定配置目录"))?;
+    if target.exists() && !force {
+        anyhow::bail!("已导入过配置 {}，加上 --force 覆盖", target.display());
+    }
+
+    match &profile.secret {
+        Some(SecretRef::Inline { value }) => {
+            let path = profile::save_secret(value)?;
+            status!("🔑 密钥已保存到 {}", path.display());
+            profile.secret = Some(SecretRef::File { path });
+        }
+        Some(SecretRef::File { path }) if !path.exists() => {
+            eprintln!("⚠️ 密钥文件 {} 不存在，启动前请把密钥复制到该位置", path.display());
+        }
+        Some(SecretRef::Env) if std::env::var_os(config::SECRET_ENV).is_none() => {
+            eprintln!("⚠️ 配置的密钥来自环境变量 {}，启动前请设置", config::SECRET_ENV);
+        }
+        _ => {}
+    }
+    let peers = std::mem::take(&mut profile.peers);
+    let count = peers.len();
+    PeerStore::load_default()?.merge(peers)?;
+    profile.save(&target, true)?;
+
+    status!("📥 已导入配置到 {}，合并了 {} 个已配对设备", target.display(), count);
+    status!("▶️ 不带参数运行即按导入的配置启动: {}", profile.to_args().join(" "));
+    Ok(())
+}
+
+/// 初始化剪贴板管理器
+fn init_clipboard(sync: &SyncArgs) -> Result<ClipboardManager> {
+    if sync.os_history {
+        if !os_history::is_supported() {
+            eprintln!("⚠️ 当前平台或构建不支持系统剪贴板历史，已忽略 --os-history");
+        } else if os_history::history_enabled() == Some(false) {
+            eprintln!("⚠️ 系统剪贴板历史未开启，可在 设置 > 系统 > 剪贴板 中打开");
+        } else {
+            status!("📚 收到的文本将推入系统剪贴板历史");
+        }
+    }
+    if sync.selection != Selection::Clipboard {
+        if Selection::is_supported() {
+            status!("🖱️ 同步的选区: {}", sync.selection);
+        } else {
+            eprintln!("⚠️ 当前平台不区分 PRIMARY 和 CLIPBOARD 选区，已忽略 --selection");
+        }
+    }
+    if let Some(encoding) = sync.text_encoding {
+        status!("🔤 剪贴板文本不是有效的 UTF-8 时按 {} 解码", encoding);
+    }
+    let clipboard = ClipboardManager::for_selection(sync.selection)?
+        .with_priority(sync.prefer)
+        .with_recovery(clipboard::DEFAULT_REINIT_AFTER_FAILURES, sync.clipboard_reinit)
+        .with_os_history(sync.os_history)
+        .with_text_encoding(sync.text_encoding);
+    #[cfg(feature = "image-support")]
+    let clipboard = clipboard
+        .with_max_image_pixels(sync.max_image_megapixels.saturating_mul(1_000_000))
+        .with_min_image_size(sync.min_image_size);
+    Ok(clipboard)
+}
+
+/// 按命令行参数设置日志的输出方式和详细程度
+fn init_logging(sync: &SyncArgs) {
+    events::set_json_output(sync.events_json);
+    events::set_redact_logs(sync.redact_logs);
+    let level = if sync.verbose { sync.log_level.max(LogLevel::Debug) } else { sync.log_level };
+    events::set_log_level(level);
+    events::set_trace_payload(sync.unsafe_trace_payload);
+    if sync.unsafe_trace_payload && level < LogLevel::Trace {
+        eprintln!("⚠️ --unsafe-trace-payload 只在 --log-level trace 时生效");
+    }
+}
+
+/// 初始化剪贴板，--no-clipboard 时返回 `None`，不访问剪贴板
+fn init_optional_clipboard(sync: &SyncArgs) -> Result<Option<ClipboardManager>> {
+    if sync.no_clipboard {
+        return Ok(None);
+    }
+    init_clipboard(sync).map(Some)
+}
+
+/// 初始化通知管理器
+fn init_notifier(sync: &SyncArgs) -> NotificationManager {
+    let mut notifier = NotificationManager::new()
+        .with_throttle(Duration::from_millis(sync.notify_throttle))
+        .with_timeout(Duration::from_millis(sync.notify_timeout))
+        .with_app_name(&sync.notify_app_name);
+    if let Some(icon) = &sync.notify_icon {
+        notifier = notifier.with_icon(icon);
+    }
+    notifier.set_enabled(!sync.no_notify);
+    notifier
+}
+
+/// 确定本机设备标识：优先使用 --device-id，否则读取或生成保存的标识
+fn device_id(id: Option<&str>) -> String {
+    if let Some(id) = id {
+        return id.to_string();
+    }
+    config::load_or_create_device_id().unwrap_or_else(|e| {
+        eprintln!("⚠️ 无法保存设备标识，本次使用临时标识: {}", e);
+        config::generate_device_id()
+    })
+}
+
+/// 指定了 --http-port 时启动本地 HTTP 接口
+async fn start_http_api(sync: &SyncArgs, clipboard: Option<&ClipboardManager>) -> Result<()> {
+    let (Some(port), Some(clipboard)) = (sync.http_port, clipboard) else {
+        return Ok(());
+    };
+    let addr = http_api::start(SocketAddr::new(sync.http_bind, port), clipboard.clone(), sync.http_token.clone()).await?;
+    if !addr.ip().is_loopback() && sync.http_token.is_none() {
+        eprintln!("⚠️ HTTP 接口监听在 {} 且未设置 --http-token，局域网内的任何人都可以读写剪贴板", addr);
+    }
+    status!("🌍 HTTP 接口: http://{}/clipboard", addr);
+    Ok(())
+}
+
+/// 根据命令行参数构造 TLS 设置
+#[cfg(feature = "tls")]
+fn tls_settings(args: &TlsArgs) -> Result<TlsSettings> {
+    let mut tls = TlsSettings::new();
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        tls = tls.with_server_cert(cert, key, args.ca_cert.as_deref(), args.require_client_cert)?;
+    }
+    if args.tls {
+        let ca = args.ca_cert.as_deref().ok_or_else(|| anyhow::anyhow!("--tls 需要指定 --ca-cert"))?;
+        let identity = args.client_cert.as_deref().zip(args.client_key.as_deref());
+        tls = tls.with_client(ca, identity)?;
+    }
+    if let Some(name) = &args.tls_server_name {
+        tls = tls.with_server_name(name);
+    }
+    Ok(tls)
+}
+
+/// 创建选择的传输方式，当前构建不支持时返回错误
+fn transport(sync: &SyncArgs) -> Result<Arc<dyn Transport>> {
+    if !sync.transport.is_available() {
+        return Err(anyhow::anyhow!("--transport {} 需要使用 --features websocket 编译", sync.transport));
+    }
+    sync.transport.build()
+}
+
+/// 检查选择的加密方式，当前构建不支持时返回错误
+fn encryption(sync: &SyncArgs) -> Result<Encryption> {
+    if !sync.encrypt.is_available() {
+        return Err(anyhow::anyhow!("--encrypt {} 需要使用 --features noise 编译", sync.encrypt));
+    }
+    if sync.encrypt == Encryption::Noise {
+        status!("🔒 连接使用 Noise 加密");
+    }
+    Ok(sync.encrypt)
+}
+
+/// 根据命令行参数构造套接字选项
+fn socket_options(sync: &SyncArgs) -> SocketOptions {
+    SocketOptions {
+        nodelay: !sync.no_nodelay,
+        keepalive_idle: Duration::from_secs(sync.keepalive_idle),
+        keepalive_interval: Duration::from_secs(sync.keepalive_interval),
+    }
+}
+
+/// 加载已配对设备存储，失败时仅提示，不影响手动指定目标的连接
+fn load_peer_store() -> Option<PeerStore> {
+    match PeerStore::load_default() {
+        Ok(store) => Some(store),
+        Err(e) => {
+            eprintln!("⚠️ 无法加载已配对设备: {}", e);
+            None
+        }
+    }
+}
+
+/// 根据命令行参数构造监控选项
+fn monitor_options(sync: &SyncArgs) -> Result<MonitorOptions> {
+    if !sync.apply_only_in.is_empty() {
+        if foreground::is_supported() {
+            status!("🪟 只在以下应用位于前台时应用收到的内容: {}", sync.apply_only_in.join(", "));
+        } else {
+            eprintln!("⚠️ 当前平台或构建不支持查询前台应用，已忽略 --apply-only-in");
+        }
+    }
+    Ok(MonitorOptions {
+        sync_clears: sync.sync_clears,
+        normalize_text: sync.normalize_text,
+        max_text_bytes: sync.max_text_bytes,
+        transform: TextTransform::parse(&sync.transforms)?,
+        transform_incoming: sync.transform_incoming,
+        max_updates_per_sec: sync.max_updates_per_sec,
+        apply_only_in: AppFilter::new(sync.apply_only_in.clone()),
+        conflict: sync.conflict,
+        apply_suppression: Duration::from_millis(sync.apply_suppression),
+        interactive: sync.interactive,
+        session_limits: SessionLimits {
+            idle_timeout: sync.idle_timeout.map(|mins| Duration::from_secs(mins * 60)),
+            max_session: sync.max_session.map(|mins| Duration::from_secs(mins * 60)),
+        },
+        image_cache: match sync.image_cache_mb {
+            Some(mb) => Some(ImageCache::new(
+                image_cache_dir(sync.image_cache_dir.clone())?,
+                mb.saturating_mul(1024 * 1024),
+            )),
+            None => None,
+        },
+        history: open_history(sync)?,
+        ..MonitorOptions::default()
+    })
+}
+
+/// 打开 `--history-db` 指定的历史数据库，未指定时只在内存中记录
+fn open_history(sync: &SyncArgs) -> Result<History> {
+    let Some(path) = &sync.history_db else {
+        return Ok(History::new());
+    };
+    #[cfg(feature = "history-sqlite")]
+    {
+        let store = SqliteHistory::open(path)?.with_max_entries(sync.history_max_entries);
+        status!("🗄️ 同步历史保存到 {}", path.display());
+        Ok(History::with_store(store))
+    }
+    #[cfg(not(feature = "history-sqlite"))]
+    {
+        let _ = path;
+        Err(anyhow::anyhow!("--history-db 需要使用 --features history-sqlite 编译"))
+    }
+}
+
+/// 启动信息中告诉其他设备的连接方式
+struct Pairing {
+    /// 显示给其他设备的本机地址，未指定时自动选择
+    advertise: Option<IpAddr>,
+    /// 配对二维码，`None` 时不显示
+    qr: Option<PairingQr>,
+}
+
+/// 配对二维码的内容
+struct PairingQr {
+    /// 二维码中使用的本机地址，未指定时自动选择
+    addr: Option<IpAddr>,
+    /// 写入链接的预共享密钥
+    secret: Option<String>,
+}
+
+/// 运行服务器模式，按 `pairing` 显示连接方式和配对二维码，
+/// `outbound` 中的设备会被主动连接，与接受的连接一起同步
+async fn run_server(
+    clipboard: Option<ClipboardManager>,
+    network: NetworkManager,
+    notifier: NotificationManager,
+    opts: MonitorOptions,
+    port: u16,
+    pairing: Pairing,
+    outbound: &[ConnectTarget],
+) -> Result<()> {
+    status!("🚀 启动剪贴板同步服务...");
+    
+    // 启动网络服务（端口被占用时可能绑定到后续端口）
+    let port = network.start_server(port).await?;
+    #[cfg(feature = "systemd")]
+    notify_ready();
     
     // 发送启动通知
     notifier.send("剪贴板同步", "同步服务已启动")?;
     
     // 显示设备信息
-    println!("📱 设备名称: {}", network.get_device_name());
-    println!("🔌 监听端口: {}", port);
+    status!("📱 设备名称: {}", network.get_device_name());
+    status!("🆔 设备标识: {}", config::short_id(network.get_device_id()));
+    status!("🔌 监听端口: {}", port);
     
-    // 获取并显示本地IP地址
-    if let Ok(local_ip) = get_local_ip() {
-        println!("🌐 本地地址: {}:{}", local_ip, port);
-        println!("💡 其他设备可以使用以下命令连接:");
-        println!("   cargo run -- connect --name \"设备名称\" {} --port {}", local_ip, port);
+    // 显示本地IP地址，指定了 --advertise 时使用指定的地址
+    let local_ip = match pairing.advertise {
+        Some(ip) => Some(ip.to_string()),
+        None => get_local_ip().ok(),
+    };
+    if let Some(local_ip) = local_ip {
+        status!("🌐 本地地址: {}:{}", local_ip, port);
+        status!("💡 其他设备可以使用以下命令连接:");
+        status!("   cargo run -- connect --name \"设备名称\" {} --port {}", local_ip, port);
     }
     
-    println!("");
-    println!("📋 监控剪贴板变化中...");
-    println!("按 Ctrl+C 停止服务");
-    
-    // 设置消息处理器
-    let mut message_receiver = network.setup_message_handler().await;
+    if let Some(qr) = pairing.qr {
+        print_pairing_qr(&network.get_device_name(), qr, pairing.advertise, port)?;
+    }
     
-    // 启动消息处理任务
-    let clipboard_clone = clipboard.clone();
-    let notifier_clone = notifier.clone();
-    tokio::spawn(async move {
-        while let Some(message) = message_receiver.recv().await {
-            println!("📨 收到剪贴板消息: {} (来自: {})", 
-                     message.content.preview(50), 
-                     message.sender_name);
-            
-            // 根据消息类型更新本地剪贴板
-            match &message.content {
-                network_alternative::ClipboardContent::Text(text) => {
-                    if let Err(e) = clipboard_clone.set_text(text) {
-                        eprintln!("❌ 更新文本剪贴板失败: {}", e);
-                    } else {
-                        let preview = message.content.preview(50);
-                        let _ = notifier_clone.send("文本剪贴板已同步", &preview);
-                    }
-                }
-                network_alternative::ClipboardContent::Image { width, height, data } => {
-                    if let Err(e) = clipboard_clone.set_image(*width, *height, data) {
-                        eprintln!("❌ 更新图片剪贴板失败: {}", e);
-                    } else {
-                        let preview = format!("图片 {}x{}", width, height);
-                        let _ = notifier_clone.send("图片剪贴板已同步", &preview);
-                    }
-                }
-            }
+    // 主动连接失败不影响监听，其他设备仍可连接进来
+    if !outbound.is_empty() {
+        if let Err(e) = connect_all(&network, outbound).await {
+            eprintln!("⚠️ {}，继续等待其他设备连接", e);
         }
-    });
+    }
+    
+    status!();
+    if clipboard.is_some() {
+        status!("📋 监控剪贴板变化中...");
+    }
+    status!("按 Ctrl+C 停止服务（也可发送 SIGTERM）");
     
-    // 剪贴板监控循环
-    let mut last_text_content = String::new();
-    let mut last_content_type = clipboard::ClipboardContentType::Empty;
+    run_sync(clipboard, &network, notifier, opts).await;
     
-    loop {
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        
-        // 检查剪贴板内容类型
-        let current_type = clipboard.get_content_type();
-        
-        match current_type {
-            clipboard::ClipboardContentType::Text => {
-                if let Ok(current_content) = clipboard.get_text() {
-                    if current_content != last_text_content && !current_content.is_empty() {
-                        println!("📋 检测到文本剪贴板变化: {}", current_content);
-                        
-                        // 广播文本到其他设备
-                        if let Err(e) = network.broadcast_clipboard(&current_content).await {
-                            eprintln!("❌ 文本广播失败: {}", e);
-                        }
-                        
-                        last_text_content = current_content;
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Image => {
-                // 只有当之前不是图片类型时才处理，避免重复处理
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Image) {
-                    if let Ok(Some((width, height, png_data))) = clipboard.get_image() {
-                        println!("🖼️ 检测到图片剪贴板变化: {}x{}", width, height);
-                        
-                        // 广播图片到其他设备
-                        if let Err(e) = network.broadcast_image(width, height, png_data).await {
-                            eprintln!("❌ 图片广播失败: {}", e);
-                        }
-                        
-                        last_content_type = current_type;
-                    }
+    network.shutdown().await;
+    status!("🔴 同步服务已停止");
+    
+    Ok(())
+}
+
+/// 作为 systemd 服务运行时通知服务已就绪，不在 systemd 下运行（没有 NOTIFY_SOCKET）时什么都不做
+#[cfg(feature = "systemd")]
+fn notify_ready() {
+    match sd_notify::notify(false, &[sd_notify::NotifyState::Ready]) {
+        Ok(()) => clipboard_sync_alt::verbose!("✅ 已通知 systemd 服务就绪"),
+        Err(e) => eprintln!("⚠️ 通知 systemd 服务就绪失败: {}", e),
+    }
+}
+
+/// 显示配对链接和二维码，依次使用 --qr-addr、--advertise 指定的地址，都未指定时使用第一个可用的本机地址
+fn print_pairing_qr(name: &str, qr: PairingQr, advertise: Option<IpAddr>, port: u16) -> Result<()> {
+    let ip = match qr.addr.or(advertise) {
+        Some(ip) => ip,
+        None => {
+            let addresses = pairing::local_addresses();
+            if addresses.len() > 1 {
+                status!("💡 检测到多个本机地址，可使用 --advertise 或 --qr-addr 选择二维码中的地址:");
+                for ip in &addresses {
+                    status!("   {}", ip);
                 }
             }
-            clipboard::ClipboardContentType::Empty => {
-                // 剪贴板为空，更新状态
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Empty) {
-                    last_content_type = current_type;
-                    last_text_content.clear();
-                }
+            match addresses.first() {
+                Some(ip) => *ip,
+                None => get_local_ip()?.parse()?,
             }
         }
+    };
+    
+    let target = ConnectTarget {
+        name: Some(name.to_string()),
+        secret: qr.secret,
+        ..ConnectTarget::from(SocketAddr::new(ip, port))
+    };
+    let url = target.to_url();
+    status!("📱 扫描二维码或复制链接连接: {}", url);
+    status!("{}", pairing::render_qr(&url)?);
+    Ok(())
+}
+
+/// 汇总位置参数和 --peer 指定的所有连接目标
+fn connect_targets(ip: Option<&str>, peers: &[String], default_port: u16) -> Result<Vec<ConnectTarget>> {
+    ip.into_iter()
+        .chain(peers.iter().map(String::as_str))
+        .map(|target| parse_peer(target, default_port))
+        .collect()
+}
+
+/// 解析 --room-secret 的 <房间>=<密钥>
+fn parse_room_secret(value: &str) -> Result<(String, String)> {
+    match value.split_once('=') {
+        Some((room, secret)) if !room.is_empty() && !secret.is_empty() => Ok((room.to_string(), secret.to_string())),
+        _ => Err(anyhow::anyhow!("无效的房间密钥 {:?}: 格式应为 <房间>=<密钥>", value)),
+    }
+}
+
+/// 解析 ip:port、[ipv6]:port 或 clipsync:// 配对链接，未指定端口时使用默认端口
+///
+/// 地址后可以追加 `:send`、`:recv` 或 `:both` 指定与该设备的同步方向
+fn parse_peer(target: &str, default_port: u16) -> Result<ConnectTarget> {
+    if pairing::is_pairing_url(target) {
+        return target.parse();
+    }
+    // 只有能识别的方向才视为后缀，避免把 IPv6 地址的最后一段当作方向
+    let (addr, direction) = target
+        .rsplit_once(':')
+        .and_then(|(addr, suffix)| Some((addr, suffix.parse::<SyncDirection>().ok()?)))
+        .unwrap_or((target, SyncDirection::Both));
+    Ok(ConnectTarget { direction, ..ConnectTarget::from(parse_addr(addr, default_port)?) })
+}
 
-        // 检查退出信号
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                break;
+/// 解析 ip:port 或 [ipv6]:port 形式的地址，未指定端口时使用默认端口
+fn parse_addr(target: &str, default_port: u16) -> Result<SocketAddr> {
+    if let Ok(addr) = target.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let ip: IpAddr = target
+        .parse()
+        .map_err(|e| anyhow::anyhow!("无效的设备地址 {}: {}", target, e))?;
+    Ok(SocketAddr::new(ip, default_port))
+}
+
+/// 连接所有目标设备并输出结果，全部失败时返回错误，成功时返回已连接数量
+async fn connect_all(network: &NetworkManager, targets: &[ConnectTarget]) -> Result<usize> {
+    let mut connected = Vec::new();
+    let mut failed = Vec::new();
+    
+    for target in targets {
+        status!("🔗 正在连接到设备: {}", target);
+        
+        // 连接到指定设备（忽略返回的device_id），链接中的密钥优先于全局密钥
+        match network.connect(target).await {
+            Ok(_device_id) => connected.push(target),
+            Err(e) => {
+                eprintln!("❌ 连接 {} 失败: {}", target, e);
+                events::emit(Event::error(Some(&target.addr.to_string()), format!("连接失败: {}", e)));
+                failed.push(target);
             }
-            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
         }
     }
     
-    network.shutdown().await;
-    println!("🔴 同步服务已停止");
+    if connected.is_empty() {
+        return Err(anyhow::anyhow!("无法连接到任何设备"));
+    }
     
-    Ok(())
+    status!("🆔 设备标识: {}", config::short_id(network.get_device_id()));
+    status!("✅ 已连接 {} 个设备", connected.len());
+    for target in &connected {
+        status!("   ✅ {}", target);
+    }
+    for target in &failed {
+        status!("   ❌ {}", target);
+    }
+    
+    Ok(connected.len())
 }
 
 /// 连接到服务器模式
-async fn connect_to_server(clipboard: ClipboardManager, network: NetworkManager, ip: &str, port: u16) -> Result<()> {
-    let notifier = NotificationManager::new();
-    
-    println!("🔗 正在连接到设备: {}:{}", ip, port);
+async fn connect_to_server(
+    clipboard: Option<ClipboardManager>,
+    network: NetworkManager,
+    notifier: NotificationManager,
+    opts: MonitorOptions,
+    targets: &[ConnectTarget],
+) -> Result<()> {
+    let connected = connect_all(&network, targets).await?;
+    notifier.send("剪贴板同步", &format!("已连接到 {} 个设备", connected))?;
     
-    // 连接到指定设备（忽略返回的device_id）
-    let _device_id = network.connect_to_device(ip, port).await?;
-    
-    println!("✅ 连接成功！开始同步剪贴板内容...");
-    notifier.send("剪贴板同步", "已连接到设备")?;
+    if clipboard.is_some() {
+        status!("📋 开始同步剪贴板内容，监控剪贴板变化中...");
+    }
+    status!("按 Ctrl+C 断开连接");
     
-    // 设置消息处理器
-    let mut message_receiver = network.setup_message_handler().await;
+    run_sync(clipboard, &network, notifier, opts).await;
     
-    // 启动消息处理任务
-    let clipboard_clone = clipboard.clone();
-    let notifier_clone = notifier.clone();
-    tokio::spawn(async move {
-        while let Some(message) = message_receiver.recv().await {
-            println!("📨 收到剪贴板消息: {} (来自: {})", 
-                     message.content.preview(50), 
-                     message.sender_name);
-            
-            // 根据消息类型更新本地剪贴板
-            match &message.content {
-                network_alternative::ClipboardContent::Text(text) => {
-                    if let Err(e) = clipboard_clone.set_text(text) {
-                        eprintln!("❌ 更新文本剪贴板失败: {}", e);
-                    } else {
-                        let preview = message.content.preview(50);
-                        let _ = notifier_clone.send("文本剪贴板已同步", &preview);
-                    }
-                }
-                network_alternative::ClipboardContent::Image { width, height, data } => {
-                    if let Err(e) = clipboard_clone.set_image(*width, *height, data) {
-                        eprintln!("❌ 更新图片剪贴板失败: {}", e);
-                    } else {
-                        let preview = format!("图片 {}x{}", width, height);
-                        let _ = notifier_clone.send("图片剪贴板已同步", &preview);
-                    }
-                }
-            }
-        }
-    });
+    network.shutdown().await;
+    status!("🔴 连接已断开");
     
-    println!("📋 监控剪贴板变化中...");
-    println!("按 Ctrl+C 断开连接");
+    Ok(())
+}
+
+/// 观察模式：连接所有设备后只记录收到的内容
+async fn observe_devices(
+    network: NetworkManager,
+    targets: &[ConnectTarget],
+    full: bool,
+    transcript: Option<observe::Transcript>,
+) -> Result<()> {
+    connect_all(&network, targets).await?;
+    status!("👁️ 观察模式：只记录收到的内容，不访问本地剪贴板，也不发送任何内容");
+    status!("按 Ctrl+C 退出");
     
-    // 剪贴板监控循环
-    let mut last_text_content = String::new();
-    let mut last_content_type = clipboard::ClipboardContentType::Empty;
+    observe::run_observer(&network, full, transcript).await;
     
-    loop {
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        
-        // 检查剪贴板内容类型
-        let current_type = clipboard.get_content_type();
-        
-        match current_type {
-            clipboard::ClipboardContentType::Text => {
-                if let Ok(current_content) = clipboard.get_text() {
-                    if current_content != last_text_content && !current_content.is_empty() {
-                        println!("📋 检测到文本剪贴板变化: {}", current_content);
-                        
-                        // 广播文本到其他设备
-                        if let Err(e) = network.broadcast_clipboard(&current_content).await {
-                            eprintln!("❌ 文本广播失败: {}", e);
-                        }
-                        
-                        last_text_content = current_content;
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Image => {
-                // 只有当之前不是图片类型时才处理，避免重复处理
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Image) {
-                    if let Ok(Some((width, height, png_data))) = clipboard.get_image() {
-                        println!("🖼️ 检测到图片剪贴板变化: {}x{}", width, height);
-                        
-                        // 广播图片到其他设备
-                        if let Err(e) = network.broadcast_image(width, height, png_data).await {
-                            eprintln!("❌ 图片广播失败: {}", e);
-                        }
-                        
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Empty => {
-                // 剪贴板为空，更新状态
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Empty) {
-                    last_content_type = current_type;
-                    last_text_content.clear();
-                }
-            }
-        }
+    network.shutdown().await;
+    status!("🔴 连接已断开");
+    Ok(())
+}
 
-        // 检查退出信号
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                break;
-            }
+/// 有剪贴板时运行监控循环，否则作为中继只转发消息
+async fn run_sync(
+    clipboard: Option<ClipboardManager>,
+    network: &NetworkManager,
+    notifier: NotificationManager,
+    opts: MonitorOptions,
+) {
+    match clipboard {
+        Some(clipboard) => run_clipboard_monitor(clipboard, network, notifier, opts).await,
+        None => {
+            status!("🔁 中继模式：不访问本地剪贴板，只在已连接的设备之间转发消息");
+            run_relay(network, opts).await;
         }
     }
+}
+
+/// 单次推送模式：发送当前剪贴板内容后断开
+async fn push_once(
+    clipboard: ClipboardManager,
+    network: NetworkManager,
+    opts: MonitorOptions,
+    targets: &[ConnectTarget],
+) -> Result<()> {
+    connect_all(&network, targets).await?;
+    
+    let result = sync_once(&clipboard, &network, &opts).await;
+    network.shutdown().await;
+    
+    let Some(result) = result? else {
+        return Err(anyhow::anyhow!("剪贴板为空，没有可发送的内容"));
+    };
+    if result.delivered == 0 {
+        return Err(anyhow::anyhow!("发送失败：0/{} 个设备收到内容", result.total()));
+    }
+    status!("✅ 当前剪贴板内容已发送到 {}/{} 个设备", result.delivered, result.total());
+    Ok(())
+}
+
+/// 发送指定的文本后断开
+async fn push_text(network: NetworkManager, text: &str, targets: &[ConnectTarget]) -> Result<()> {
+    connect_all(&network, targets).await?;
     
+    let result = send_text(&network, text, &MonitorOptions::default()).await;
     network.shutdown().await;
-    println!("🔴 连接已断开");
     
+    let result = result?;
+    if result.delivered == 0 {
+        return Err(anyhow::anyhow!("发送失败：0/{} 个设备收到内容", result.total()));
+    }
+    status!("✅ 文本已发送到 {}/{} 个设备", result.delivered, result.total());
     Ok(())
 }
 
+/// `send-text` 的文本参数，`-` 表示从标准输入读取，去掉末尾的一个换行（如 echo 的输出）
+fn read_text_arg(text: String) -> Result<String> {
+    if text != "-" {
+        return Ok(text);
+    }
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+        .map_err(|e| anyhow::anyhow!("无法从标准输入读取文本: {}", e))?;
+    let trimmed = input
+        .strip_suffix('\n')
+        .map(|rest| rest.strip_suffix('\r').unwrap_or(rest))
+        .unwrap_or(&input);
+    Ok(trimmed.to_string())
+}
+
 /// 获取本地IP地址
 fn get_local_ip() -> Result<String> {
     use std::net::{UdpSocket, SocketAddr};
@@ -312,3 +1393,70 @@ fn get_local_ip() -> Result<String> {
     let local_addr = socket.local_addr()?;
     Ok(local_addr.ip().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_json() {
+        let value = serde_json::to_value(BuildInfo::current()).unwrap();
+        assert_eq!(value["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(value["protocol_version"], PROTOCOL_VERSION);
+        assert!(!value["target"].as_str().unwrap().is_empty());
+        assert_eq!(value["features"]["tray"], cfg!(feature = "tray"));
+        assert_eq!(value["features"]["image-support"], cfg!(feature = "image-support"));
+        assert_eq!(value["features"]["tls"], cfg!(feature = "tls"));
+    }
+
+    #[test]
+    fn test_parse_peer() {
+        let addr = |target: &str| parse_peer(target, 8765).unwrap().addr;
+        assert_eq!(addr("192.168.1.2:9000"), "192.168.1.2:9000".parse::<SocketAddr>().unwrap());
+        assert_eq!(addr("192.168.1.2"), "192.168.1.2:8765".parse::<SocketAddr>().unwrap());
+        assert_eq!(addr("[::1]:9000"), "[::1]:9000".parse::<SocketAddr>().unwrap());
+        assert_eq!(addr("::1"), "[::1]:8765".parse::<SocketAddr>().unwrap());
+        assert!(parse_peer("not-an-ip", 8765).is_err());
+    }
+
+    #[test]
+    fn test_parse_peer_direction() {
+        let target = parse_peer("192.168.1.2:9000:recv", 8765).unwrap();
+        assert_eq!(target.addr, "192.168.1.2:9000".parse::<SocketAddr>().unwrap());
+        assert_eq!(target.direction, SyncDirection::Receive);
+
+        let target = parse_peer("[::1]:9000:send", 8765).unwrap();
+        assert_eq!(target.addr, "[::1]:9000".parse::<SocketAddr>().unwrap());
+        assert_eq!(target.direction, SyncDirection::Send);
+
+        assert_eq!(parse_peer("192.168.1.2:both", 8765).unwrap().addr.port(), 8765);
+        assert_eq!(parse_peer("192.168.1.2:9000", 8765).unwrap().direction, SyncDirection::Both);
+        assert!(parse_peer("192.168.1.2:9000:upload", 8765).is_err());
+        assert_eq!(parse_peer("fe80::ab", 8765).unwrap().direction, SyncDirection::Both);
+    }
+
+    #[test]
+    fn test_parse_peer_url() {
+        let target = parse_peer("clipsync://laptop@192.168.1.2:9000?secret=KEY", 8765).unwrap();
+        assert_eq!(target.name.as_deref(), Some("laptop"));
+        assert_eq!(target.addr, "192.168.1.2:9000".parse::<SocketAddr>().unwrap());
+        assert_eq!(target.secret.as_deref(), Some("KEY"));
+    }
+
+    #[test]
+    fn test_connect_targets_combines_ip_and_peers() {
+        let peers = vec!["10.0.0.2:9000".to_string(), "10.0.0.3".to_string()];
+        let targets = connect_targets(Some("10.0.0.1"), &peers, 8765).unwrap();
+        let expected: Vec<ConnectTarget> = ["10.0.0.1:8765", "10.0.0.2:9000", "10.0.0.3:8765"]
+            .iter()
+            .map(|addr| ConnectTarget::from(addr.parse::<SocketAddr>().unwrap()))
+            .collect();
+        assert_eq!(targets, expected);
+    }
+    #[test]
+    fn test_parse_room_secret() {
+        assert_eq!(parse_room_secret("家里=a=b").unwrap(), ("家里".to_string(), "a=b".to_string()));
+        assert!(parse_room_secret("家里").is_err());
+        assert!(parse_room_secret("=key").is_err());
+    }
+}
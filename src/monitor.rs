@@ -0,0 +1,1501 @@
+use crate::clipboard::{ClipboardContentType, ClipboardManager};
+use crate::error::{SyncError, SyncResult};
+use crate::events::{self, Event};
+use crate::foreground::AppFilter;
+use crate::history::{History, HistorySource};
+use crate::image_cache::ImageCache;
+use crate::network_alternative::{is_connection_id, BroadcastResult, ClipboardContent, ClipboardMessage, NetworkManager};
+use crate::notification::{NotificationManager, NotifyKind};
+use crate::session::{self, SessionLimits};
+use crate::structured::Color;
+use crate::transform::TextTransform;
+use std::collections::HashMap;
+#[cfg(feature = "image-support")]
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// 默认每个发送方每秒最多应用的更新数
+pub const DEFAULT_MAX_UPDATES_PER_SEC: u32 = 20;
+/// 有暂缓的内容时检查前台应用的间隔
+const HELD_RECHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// 默认的应用后抑制窗口：写入收到的内容后这段时间内监控循环不检测本地变化，
+/// 等写入完成后再比较，避免读到写了一半的剪贴板或把刚写入的内容当作本地变化
+pub const DEFAULT_APPLY_SUPPRESSION: Duration = Duration::from_millis(300);
+/// `ConflictPolicy::Local` 下，本地变化早于收到的消息不超过该秒数时仍保留本地内容
+const LOCAL_CONFLICT_WINDOW_SECS: u64 = 2;
+
+/// 本地剪贴板的变化与收到的内容冲突时保留哪一方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// 总是应用收到的内容（默认）
+    #[default]
+    Remote,
+    /// 比较时间，本地变化晚于消息的时间戳时保留本地内容
+    Latest,
+    /// 本地变化晚于消息，或早于消息不超过 2 秒时都保留本地内容
+    Local,
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "remote" => Ok(ConflictPolicy::Remote),
+            "latest" => Ok(ConflictPolicy::Latest),
+            "local" => Ok(ConflictPolicy::Local),
+            other => Err(format!("未知的冲突处理方式: {} (可选: latest, remote, local)", other)),
+        }
+    }
+}
+
+impl ConflictPolicy {
+    /// 是否应保留本地内容而跳过收到的消息，时间均为 Unix 秒时间戳
+    ///
+    /// 消息时间戳来自发送方的时钟，设备之间时钟不同步时结果不可靠
+    fn keeps_local(self, last_local_change: Option<u64>, message_timestamp: u64) -> bool {
+        let Some(local) = last_local_change else {
+            return false;
+        };
+        match self {
+            ConflictPolicy::Remote => false,
+            ConflictPolicy::Latest => local > message_timestamp,
+            ConflictPolicy::Local => local + LOCAL_CONFLICT_WINDOW_SECS >= message_timestamp,
+        }
+    }
+}
+
+/// 运行时控制状态，监控循环与托盘等外部入口共享
+#[derive(Clone, Default)]
+pub struct SyncControl {
+    /// 暂停时既不广播本地变化，也不应用远程内容
+    paused: Arc<AtomicBool>,
+    /// 触发后监控循环退出，进入正常的关闭流程
+    shutdown: CancellationToken,
+}
+
+impl SyncControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 是否已暂停同步
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// 暂停或恢复同步
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// 切换暂停状态，返回切换后是否处于暂停
+    pub fn toggle_paused(&self) -> bool {
+        !self.paused.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    /// 请求监控循环退出
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// 是否已请求退出
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    /// 等待退出请求
+    pub async fn cancelled(&self) {
+        self.shutdown.cancelled().await
+    }
+}
+
+/// 剪贴板监控选项
+pub struct MonitorOptions {
+    /// 剪贴板轮询间隔
+    pub poll_interval: Duration,
+    /// 是否广播和应用清空操作
+    pub sync_clears: bool,
+    /// 比较和广播前规范化文本：CRLF 转为 LF，并去掉末尾的一个换行
+    pub normalize_text: bool,
+    /// 广播文本的最大字节数，超出时在字符边界截断
+    pub max_text_bytes: Option<usize>,
+    /// 广播前对文本应用的替换规则
+    pub transform: TextTransform,
+    /// 是否对收到的文本也应用替换规则
+    pub transform_incoming: bool,
+    /// 同步过的图片另存到磁盘缓存
+    pub image_cache: Option<ImageCache>,
+    /// 每个发送方每秒最多应用的更新数，超出的更新合并，0 表示不限制
+    pub max_updates_per_sec: u32,
+    /// 只在这些前台应用中应用收到的内容，为空时不限制
+    pub apply_only_in: AppFilter,
+    /// 本地变化与收到的内容冲突时保留哪一方
+    pub conflict: ConflictPolicy,
+    /// 写入收到的内容后暂停检测本地变化的时长，窗口内的变化推迟到窗口结束后检测，0 表示不暂停
+    pub apply_suppression: Duration,
+    /// 从标准输入读取控制命令（如 `send <id>`、`pause`、`quit`）
+    pub interactive: bool,
+    /// 空闲或运行超过一定时间后自动退出
+    pub session_limits: SessionLimits,
+    /// 记录本次运行中同步过的内容及其来源，交互模式下用 `history` 命令查看
+    pub history: History,
+    pub control: SyncControl,
+}
+
+impl Default for MonitorOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(500),
+            sync_clears: false,
+            normalize_text: false,
+            max_text_bytes: None,
+            transform: TextTransform::default(),
+            transform_incoming: false,
+            image_cache: None,
+            max_updates_per_sec: DEFAULT_MAX_UPDATES_PER_SEC,
+            apply_only_in: AppFilter::default(),
+            conflict: ConflictPolicy::default(),
+            apply_suppression: DEFAULT_APPLY_SUPPRESSION,
+            interactive: false,
+            session_limits: SessionLimits::default(),
+            history: History::default(),
+            control: SyncControl::default(),
+        }
+    }
+}
+
+/// 等待退出信号：Ctrl+C（SIGINT），Unix 上还包括 systemd 等服务管理器停止服务时发送的 SIGTERM
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => eprintln!("⚠️ 无法监听 SIGTERM: {}，只能通过 Ctrl+C 退出", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// 运行剪贴板监控：应用收到的远程内容，并广播本地变化，直到收到 Ctrl+C、SIGTERM 或退出请求
+pub async fn run_clipboard_monitor(
+    clipboard: ClipboardManager,
+    network: &NetworkManager,
+    notifier: NotificationManager,
+    opts: MonitorOptions,
+) {
+    // 设置消息处理器
+    let mut message_receiver = network.setup_message_queue().await;
+    let mut current_requests = network.setup_request_handler();
+    
+    // 监控循环与消息处理任务共享检测状态，应用远程清空时同步记录，避免回传
+    let detector = Arc::new(Mutex::new(ChangeDetector::new()));
+    
+    // 启动消息处理任务
+    let mut applier = RemoteApplier {
+        clipboard: clipboard.clone(),
+        detector: detector.clone(),
+        notifier,
+        sync_clears: opts.sync_clears,
+        incoming_transform: opts.transform_incoming.then(|| opts.transform.clone()),
+        #[cfg(feature = "image-support")]
+        image_cache: opts.image_cache.clone(),
+        apply_only_in: opts.apply_only_in.clone(),
+        history: opts.history.clone(),
+        held: None,
+        conflict: opts.conflict,
+    };
+    let activity = session::watch(opts.session_limits, opts.control.clone());
+    let control = opts.control.clone();
+    let max_updates = opts.max_updates_per_sec;
+    let message_activity = activity.clone();
+    tokio::spawn(async move {
+        // 每个发送方每秒最多应用 max_updates 条，超出的更新在窗口结束时合并为最新的一条
+        let mut limiter = (max_updates > 0).then(|| RateLimiter::new(max_updates, Duration::from_secs(1)));
+        loop {
+            let release_at = limiter.as_ref().and_then(RateLimiter::next_release);
+            tokio::select! {
+                message = message_receiver.recv() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    message_activity.touch();
+                    status!("📨 收到剪贴板消息: {} (来自: {})", message.content.preview(50), message.sender_label());
+                    
+                    if control.is_paused() {
+                        status!("⏸️ 同步已暂停，忽略该消息");
+                        continue;
+                    }
+                    let Some(limiter) = &mut limiter else {
+                        applier.apply(message).await;
+                        continue;
+                    };
+                    let sender = message.sender_label();
+                    match limiter.admit(message.sender_id.clone(), message, Instant::now()) {
+                        Admission::Apply(message) => applier.apply(message).await,
+                        Admission::Throttled { first: true } => {
+                            status!("🚦 {} 的更新过于频繁（每秒超过 {} 条），多余的更新将被合并", sender, max_updates);
+                        }
+                        Admission::Throttled { first: false } => {}
+                    }
+                }
+                _ = tokio::time::sleep_until(release_at.unwrap_or_else(Instant::now).into()), if release_at.is_some() => {
+                    let Some(limiter) = &mut limiter else {
+                        continue;
+                    };
+                    for (message, coalesced) in limiter.release(Instant::now()) {
+                        if control.is_paused() {
+                            continue;
+                        }
+                        status!("🚦 已合并来自 {} 的 {} 条更新，应用最新的一条", message.sender_label(), coalesced);
+                        applier.apply(message).await;
+                    }
+                }
+                _ = tokio::time::sleep(HELD_RECHECK_INTERVAL), if applier.held.is_some() => {
+                    if !control.is_paused() {
+                        applier.apply_held().await;
+                    }
+                }
+            }
+        }
+    });
+    
+    // Ctrl+C、SIGTERM 与托盘的退出菜单走同一个关闭流程
+    let control = opts.control.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        control.shutdown();
+    });
+    
+    let mut commands = opts.interactive.then(spawn_command_reader);
+    if commands.is_some() {
+        status!("⌨️ 交互模式已开启，输入 help 查看可用命令");
+    }
+    
+    // 剪贴板监控循环
+    loop {
+        // 检查退出信号
+        tokio::select! {
+            _ = opts.control.cancelled() => {
+                break;
+            }
+            line = next_command(&mut commands) => {
+                match line {
+                    Some(line) => {
+                        activity.touch();
+                        run_command(&line, &clipboard, network, &opts).await
+                    }
+                    // 标准输入已关闭，继续同步
+                    None => commands = None,
+                }
+                continue;
+            }
+            Some(device_id) = current_requests.recv() => {
+                if opts.control.is_paused() {
+                    status!("⏸️ 同步已暂停，不响应 {} 的请求", device_id);
+                } else {
+                    send_current(&clipboard, network, &opts, &device_id).await;
+                }
+                continue;
+            }
+            _ = tokio::time::sleep(opts.poll_interval) => {}
+        }
+        
+        // 刚写入收到的内容时先不检测，也不消耗变化序号，窗口结束后再与记录的内容比较
+        if detector.lock().unwrap().suppressed(opts.apply_suppression, Instant::now()) {
+            continue;
+        }
+        
+        // 支持变化序号的平台上，序号未变时跳过读取内容
+        if !clipboard.poll_for_change() {
+            continue;
+        }
+        
+        let change = detect_local_change(&clipboard, &mut detector.lock().unwrap(), opts.normalize_text);
+        if change.is_some() {
+            clipboard.record_local_change();
+            activity.touch();
+        }
+        
+        // 暂停期间仍记录本地状态，恢复后不会补发暂停期间的变化
+        if opts.control.is_paused() {
+            continue;
+        }
+        
+        match change {
+            Some(LocalChange::Text(mut current_content)) => {
+                status!("📋 检测到文本剪贴板变化: {}", events::loggable_text(&current_content));
+                current_content = prepare_outgoing_text(current_content, &opts);
+                let content = ClipboardContent::from_text(current_content);
+                opts.history.record(HistorySource::Local, &content);
+                
+                // 广播文本到其他设备
+                report_broadcast("文本", network.broadcast_content(content).await);
+            }
+            Some(LocalChange::Html { html, text }) => {
+                status!("📋 检测到 HTML 剪贴板变化: {}", events::loggable_text(&text));
+                let content = outgoing_html(html, text, &opts);
+                opts.history.record(HistorySource::Local, &content);
+                report_broadcast("HTML", network.broadcast_content(content).await);
+            }
+            #[cfg(feature = "image-support")]
+            Some(LocalChange::Image { width, height, png_data }) => {
+                status!("🖼️ 检测到图片剪贴板变化: {}x{}", width, height);
+                
+                let content = ClipboardContent::Image { width, height, data: png_data };
+                record_image(&opts.image_cache, &opts.history, HistorySource::Local, &content);
+                
+                // 广播图片到其他设备
+                report_broadcast("图片", network.broadcast_content(content).await);
+            }
+            Some(LocalChange::Cleared) if opts.sync_clears => {
+                status!("🧹 检测到剪贴板被清空");
+                
+                report_broadcast("清空", network.broadcast_clear().await);
+            }
+            Some(LocalChange::Cleared) | None => {}
+        }
+    }
+}
+
+/// 交互模式下的控制命令
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    /// 参数为已连接的设备时只把当前剪贴板内容发送给它，否则把参数作为文本广播给所有设备
+    Send(String),
+    /// 列出已连接的设备
+    Peers,
+    /// 显示各连接的心跳延迟和最近收到数据的时间
+    Status,
+    /// 列出最近同步过的内容及其来源，指定关键字时只列出文本包含关键字的
+    History(Option<String>),
+    Pause,
+    Resume,
+    /// 断开与指定设备的连接
+    Kick(String),
+    Quit,
+    Help,
+}
+
+/// 交互命令的名称，输入能唯一确定命令的开头部分即可（如 `pe` 表示 `peers`）
+const COMMANDS: &[&str] = &["send", "peers", "status", "history", "pause", "resume", "kick", "quit", "help"];
+
+/// 把输入的命令名（不区分大小写，可以是开头部分或别名）解析为完整的命令名
+fn resolve_command(word: &str) -> std::result::Result<&'static str, String> {
+    let word = word.to_lowercase();
+    let word = match word.as_str() {
+        "exit" => "quit",
+        "?" => "help",
+        other => other,
+    };
+    if let Some(name) = COMMANDS.iter().find(|name| **name == word) {
+        return Ok(name);
+    }
+    let candidates: Vec<&str> = COMMANDS.iter().copied().filter(|name| name.starts_with(word)).collect();
+    match candidates.as_slice() {
+        [name] => Ok(name),
+        [] => Err(format!("未知的命令: {}，可用命令: {}（输入 help 查看说明）", word, COMMANDS.join("、"))),
+        _ => Err(format!("命令 {} 不明确，可能是: {}", word, candidates.join("、"))),
+    }
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> std::result::Result<Self, Self::Err> {
+        let line = line.trim();
+        let (word, arg) = match line.split_once(char::is_whitespace) {
+            Some((word, arg)) => (word, arg.trim()),
+            None => (line, ""),
+        };
+        match (resolve_command(word)?, arg) {
+            ("send", "") => Err("用法: send <设备> 或 send <文本>".to_string()),
+            ("send", arg) => Ok(Command::Send(arg.to_string())),
+            ("kick", id) if id.is_empty() || id.contains(char::is_whitespace) => {
+                Err("用法: kick <设备>，设备标识可以用 peers 查看".to_string())
+            }
+            ("kick", id) => Ok(Command::Kick(id.to_string())),
+            ("history", "") => Ok(Command::History(None)),
+            ("history", arg) => match arg.strip_prefix("--search").filter(|rest| rest.starts_with(char::is_whitespace)) {
+                Some(query) => Ok(Command::History(Some(query.trim().to_string()))),
+                _ => Err("用法: history [--search <关键字>]".to_string()),
+            },
+            (name, arg) if !arg.is_empty() => Err(format!("{} 不需要参数", name)),
+            ("peers", _) => Ok(Command::Peers),
+            ("status", _) => Ok(Command::Status),
+            ("pause", _) => Ok(Command::Pause),
+            ("resume", _) => Ok(Command::Resume),
+            ("quit", _) => Ok(Command::Quit),
+            _ => Ok(Command::Help),
+        }
+    }
+}
+
+/// 在后台逐行读取标准输入，标准输入关闭后通道随之关闭
+fn spawn_command_reader() -> tokio::sync::mpsc::UnboundedReceiver<String> {
+    use tokio::io::AsyncBufReadExt;
+    
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if !line.trim().is_empty() && sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// 等待下一条命令，未开启交互模式时一直等待
+async fn next_command(commands: &mut Option<tokio::sync::mpsc::UnboundedReceiver<String>>) -> Option<String> {
+    match commands {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 执行一条交互命令
+async fn run_command(line: &str, clipboard: &ClipboardManager, network: &NetworkManager, opts: &MonitorOptions) {
+    match line.parse::<Command>() {
+        // 形如连接标识的参数按设备处理，设备未连接时报错，不会把打错的标识当作文本发出
+        Ok(Command::Send(text)) if !is_connection_id(&text) && !network.connected_devices().await.contains(&text) => {
+            let text = prepare_outgoing_text(text, opts);
+            if text.is_empty() {
+                status!("⚠️ 应用替换规则后文本为空，没有可发送的内容");
+                return;
+            }
+            let content = ClipboardContent::from_text(text);
+            opts.history.record(HistorySource::Local, &content);
+            report_broadcast("文本", network.broadcast_content(content).await);
+        }
+        Ok(Command::Send(device_id)) => send_current(clipboard, network, opts, &device_id).await,
+        Ok(Command::Peers) => {
+            let connections = network.connection_health().await;
+            if connections.is_empty() {
+                status!("📭 没有已连接的设备");
+            }
+            for snapshot in connections {
+                status!("🔗 {}（接受: {}）", snapshot.peer, snapshot.accept);
+            }
+        }
+        Ok(Command::Status) => {
+            let health = network.connection_health().await;
+            if health.is_empty() {
+                status!("📭 没有已连接的设备");
+            }
+            for snapshot in health {
+                let icon = if snapshot.is_unresponsive() { "⚠️" } else { "💓" };
+                status!("{} {}", icon, snapshot);
+            }
+        }
+        Ok(Command::History(query)) => {
+            let entries = match &query {
+                Some(query) => opts.history.search(query),
+                None => opts.history.entries(),
+            };
+            let entries = match entries {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("❌ 读取同步历史失败: {}", e);
+                    return;
+                }
+            };
+            match &query {
+                Some(query) if entries.is_empty() => status!("📭 没有包含“{}”的同步记录", query),
+                None if entries.is_empty() => status!("📭 还没有同步过的内容"),
+                _ => {}
+            }
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            for entry in entries {
+                status!(
+                    "🕘 {:>5} 秒前  {:<24}  {}",
+                    now.saturating_sub(entry.timestamp),
+                    entry.source.to_string(),
+                    events::loggable_text(&entry.preview)
+                );
+            }
+        }
+        Ok(Command::Pause) => {
+            if opts.control.is_paused() {
+                status!("⏸️ 同步已经处于暂停状态");
+            } else {
+                opts.control.set_paused(true);
+                status!("⏸️ 同步已暂停，输入 resume 恢复");
+            }
+        }
+        Ok(Command::Resume) => {
+            if opts.control.is_paused() {
+                opts.control.set_paused(false);
+                status!("▶️ 同步已恢复");
+            } else {
+                status!("▶️ 同步没有暂停");
+            }
+        }
+        Ok(Command::Kick(device_id)) => {
+            if let Err(e) = network.disconnect(&device_id).await {
+                eprintln!("⚠️ {}", e);
+            }
+        }
+        Ok(Command::Quit) => {
+            status!("👋 正在退出");
+            opts.control.shutdown();
+        }
+        Ok(Command::Help) => {
+            status!(
+                "可用命令（输入开头部分即可，如 pe 表示 peers）:\n  send <设备>  只把当前剪贴板内容发送给指定的设备\n  send <文本>  把文本发送给所有设备，不修改本地剪贴板\n  peers        列出已连接的设备及允许它们发送的内容类型\n  status       显示各连接的延迟、最近收到数据的时间和允许发送的内容类型\n  history      列出最近同步过的内容，以及来自本机还是哪台设备；history --search <关键字> 只列出文本包含关键字的\n  pause        暂停同步\n  resume       恢复同步\n  kick <设备>  断开与指定设备的连接\n  quit         退出\n  help         显示本帮助"
+            );
+        }
+        Err(e) => eprintln!("⚠️ {}", e),
+    }
+}
+
+/// 把收到的远程内容写入本地剪贴板
+struct RemoteApplier {
+    clipboard: ClipboardManager,
+    /// 与监控循环共享，应用远程内容时同步记录，避免回传
+    detector: Arc<Mutex<ChangeDetector>>,
+    notifier: NotificationManager,
+    sync_clears: bool,
+    /// 对收到的文本应用的替换规则
+    incoming_transform: Option<TextTransform>,
+    #[cfg(feature = "image-support")]
+    image_cache: Option<ImageCache>,
+    /// 只在这些前台应用中写入剪贴板
+    apply_only_in: AppFilter,
+    /// 已写入剪贴板的内容记录到这里
+    history: History,
+    /// 前台应用不在列表中时暂缓的最新一条消息
+    held: Option<ClipboardMessage>,
+    /// 本地变化与收到的内容冲突时保留哪一方
+    conflict: ConflictPolicy,
+}
+
+impl RemoteApplier {
+    /// 应用收到的消息；前台应用不在 --apply-only-in 列表中时暂缓，只保留最新的一条
+    async fn apply(&mut self, message: ClipboardMessage) {
+        if let Some(app) = self.apply_only_in.blocked_by() {
+            if self.held.is_none() {
+                status!("⏳ 前台应用 {} 不在 --apply-only-in 列表中，切换到允许的应用后再应用", app);
+            }
+            self.held = Some(message);
+            return;
+        }
+        self.held = None;
+        self.write(&message).await;
+    }
+
+    /// 前台应用切换到允许的应用后，应用暂缓的消息
+    async fn apply_held(&mut self) {
+        if self.apply_only_in.blocked_by().is_some() {
+            return;
+        }
+        if let Some(message) = self.held.take() {
+            status!("▶️ 已切换到允许的应用，应用暂缓的内容 (来自: {})", message.sender_label());
+            self.write(&message).await;
+        }
+    }
+
+    /// 在阻塞线程中写入剪贴板：写入失败时会间隔重试，不能占用异步运行时的工作线程
+    async fn write_clipboard(&self, write: impl FnOnce(&ClipboardManager) -> SyncResult<()> + Send + 'static) -> SyncResult<()> {
+        let clipboard = self.clipboard.clone();
+        tokio::task::spawn_blocking(move || write(&clipboard))
+            .await
+            .unwrap_or_else(|e| Err(SyncError::Clipboard(format!("写入剪贴板的线程异常退出: {}", e))))
+    }
+
+    /// 根据消息类型更新本地剪贴板
+    ///
+    /// 只在检查和记录时短暂持有检测状态锁，写入期间监控循环暂停检测，不会把写入的内容当作本地变化
+    async fn write(&self, message: &ClipboardMessage) {
+        let last_local_change = self
+            .clipboard
+            .last_local_change()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs());
+        if self.conflict.keeps_local(last_local_change, message.timestamp) {
+            status!("⏭️ 本地剪贴板在 {} 的消息之后有新的变化，保留本地内容", message.sender_label());
+            return;
+        }
+        match &message.content {
+            // 颜色目前以原始文本写入剪贴板
+            ClipboardContent::Text(text) | ClipboardContent::Color(Color { text, .. }) => {
+                let text = match &self.incoming_transform {
+                    Some(transform) => transform.apply(text),
+                    None => text.clone(),
+                };
+                
+                let incoming = {
+                    let mut detector = self.detector.lock().unwrap();
+                    let incoming = incoming_text(&detector, text);
+                    if matches!(incoming, IncomingText::Write(_)) {
+                        detector.begin_apply();
+                    }
+                    incoming
+                };
+                let text = match incoming {
+                    IncomingText::Write(text) => text,
+                    IncomingText::Unchanged => {
+                        // 与本地最后的内容相同时跳过，避免无意义地改写剪贴板
+                        status!("⏭️ 内容与本地剪贴板相同，跳过");
+                        return;
+                    }
+                    IncomingText::Clear => {
+                        self.clear(message).await;
+                        return;
+                    }
+                };
+                let written = text.clone();
+                let result = self.write_clipboard(move |clipboard| clipboard.set_text(&written)).await;
+                let mut detector = self.detector.lock().unwrap();
+                if let Err(e) = result {
+                    detector.cancel_apply();
+                    eprintln!("❌ 更新文本剪贴板失败: {}", e);
+                    events::emit(Event::error(Some(&message.sender_id), format!("更新文本剪贴板失败: {}", e)));
+                } else {
+                    // 记录为已处理，监控循环不会把它当作本地变化再广播回去
+                    detector.record_text(text);
+                    detector.record_applied(Instant::now());
+                    self.history.record(HistorySource::peer(message), &message.content);
+                    let preview = message.content.preview(50);
+                    let _ = self.notifier.send_synced(NotifyKind::Text, "文本剪贴板已同步", &preview);
+                }
+            }
+            // 替换规则只针对纯文本，HTML 原样写入
+            ClipboardContent::Html { html, text } => {
+                {
+                    let mut detector = self.detector.lock().unwrap();
+                    if !detector.html_changed(html) {
+                        status!("⏭️ 内容与本地剪贴板相同，跳过");
+                        return;
+                    }
+                    detector.begin_apply();
+                }
+                let (written_text, written_html) = (text.clone(), html.clone());
+                let result = self
+                    .write_clipboard(move |clipboard| clipboard.set_rich(Some(&written_text), Some(&written_html)))
+                    .await;
+                let mut detector = self.detector.lock().unwrap();
+                if let Err(e) = result {
+                    detector.cancel_apply();
+                    eprintln!("❌ 更新 HTML 剪贴板失败: {}", e);
+                    events::emit(Event::error(Some(&message.sender_id), format!("更新 HTML 剪贴板失败: {}", e)));
+                } else {
+                    detector.record_html(html.clone(), text.clone());
+                    detector.record_applied(Instant::now());
+                    self.history.record(HistorySource::peer(message), &message.content);
+                    let preview = message.content.preview(50);
+                    let _ = self.notifier.send_synced(NotifyKind::Text, "文本剪贴板已同步", &preview);
+                }
+            }
+            #[cfg(feature = "image-support")]
+            ClipboardContent::Image { width, height, data } => {
+                let fingerprint = image_fingerprint(*width, *height, data);
+                {
+                    let mut detector = self.detector.lock().unwrap();
+                    if !detector.image_changed(fingerprint) {
+                        status!("⏭️ 图片与本地剪贴板相同，跳过");
+                        return;
+                    }
+                    detector.begin_apply();
+                }
+                let (written_width, written_height, written_data) = (*width, *height, data.clone());
+                let result = self
+                    .write_clipboard(move |clipboard| clipboard.set_image(written_width, written_height, &written_data))
+                    .await;
+                let mut detector = self.detector.lock().unwrap();
+                if let Err(e) = result {
+                    detector.cancel_apply();
+                    eprintln!("❌ 更新图片剪贴板失败: {}", e);
+                    events::emit(Event::error(Some(&message.sender_id), format!("更新图片剪贴板失败: {}", e)));
+                } else {
+                    detector.record_image(fingerprint);
+                    detector.adopt_next_image();
+                    detector.record_applied(Instant::now());
+                    record_image(&self.image_cache, &self.history, HistorySource::peer(message), &message.content);
+                    let preview = format!("图片 {}x{}", width, height);
+                    let _ = self.notifier.send_synced(NotifyKind::Image, "图片剪贴板已同步", &preview);
+                }
+            }
+            // 图片照常由网络层转发给其他设备，只是不解码写入本地剪贴板
+            #[cfg(not(feature = "image-support"))]
+            ClipboardContent::Image { width, height, .. } => {
+                status!("⏭️ 未编译图片支持，不应用收到的图片 {}x{}", width, height);
+            }
+            ClipboardContent::Clear => self.clear(message).await,
+        }
+    }
+
+    /// 响应清空请求，只在启用 --sync-clears 时生效
+    async fn clear(&self, message: &ClipboardMessage) {
+        if !self.sync_clears {
+            status!("⏭️ 未启用 --sync-clears，忽略清空请求");
+            return;
+        }
+        
+        // 清空期间监控循环暂停检测，不会把这次清空当作本地变化
+        self.detector.lock().unwrap().begin_apply();
+        let result = self.write_clipboard(|clipboard| clipboard.clear()).await;
+        let mut detector = self.detector.lock().unwrap();
+        if let Err(e) = result {
+            detector.cancel_apply();
+            eprintln!("❌ 清空剪贴板失败: {}", e);
+            events::emit(Event::error(Some(&message.sender_id), format!("清空剪贴板失败: {}", e)));
+        } else {
+            detector.record_empty();
+            detector.record_applied(Instant::now());
+            let _ = self.notifier.send_synced(NotifyKind::Info, "剪贴板已清空", &message.sender_label());
+        }
+    }
+}
+
+/// 收到的远程文本对本地剪贴板的操作
+#[derive(Debug, PartialEq, Eq)]
+enum IncomingText {
+    /// 写入剪贴板
+    Write(String),
+    /// 与本地最后的内容相同，跳过
+    Unchanged,
+    /// 空文本，按清空请求处理
+    Clear,
+}
+
+/// 判断如何应用收到的文本：空文本（包括替换规则处理后变为空的文本）不写入剪贴板，而是视为清空请求
+fn incoming_text(detector: &ChangeDetector, text: String) -> IncomingText {
+    if text.is_empty() {
+        IncomingText::Clear
+    } else if detector.text_changed(&text) {
+        IncomingText::Write(text)
+    } else {
+        IncomingText::Unchanged
+    }
+}
+
+/// 限流器对一条更新的处理结果
+#[derive(Debug, PartialEq)]
+enum Admission<T> {
+    /// 未超出限制，立即应用
+    Apply(T),
+    /// 超出限制，暂存为窗口结束时应用的最新更新；`first` 表示本窗口内第一次被限流
+    Throttled { first: bool },
+}
+
+/// 一个发送方的计数窗口
+struct SenderWindow<T> {
+    start: Instant,
+    count: u32,
+    /// 被限流的最新更新
+    pending: Option<T>,
+    /// 本窗口内被合并的更新数
+    coalesced: usize,
+}
+
+/// 按发送方限制每个窗口内应用的更新数，超出的更新合并为窗口结束时的最新一条
+struct RateLimiter<T> {
+    max_per_window: u32,
+    window: Duration,
+    senders: HashMap<String, SenderWindow<T>>,
+}
+
+impl<T> RateLimiter<T> {
+    fn new(max_per_window: u32, window: Duration) -> Self {
+        Self { max_per_window, window, senders: HashMap::new() }
+    }
+
+    /// 记录一条来自 `sender` 的更新
+    fn admit(&mut self, sender: String, item: T, now: Instant) -> Admission<T> {
+        // 清理已结束且没有暂存更新的窗口，保持表很小
+        let window = self.window;
+        self.senders
+            .retain(|_, w| w.pending.is_some() || now.duration_since(w.start) < window);
+
+        let entry = self.senders.entry(sender).or_insert_with(|| SenderWindow {
+            start: now,
+            count: 0,
+            pending: None,
+            coalesced: 0,
+        });
+        if entry.count < self.max_per_window {
+            entry.count += 1;
+            return Admission::Apply(item);
+        }
+        entry.coalesced += 1;
+        let first = entry.pending.replace(item).is_none();
+        Admission::Throttled { first }
+    }
+
+    /// 最早需要应用暂存更新的时间
+    fn next_release(&self) -> Option<Instant> {
+        self.senders
+            .values()
+            .filter(|w| w.pending.is_some())
+            .map(|w| w.start + self.window)
+            .min()
+    }
+
+    /// 取出窗口已结束的暂存更新及合并的更新数，应用的这一条计入新窗口
+    fn release(&mut self, now: Instant) -> Vec<(T, usize)> {
+        let mut released = Vec::new();
+        for w in self.senders.values_mut() {
+            if now.duration_since(w.start) < self.window {
+                continue;
+            }
+            if let Some(item) = w.pending.take() {
+                released.push((item, std::mem::take(&mut w.coalesced)));
+                w.start = now;
+                w.count = 1;
+            }
+        }
+        released
+    }
+}
+
+/// 记录同步过的图片；启用图片缓存时在后台线程把图片写入磁盘，不阻塞剪贴板的读写，
+/// 保存后的路径随记录一起保存
+#[cfg(feature = "image-support")]
+fn record_image(cache: &Option<ImageCache>, history: &History, source: HistorySource, content: &ClipboardContent) {
+    let (Some(mut entry), ClipboardContent::Image { data, .. }) = (crate::history::HistoryEntry::new(source, content), content) else {
+        return;
+    };
+    let Some(cache) = cache.clone() else {
+        history.push(entry);
+        return;
+    };
+    let history = history.clone();
+    let data = data.clone();
+    tokio::task::spawn_blocking(move || {
+        match cache.save(&data) {
+            Ok(path) => {
+                status!("💾 图片已保存到 {}", path.display());
+                entry.image_path = Some(path);
+            }
+            Err(e) => eprintln!("⚠️ 保存图片到缓存失败: {}", e),
+        }
+        history.push(entry);
+    });
+}
+
+/// 无剪贴板的中继模式：不读写本地剪贴板，只维持连接并由网络层转发消息，直到收到 Ctrl+C、SIGTERM 或退出请求
+///
+/// 需要配合 `NetworkManager::with_relay` 使用
+pub async fn run_relay(network: &NetworkManager, opts: MonitorOptions) {
+    // 收到的消息已在网络层转发，这里只需消费掉并记录活动
+    let mut message_receiver = network.setup_message_queue().await;
+    let activity = session::watch(opts.session_limits, opts.control.clone());
+    tokio::spawn(async move {
+        while message_receiver.recv().await.is_some() {
+            activity.touch();
+        }
+    });
+    
+    tokio::select! {
+        _ = opts.control.cancelled() => {}
+        _ = shutdown_signal() => {}
+    }
+}
+
+/// 读取当前剪贴板内容并广播一次，不进入监控循环，剪贴板为空时返回 `None`
+pub async fn sync_once(
+    clipboard: &ClipboardManager,
+    network: &NetworkManager,
+    opts: &MonitorOptions,
+) -> anyhow::Result<Option<BroadcastResult>> {
+    match current_content(clipboard, opts) {
+        Some(content) => Ok(Some(network.broadcast_content(content).await?)),
+        None => Ok(None),
+    }
+}
+
+/// 广播指定的文本（应用替换规则和截断），不读取也不修改本地剪贴板
+///
+/// 空文本在接收方会被当作清空请求，因此直接返回错误
+pub async fn send_text(network: &NetworkManager, text: &str, opts: &MonitorOptions) -> anyhow::Result<BroadcastResult> {
+    let text = prepare_outgoing_text(text.to_string(), opts);
+    if text.is_empty() {
+        anyhow::bail!("要发送的文本为空");
+    }
+    Ok(network.broadcast_content(ClipboardContent::from_text(text)).await?)
+}
+
+/// 当前剪贴板中要发送的内容（已应用替换规则和截断），剪贴板为空时返回 `None`
+fn current_content(clipboard: &ClipboardManager, opts: &MonitorOptions) -> Option<ClipboardContent> {
+    let mut detector = ChangeDetector::new();
+    match detect_local_change(clipboard, &mut detector, opts.normalize_text)? {
+        LocalChange::Text(text) => Some(ClipboardContent::from_text(prepare_outgoing_text(text, opts))),
+        LocalChange::Html { html, text } => Some(outgoing_html(html, text, opts)),
+        #[cfg(feature = "image-support")]
+        LocalChange::Image { width, height, png_data } => Some(ClipboardContent::Image { width, height, data: png_data }),
+        LocalChange::Cleared => None,
+    }
+}
+
+/// 只把当前剪贴板内容发送给一个设备，用于 `send <设备>` 命令和回复对方的请求
+async fn send_current(clipboard: &ClipboardManager, network: &NetworkManager, opts: &MonitorOptions, device_id: &str) {
+    let Some(content) = current_content(clipboard, opts) else {
+        status!("⚠️ 剪贴板为空，没有可发送的内容");
+        return;
+    };
+    match network.send_to(device_id, content).await {
+        Ok(()) => status!("✅ 当前剪贴板内容已发送到 {}", device_id),
+        Err(e) => eprintln!("❌ 发送到 {} 失败: {}", device_id, e),
+    }
+}
+
+/// 输出广播结果，部分或全部设备未收到时给出警告
+fn report_broadcast(kind: &str, result: SyncResult<BroadcastResult>) {
+    match result {
+        Ok(result) if result.queued => {
+            status!("🕒 {}暂未同步：没有设备收到，将在设备连接后发送", kind);
+        }
+        Ok(result) if result.total() == 0 => {
+            status!("⚠️ {}未同步：没有已连接的设备", kind);
+        }
+        Ok(result) if result.failed > 0 => {
+            eprintln!("⚠️ {}仅同步到 {}/{} 个设备", kind, result.delivered, result.total());
+        }
+        Ok(result) => {
+            status!("✅ {}已同步到 {} 个设备", kind, result.delivered);
+        }
+        Err(e) if e.is_encode_error() => {
+            eprintln!("❌ {}无法编码，未发送给任何设备: {}", kind, e);
+            events::emit(Event::error(None, format!("{}无法编码: {}", kind, e)));
+        }
+        Err(e) => {
+            eprintln!("❌ {}广播失败: {}", kind, e);
+            events::emit(Event::error(None, format!("{}广播失败: {}", kind, e)));
+        }
+    }
+}
+
+/// 要发送的 HTML 内容：替换规则和截断只作用于纯文本形式，HTML 超过 `max_text_bytes` 时只发送纯文本
+fn outgoing_html(html: String, text: String, opts: &MonitorOptions) -> ClipboardContent {
+    let text = prepare_outgoing_text(text, opts);
+    match opts.max_text_bytes {
+        Some(max_bytes) if html.len() > max_bytes => {
+            eprintln!("⚠️ HTML 过大 ({} bytes)，只广播纯文本", html.len());
+            ClipboardContent::from_text(text)
+        }
+        _ => ClipboardContent::Html { html, text },
+    }
+}
+
+/// 广播前处理文本：应用替换规则，并按配置截断过大的文本
+fn prepare_outgoing_text(mut text: String, opts: &MonitorOptions) -> String {
+    if !opts.transform.is_empty() {
+        text = opts.transform.apply(&text);
+    }
+    
+    if let Some(max_bytes) = opts.max_text_bytes {
+        let original_len = text.len();
+        if truncate_utf8(&mut text, max_bytes) {
+            eprintln!("⚠️ 文本过大 ({} bytes)，已截断为 {} bytes 后广播", original_len, text.len());
+        }
+    }
+    text
+}
+
+/// 监控循环检测到的本地剪贴板变化
+enum LocalChange {
+    Text(String),
+    /// HTML 及其纯文本形式
+    Html { html: String, text: String },
+    #[cfg(feature = "image-support")]
+    Image { width: u32, height: u32, png_data: Vec<u8> },
+    Cleared,
+}
+
+/// 读取剪贴板并与上次记录的状态比较，返回需要广播的变化
+fn detect_local_change(
+    clipboard: &ClipboardManager,
+    detector: &mut ChangeDetector,
+    normalize: bool,
+) -> Option<LocalChange> {
+    // 检查剪贴板内容类型
+    match clipboard.get_content_type() {
+        ClipboardContentType::Text => {
+            let mut current_content = clipboard.get_text().ok()?;
+            if normalize {
+                current_content = normalize_text(&current_content);
+            }
+            local_text_change(detector, current_content)
+        }
+        ClipboardContentType::Html => {
+            let html = clipboard.get_html().ok()?;
+            let mut text = clipboard.get_text().unwrap_or_default();
+            if normalize {
+                text = normalize_text(&text);
+            }
+            if !detector.html_changed(&html) {
+                return None;
+            }
+            detector.record_html(html.clone(), text.clone());
+            Some(LocalChange::Html { html, text })
+        }
+        #[cfg(feature = "image-support")]
+        ClipboardContentType::Image => {
+            // 与文本一样按内容判断：像素与最近处理过的图片相同时（包括从文本切换回来）不重复广播，
+            // 相同的图片也不再编码
+            let hash = clipboard.image_fingerprint()?;
+            let changed = detector.image_hash_changed(hash);
+            detector.record_image_hash(hash);
+            if !changed {
+                clipboard.discard_cached_image();
+                return None;
+            }
+            let (width, height, png_data) = clipboard.get_image().ok()??;
+            detector.record_image(image_fingerprint(width, height, &png_data));
+            Some(LocalChange::Image { width, height, png_data })
+        }
+        // 未编译图片支持时剪贴板管理器不会报告图片
+        #[cfg(not(feature = "image-support"))]
+        ClipboardContentType::Image => None,
+        ClipboardContentType::Empty => {
+            // 剪贴板为空，更新状态
+            detector.record_empty().then_some(LocalChange::Cleared)
+        }
+    }
+}
+
+/// 本地文本变化：空文本（包括规范化后变为空的文本）按剪贴板被清空处理，不会作为文本广播
+fn local_text_change(detector: &mut ChangeDetector, text: String) -> Option<LocalChange> {
+    if text.is_empty() {
+        return detector.record_empty().then_some(LocalChange::Cleared);
+    }
+    if !detector.text_changed(&text) {
+        return None;
+    }
+    detector.record_text(text.clone());
+    Some(LocalChange::Text(text))
+}
+
+/// 图片内容指纹
+#[cfg(feature = "image-support")]
+fn image_fingerprint(width: u32, height: u32, data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (width, height, data).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 文本规范化：CRLF 统一为 LF，去掉末尾的一个换行，其余空白保持不变
+fn normalize_text(text: &str) -> String {
+    let text = text.replace("\r\n", "\n");
+    match text.strip_suffix('\n') {
+        Some(trimmed) => trimmed.to_string(),
+        None => text,
+    }
+}
+
+/// 将文本截断到不超过 `max_bytes` 字节，不会拆开多字节字符，返回是否发生了截断
+fn truncate_utf8(text: &mut String, max_bytes: usize) -> bool {
+    if text.len() <= max_bytes {
+        return false;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    true
+}
+
+/// 剪贴板变化检测状态 - 记录上一次观察到的内容，用于判断是否需要广播
+struct ChangeDetector {
+    last_text_content: String,
+    /// 上次记录的 HTML，内容不是 HTML 时为空
+    last_html: String,
+    /// 上次记录的图片指纹，用于跳过重复应用相同的远程图片
+    last_image_fingerprint: Option<u64>,
+    /// 最近一次在本地剪贴板读到的图片的像素指纹，剪贴板切换为文本后仍然保留，切换回同一张图片时不再广播
+    last_image_hash: Option<u64>,
+    /// 刚把收到的图片写入剪贴板：读回的像素不一定与收到的数据一致，下一次读到的图片只记录指纹，不当作本地变化
+    adopt_image: bool,
+    last_content_type: ClipboardContentType,
+    /// 最近一次把收到的内容写入剪贴板的时间
+    last_applied: Option<Instant>,
+    /// 正在把收到的内容写入剪贴板
+    applying: bool,
+}
+
+impl ChangeDetector {
+    fn new() -> Self {
+        Self {
+            last_text_content: String::new(),
+            last_html: String::new(),
+            last_image_fingerprint: None,
+            last_image_hash: None,
+            adopt_image: false,
+            last_content_type: ClipboardContentType::Empty,
+            last_applied: None,
+            applying: false,
+        }
+    }
+
+    /// 开始把收到的内容写入剪贴板，写入完成前监控循环不检测本地变化
+    fn begin_apply(&mut self) {
+        self.applying = true;
+    }
+
+    /// 写入失败，恢复检测
+    fn cancel_apply(&mut self) {
+        self.applying = false;
+    }
+
+    /// 记录刚把收到的内容写入剪贴板
+    fn record_applied(&mut self, now: Instant) {
+        self.applying = false;
+        self.last_applied = Some(now);
+    }
+
+    /// 是否正在写入，或仍在最近一次写入后的抑制窗口内
+    fn suppressed(&self, window: Duration, now: Instant) -> bool {
+        self.applying || self.last_applied.is_some_and(|applied| now.saturating_duration_since(applied) < window)
+    }
+
+    /// 文本是否与上次记录的不同（空文本不视为变化）
+    fn text_changed(&self, text: &str) -> bool {
+        text != self.last_text_content && !text.is_empty()
+    }
+
+    /// 记录已处理的文本
+    fn record_text(&mut self, text: String) {
+        self.last_text_content = text;
+        self.last_html.clear();
+        self.last_image_fingerprint = None;
+        self.adopt_image = false;
+        self.last_content_type = ClipboardContentType::Text;
+    }
+
+    /// HTML 是否与上次记录的不同
+    fn html_changed(&self, html: &str) -> bool {
+        html != self.last_html && !html.is_empty()
+    }
+
+    /// 记录已处理的 HTML 及其纯文本形式，之后读到相同的纯文本也不视为变化
+    fn record_html(&mut self, html: String, text: String) {
+        self.last_html = html;
+        self.last_text_content = text;
+        self.last_image_fingerprint = None;
+        self.adopt_image = false;
+        self.last_content_type = ClipboardContentType::Html;
+    }
+
+    /// 图片是否与上次记录的不同
+    #[cfg(feature = "image-support")]
+    fn image_changed(&self, fingerprint: u64) -> bool {
+        self.last_image_fingerprint != Some(fingerprint)
+    }
+
+    /// 本地图片的像素是否与最近读到的图片不同，中间切换过文本也不影响；刚写入收到的图片时不算变化
+    #[cfg(feature = "image-support")]
+    fn image_hash_changed(&self, hash: u64) -> bool {
+        !self.adopt_image && self.last_image_hash != Some(hash)
+    }
+
+    /// 记录在本地剪贴板读到的图片的像素指纹
+    #[cfg(feature = "image-support")]
+    fn record_image_hash(&mut self, hash: u64) {
+        self.last_text_content.clear();
+        self.last_html.clear();
+        self.last_image_hash = Some(hash);
+        self.adopt_image = false;
+        self.last_content_type = ClipboardContentType::Image;
+    }
+
+    /// 记录已处理的图片
+    #[cfg(feature = "image-support")]
+    fn record_image(&mut self, fingerprint: u64) {
+        self.last_text_content.clear();
+        self.last_html.clear();
+        self.last_image_fingerprint = Some(fingerprint);
+        self.last_content_type = ClipboardContentType::Image;
+    }
+
+    /// 刚把收到的图片写入剪贴板，下一次读到的图片视为这张图片
+    #[cfg(feature = "image-support")]
+    fn adopt_next_image(&mut self) {
+        self.adopt_image = true;
+    }
+
+    /// 记录剪贴板变为空，返回是否是从非空变为空
+    fn record_empty(&mut self) -> bool {
+        let was_empty = matches!(self.last_content_type, ClipboardContentType::Empty);
+        self.last_content_type = ClipboardContentType::Empty;
+        self.last_text_content.clear();
+        self.last_html.clear();
+        self.last_image_fingerprint = None;
+        self.last_image_hash = None;
+        self.adopt_image = false;
+        !was_empty
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "image-support")]
+    use crate::clipboard::{ClipboardBackend, MemoryClipboard};
+    #[cfg(feature = "image-support")]
+    use arboard::ImageData;
+
+    #[test]
+    fn test_rate_limiter_coalesces_excess_updates() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(limiter.admit("a".to_string(), 1, start), Admission::Apply(1));
+        assert_eq!(limiter.admit("a".to_string(), 2, start), Admission::Apply(2));
+        assert_eq!(limiter.admit("a".to_string(), 3, start), Admission::Throttled { first: true });
+        assert_eq!(limiter.admit("a".to_string(), 4, start), Admission::Throttled { first: false });
+        // 其他发送方不受影响
+        assert_eq!(limiter.admit("b".to_string(), 5, start), Admission::Apply(5));
+
+        // 窗口结束前不释放，结束后只应用最新的一条
+        assert_eq!(limiter.next_release(), Some(start + Duration::from_secs(1)));
+        assert!(limiter.release(start + Duration::from_millis(500)).is_empty());
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.release(later), vec![(4, 2)]);
+        assert_eq!(limiter.next_release(), None);
+
+        // 释放的那条计入新窗口
+        assert_eq!(limiter.admit("a".to_string(), 6, later), Admission::Apply(6));
+        assert_eq!(limiter.admit("a".to_string(), 7, later), Admission::Throttled { first: true });
+    }
+
+    #[test]
+    fn test_rate_limiter_forgets_idle_senders() {
+        let mut limiter = RateLimiter::new(1, Duration::from_secs(1));
+        let start = Instant::now();
+        assert_eq!(limiter.admit("a".to_string(), 1, start), Admission::Apply(1));
+        assert_eq!(limiter.admit("b".to_string(), 2, start + Duration::from_secs(2)), Admission::Apply(2));
+        assert_eq!(limiter.senders.len(), 1);
+    }
+
+    #[test]
+    fn test_truncate_utf8() {
+        let mut text = "ab".to_string();
+        assert!(!truncate_utf8(&mut text, 2));
+        assert_eq!(text, "ab");
+
+        // "中" 占 3 个字节，不能从中间截断
+        let mut text = "a中文".to_string();
+        assert!(truncate_utf8(&mut text, 3));
+        assert_eq!(text, "a");
+
+        let mut text = "a中文".to_string();
+        assert!(truncate_utf8(&mut text, 4));
+        assert_eq!(text, "a中");
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        assert_eq!(normalize_text("a\r\nb\r\n"), "a\nb");
+        assert_eq!(normalize_text("echo hi\n"), "echo hi");
+        // 只去掉一个换行，缩进和行尾空格保持原样
+        assert_eq!(normalize_text("fn main() {\n    x  \n}\n\n"), "fn main() {\n    x  \n}\n");
+        assert_eq!(normalize_text("  trailing  "), "  trailing  ");
+        assert_eq!(normalize_text("lone\rcr"), "lone\rcr");
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_change_detector_text_and_image() {
+        let mut detector = ChangeDetector::new();
+        
+        // 空文本不算变化
+        assert!(!detector.text_changed(""));
+        assert!(detector.text_changed("hello"));
+        
+        detector.record_text("hello".to_string());
+        assert!(!detector.text_changed("hello"));
+        assert!(detector.text_changed("world"));
+        
+        // 图片按像素指纹判断，相同的图片只处理一次
+        assert!(detector.image_hash_changed(1));
+        detector.record_image_hash(1);
+        assert!(!detector.image_hash_changed(1));
+        assert!(detector.image_hash_changed(2));
+        
+        // 清空后相同文本和图片再次出现需要重新广播
+        assert!(detector.record_empty());
+        assert!(detector.image_hash_changed(1));
+        assert!(detector.text_changed("hello"));
+
+        // 刚写入收到的图片后，读回的图片不当作本地变化
+        detector.record_image(3);
+        detector.adopt_next_image();
+        assert!(!detector.image_hash_changed(4));
+        detector.record_image_hash(4);
+        assert!(detector.image_hash_changed(5));
+    }
+
+    #[test]
+    fn test_suppression_window_after_apply() {
+        let window = DEFAULT_APPLY_SUPPRESSION;
+        let now = Instant::now();
+        let mut detector = ChangeDetector::new();
+        assert!(!detector.suppressed(window, now));
+
+        detector.record_applied(now);
+        assert!(detector.suppressed(window, now));
+        assert!(detector.suppressed(window, now + window / 2));
+        assert!(!detector.suppressed(window, now + window));
+        // 窗口为 0 时不抑制
+        assert!(!detector.suppressed(Duration::ZERO, now));
+    }
+
+    #[test]
+    fn test_suppressed_while_applying() {
+        let now = Instant::now();
+        let mut detector = ChangeDetector::new();
+
+        // 写入期间即使窗口为 0 也不检测
+        detector.begin_apply();
+        assert!(detector.suppressed(Duration::ZERO, now));
+        detector.cancel_apply();
+        assert!(!detector.suppressed(DEFAULT_APPLY_SUPPRESSION, now));
+
+        detector.begin_apply();
+        detector.record_applied(now);
+        assert!(!detector.suppressed(Duration::ZERO, now));
+        assert!(detector.suppressed(DEFAULT_APPLY_SUPPRESSION, now));
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_change_detector_skips_identical_incoming() {
+        let mut detector = ChangeDetector::new();
+        
+        // 已应用的文本再次到达时跳过
+        detector.record_text("hello".to_string());
+        assert!(!detector.text_changed("hello"));
+        
+        // 相同图片跳过，不同图片需要应用
+        let image = image_fingerprint(2, 2, &[1, 2, 3]);
+        assert!(detector.image_changed(image));
+        detector.record_image(image);
+        assert!(!detector.image_changed(image_fingerprint(2, 2, &[1, 2, 3])));
+        assert!(detector.image_changed(image_fingerprint(2, 2, &[1, 2, 4])));
+        assert!(detector.image_changed(image_fingerprint(1, 4, &[1, 2, 3])));
+        
+        // 剪贴板变为图片后，之前的文本再次到达需要重新应用
+        assert!(detector.text_changed("hello"));
+        detector.record_text("hello".to_string());
+        assert!(detector.image_changed(image));
+    }
+
+    #[cfg(feature = "image-support")]
+    #[test]
+    fn test_unchanged_image_sent_once_across_type_toggles() {
+        let memory = MemoryClipboard::new();
+        let clipboard = ClipboardManager::with_backend(memory.clone());
+        let mut detector = ChangeDetector::new();
+        let image = || ImageData { width: 8, height: 8, bytes: vec![0x40; 256].into() };
+        let mut broadcasts = 0;
+
+        for _ in 0..3 {
+            memory.clone().set_image(image()).unwrap();
+            if let Some(LocalChange::Image { .. }) = detect_local_change(&clipboard, &mut detector, false) {
+                broadcasts += 1;
+            }
+            memory.clone().set_text("切换到文本").unwrap();
+            detect_local_change(&clipboard, &mut detector, false);
+        }
+        assert_eq!(broadcasts, 1);
+
+        // 换一张图片后照常广播
+        memory.clone().set_image(ImageData { width: 8, height: 8, bytes: vec![0x41; 256].into() }).unwrap();
+        assert!(matches!(detect_local_change(&clipboard, &mut detector, false), Some(LocalChange::Image { .. })));
+
+        // 中间没有文本，直接换成另一张图片也照常广播，同一张图片不重复广播
+        memory.clone().set_image(ImageData { width: 8, height: 8, bytes: vec![0x42; 256].into() }).unwrap();
+        assert!(matches!(detect_local_change(&clipboard, &mut detector, false), Some(LocalChange::Image { .. })));
+        assert!(detect_local_change(&clipboard, &mut detector, false).is_none());
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("send client_10.0.0.2:50000".parse::<Command>(), Ok(Command::Send("client_10.0.0.2:50000".to_string())));
+        assert_eq!("  peers ".parse::<Command>(), Ok(Command::Peers));
+        assert_eq!("status".parse::<Command>(), Ok(Command::Status));
+        assert_eq!("history".parse::<Command>(), Ok(Command::History(None)));
+        assert_eq!("hi --search  foo bar".parse::<Command>(), Ok(Command::History(Some("foo bar".to_string()))));
+        assert!("history --search".parse::<Command>().is_err());
+        assert!("history foo".parse::<Command>().is_err());
+        assert!("send".parse::<Command>().is_err());
+        assert_eq!("send  你好 世界 ".parse::<Command>(), Ok(Command::Send("你好 世界".to_string())));
+        // 不区分大小写，可以只输入能唯一确定命令的开头部分
+        assert_eq!("QUIT".parse::<Command>(), Ok(Command::Quit));
+        assert_eq!("exit".parse::<Command>(), Ok(Command::Quit));
+        assert_eq!("pa".parse::<Command>(), Ok(Command::Pause));
+        assert_eq!("kick client_10.0.0.2:50000".parse::<Command>(), Ok(Command::Kick("client_10.0.0.2:50000".to_string())));
+        assert!("p".parse::<Command>().unwrap_err().contains("不明确"));
+        assert!("kick".parse::<Command>().is_err());
+        assert!("peers now".parse::<Command>().unwrap_err().contains("不需要参数"));
+        assert!("launch".parse::<Command>().unwrap_err().contains("可用命令"));
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        // 从未发现本地变化时总是应用
+        assert!(!ConflictPolicy::Latest.keeps_local(None, 100));
+        assert!(!ConflictPolicy::Remote.keeps_local(Some(200), 100));
+        
+        // 按时间：同一秒内收到的内容仍然应用
+        assert!(ConflictPolicy::Latest.keeps_local(Some(101), 100));
+        assert!(!ConflictPolicy::Latest.keeps_local(Some(100), 100));
+        
+        // 保留本地：本地变化稍早于消息时也保留
+        assert!(ConflictPolicy::Local.keeps_local(Some(98), 100));
+        assert!(!ConflictPolicy::Local.keeps_local(Some(97), 100));
+        assert_eq!("latest".parse::<ConflictPolicy>(), Ok(ConflictPolicy::Latest));
+        assert!("newest".parse::<ConflictPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_local_empty_text_is_clear() {
+        let mut detector = ChangeDetector::new();
+        assert!(matches!(local_text_change(&mut detector, "hello".to_string()), Some(LocalChange::Text(_))));
+        
+        // 空文本按清空处理，只报告一次
+        assert!(matches!(local_text_change(&mut detector, String::new()), Some(LocalChange::Cleared)));
+        assert!(local_text_change(&mut detector, String::new()).is_none());
+        
+        // 清空后相同文本再次出现需要重新广播
+        assert!(matches!(local_text_change(&mut detector, "hello".to_string()), Some(LocalChange::Text(_))));
+    }
+
+    #[test]
+    fn test_remote_empty_text_is_clear() {
+        let mut detector = ChangeDetector::new();
+        assert_eq!(incoming_text(&detector, String::new()), IncomingText::Clear);
+        assert_eq!(incoming_text(&detector, "hello".to_string()), IncomingText::Write("hello".to_string()));
+        
+        detector.record_text("hello".to_string());
+        assert_eq!(incoming_text(&detector, "hello".to_string()), IncomingText::Unchanged);
+        // 本地已有文本时空文本仍然是清空请求，而不是“内容相同”
+        assert_eq!(incoming_text(&detector, String::new()), IncomingText::Clear);
+    }
+
+    #[test]
+    fn test_change_detector_reports_clear_once() {
+        let mut detector = ChangeDetector::new();
+        
+        // 初始即为空，不算清空
+        assert!(!detector.record_empty());
+        
+        detector.record_text("hello".to_string());
+        assert!(detector.record_empty());
+        assert!(!detector.record_empty());
+    }
+}
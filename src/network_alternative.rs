@@ -1,39 +1,320 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, Mutex, Semaphore};
+use unicode_segmentation::UnicodeSegmentation;
 use tokio::net::{TcpListener as TokioTcpListener, TcpStream as TokioTcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use crate::access::{AccessList, ContentPolicy, ContentTypes};
+use crate::auth;
+use crate::compression::{self, CompressionPolicy, Encoding};
+use crate::config;
+use crate::error::{SyncError, SyncResult};
+use crate::events::{self, Event};
+use crate::frame;
+use crate::handshake::{self, PeerIdentity};
+use crate::health::{HealthSnapshot, HeartbeatOutcome, PeerHealth, DEFAULT_HEARTBEAT_INTERVAL};
+use crate::notification::NotificationManager;
+use crate::pairing::ConnectTarget;
+#[cfg(feature = "tls")]
+use crate::tls::TlsSettings;
+use crate::peers::PeerStore;
+use crate::structured::Color;
+use crate::queue::{self, MessageQueue, QueueFullPolicy, QueueSender, DEFAULT_QUEUE_CAPACITY};
+#[cfg(not(feature = "tls"))]
+use crate::transport::split_plain;
+use crate::transport::{
+    BoxedReader, BoxedWriter, Encryption, FramedConnection, FramedReader, FramedWriter, Incoming, MessageEncoder, OutgoingFrames, TcpTransport,
+    Transport,
+};
 
 // 网络配置常量
-const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
-const MESSAGE_MAX_SIZE: usize = 10 * 1024 * 1024; // 10MB最大消息大小
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_KEEPALIVE_IDLE: Duration = Duration::from_secs(60);
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// 中继模式下记住最近转发过的消息数，用于避免多个中继之间循环转发
+const RELAY_HISTORY: usize = 64;
+/// 消息时间戳与本机时间相差超过该秒数时提示时钟偏差
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+
+/// 每个 TCP 连接的套接字选项
+#[derive(Debug, Clone, Copy)]
+pub struct SocketOptions {
+    /// 禁用 Nagle 算法，小消息立即发送
+    pub nodelay: bool,
+    /// 连接空闲多久后开始发送保活探测
+    pub keepalive_idle: Duration,
+    /// 保活探测的间隔
+    pub keepalive_interval: Duration,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive_idle: DEFAULT_KEEPALIVE_IDLE,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// 在开始读写前应用到连接上
+    fn apply(&self, stream: &TokioTcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        
+        let keepalive = socket2::TcpKeepalive::new().with_time(self.keepalive_idle);
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        let keepalive = keepalive.with_interval(self.keepalive_interval);
+        socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)
+    }
+}
+
+/// 与某个设备之间的同步方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncDirection {
+    /// 双向同步（默认）
+    #[default]
+    Both,
+    /// 只向该设备发送，忽略它发来的内容
+    Send,
+    /// 只接收该设备的内容，广播时跳过它
+    Receive,
+}
+
+impl SyncDirection {
+    /// 是否向该设备发送本机内容
+    pub fn sends(self) -> bool {
+        self != SyncDirection::Receive
+    }
+
+    /// 是否接受该设备发来的内容
+    pub fn receives(self) -> bool {
+        self != SyncDirection::Send
+    }
+}
+
+impl FromStr for SyncDirection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "both" => Ok(SyncDirection::Both),
+            "send" => Ok(SyncDirection::Send),
+            "recv" | "receive" => Ok(SyncDirection::Receive),
+            other => Err(anyhow::anyhow!("未知的同步方向: {} (可选: send, recv, both)", other)),
+        }
+    }
+}
+
+impl fmt::Display for SyncDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SyncDirection::Both => "both",
+            SyncDirection::Send => "send",
+            SyncDirection::Receive => "recv",
+        })
+    }
+}
+
+/// 发现其他设备与本机同名时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameCollision {
+    /// 只输出警告（默认）
+    #[default]
+    Warn,
+    /// 在本机名称后追加设备标识的前几位，之后发送的消息使用新名称
+    Suffix,
+}
+
+impl FromStr for NameCollision {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "warn" => Ok(NameCollision::Warn),
+            "suffix" => Ok(NameCollision::Suffix),
+            other => Err(anyhow::anyhow!("未知的同名处理方式: {} (可选: warn, suffix)", other)),
+        }
+    }
+}
+
+/// 判断同一发送方消息先后顺序的依据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageOrdering {
+    /// 按发送方的系统时间（默认），时钟被调回时新消息可能被当作旧消息丢弃
+    #[default]
+    Time,
+    /// 按发送方为每条消息递增的序号，不受时钟影响；对方为旧版本（消息没有序号）时仍按时间
+    Sequence,
+}
+
+impl FromStr for MessageOrdering {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "time" => Ok(MessageOrdering::Time),
+            "sequence" => Ok(MessageOrdering::Sequence),
+            other => Err(anyhow::anyhow!("未知的消息排序方式: {} (可选: time, sequence)", other)),
+        }
+    }
+}
+
+/// 记录每个发送方最新消息的序号和时间戳，丢弃晚到的旧消息，并提示时钟偏差过大的设备
+#[derive(Debug, Default)]
+struct OrderTracker {
+    /// 发送方标识 -> (序号, 时间戳)
+    latest: HashMap<String, (u64, u64)>,
+    /// 已提示过时钟偏差的发送方
+    skew_warned: HashSet<String>,
+}
+
+impl OrderTracker {
+    /// 消息是否比该发送方之前的消息新，是则记为最新
+    fn admit(&mut self, ordering: MessageOrdering, message: &ClipboardMessage) -> bool {
+        let current = (message.seq, message.timestamp);
+        let newer = match self.latest.get(&message.sender_id) {
+            None => true,
+            Some(&(seq, _)) if ordering == MessageOrdering::Sequence && message.seq > 0 && seq > 0 => message.seq > seq,
+            Some(&(_, timestamp)) => message.timestamp >= timestamp,
+        };
+        if newer {
+            self.latest.insert(message.sender_id.clone(), current);
+        }
+        newer
+    }
+
+    /// 消息时间戳与本机时间 `now` 的偏差超过阈值且尚未提示过时，返回偏差秒数（正数表示对方时钟较快）
+    fn check_skew(&mut self, message: &ClipboardMessage, now: u64) -> Option<i64> {
+        if message.timestamp.abs_diff(now) <= MAX_CLOCK_SKEW_SECS {
+            return None;
+        }
+        self.skew_warned
+            .insert(message.sender_id.clone())
+            .then(|| message.timestamp as i64 - now as i64)
+    }
+}
+
+/// 记录见过的设备名称及其设备标识，用于发现多个设备使用同一名称
+#[derive(Debug, Default)]
+struct NameRegistry {
+    names: HashMap<String, HashSet<String>>,
+}
+
+impl NameRegistry {
+    /// 记录一个设备，该名称此前已属于其他设备且这个设备是第一次出现时返回 true
+    fn observe(&mut self, name: &str, device_id: &str) -> bool {
+        let ids = self.names.entry(name.to_string()).or_default();
+        let is_new = ids.insert(device_id.to_string());
+        is_new && ids.len() > 1
+    }
+}
+
+/// 连接池中的一个连接
+struct Connection {
+    /// 写端有自己的锁：发送时先从连接池中取出，写入期间不持有连接池的锁，
+    /// 向一个接收缓慢的设备写入不会阻塞其他连接的读取任务
+    writer: Arc<Mutex<FramedWriter>>,
+    direction: SyncDirection,
+    /// 中继模式下该连接加入的房间
+    room: Option<String>,
+    /// 允许对方发送的内容类型
+    accept: ContentTypes,
+    /// 心跳延迟和最近收到数据的时间，由读取任务和心跳任务共同更新
+    health: Arc<std::sync::Mutex<PeerHealth>>,
+}
+
+impl Connection {
+    fn new(writer: FramedWriter, direction: SyncDirection, room: Option<String>, accept: ContentTypes) -> Self {
+        let health = PeerHealth::new(writer.supports_heartbeat(), Instant::now());
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+            direction,
+            room,
+            accept,
+            health: Arc::new(std::sync::Mutex::new(health)),
+        }
+    }
+}
+
+/// 锁定连接的健康状况，其他线程持锁时崩溃不影响继续使用
+fn lock_health(health: &std::sync::Mutex<PeerHealth>) -> std::sync::MutexGuard<'_, PeerHealth> {
+    health.lock().unwrap_or_else(|e| e.into_inner())
+}
 
 /// 剪贴板同步内容
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClipboardContent {
     Text(String),
     Image { width: u32, height: u32, data: Vec<u8> },
+    /// 清空剪贴板
+    Clear,
+    /// 识别为颜色的文本，协议 v4 起支持，发给旧版本时退回原始文本
+    Color(Color),
+    /// 带格式的 HTML 及其纯文本形式，协议 v6 起支持，发给旧版本时退回纯文本
+    Html { html: String, text: String },
 }
 
 impl ClipboardContent {
-    /// 获取内容预览
+    /// 从剪贴板文本创建内容，能识别的结构化内容（如颜色）保留结构，其余为普通文本
+    pub fn from_text(text: String) -> Self {
+        match Color::parse(&text) {
+            Some(color) => ClipboardContent::Color(color),
+            None => ClipboardContent::Text(text),
+        }
+    }
+
+    /// 内容类型名称: text、image、clear、color 或 html
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClipboardContent::Text(_) => "text",
+            ClipboardContent::Image { .. } => "image",
+            ClipboardContent::Clear => "clear",
+            ClipboardContent::Color(_) => "color",
+            ClipboardContent::Html { .. } => "html",
+        }
+    }
+
+    /// 结构化内容的文本形式，其他内容返回 `None`
+    pub fn structured_text(&self) -> Option<&str> {
+        match self {
+            ClipboardContent::Color(color) => Some(&color.text),
+            ClipboardContent::Html { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// 对方能解码该内容所需的最低协议版本，低于该版本时结构化内容以文本发送
+    pub(crate) fn required_version(&self) -> u32 {
+        match self {
+            ClipboardContent::Color(_) => handshake::STRUCTURED_CONTENT_VERSION,
+            ClipboardContent::Html { .. } => handshake::HTML_CONTENT_VERSION,
+            _ => frame::MIN_PROTOCOL_VERSION,
+        }
+    }
+
+    /// 获取内容预览，文本最多保留 `max_length` 个字素簇，不会截断组合字符或 emoji 序列
     pub fn preview(&self, max_length: usize) -> String {
         match self {
             ClipboardContent::Text(text) => {
-                if text.chars().count() > max_length {
-                    let truncated: String = text.chars().take(max_length).collect();
-                    format!("{}...", truncated)
-                } else {
-                    text.clone()
+                match text.grapheme_indices(true).nth(max_length) {
+                    Some((end, _)) => format!("{}...", &text[..end]),
+                    None => text.clone(),
                 }
             }
             ClipboardContent::Image { width, height, .. } => {
                 format!("图片 {}x{}", width, height)
             }
+            ClipboardContent::Clear => "清空剪贴板".to_string(),
+            ClipboardContent::Color(color) => format!("颜色 {}", color),
+            ClipboardContent::Html { text, .. } => format!("HTML {}", ClipboardContent::Text(text.clone()).preview(max_length)),
         }
     }
 }
@@ -42,185 +323,791 @@ impl ClipboardContent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipboardMessage {
     pub content: ClipboardContent,
+    /// 发送方生成消息时的 Unix 秒时间戳
     pub timestamp: u64,
+    /// 发送方为每条消息递增的序号，旧版本发送的消息没有序号，为 0
+    #[serde(default)]
+    pub seq: u64,
     pub sender_id: String,
     pub sender_name: String,
 }
 
 impl ClipboardMessage {
-    /// 创建文本消息
-    pub fn new_text(content: String, sender_id: String, sender_name: String) -> Self {
+    /// 创建消息，时间戳为当前时间，序号由 `NetworkManager` 发送时填写
+    pub fn new(content: ClipboardContent, sender_id: String, sender_name: String) -> Self {
         Self {
-            content: ClipboardContent::Text(content),
+            content,
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            seq: 0,
             sender_id,
             sender_name,
         }
     }
 
+    /// 创建文本消息
+    pub fn new_text(content: String, sender_id: String, sender_name: String) -> Self {
+        Self::new(ClipboardContent::Text(content), sender_id, sender_name)
+    }
+
     /// 创建图片消息
     pub fn new_image(width: u32, height: u32, data: Vec<u8>, sender_id: String, sender_name: String) -> Self {
-        Self {
-            content: ClipboardContent::Image { width, height, data },
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            sender_id,
-            sender_name,
-        }
+        Self::new(ClipboardContent::Image { width, height, data }, sender_id, sender_name)
+    }
+
+    /// 创建清空消息
+    pub fn new_clear(sender_id: String, sender_name: String) -> Self {
+        Self::new(ClipboardContent::Clear, sender_id, sender_name)
+    }
+
+    /// 发送方的名称和设备标识前几位，用于在日志和通知中区分同名设备
+    pub fn sender_label(&self) -> String {
+        format!("{} [{}]", self.sender_name, config::short_id(&self.sender_id))
     }
 
     /// 序列化为字节
-    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    pub fn to_bytes(&self) -> SyncResult<Vec<u8>> {
         serde_json::to_vec(self).map_err(Into::into)
     }
 
     /// 从字节反序列化
-    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+    pub fn from_bytes(data: &[u8]) -> SyncResult<Self> {
         serde_json::from_slice(data).map_err(Into::into)
     }
 }
 
+/// 收到的剪贴板消息的投递方式
+#[derive(Clone)]
+enum MessageSink {
+    /// 无界通道（默认）
+    Unbounded(mpsc::UnboundedSender<ClipboardMessage>),
+    /// 有界通道，消费者处理不过来时读取任务会等待，由 TCP 向发送方施加背压
+    Bounded(mpsc::Sender<ClipboardMessage>),
+    /// 按策略处理队列已满的有界队列
+    Queue(Arc<QueueSender>),
+    /// 回调函数，在连接的读取任务中直接调用
+    Callback(Arc<dyn Fn(ClipboardMessage) + Send + Sync>),
+}
+
+impl MessageSink {
+    /// 投递一条消息
+    async fn deliver(&self, message: ClipboardMessage) -> Result<()> {
+        match self {
+            MessageSink::Unbounded(sender) => sender
+                .send(message)
+                .map_err(|e| anyhow::anyhow!("消息通道已关闭: {}", e)),
+            MessageSink::Bounded(sender) => sender
+                .send(message)
+                .await
+                .map_err(|e| anyhow::anyhow!("消息通道已关闭: {}", e)),
+            MessageSink::Queue(sender) => sender.send(message).await,
+            MessageSink::Callback(callback) => {
+                callback(message);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 网络层事件，包括连接状态变化和收到的消息，供界面等需要感知连接状态的使用者订阅
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    /// 与设备建立了连接（连接标识）
+    Connected(String),
+    /// 与设备的连接断开（连接标识）
+    Disconnected(String),
+    /// 收到其他设备的剪贴板消息
+    Message(ClipboardMessage),
+    /// 连接或收发过程中的错误
+    Error(String),
+}
+
+/// 是否形如连接标识：接受的连接为 `client_<地址>`，主动建立的连接为 `server_<地址>`
+pub(crate) fn is_connection_id(s: &str) -> bool {
+    (s.starts_with("client_") || s.starts_with("server_")) && !s.contains(char::is_whitespace)
+}
+
+/// 一次广播的发送结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BroadcastResult {
+    /// 成功发送的设备数
+    pub delivered: usize,
+    /// 发送失败（连接已被移除）的设备数
+    pub failed: usize,
+    /// 没有设备收到，内容已保留到设备连接后发送（见 `NetworkManager::with_send_on_connect`）
+    pub queued: bool,
+}
+
+impl BroadcastResult {
+    /// 尝试发送的设备总数
+    pub fn total(&self) -> usize {
+        self.delivered + self.failed
+    }
+}
+
+/// 没有设备收到的本机内容，每种内容类型只保留最新的一条，设备连接后发送给它
+#[derive(Debug, Default)]
+struct PendingBroadcasts(HashMap<&'static str, ClipboardMessage>);
+
+impl PendingBroadcasts {
+    fn queue(&mut self, message: ClipboardMessage) {
+        self.0.insert(message.content.kind(), message);
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// 取出全部内容，按发出的先后排列，最新的一条最后发送
+    fn take(&mut self) -> Vec<ClipboardMessage> {
+        let mut messages: Vec<_> = self.0.drain().map(|(_, message)| message).collect();
+        messages.sort_by_key(|message| message.seq);
+        messages
+    }
+}
+
 /// 网络管理器
 #[derive(Clone)]
 pub struct NetworkManager {
-    device_name: String,
-    connections: Arc<Mutex<HashMap<String, TokioTcpStream>>>,
-    message_sender: Arc<Mutex<Option<mpsc::UnboundedSender<ClipboardMessage>>>>,
+    /// 设备唯一标识，作为消息的 sender_id
+    device_id: String,
+    /// 设备名称，同名处理方式为 `Suffix` 时运行中可能追加后缀
+    device_name: Arc<std::sync::RwLock<String>>,
+    name_collision: NameCollision,
+    names: Arc<std::sync::Mutex<NameRegistry>>,
+    /// 所有连接（主动发起和被动接受的）的写端及同步方向，广播时统一发送
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
+    message_sender: Arc<Mutex<Option<MessageSink>>>,
+    /// 事件订阅者，与消息处理器相互独立
+    event_sender: Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<SyncEvent>>>>,
     is_running: Arc<Mutex<bool>>,
+    connect_timeout: Duration,
+    /// 监听端口被占用时依次尝试后续端口的次数
+    port_retry: u16,
+    /// 同时保持的最大连接数，`None` 表示不限制
+    max_connections: Option<usize>,
+    /// 按来源地址限制入站连接
+    access: Arc<AccessList>,
+    /// 按设备地址限制它可以发送的内容类型
+    content_policy: Arc<ContentPolicy>,
+    socket_options: SocketOptions,
+    /// TLS 设置，未设置时使用明文 TCP
+    #[cfg(feature = "tls")]
+    tls: TlsSettings,
+    /// 预共享密钥，设置后每个连接都要先通过认证
+    secret: Option<Arc<[u8]>>,
+    /// 连接成功后记录到已配对设备列表
+    peer_store: Option<Arc<std::sync::Mutex<PeerStore>>>,
+    /// 本机监听的端口，握手时告诉对方以便它下次主动连接；0 表示尚未监听
+    listen_port: Arc<AtomicU16>,
+    /// 是否把收到的消息转发给其他已连接的设备
+    relay: bool,
+    /// 最近转发过的消息指纹
+    relayed: Arc<std::sync::Mutex<VecDeque<u64>>>,
+    /// 作为中继运行时各房间的预共享密钥，设置后每个连接都要先加入房间，消息只在同一房间内转发
+    rooms: Option<Arc<HashMap<String, Arc<[u8]>>>>,
+    /// 通过中继连接时要加入的房间
+    room: Option<String>,
+    /// 连接使用的传输方式，默认直接使用 TCP
+    transport: Arc<dyn Transport>,
+    /// 传输层之下的加密方式
+    encryption: Encryption,
+    /// 本机能解码的编码方式，按发送时的优先级排列
+    encodings: Vec<Encoding>,
+    /// 按内容类型决定是否压缩发送的消息
+    compression_policy: CompressionPolicy,
+    /// 判断消息先后顺序的依据
+    ordering: MessageOrdering,
+    /// 每个发送方最新消息的序号和时间戳
+    order: Arc<std::sync::Mutex<OrderTracker>>,
+    /// 下一条发出的消息的序号，以启动时的毫秒时间戳为起点，重启后通常仍大于之前发出的序号
+    next_seq: Arc<AtomicU64>,
+    /// `setup_message_queue` 创建的接收队列的容量
+    queue_capacity: usize,
+    /// 接收队列已满时的处理方式
+    queue_policy: QueueFullPolicy,
+    /// 心跳间隔，`None` 时不发送心跳
+    heartbeat: Option<Duration>,
+    /// 心跳任务是否在运行，有连接时运行，连接全部断开后退出
+    heartbeat_running: Arc<AtomicBool>,
+    /// 连接建立和断开时发送系统通知
+    connection_notifier: Option<NotificationManager>,
+    /// 没有设备收到的内容，设置后在下一个设备连接时发送给它
+    pending: Option<Arc<std::sync::Mutex<PendingBroadcasts>>>,
+    /// 发送前序列化消息的方式，测试中替换为会失败的实现
+    encoder: MessageEncoder,
+    /// 主动连接设备后请求对方发送当前的剪贴板内容
+    request_current: bool,
+    /// 对方请求当前剪贴板内容时通知的订阅者，收到的是连接标识
+    current_requests: Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<String>>>>,
 }
 
 impl NetworkManager {
-    /// 创建新的网络管理器
+    /// 创建新的网络管理器，默认使用随机生成的设备标识
     pub fn new(device_name: String) -> Self {
-        println!("🌐 启动网络通信服务...");
+        status!("🌐 启动网络通信服务...");
         
-        println!("📱 设备名称: {}", device_name);
+        status!("📱 设备名称: {}", device_name);
         
         Self {
-            device_name,
+            device_id: config::generate_device_id(),
+            device_name: Arc::new(std::sync::RwLock::new(device_name)),
+            name_collision: NameCollision::default(),
+            names: Arc::new(std::sync::Mutex::new(NameRegistry::default())),
             connections: Arc::new(Mutex::new(HashMap::new())),
             message_sender: Arc::new(Mutex::new(None)),
+            event_sender: Arc::new(std::sync::Mutex::new(None)),
             is_running: Arc::new(Mutex::new(false)),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            port_retry: 0,
+            max_connections: None,
+            access: Arc::new(AccessList::default()),
+            content_policy: Arc::new(ContentPolicy::default()),
+            socket_options: SocketOptions::default(),
+            #[cfg(feature = "tls")]
+            tls: TlsSettings::default(),
+            secret: None,
+            peer_store: None,
+            listen_port: Arc::new(AtomicU16::new(0)),
+            relay: false,
+            relayed: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            rooms: None,
+            room: None,
+            transport: Arc::new(TcpTransport),
+            encryption: Encryption::default(),
+            encodings: Encoding::ALL.to_vec(),
+            compression_policy: CompressionPolicy::default(),
+            ordering: MessageOrdering::default(),
+            order: Arc::new(std::sync::Mutex::new(OrderTracker::default())),
+            next_seq: Arc::new(AtomicU64::new(
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            )),
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            queue_policy: QueueFullPolicy::default(),
+            heartbeat: Some(DEFAULT_HEARTBEAT_INTERVAL),
+            heartbeat_running: Arc::new(AtomicBool::new(false)),
+            connection_notifier: None,
+            pending: None,
+            encoder: ClipboardMessage::to_bytes,
+            request_current: false,
+            current_requests: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    /// 与设备建立连接和连接意外断开时发送系统通知，本机关闭时断开的连接不通知
+    pub fn with_connection_notifications(mut self, notifier: NotificationManager) -> Self {
+        self.connection_notifier = Some(notifier);
+        self
+    }
+
+    /// 广播时没有设备收到的内容保留下来，下一个设备连接后立即发送给它，晚连接的设备也能拿到当前的剪贴板内容；
+    /// 每种内容类型只保留最新的一条，之后有设备收到广播时清空
+    pub fn with_send_on_connect(mut self, enabled: bool) -> Self {
+        self.pending = enabled.then(|| Arc::new(std::sync::Mutex::new(PendingBroadcasts::default())));
+        self
+    }
+
+    /// 主动连接设备后请求对方发送它当前的剪贴板内容，不必等到对方下一次复制；
+    /// 请求不受同步方向限制，仅接收的连接（如观察模式）也会请求。对方版本低于 v7 时不请求
+    pub fn with_request_current(mut self, enabled: bool) -> Self {
+        self.request_current = enabled;
+        self
+    }
+
+    /// 设置心跳间隔，`None` 时不发送心跳
+    ///
+    /// 协议 v5 及以上的连接定期交换心跳，测量往返延迟；延迟突增或连续未回复心跳时给出提示，
+    /// 开启详细日志时每次心跳后输出各连接的状况
+    pub fn with_heartbeat(mut self, interval: Option<Duration>) -> Self {
+        self.heartbeat = interval.filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// 使用指定的设备标识，通常为 `config::load_or_create_device_id` 保存的固定标识
+    pub fn with_device_id(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = device_id.into();
+        self
+    }
+
+    /// 设置连接设备的超时时间
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// 设置监听端口被占用时向后尝试的端口数
+    pub fn with_port_retry(mut self, retries: u16) -> Self {
+        self.port_retry = retries;
+        self
+    }
+
+    /// 限制同时保持的连接数（包括主动发起的连接），达到上限后新接受的连接会被立即关闭
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// 设置入站连接的来源地址限制
+    pub fn with_access_list(mut self, access: AccessList) -> Self {
+        self.access = Arc::new(access);
+        self
+    }
+
+    /// 按设备地址限制它可以发送的内容类型，不允许的内容会被丢弃，既不应用也不转发
+    pub fn with_content_policy(mut self, policy: ContentPolicy) -> Self {
+        self.content_policy = Arc::new(policy);
+        self
+    }
+
+    /// 设置新连接的套接字选项（TCP_NODELAY 和保活）
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// 设置 TLS，接受和主动发起的连接分别按其中的服务器和客户端配置加密
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: TlsSettings) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// 设置预共享密钥，双方密钥一致才能建立连接
+    pub fn with_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.secret = Some(Arc::from(secret.as_ref()));
+        self
+    }
+
+    /// 连接设备成功、或接受了通过认证且自己也在监听的设备后，以对方的设备标识为键记录到已配对设备存储，下次启动时可自动重连
+    pub fn with_peer_store(mut self, store: PeerStore) -> Self {
+        self.peer_store = Some(Arc::new(std::sync::Mutex::new(store)));
+        self
+    }
+
+    /// 设置发现其他设备与本机同名时的处理方式
+    pub fn with_name_collision(mut self, policy: NameCollision) -> Self {
+        self.name_collision = policy;
+        self
+    }
+
+    /// 设置接收队列的容量和队列已满时的处理方式，用于 `setup_message_queue`
+    pub fn with_message_queue(mut self, capacity: usize, policy: QueueFullPolicy) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self.queue_policy = policy;
+        self
+    }
+
+    /// 设置连接使用的传输方式（如 `TransportKind::build` 创建的内置传输方式），监听端和连接端必须一致
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// 设置连接的加密方式，监听端和连接端必须一致；Noise 加密在设置了预共享密钥时以它认证双方
+    pub fn with_encryption(mut self, encryption: Encryption) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// 设置连接时声明的编码方式（按优先级排列），发送时使用双方都支持的第一种
+    ///
+    /// 不压缩总是可用，对方为不支持协商的旧版本时也不压缩
+    pub fn with_encodings(mut self, encodings: Vec<Encoding>) -> Self {
+        self.encodings = encodings;
+        if !self.encodings.contains(&Encoding::None) {
+            self.encodings.push(Encoding::None);
         }
+        self
+    }
+
+    /// 设置是否压缩发送的消息，默认按内容类型决定（图片不压缩），`Always`/`Never` 时忽略内容类型
+    pub fn with_compression_policy(mut self, policy: CompressionPolicy) -> Self {
+        self.compression_policy = policy;
+        self
+    }
+
+    /// 设置判断同一发送方消息先后顺序的依据，设备之间时钟不同步时可使用 `MessageOrdering::Sequence`
+    pub fn with_ordering(mut self, ordering: MessageOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// 中继模式：把从一个设备收到的消息原样转发给其他已连接的设备（不会发回来源）
+    ///
+    /// 适合在没有剪贴板的常驻服务器上作为集线器，让只连接到它的设备互相同步
+    pub fn with_relay(mut self, relay: bool) -> Self {
+        self.relay = relay;
+        self
+    }
+
+    /// 作为中继运行：每个连接先加入一个房间，收到的消息只转发给同一房间的其他设备
+    ///
+    /// `room_secrets` 为各房间的预共享密钥，未列出的房间使用 `with_secret` 设置的密钥
+    pub fn with_rooms(mut self, room_secrets: HashMap<String, String>) -> Self {
+        let secrets = room_secrets
+            .into_iter()
+            .map(|(room, secret)| (room, Arc::from(secret.into_bytes())))
+            .collect();
+        self.rooms = Some(Arc::new(secrets));
+        self.relay = true;
+        self
+    }
+
+    /// 通过中继连接时加入的房间，同一房间的设备互相同步
+    pub fn with_room(mut self, room: impl Into<String>) -> Self {
+        self.room = Some(room.into());
+        self
     }
 
     /// 设置消息处理器
     pub async fn setup_message_handler(&self) -> mpsc::UnboundedReceiver<ClipboardMessage> {
         let (sender, receiver) = mpsc::unbounded_channel();
-        *self.message_sender.lock().await = Some(sender);
+        *self.message_sender.lock().await = Some(MessageSink::Unbounded(sender));
+        receiver
+    }
+
+    /// 设置有界的消息处理器，通道满时暂停读取连接，避免慢消费者导致内存无限增长
+    pub async fn setup_message_handler_bounded(&self, capacity: usize) -> mpsc::Receiver<ClipboardMessage> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        *self.message_sender.lock().await = Some(MessageSink::Bounded(sender));
+        receiver
+    }
+
+    /// 设置有界的接收队列，容量和队列已满时的处理方式由 `with_message_queue` 指定
+    pub async fn setup_message_queue(&self) -> MessageQueue {
+        let (sender, receiver) = queue::message_queue(self.queue_capacity, self.queue_policy);
+        *self.message_sender.lock().await = Some(MessageSink::Queue(Arc::new(sender)));
         receiver
     }
 
-    /// 启动网络服务（作为服务器监听连接）
-    pub async fn start_server(&self, port: u16) -> Result<()> {
+    /// 以回调函数接收消息，回调在连接的读取任务中执行，不应长时间阻塞
+    pub async fn set_message_callback<F>(&self, callback: F)
+    where
+        F: Fn(ClipboardMessage) + Send + Sync + 'static,
+    {
+        *self.message_sender.lock().await = Some(MessageSink::Callback(Arc::new(callback)));
+    }
+
+    /// 订阅连接、断开、消息和错误事件，与 `setup_message_handler` 的消息通道互不影响
+    pub fn setup_event_handler(&self) -> mpsc::UnboundedReceiver<SyncEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.event_sender.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
+        receiver
+    }
+
+    /// 订阅其他设备发来的当前剪贴板内容请求，收到的是请求方的连接标识，
+    /// 订阅者读取本机剪贴板后用 `send_to` 回复；没有订阅者时忽略请求
+    pub fn setup_request_handler(&self) -> mpsc::UnboundedReceiver<String> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.current_requests.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
+        receiver
+    }
+
+    /// 把对方的请求转交给订阅者
+    fn current_requested(&self, connection_id: &str) {
+        let mut sender = self.current_requests.lock().unwrap_or_else(|e| e.into_inner());
+        if sender.as_ref().is_some_and(|s| s.send(connection_id.to_string()).is_ok()) {
+            status!("📥 {} 请求当前的剪贴板内容", connection_id);
+        } else {
+            *sender = None;
+            status!("⏭️ 本机不提供剪贴板内容，忽略 {} 的请求", connection_id);
+        }
+    }
+
+    /// 请求已连接的设备发送它当前的剪贴板内容，对方以一条普通消息回复；
+    /// 不受同步方向限制，设备未连接或版本低于 v7 时返回错误
+    pub async fn request_current(&self, device_id: &str) -> SyncResult<()> {
+        let writer = self
+            .connections
+            .lock()
+            .await
+            .get(device_id)
+            .map(|connection| connection.writer.clone())
+            .ok_or_else(|| SyncError::PeerNotConnected(device_id.to_string()))?;
+        let mut writer = writer.lock().await;
+        if !writer.supports_request_current() {
+            return Err(anyhow::anyhow!("设备 {} 的版本不支持请求当前剪贴板内容", device_id).into());
+        }
+        writer.send_request_current().await?;
+        status!("📥 已请求 {} 的当前剪贴板内容", device_id);
+        Ok(())
+    }
+
+    /// 向事件订阅者发送事件，没有订阅者时忽略
+    fn notify(&self, event: SyncEvent) {
+        let mut sender = self.event_sender.lock().unwrap_or_else(|e| e.into_inner());
+        // 订阅者已关闭接收端时不再发送
+        if sender.as_ref().is_some_and(|s| s.send(event).is_err()) {
+            *sender = None;
+        }
+    }
+
+    /// 启动网络服务（作为服务器监听连接），返回实际监听的端口
+    pub async fn start_server(&self, port: u16) -> SyncResult<u16> {
         *self.is_running.lock().await = true;
         
         // 启动TCP数据服务器
-        self.start_data_server(port).await?;
+        let port = self.start_data_server(port).await?;
         
-        println!("✅ 网络服务启动完成，监听端口: {}", port);
-        Ok(())
+        status!("✅ 网络服务启动完成，监听端口: {}", port);
+        Ok(port)
     }
 
-    /// 启动TCP数据服务器
-    async fn start_data_server(&self, port: u16) -> Result<()> {
-        let listener = TokioTcpListener::bind(SocketAddr::new(
-            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
-            port,
-        )).await?;
+    /// 绑定监听端口，端口被占用时按配置依次尝试后续端口
+    async fn bind_listener(&self, port: u16) -> Result<TokioTcpListener> {
+        let mut candidate = port;
+        let mut retries_left = if port == 0 { 0 } else { self.port_retry };
         
-        println!("🔄 TCP数据服务器启动在端口  {}", port);
+        loop {
+            let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), candidate);
+            match TokioTcpListener::bind(addr).await {
+                Ok(listener) => return Ok(listener),
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && retries_left > 0 => {
+                    let Some(next) = candidate.checked_add(1) else {
+                        return Err(e.into());
+                    };
+                    status!("⚠️ 端口 {} 已被占用，尝试端口 {}", candidate, next);
+                    candidate = next;
+                    retries_left -= 1;
+                }
+                Err(e) => return Err(anyhow::anyhow!("无法监听端口 {}: {}", candidate, e)),
+            }
+        }
+    }
+
+    /// 启动TCP数据服务器，返回实际监听的端口
+    async fn start_data_server(&self, port: u16) -> Result<u16> {
+        let listener = self.bind_listener(port).await?;
+        let port = listener.local_addr()?.port();
+        self.listen_port.store(port, Ordering::Relaxed);
         
-        let message_sender = self.message_sender.clone();
-        let device_name = self.device_name.clone();
+        status!("🔄 TCP数据服务器启动在端口  {}", port);
+        if let Some(endpoint) = self.transport.endpoint(port) {
+            status!("🌐 使用 {} 传输: {}", self.transport.name(), endpoint);
+        }
+        events::emit(Event::Listening { port });
+        
+        let network = self.clone();
         let is_running = self.is_running.clone();
         let connections = self.connections.clone();
+        let max_connections = self.max_connections;
+        // 接受的连接从握手开始就占用一个名额，握手失败或断开后释放，大量握手中的连接同样受上限约束
+        let slots = max_connections.map(|max| Arc::new(Semaphore::new(max)));
+        let access = self.access.clone();
+        let socket_options = self.socket_options;
         
         tokio::spawn(async move {
             while *is_running.lock().await {
                 match listener.accept().await {
                     Ok((stream, addr)) => {
-                        println!("📥 接受来自 {} 的连接", addr);
+                        if !access.permits(addr.ip()) {
+                            // 直接丢弃连接，不进行握手
+                            eprintln!("🚫 {} 不在允许的地址范围内，已拒绝连接", addr);
+                            events::emit(Event::error(None, format!("拒绝来自 {} 的连接: 地址不在允许范围内", addr)));
+                            continue;
+                        }
+                        let slot = match &slots {
+                            Some(slots) => match slots.clone().try_acquire_owned() {
+                                Ok(slot) => Some(slot),
+                                Err(_) => {
+                                    // 不启动握手任务，直接关闭连接
+                                    eprintln!("⚠️ 已达到连接数上限 ({})，拒绝来自 {} 的连接", max_connections.unwrap_or_default(), addr);
+                                    network.notify(SyncEvent::Error(format!("已达到连接数上限，拒绝来自 {} 的连接", addr)));
+                                    events::emit(Event::error(None, format!("已达到连接数上限，拒绝来自 {} 的连接", addr)));
+                                    continue;
+                                }
+                            },
+                            None => None,
+                        };
+                        status!("📥 接受来自 {} 的连接", addr);
                         
-                        let message_sender = message_sender.clone();
-                        let device_name = device_name.clone();
+                        // 每个连接在独立的任务中握手和读取，不阻塞接受其他连接
+                        let network = network.clone();
                         let connections = connections.clone();
-                        
-                        // 为每个连接生成一个唯一标识符
-                        let device_id = format!("client_{}", addr);
-                        
-                        // 将连接保存到服务器的连接池中
-                        connections.lock().await.insert(device_id.clone(), stream);
+                        tokio::spawn(async move {
+                            // 任务结束（握手失败或连接断开）时释放名额
+                            let _slot = slot;
+                            if let Err(e) = socket_options.apply(&stream) {
+                                eprintln!("⚠️ 设置 {} 的套接字选项失败: {}", addr, e);
+                            }
+                            
+                            // 为每个连接生成一个唯一标识符
+                            let device_id = format!("client_{}", addr);
+                            
+                            // 启用 TLS 时先完成 TLS 握手，未通过证书校验的连接不会读取任何帧
+                            let mut connection = match network.accept_stream(stream, addr).await {
+                                Ok(connection) => connection,
+                                Err(e) => {
+                                    eprintln!("🔐 拒绝来自 {} 的连接: {}", addr, e);
+                                    network.notify(SyncEvent::Error(format!("拒绝来自 {} 的连接: {}", addr, e)));
+                                    events::emit(Event::error(Some(&device_id), e));
+                                    return;
+                                }
+                            };
+                            let version = match network.negotiate(&mut connection).await {
+                                Ok((version, encoding)) => {
+                                    status!("🤝 与 {} 协商使用协议 v{}，压缩: {}", addr, version, encoding);
+                                    version
+                                }
+                                Err(e) => {
+                                    eprintln!("🚫 拒绝来自 {} 的连接: {}", addr, e);
+                                    network.notify(SyncEvent::Error(format!("拒绝来自 {} 的连接: {}", addr, e)));
+                                    events::emit(Event::error(Some(&device_id), e));
+                                    return;
+                                }
+                            };
+                            // 中继模式下先加入房间，使用该房间的密钥认证
+                            let room = match &network.rooms {
+                                Some(_) => match handshake::read_join(connection.streams().0).await {
+                                    Ok(room) => Some(room),
+                                    Err(e) => {
+                                        eprintln!("🚫 拒绝来自 {} 的连接: {}", addr, e);
+                                        network.notify(SyncEvent::Error(format!("拒绝来自 {} 的连接: {}", addr, e)));
+                                        events::emit(Event::error(Some(&device_id), e));
+                                        return;
+                                    }
+                                },
+                                None => None,
+                            };
+                            if let Some(secret) = network.secret_for(room.as_deref()) {
+                                let (reader, writer) = connection.streams();
+                                if let Err(e) = auth::authenticate(reader, writer, &secret).await {
+                                    eprintln!("🔒 拒绝来自 {} 的连接: {}", addr, e);
+                                    network.notify(SyncEvent::Error(format!("认证失败，拒绝来自 {} 的连接: {}", addr, e)));
+                                    events::emit(Event::error(Some(&device_id), format!("认证失败: {}", e)));
+                                    return;
+                                }
+                            }
+                            let identity = match network.exchange_identity(&mut connection, version).await {
+                                Ok(identity) => identity,
+                                Err(e) => {
+                                    eprintln!("🚫 拒绝来自 {} 的连接: {}", addr, e);
+                                    network.notify(SyncEvent::Error(format!("拒绝来自 {} 的连接: {}", addr, e)));
+                                    events::emit(Event::error(Some(&device_id), e));
+                                    return;
+                                }
+                            };
+                            let (mut reader, writer) = connection.split();
+                            let accept = network.accepted_content(addr.ip());
+                            {
+                                let mut connections = connections.lock().await;
+                                // 主动发起的连接不占用名额，加入连接池前再按总数检查一次
+                                if max_connections.is_some_and(|max| connections.len() >= max) {
+                                    eprintln!("⚠️ 已达到连接数上限 ({})，拒绝来自 {} 的连接", connections.len(), addr);
+                                    network.notify(SyncEvent::Error(format!("已达到连接数上限，拒绝来自 {} 的连接", addr)));
+                                    events::emit(Event::error(Some(&device_id), "已达到连接数上限"));
+                                    return;
+                                }
+                                connections.insert(device_id.clone(), Connection::new(writer, SyncDirection::Both, room.clone(), accept));
+                            }
+                            network.start_heartbeat();
+                            if let Some(room) = &room {
+                                status!("🏠 {} 加入房间 {}", addr, room);
+                            }
 
-                        println!("✅ 添加与 {} 的连接", device_id);
-                        println!("connections len: {}", connections.lock().await.len());
-                        
-                        // 从连接池中获取连接的可变引用
-                        if let Some(stream) = connections.lock().await.get_mut(&device_id) {
-                            let _ = Self::handle_tcp_connection(stream, message_sender, device_name).await;
-                        }
-                        
-                        // 删除连接
-                        connections.lock().await.remove(&device_id);
-                        println!("📤 断开与 {} 的连接", addr);
+                            status!("✅ 添加与 {} 的连接", device_id);
+                            // 只有对方也在监听时才能在下次启动时主动连接它，连接用的临时端口没有意义
+                            if let Some(PeerIdentity { device_id: peer_id, listen_port: Some(listen_port) }) = &identity {
+                                network.remember_peer(peer_id, SocketAddr::new(addr.ip(), *listen_port));
+                            }
+                            network.peer_connected(&device_id);
+                            network.flush_pending(&device_id).await;
+
+                            // 读端由本任务独占，连接池只保存写端，读取时不占用连接池的锁
+                            let _ = network.handle_tcp_connection(&mut reader, &device_id, SyncDirection::Both, accept).await;
+                            
+                            // 删除连接
+                            let dropped = connections.lock().await.remove(&device_id).is_some();
+                            status!("📤 断开与 {} 的连接", addr);
+                            network.peer_disconnected(&device_id, dropped);
+                        });
                     }
                     Err(e) => {
                         eprintln!("❌ 接受连接失败: {}", e);
+                        network.notify(SyncEvent::Error(format!("接受连接失败: {}", e)));
+                        events::emit(Event::error(None, format!("接受连接失败: {}", e)));
                         tokio::time::sleep(Duration::from_millis(100)).await;
                     }
                 }
             }
         });
         
-        Ok(())
+        Ok(port)
     }
 
-    /// 处理TCP连接
+    /// 处理TCP连接，`connection_id` 为该连接在连接池中的标识
     async fn handle_tcp_connection(
-        stream: &mut TokioTcpStream,
-        message_sender: Arc<Mutex<Option<mpsc::UnboundedSender<ClipboardMessage>>>>,
-        _device_name: String,
+        &self,
+        reader: &mut FramedReader,
+        connection_id: &str,
+        direction: SyncDirection,
+        accept: ContentTypes,
     ) -> Result<()> {
-        let mut buffer = vec![0u8; MESSAGE_MAX_SIZE];
-        
+        // 读取期间需要的连接状态先取出，之后读取任务不再等待连接池的锁
+        let (health, writer) = match self.connections.lock().await.get(connection_id) {
+            Some(connection) => (Some(connection.health.clone()), Some(connection.writer.clone())),
+            None => (None, None),
+        };
         loop {
-            // 首先读取消息长度（4字节）
-        let mut len_buf = [0u8; 4];
-        match stream.read_exact(&mut len_buf).await {
-            Ok(_) => {},
-                Err(_) => break, // 连接断开
-        }
-        
-        let message_len = u32::from_be_bytes(len_buf) as usize;
-        if message_len > MESSAGE_MAX_SIZE {
-                eprintln!("❌ 消息过大: {} bytes", message_len);
-                break;
-        }
-        
-        // 读取消息内容
-            buffer.resize(message_len, 0);
-        stream.read_exact(&mut buffer).await?;
-        
-        match ClipboardMessage::from_bytes(&buffer) {
-            Ok(message) => {
-                println!("📨 收到消息: {} (来自: {})", 
-                         message.content.preview(50), 
-                         message.sender_name);
-                
-                // 转发消息给处理器
-                if let Some(sender) = message_sender.lock().await.as_ref() {
-                    if let Err(e) = sender.send(message) {
-                        eprintln!("❌ 转发消息失败: {}", e);
+            let incoming = reader.recv_incoming().await;
+            if let (Ok(Some(_)), Some(health)) = (&incoming, &health) {
+                lock_health(health).record_seen(Instant::now());
+            }
+            let message = match incoming {
+                Ok(Some(Incoming::Message(message))) => message,
+                Ok(Some(Incoming::Ping(token))) => {
+                    if let Some(writer) = &writer {
+                        Self::send_pong(connection_id, writer.clone(), token);
+                    }
+                    continue;
+                }
+                Ok(Some(Incoming::Pong(token))) => {
+                    if let Some(health) = &health {
+                        self.record_pong(connection_id, health, token);
                     }
+                    continue;
+                }
+                Ok(Some(Incoming::RequestCurrent)) => {
+                    self.current_requested(connection_id);
+                    continue;
+                }
+                Ok(None) => break, // 连接断开
+                Err(e) => {
+                    eprintln!("❌ 读取消息失败: {}", e);
+                    self.notify(SyncEvent::Error(format!("读取 {} 的消息失败: {}", connection_id, e)));
+                    events::emit(Event::error(None, format!("读取消息失败: {}", e)));
+                    break;
                 }
+            };
+            
+            status!("📨 收到消息: {} (来自: {})", message.content.preview(50), message.sender_label());
+            self.check_name_collision(&message);
+            if !self.check_order(&message) {
+                continue;
             }
-            Err(e) => {
-                    eprintln!("❌ 解析消息失败: {}", e);
+            
+            if !direction.receives() {
+                status!("⏭️ 该设备设置为仅发送，忽略收到的内容");
+                continue;
+            }
+            if !accept.allows(&message.content) {
+                status!("🚫 {} 不允许发送 {} 内容（允许: {}），已丢弃", connection_id, message.content.kind(), accept);
+                continue;
+            }
+            events::emit(Event::content_received(&message.content, &message.sender_id, &message.sender_name));
+            self.notify(SyncEvent::Message(message.clone()));
+            
+            if self.relay {
+                self.relay_message(&message, connection_id).await;
+            }
+            
+            // 转发消息给处理器（先取出再投递，等待有界通道时不持有锁）
+            let sink = self.message_sender.lock().await.clone();
+            if let Some(sink) = sink {
+                if let Err(e) = sink.deliver(message).await {
+                    eprintln!("❌ 转发消息失败: {}", e);
                 }
             }
         }
@@ -229,109 +1116,848 @@ impl NetworkManager {
     }
 
     /// 连接到指定设备
-    pub async fn connect_to_device(&self, ip: &str, port: u16) -> Result<String> {
+    pub async fn connect_to_device(&self, ip: &str, port: u16) -> SyncResult<String> {
+        let addr = Self::parse_addr(ip, port)?;
+        self.connect_addr(addr, self.secret.as_deref(), SyncDirection::Both).await
+    }
+
+    /// 使用指定的预共享密钥连接设备，覆盖 `with_secret` 的设置
+    pub async fn connect_to_device_with_secret(&self, ip: &str, port: u16, secret: &str) -> SyncResult<String> {
+        let addr = Self::parse_addr(ip, port)?;
+        self.connect_addr(addr, Some(secret.as_bytes()), SyncDirection::Both).await
+    }
+
+    /// 按连接目标连接设备，目标中的密钥优先于 `with_secret` 的设置，同步方向只作用于这个连接
+    pub async fn connect(&self, target: &ConnectTarget) -> SyncResult<String> {
+        #[cfg(feature = "tls")]
+        let uses_tls = self.tls.connects_with_tls();
+        #[cfg(not(feature = "tls"))]
+        let uses_tls = false;
+        if target.tls && !uses_tls {
+            return Err(anyhow::anyhow!("{} 要求使用 TLS 连接，请使用 --tls 和 --ca-cert", target.addr).into());
+        }
+        let secret = match &target.secret {
+            Some(secret) => Some(secret.as_bytes()),
+            None => self.secret.as_deref(),
+        };
+        self.connect_addr(target.addr, secret, target.direction).await
+    }
+
+    fn parse_addr(ip: &str, port: u16) -> Result<SocketAddr> {
         let ip_addr: IpAddr = ip.parse().map_err(|e| anyhow::anyhow!("无效的IP地址: {}", e))?;
-        let addr = SocketAddr::new(ip_addr, port);
+        Ok(SocketAddr::new(ip_addr, port))
+    }
+
+    async fn connect_addr(&self, addr: SocketAddr, secret: Option<&[u8]>, direction: SyncDirection) -> SyncResult<String> {
+        let (ip, port) = (addr.ip(), addr.port());
         
-        println!("🔗 正在连接到设备: {}:{}", ip, port);
+        status!("🔗 正在连接到设备: {}:{}", ip, port);
         
-        match tokio::time::timeout(CONNECTION_TIMEOUT, TokioTcpStream::connect(addr)).await {
+        match tokio::time::timeout(self.connect_timeout, TokioTcpStream::connect(addr)).await {
             Ok(Ok(stream)) => {
-                println!("✅ 成功连接到设备 {}:{}", ip, port);
+                status!("✅ 成功连接到设备 {}:{}", ip, port);
+                if let Err(e) = self.socket_options.apply(&stream) {
+                    eprintln!("⚠️ 设置 {}:{} 的套接字选项失败: {}", ip, port, e);
+                }
                 
                 // 生成设备标识符
                 let device_id = format!("server_{}:{}", ip, port);
                 
-                // 保存连接
-                self.connections.lock().await.insert(device_id.clone(), stream);
+                let mut connection = self.connect_stream(stream, addr).await?;
+                let (version, encoding) = self.negotiate(&mut connection).await?;
+                status!("🤝 与 {}:{} 协商使用协议 v{}，压缩: {}", ip, port, version, encoding);
+                if let Some(room) = &self.room {
+                    handshake::send_join(connection.streams().1, room).await?;
+                    status!("🏠 加入中继 {}:{} 的房间 {}", ip, port, room);
+                }
+                if let Some(secret) = secret {
+                    let (reader, writer) = connection.streams();
+                    auth::authenticate(reader, writer, secret)
+                        .await
+                        .map_err(|e| SyncError::AuthFailed(e.to_string()))?;
+                    status!("🔒 已通过 {}:{} 的密钥认证", ip, port);
+                }
+                let identity = self.exchange_identity(&mut connection, version).await?;
+                
+                // 保存连接的写端，并在后台接收对方发来的消息
+                let (reader, writer) = connection.split();
+                let accept = self.accepted_content(ip);
+                self.connections.lock().await.insert(device_id.clone(), Connection::new(writer, direction, None, accept));
+                self.start_heartbeat();
+                self.spawn_reader(device_id.clone(), reader, direction, accept);
+                // 旧版本的设备不告知设备标识，退回以连接标识记录
+                let peer_id = identity.as_ref().map_or(device_id.as_str(), |identity| identity.device_id.as_str());
+                self.remember_peer(peer_id, addr);
+                self.peer_connected(&device_id);
+                self.flush_pending(&device_id).await;
+                if self.request_current {
+                    if let Err(e) = self.request_current(&device_id).await {
+                        eprintln!("⚠️ 无法请求 {} 的当前剪贴板内容: {}", device_id, e);
+                    }
+                }
                 
                 Ok(device_id)
             }
-            Ok(Err(e)) => Err(anyhow::anyhow!("连接失败: {}", e)),
-            Err(_) => Err(anyhow::anyhow!("连接超时")),
+            Ok(Err(e)) => Err(SyncError::Connect { addr, source: e }),
+            Err(_) => Err(SyncError::ConnectTimeout { addr, timeout: self.connect_timeout }),
         }
     }
 
-    /// 广播剪贴板消息到所有连接的设备
-    pub async fn broadcast_message(&self, message: ClipboardMessage) -> Result<()> {
-        let data = message.to_bytes()?;
-        let message_len = data.len() as u32;
+    /// 协商协议版本，新版本再交换双方能解码的编码方式，返回协商出的版本和发送时使用的编码方式
+    async fn negotiate(&self, connection: &mut FramedConnection) -> Result<(u32, Encoding)> {
+        let (reader, writer) = connection.streams();
+        let version = handshake::negotiate(reader, writer, handshake::supported_versions()).await?;
+        if version < handshake::CAPABILITIES_VERSION {
+            connection.set_version(version);
+            return Ok((version, Encoding::None));
+        }
+        let peer = handshake::exchange_encodings(reader, writer, &self.encodings).await?;
+        connection.set_version(version);
+        let encoding = compression::select(&self.encodings, &peer);
+        connection.set_encoding(encoding);
+        Ok((version, encoding))
+    }
+
+    /// 认证后交换设备标识和监听端口，对方版本低于 v8 时返回 `None`
+    async fn exchange_identity(&self, connection: &mut FramedConnection, version: u32) -> Result<Option<PeerIdentity>> {
+        if version < handshake::IDENTITY_VERSION {
+            return Ok(None);
+        }
+        let listen_port = match self.listen_port.load(Ordering::Relaxed) {
+            0 => None,
+            port => Some(port),
+        };
+        let (reader, writer) = connection.streams();
+        handshake::exchange_identity(reader, writer, &self.device_id, listen_port).await.map(Some)
+    }
+
+    /// 在接受的连接上依次完成 TLS（启用时）和传输层（如 WebSocket）的握手
+    async fn accept_stream(&self, stream: TokioTcpStream, addr: SocketAddr) -> Result<FramedConnection> {
+        #[cfg(feature = "tls")]
+        let (reader, writer) = self.tls.accept(stream, addr).await?;
+        #[cfg(not(feature = "tls"))]
+        let (reader, writer) = {
+            let _ = addr;
+            split_plain(stream)
+        };
+        let (reader, writer) = self.encrypt(reader, writer, false).await?;
+        self.transport.accept(reader, writer).await
+    }
+
+    /// 在主动发起的连接上依次完成 TLS（启用时）和传输层（如 WebSocket）的握手
+    async fn connect_stream(&self, stream: TokioTcpStream, addr: SocketAddr) -> Result<FramedConnection> {
+        #[cfg(feature = "tls")]
+        let (reader, writer) = self.tls.connect(stream, addr.ip()).await?;
+        #[cfg(not(feature = "tls"))]
+        let (reader, writer) = split_plain(stream);
+        let (reader, writer) = self.encrypt(reader, writer, true).await?;
+        self.transport.connect(reader, writer, addr).await
+    }
+
+    /// 启用加密时在连接上完成握手，之后收发的数据都经过加密；`initiator` 为主动发起连接的一方
+    async fn encrypt(&self, reader: BoxedReader, writer: BoxedWriter, initiator: bool) -> Result<(BoxedReader, BoxedWriter)> {
+        match self.encryption {
+            Encryption::None => Ok((reader, writer)),
+            #[cfg(feature = "noise")]
+            Encryption::Noise => {
+                // 房间的密钥要等加入房间后才能确定，中继模式下只做不认证的密钥交换，随后照常按房间认证
+                let secret = match (&self.rooms, &self.room) {
+                    (None, None) => self.secret.as_deref(),
+                    _ => None,
+                };
+                crate::noise::handshake(reader, writer, secret, initiator).await
+            }
+            #[cfg(not(feature = "noise"))]
+            Encryption::Noise => {
+                let _ = initiator;
+                Err(anyhow::anyhow!("Noise 加密需要使用 --features noise 编译"))
+            }
+        }
+    }
+
+    /// 在后台读取主动发起的连接，连接断开后从连接池中移除
+    fn spawn_reader(&self, device_id: String, mut reader: FramedReader, direction: SyncDirection, accept: ContentTypes) {
+        let network = self.clone();
         
-        // 准备发送的数据：4字节长度 + 消息内容
-        let mut send_data = Vec::with_capacity(4 + data.len());
-        send_data.extend_from_slice(&message_len.to_be_bytes());
-        send_data.extend_from_slice(&data);
+        tokio::spawn(async move {
+            let _ = network.handle_tcp_connection(&mut reader, &device_id, direction, accept).await;
+            let dropped = network.connections.lock().await.remove(&device_id).is_some();
+            status!("📤 断开与 {} 的连接", device_id);
+            network.peer_disconnected(&device_id, dropped);
+        });
+    }
+
+    /// 连接建立后通知事件订阅者，按设置发送系统通知
+    fn peer_connected(&self, device_id: &str) {
+        self.notify(SyncEvent::Connected(device_id.to_string()));
+        events::emit(Event::PeerConnected { peer: device_id });
+        if let Some(notifier) = &self.connection_notifier {
+            let _ = notifier.send_connection("剪贴板同步", &format!("已连接到 {}", device_id));
+        }
+    }
+
+    /// 把没有设备收到的内容发送给新建立的连接，连接设置为仅接收时留给下一个连接
+    async fn flush_pending(&self, device_id: &str) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        let writer = self
+            .connections
+            .lock()
+            .await
+            .get(device_id)
+            .filter(|c| c.direction.sends())
+            .map(|c| c.writer.clone());
+        let Some(writer) = writer else {
+            return;
+        };
+        let mut writer = writer.lock().await;
+        let messages = pending.lock().unwrap_or_else(|e| e.into_inner()).take();
+        for message in messages {
+            status!("📤 发送设备连接前的内容到 {}: {}", device_id, message.content.preview(50));
+            let result = match self.outgoing_frames(&message) {
+                Ok(mut frames) => writer.send_frames(&mut frames).await.map_err(SyncError::from),
+                Err(e) => Err(e),
+            };
+            let sent = result.is_ok() as usize;
+            events::emit(Event::content_sent(&message.content, sent, 1 - sent));
+            if let Err(e) = result {
+                eprintln!("❌ 发送到 {} 失败: {}", device_id, e);
+                break;
+            }
+        }
+    }
+
+    /// 连接断开后通知事件订阅者；`dropped` 表示连接是意外断开的（仍在连接池中），而不是本机关闭的，此时才发送系统通知
+    fn peer_disconnected(&self, device_id: &str, dropped: bool) {
+        self.notify(SyncEvent::Disconnected(device_id.to_string()));
+        events::emit(Event::PeerDisconnected { peer: device_id });
+        if let (Some(notifier), true) = (&self.connection_notifier, dropped) {
+            let _ = notifier.send_connection("剪贴板同步", &format!("与 {} 的连接已断开", device_id));
+        }
+    }
+
+    /// 有连接时启动心跳任务，已在运行或未启用心跳时什么也不做
+    fn start_heartbeat(&self) {
+        let Some(interval) = self.heartbeat else {
+            return;
+        };
+        if self.heartbeat_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let network = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                if !network.send_heartbeats().await {
+                    break;
+                }
+                for snapshot in network.connection_health().await {
+                    verbose!("💓 {}", snapshot);
+                }
+            }
+            network.heartbeat_running.store(false, Ordering::SeqCst);
+            // 退出前恰好建立的连接由这里重新启动心跳
+            if network.connection_count().await > 0 {
+                network.start_heartbeat();
+            }
+        });
+    }
+
+    /// 向每个支持心跳的连接发送一次心跳，没有任何连接时返回 false
+    async fn send_heartbeats(&self) -> bool {
+        let now = Instant::now();
+        let targets: Vec<_> = self
+            .connections
+            .lock()
+            .await
+            .iter()
+            .map(|(device_id, connection)| (device_id.clone(), connection.writer.clone(), connection.health.clone()))
+            .collect();
+        for (device_id, writer, health) in &targets {
+            let mut writer = writer.lock().await;
+            if !writer.supports_heartbeat() {
+                continue;
+            }
+            let (token, missed) = lock_health(health).next_ping(now);
+            if let Some(missed) = missed {
+                eprintln!("⚠️ {} 已连续 {} 次未回复心跳，连接可能不稳定", device_id, missed);
+                events::emit(Event::error(Some(device_id), format!("连续 {} 次未回复心跳", missed)));
+            }
+            // 发送失败说明连接已断开，由读取任务清理
+            if let Err(e) = writer.send_ping(token).await {
+                eprintln!("⚠️ 向 {} 发送心跳失败: {}", device_id, e);
+            }
+        }
+        !targets.is_empty()
+    }
+
+    /// 在单独的任务中回复对方的心跳，读取任务不等待写端，继续读取对方发来的数据
+    ///
+    /// 否则两台设备互相发送大量数据时，双方的读取任务都在等待写入完成而不再读取，写入也就永远无法完成
+    fn send_pong(connection_id: &str, writer: Arc<Mutex<FramedWriter>>, token: u64) {
+        let connection_id = connection_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = writer.lock().await.send_pong(token).await {
+                eprintln!("⚠️ 回复 {} 的心跳失败: {}", connection_id, e);
+            }
+        });
+    }
+
+    /// 记录心跳回复，延迟突增或从未回复中恢复时给出提示
+    fn record_pong(&self, connection_id: &str, health: &std::sync::Mutex<PeerHealth>, token: u64) {
+        let outcome = lock_health(health).record_pong(token, Instant::now());
+        match outcome {
+            Some(HeartbeatOutcome::Spike { rtt, average }) => {
+                eprintln!("⚠️ {} 的延迟突增: {} ms（平均 {} ms）", connection_id, rtt.as_millis(), average.as_millis());
+            }
+            Some(HeartbeatOutcome::Recovered { rtt, missed }) => {
+                status!("💓 {} 在 {} 次心跳未回复后恢复响应，延迟 {} ms", connection_id, missed, rtt.as_millis());
+            }
+            Some(HeartbeatOutcome::Normal(_)) | None => {}
+        }
+    }
+
+    /// 各连接的健康状况（心跳延迟、最近收到数据的时间）和允许发送的内容类型，按连接标识排列
+    pub async fn connection_health(&self) -> Vec<HealthSnapshot> {
+        let now = Instant::now();
+        let connections = self.connections.lock().await;
+        let mut snapshots: Vec<HealthSnapshot> = connections
+            .iter()
+            .map(|(device_id, connection)| lock_health(&connection.health).snapshot(device_id, connection.accept, now))
+            .collect();
+        snapshots.sort_by(|a, b| a.peer.cmp(&b.peer));
+        snapshots
+    }
+
+    /// 以对方的设备标识记录已配对的设备，地址变化时覆盖之前的地址，保存失败不影响连接
+    fn remember_peer(&self, device_id: &str, addr: SocketAddr) {
+        // 中继的地址离开房间就无法使用，不记录
+        let (Some(store), None) = (&self.peer_store, &self.room) else {
+            return;
+        };
+        let mut store = store.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = store.record(device_id, addr) {
+            eprintln!("⚠️ 保存已配对设备失败: {}", e);
+        }
+    }
+
+    /// 广播剪贴板消息到所有连接的设备
+    pub async fn broadcast_message(&self, message: ClipboardMessage) -> SyncResult<BroadcastResult> {
+        // 准备发送的帧，超过单帧上限的消息会被分块；消息本身无法编码时与发送给哪个设备无关，
+        // 在发送前直接返回，不会逐个设备报错，也不会因此断开连接
+        let mut frames = self.outgoing_frames(&message)?;
         
         // 记录日志
         match &message.content {
             ClipboardContent::Text(text) => {
-                println!("📤 广播文本内容: {}", text);
+                status!("📤 广播文本内容: {}", events::loggable_text(text));
             }
             ClipboardContent::Image { width, height, .. } => {
-                println!("📤 广播图片内容: {}x{}", width, height);
+                status!("📤 广播图片内容: {}x{}", width, height);
+            }
+            ClipboardContent::Clear => {
+                status!("📤 广播清空剪贴板");
+            }
+            ClipboardContent::Color(color) => {
+                status!("📤 广播颜色: {}", color);
+            }
+            ClipboardContent::Html { text, .. } => {
+                status!("📤 广播 HTML 内容: {}", events::loggable_text(text));
             }
         }
         
         // 向所有连接的设备发送消息
-        let mut connections = self.connections.lock().await;
+        let mut result = self.send_frames(&mut frames, None).await;
+        events::emit(Event::content_sent(&message.content, result.delivered, result.failed));
+        
+        if let Some(pending) = &self.pending {
+            let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+            if result.delivered == 0 {
+                pending.queue(message);
+                result.queued = true;
+            } else {
+                pending.clear();
+            }
+        }
+        
+        Ok(result)
+    }
+
+    /// 转发从 `from` 收到的消息给其他设备，最近转发过的消息不再转发
+    async fn relay_message(&self, message: &ClipboardMessage, from: &str) {
+        if message.sender_id == self.device_id {
+            return;
+        }
+        let mut frames = match self.outgoing_frames(message) {
+            Ok(frames) => frames,
+            Err(e) => {
+                eprintln!("❌ 转发消息失败: {}", e);
+                return;
+            }
+        };
+        
+        let mut hasher = DefaultHasher::new();
+        frames.data().hash(&mut hasher);
+        let fingerprint = hasher.finish();
+        {
+            let mut relayed = self.relayed.lock().unwrap_or_else(|e| e.into_inner());
+            if relayed.contains(&fingerprint) {
+                return;
+            }
+            if relayed.len() >= RELAY_HISTORY {
+                relayed.pop_front();
+            }
+            relayed.push_back(fingerprint);
+        }
+        
+        let result = self.send_frames(&mut frames, Some(from)).await;
+        if result.total() > 0 {
+            status!("🔁 已转发到 {}/{} 个设备", result.delivered, result.total());
+        }
+    }
+
+    /// 向所有允许发送的连接写入帧，跳过 `exclude` 指定的连接，发送失败的连接会被移除
+    ///
+    /// `exclude` 指定的连接加入了房间时，只发送给同一房间的连接
+    async fn send_frames(&self, frames: &mut OutgoingFrames, exclude: Option<&str>) -> BroadcastResult {
+        let targets: Vec<_> = {
+            let connections = self.connections.lock().await;
+            let room = exclude.and_then(|id| connections.get(id)).and_then(|c| c.room.clone());
+            // 跳过设置为仅接收的设备
+            connections
+                .iter()
+                .filter(|(id, c)| c.direction.sends() && Some(id.as_str()) != exclude)
+                .filter(|(_, c)| room.is_none() || c.room == room)
+                .map(|(id, c)| (id.clone(), c.writer.clone()))
+                .collect()
+        };
+        let mut delivered = 0;
         let mut failed_connections = Vec::new();
-        println!("connections len: {}", connections.len());
-        for (device_id, stream) in connections.iter_mut() {
-            match stream.write_all(&send_data).await {
+        for (device_id, writer) in targets {
+            trace!("🔬 → 发送到 {}", device_id);
+            let result = writer.lock().await.send_frames(frames).await;
+            match result {
                 Ok(_) => {
-                    println!("✅ 消息已发送到: {}", device_id);
+                    delivered += 1;
+                    status!("✅ 消息已发送到: {}", device_id);
                 }
                 Err(e) => {
                     eprintln!("❌ 发送到 {} 失败: {}", device_id, e);
-                    failed_connections.push(device_id.clone());
+                    self.notify(SyncEvent::Error(format!("发送到 {} 失败: {}", device_id, e)));
+                    events::emit(Event::error(Some(&device_id), format!("发送失败: {}", e)));
+                    failed_connections.push((device_id, writer));
                 }
             }
         }
         
+        let result = BroadcastResult {
+            delivered,
+            failed: failed_connections.len(),
+            queued: false,
+        };
+        
         // 清理失败的连接
-        for device_id in failed_connections {
-            connections.remove(&device_id);
+        for (device_id, writer) in failed_connections {
+            self.remove_connection(&device_id, &writer).await;
         }
         
-        Ok(())
+        result
     }
 
-    /// 广播文本内容
-    pub async fn broadcast_clipboard(&self, content: &str) -> Result<()> {
-        // 使用固定ID作为发送者ID
-        let message = ClipboardMessage::new_text(
-            content.to_string(),
-            "local_device".to_string(),
-            self.device_name.clone(),
+    /// 从连接池中移除发送失败的连接；写入期间同一标识已换成新的连接时保留新的连接
+    async fn remove_connection(&self, device_id: &str, writer: &Arc<Mutex<FramedWriter>>) {
+        let mut connections = self.connections.lock().await;
+        if connections.get(device_id).is_some_and(|c| Arc::ptr_eq(&c.writer, writer)) {
+            connections.remove(device_id);
+        }
+    }
+
+    /// 以本机的设备标识和名称创建消息，分配下一个序号
+    fn outgoing_message(&self, content: ClipboardContent) -> ClipboardMessage {
+        let mut message = ClipboardMessage::new(
+            content,
+            self.device_id.clone(),
+            self.get_device_name(),
         );
-        self.broadcast_message(message).await
+        message.seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        message
+    }
+
+    /// 编码要发送的消息，无法序列化或过大时返回 `SyncError::Serialization` 或 `SyncError::MessageTooLarge`
+    fn outgoing_frames(&self, message: &ClipboardMessage) -> SyncResult<OutgoingFrames> {
+        OutgoingFrames::with_encoder(message, self.encoder, self.compression_policy).map_err(SyncError::from)
+    }
+
+    /// 以本机的设备标识和名称广播任意剪贴板内容
+    pub async fn broadcast_content(&self, content: ClipboardContent) -> SyncResult<BroadcastResult> {
+        self.broadcast_message(self.outgoing_message(content)).await
+    }
+
+    /// 只把内容发送给一个已连接的设备，`device_id` 为连接标识（见 `connected_devices`）
+    ///
+    /// 设备未连接或设置为仅接收时返回错误；发送失败的连接会被移除
+    pub async fn send_to(&self, device_id: &str, content: ClipboardContent) -> SyncResult<()> {
+        let message = self.outgoing_message(content);
+        let mut frames = self.outgoing_frames(&message)?;
+        let writer = {
+            let connections = self.connections.lock().await;
+            let connection = connections
+                .get(device_id)
+                .ok_or_else(|| SyncError::PeerNotConnected(device_id.to_string()))?;
+            if !connection.direction.sends() {
+                return Err(anyhow::anyhow!("设备 {} 设置为仅接收，不向它发送", device_id).into());
+            }
+            connection.writer.clone()
+        };
+        
+        status!("📤 发送到 {}: {}", device_id, message.content.preview(50));
+        let result = writer.lock().await.send_frames(&mut frames).await;
+        let sent = result.is_ok() as usize;
+        events::emit(Event::content_sent(&message.content, sent, 1 - sent));
+        if let Err(e) = result {
+            self.remove_connection(device_id, &writer).await;
+            eprintln!("❌ 发送到 {} 失败: {}", device_id, e);
+            self.notify(SyncEvent::Error(format!("发送到 {} 失败: {}", device_id, e)));
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// 主动断开与一个设备的连接，`device_id` 为连接标识（见 `connected_devices`），设备未连接时返回错误
+    pub async fn disconnect(&self, device_id: &str) -> SyncResult<()> {
+        let connection = self
+            .connections
+            .lock()
+            .await
+            .remove(device_id)
+            .ok_or_else(|| SyncError::PeerNotConnected(device_id.to_string()))?;
+        let _ = connection.writer.lock().await.shutdown().await;
+        status!("🔌 已断开与 {} 的连接", device_id);
+        self.peer_disconnected(device_id, false);
+        Ok(())
+    }
+
+    /// 广播文本内容，能识别的结构化内容（如颜色）按结构发送
+    pub async fn broadcast_clipboard(&self, content: &str) -> SyncResult<BroadcastResult> {
+        self.broadcast_content(ClipboardContent::from_text(content.to_string())).await
     }
 
     /// 广播图片内容
-    pub async fn broadcast_image(&self, width: u32, height: u32, data: Vec<u8>) -> Result<()> {
-        // 使用固定ID作为发送者ID
-        let message = ClipboardMessage::new_image(
-            width,
-            height,
-            data,
-            "local_device".to_string(),
-            self.device_name.clone(),
-        );
-        self.broadcast_message(message).await
+    pub async fn broadcast_image(&self, width: u32, height: u32, data: Vec<u8>) -> SyncResult<BroadcastResult> {
+        self.broadcast_content(ClipboardContent::Image { width, height, data }).await
+    }
+
+    /// 广播清空剪贴板
+    pub async fn broadcast_clear(&self) -> SyncResult<BroadcastResult> {
+        self.broadcast_content(ClipboardContent::Clear).await
     }
 
     /// 停止网络服务
     pub async fn shutdown(&self) {
         *self.is_running.lock().await = false;
         
-        // 关闭所有连接
-        self.connections.lock().await.clear();
+        // 关闭所有连接，先关闭写端，确保已写入的数据发送完毕
+        let connections: Vec<_> = self.connections.lock().await.drain().collect();
+        for (_, connection) in connections {
+            let _ = connection.writer.lock().await.shutdown().await;
+        }
         
-        println!("🔴 网络服务已停止");
+        status!("🔴 网络服务已停止");
+    }
+
+    /// 当前连接的设备的连接标识，按字母顺序排列
+    pub async fn connected_devices(&self) -> Vec<String> {
+        let mut devices: Vec<String> = self.connections.lock().await.keys().cloned().collect();
+        devices.sort();
+        devices
+    }
+
+    /// 获取当前连接的设备数量
+    pub async fn connection_count(&self) -> usize {
+        self.connections.lock().await.len()
+    }
+
+    /// 获取设备标识
+    pub fn get_device_id(&self) -> &str {
+        &self.device_id
     }
 
     /// 获取设备名称
-    pub fn get_device_name(&self) -> &str {
-        &self.device_name
+    pub fn get_device_name(&self) -> String {
+        self.device_name.read().unwrap().clone()
+    }
+
+    /// 连接使用的预共享密钥：加入的房间设置了密钥时使用房间的密钥
+    fn secret_for(&self, room: Option<&str>) -> Option<Arc<[u8]>> {
+        room.and_then(|room| self.rooms.as_ref()?.get(room).cloned())
+            .or_else(|| self.secret.clone())
+    }
+
+    /// 来自 `ip` 的设备允许发送的内容类型，有限制时输出提示
+    fn accepted_content(&self, ip: IpAddr) -> ContentTypes {
+        let accept = self.content_policy.for_peer(ip);
+        if !accept.is_all() {
+            status!("🛡️ {} 只允许发送: {}", ip, accept);
+        }
+        accept
+    }
+
+    /// 提示时钟偏差过大的发送方，并判断消息是否比该发送方之前的消息新，晚到的旧消息返回 `false`
+    fn check_order(&self, message: &ClipboardMessage) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut order = self.order.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(skew) = order.check_skew(message, now) {
+            let hint = match self.ordering {
+                MessageOrdering::Time => "，请同步系统时间或使用 --ordering sequence",
+                MessageOrdering::Sequence => "",
+            };
+            eprintln!(
+                "⚠️ {} 的时钟比本机{}约 {} 秒{}",
+                message.sender_label(),
+                if skew > 0 { "快" } else { "慢" },
+                skew.unsigned_abs(),
+                hint
+            );
+        }
+        if order.admit(self.ordering, message) {
+            return true;
+        }
+        status!("⏭️ 来自 {} 的消息早于已收到的消息，已丢弃", message.sender_label());
+        false
+    }
+
+    /// 检查发送方是否与本机或其他设备同名，每个同名设备只提示一次
+    fn check_name_collision(&self, message: &ClipboardMessage) {
+        if message.sender_id == self.device_id {
+            return;
+        }
+        let own_name = self.get_device_name();
+        {
+            let mut names = self.names.lock().unwrap();
+            names.observe(&own_name, &self.device_id);
+            if !names.observe(&message.sender_name, &message.sender_id) {
+                return;
+            }
+        }
+        
+        if message.sender_name != own_name {
+            eprintln!("⚠️ 多个设备使用名称 {:?}，日志中以设备标识区分: {}", message.sender_name, message.sender_label());
+            return;
+        }
+        match self.name_collision {
+            NameCollision::Warn => {
+                eprintln!(
+                    "⚠️ 设备 {} 与本机同名，建议使用 --name 设置不同的名称，或使用 --name-collision suffix 自动改名",
+                    message.sender_label()
+                );
+            }
+            NameCollision::Suffix => {
+                let renamed = format!("{}-{}", own_name, config::short_id(&self.device_id));
+                *self.device_name.write().unwrap() = renamed.clone();
+                status!("🏷️ 设备 {} 与本机同名，本机已改名为 {:?}", message.sender_label(), renamed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_preview(text: &str, max_length: usize) -> String {
+        ClipboardContent::Text(text.to_string()).preview(max_length)
+    }
+
+    #[test]
+    fn test_preview_short_text_unchanged() {
+        assert_eq!(text_preview("hello", 5), "hello");
+        assert_eq!(text_preview("你好世界", 10), "你好世界");
+    }
+
+    #[test]
+    fn test_preview_truncates_by_grapheme() {
+        assert_eq!(text_preview("hello world", 5), "hello...");
+        // e + 组合重音符是一个字素簇
+        assert_eq!(text_preview("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}e\u{301}...");
+    }
+
+    #[test]
+    fn test_preview_keeps_zwj_sequences_intact() {
+        let family = "👨\u{200d}👩\u{200d}👧\u{200d}👦";
+        let flag = "🇨🇳";
+        let text = format!("{}{}{}", family, flag, family);
+        assert_eq!(text_preview(&text, 2), format!("{}{}...", family, flag));
+        assert_eq!(text_preview(&text, 3), text);
+    }
+
+    fn text_message(text: &str) -> ClipboardMessage {
+        ClipboardMessage::new_text(text.to_string(), "peer".to_string(), "对端".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_bounded_sink_applies_backpressure() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let sink = MessageSink::Bounded(sender);
+        
+        sink.deliver(text_message("first")).await.unwrap();
+        
+        // 通道已满，投递会等待消费者
+        let blocked = tokio::time::timeout(Duration::from_millis(50), sink.deliver(text_message("second"))).await;
+        assert!(blocked.is_err());
+        
+        assert!(matches!(receiver.recv().await.unwrap().content, ClipboardContent::Text(t) if t == "first"));
+        sink.deliver(text_message("third")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_callback_sink_invokes_closure() {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let collected = received.clone();
+        let sink = MessageSink::Callback(Arc::new(move |message: ClipboardMessage| {
+            collected.lock().unwrap().push(message.content.preview(10));
+        }));
+        
+        sink.deliver(text_message("hello")).await.unwrap();
+        assert_eq!(*received.lock().unwrap(), vec!["hello".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_socket_options_applied() {
+        let listener = TokioTcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TokioTcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        
+        SocketOptions::default().apply(&stream).unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert!(socket2::SockRef::from(&stream).keepalive().unwrap());
+        
+        let options = SocketOptions { nodelay: false, ..SocketOptions::default() };
+        options.apply(&stream).unwrap();
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_port_retry_skips_occupied_port() {
+        let occupied = TokioTcpListener::bind("0.0.0.0:0").await.unwrap();
+        let port = occupied.local_addr().unwrap().port();
+        
+        let network = NetworkManager::new("测试".to_string());
+        assert!(network.bind_listener(port).await.is_err());
+        
+        let network = network.with_port_retry(5);
+        let listener = network.bind_listener(port).await.unwrap();
+        assert_ne!(listener.local_addr().unwrap().port(), port);
+    }
+
+    #[test]
+    fn test_name_registry_reports_each_collision_once() {
+        let mut names = NameRegistry::default();
+        assert!(!names.observe("我的设备", "aaaa"));
+        assert!(!names.observe("我的设备", "aaaa"));
+        assert!(!names.observe("笔记本", "bbbb"));
+        
+        // 第二个使用同一名称的设备只报告一次
+        assert!(names.observe("我的设备", "cccc"));
+        assert!(!names.observe("我的设备", "cccc"));
+        assert!(names.observe("我的设备", "dddd"));
+    }
+
+    #[test]
+    fn test_order_tracker_drops_stale_messages() {
+        let message = |seq: u64, timestamp: u64| ClipboardMessage {
+            seq,
+            timestamp,
+            ..text_message("x")
+        };
+        
+        // 按时间：同一秒内的消息都接受，时钟被调回后的消息被丢弃
+        let mut order = OrderTracker::default();
+        assert!(order.admit(MessageOrdering::Time, &message(1, 1000)));
+        assert!(order.admit(MessageOrdering::Time, &message(2, 1000)));
+        assert!(!order.admit(MessageOrdering::Time, &message(3, 900)));
+        
+        // 按序号：不受时钟影响，重复或更小的序号被丢弃
+        let mut order = OrderTracker::default();
+        assert!(order.admit(MessageOrdering::Sequence, &message(1, 1000)));
+        assert!(order.admit(MessageOrdering::Sequence, &message(2, 900)));
+        assert!(!order.admit(MessageOrdering::Sequence, &message(2, 1100)));
+        assert!(!order.admit(MessageOrdering::Sequence, &message(1, 1200)));
+        
+        // 旧版本的消息没有序号，仍按时间
+        let mut order = OrderTracker::default();
+        assert!(order.admit(MessageOrdering::Sequence, &message(0, 1000)));
+        assert!(!order.admit(MessageOrdering::Sequence, &message(0, 900)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_clock_skew_warned_once_per_sender() {
+        let mut order = OrderTracker::default();
+        let now = 10_000;
+        let message = |timestamp: u64| ClipboardMessage { timestamp, ..text_message("x") };
+        assert_eq!(order.check_skew(&message(now + 60), now), None);
+        assert_eq!(order.check_skew(&message(now - 3600), now), Some(-3600));
+        assert_eq!(order.check_skew(&message(now + 3600), now), None);
+        
+        // 没有序号字段的旧消息也能解析
+        let legacy = r#"{"content":{"Text":"hi"},"timestamp":1,"sender_id":"a","sender_name":"b"}"#;
+        assert_eq!(ClipboardMessage::from_bytes(legacy.as_bytes()).unwrap().seq, 0);
+    }
+
+    #[test]
+    fn test_name_collision_suffix_renames_local_device() {
+        let network = NetworkManager::new("我的设备".to_string())
+            .with_device_id("0f8fad5b-d9cb-469f-a165-70867728950e")
+            .with_name_collision(NameCollision::Suffix);
+        
+        let other = ClipboardMessage::new_text("hi".to_string(), "7c9e6679-7425-40de".to_string(), "我的设备".to_string());
+        assert_eq!(other.sender_label(), "我的设备 [7c9e6679]");
+        network.check_name_collision(&other);
+        assert_eq!(network.get_device_name(), "我的设备-0f8fad5b");
+        
+        // 只改名一次
+        network.check_name_collision(&other);
+        assert_eq!(network.get_device_name(), "我的设备-0f8fad5b");
+        
+        // 默认只警告，不改名
+        let network = NetworkManager::new("我的设备".to_string());
+        network.check_name_collision(&other);
+        assert_eq!(network.get_device_name(), "我的设备");
+    }
+
+    #[test]
+    fn test_preview_image() {
+        let image = ClipboardContent::Image { width: 4, height: 3, data: vec![] };
+        assert_eq!(image.preview(1), "图片 4x3");
+    }
+
+    #[test]
+    fn test_pending_keeps_latest_per_type() {
+        let network = NetworkManager::new("本机".to_string());
+        let mut pending = PendingBroadcasts::default();
+        pending.queue(network.outgoing_message(ClipboardContent::Text("旧文本".to_string())));
+        pending.queue(network.outgoing_message(ClipboardContent::Image { width: 1, height: 1, data: vec![0; 4] }));
+        pending.queue(network.outgoing_message(ClipboardContent::Text("新文本".to_string())));
+
+        let previews: Vec<_> = pending.take().iter().map(|message| message.content.preview(50)).collect();
+        assert_eq!(previews, ["图片 1x1", "新文本"]);
+        assert!(pending.take().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_encode_failure_reported_before_sending() {
+        let server = NetworkManager::new("服务器".to_string());
+        let port = server.start_server(0).await.unwrap();
+        let mut client = NetworkManager::new("客户端".to_string());
+        client.encoder = |_| Err(SyncError::Serialization(serde::ser::Error::custom("图片数据无法序列化")));
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+
+        let error = client.broadcast_clipboard("你好").await.unwrap_err();
+        assert!(matches!(error, SyncError::Serialization(_)), "{:?}", error);
+        assert!(error.is_encode_error());
+        // 编码失败与连接无关，不计为发送失败，连接保留
+        assert_eq!(client.connection_count().await, 1);
+        client.shutdown().await;
+        server.shutdown().await;
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use snow::{Builder, StatelessTransportState};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::auth::HANDSHAKE_TIMEOUT;
+use crate::transport::{BoxedReader, BoxedWriter};
+
+/// 没有预共享密钥时只做匿名的密钥交换，提供前向保密但不认证对方
+const PATTERN: &str = "Noise_NN_25519_ChaChaPoly_BLAKE2s";
+/// 有预共享密钥时把密钥混入握手，密钥不一致的双方无法完成握手
+const PATTERN_PSK: &str = "Noise_NNpsk0_25519_ChaChaPoly_BLAKE2s";
+/// 单条 Noise 消息的最大长度
+const MAX_MESSAGE_LEN: usize = 65535;
+/// 每条加密消息附带的认证标签长度
+const TAG_LEN: usize = 16;
+/// 应用层与加密任务之间的缓冲区大小
+const PIPE_BUFFER: usize = 256 * 1024;
+
+/// 在连接上完成 Noise 握手，返回透明加解密的读端和写端
+///
+/// 握手消息和之后的密文都以 `[u16 长度][消息]` 的记录发送；长度超过单条 Noise 消息上限的数据拆成多条记录。
+/// `secret` 为预共享密钥，双方必须一致（都没有或相同），`initiator` 为主动发起连接的一方
+pub(crate) async fn handshake(
+    mut reader: BoxedReader,
+    mut writer: BoxedWriter,
+    secret: Option<&[u8]>,
+    initiator: bool,
+) -> Result<(BoxedReader, BoxedWriter)> {
+    let psk = secret.map(Sha256::digest);
+    let builder = match &psk {
+        Some(psk) => Builder::new(PATTERN_PSK.parse()?).psk(0, psk),
+        None => Builder::new(PATTERN.parse()?),
+    };
+    let mut state = if initiator { builder.build_initiator()? } else { builder.build_responder()? };
+
+    let exchange = async {
+        let mut buffer = vec![0u8; MAX_MESSAGE_LEN];
+        while !state.is_handshake_finished() {
+            if state.is_my_turn() {
+                let len = state.write_message(&[], &mut buffer)?;
+                write_record(&mut writer, &buffer[..len]).await?;
+            } else {
+                let message = read_record(&mut reader)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("对方在 Noise 握手期间断开连接，可能没有启用 --encrypt noise"))?;
+                state
+                    .read_message(&message, &mut buffer)
+                    .map_err(|_| anyhow::anyhow!("Noise 握手失败：双方的密钥不一致或对方没有启用 --encrypt noise"))?;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("Noise 握手超时"))??;
+
+    let cipher = Arc::new(state.into_stateless_transport_mode()?);
+    let (local, remote) = tokio::io::duplex(PIPE_BUFFER);
+    let (plain_reader, plain_writer) = tokio::io::split(remote);
+    tokio::spawn(decrypt_incoming(reader, plain_writer, cipher.clone()));
+    tokio::spawn(encrypt_outgoing(plain_reader, writer, cipher));
+    let (reader, writer) = tokio::io::split(local);
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// 读取一条记录，连接在记录之间正常关闭时返回 `None`
+async fn read_record<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let len = match reader.read_u16().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut message = vec![0u8; len];
+    reader.read_exact(&mut message).await?;
+    Ok(Some(message))
+}
+
+async fn write_record<W: AsyncWrite + Unpin>(writer: &mut W, message: &[u8]) -> std::io::Result<()> {
+    writer.write_u16(message.len() as u16).await?;
+    writer.write_all(message).await
+}
+
+/// 把应用层写入的明文加密后发送，应用层关闭写端后关闭连接的写端
+async fn encrypt_outgoing<R: AsyncRead + Unpin>(mut plain: R, mut writer: BoxedWriter, cipher: Arc<StatelessTransportState>) {
+    let mut plaintext = vec![0u8; MAX_MESSAGE_LEN - TAG_LEN];
+    let mut ciphertext = vec![0u8; MAX_MESSAGE_LEN];
+    for nonce in 0u64.. {
+        let len = match plain.read(&mut plaintext).await {
+            Ok(0) | Err(_) => break,
+            Ok(len) => len,
+        };
+        let len = match cipher.write_message(nonce, &plaintext[..len], &mut ciphertext) {
+            Ok(len) => len,
+            Err(e) => {
+                eprintln!("❌ 加密失败: {}", e);
+                break;
+            }
+        };
+        if write_record(&mut writer, &ciphertext[..len]).await.is_err() {
+            break;
+        }
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// 解密收到的记录交给应用层读取，连接断开或密文被篡改时让应用层读到连接关闭
+async fn decrypt_incoming<W: AsyncWrite + Unpin>(mut reader: BoxedReader, mut plain: W, cipher: Arc<StatelessTransportState>) {
+    let mut plaintext = vec![0u8; MAX_MESSAGE_LEN];
+    for nonce in 0u64.. {
+        let message = match read_record(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) | Err(_) => break,
+        };
+        let len = match cipher.read_message(nonce, &message, &mut plaintext) {
+            Ok(len) => len,
+            Err(_) => {
+                eprintln!("❌ 解密失败：数据可能被篡改，断开连接");
+                break;
+            }
+        };
+        if plain.write_all(&plaintext[..len]).await.is_err() {
+            break;
+        }
+    }
+    let _ = plain.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn halves(stream: tokio::io::DuplexStream) -> (BoxedReader, BoxedWriter) {
+        let (reader, writer) = tokio::io::split(stream);
+        (Box::new(reader), Box::new(writer))
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_larger_than_one_message() {
+        let (a, b) = tokio::io::duplex(1024);
+        let (a_reader, a_writer) = halves(a);
+        let (b_reader, b_writer) = halves(b);
+        let responder = tokio::spawn(handshake(b_reader, b_writer, Some(&b"secret"[..]), false));
+        let (_, mut writer) = handshake(a_reader, a_writer, Some(&b"secret"[..]), true).await.unwrap();
+        let (mut reader, _) = responder.await.unwrap().unwrap();
+
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let sent = data.clone();
+        tokio::spawn(async move {
+            writer.write_all(&sent).await.unwrap();
+            writer.shutdown().await.unwrap();
+        });
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, data);
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_secret_fails_handshake() {
+        let (a, b) = tokio::io::duplex(1024);
+        let (a_reader, a_writer) = halves(a);
+        let (b_reader, b_writer) = halves(b);
+        let responder = tokio::spawn(handshake(b_reader, b_writer, Some(&b"other"[..]), false));
+        let initiator = handshake(a_reader, a_writer, Some(&b"secret"[..]), true).await;
+        assert!(responder.await.unwrap().is_err());
+        drop(initiator);
+    }
+}
@@ -1,49 +1,337 @@
 use anyhow::Result;
-use notify_rust::Notification;
+use notify_rust::{Notification, Timeout};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 默认的通知合并窗口
+pub const DEFAULT_NOTIFY_THROTTLE: Duration = Duration::from_millis(2000);
+/// 默认的通知显示时长
+pub const DEFAULT_NOTIFY_TIMEOUT: Duration = Duration::from_millis(3000);
+/// 默认的通知应用名称
+pub const DEFAULT_APP_NAME: &str = "Clipboard Sync";
+
+/// 通知类别，决定通知的图标和紧急程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotifyKind {
+    /// 启动、连接等一般提示
+    #[default]
+    Info,
+    /// 收到文本
+    Text,
+    /// 收到图片
+    Image,
+    /// 与设备连接或断开
+    Connection,
+}
+
+impl NotifyKind {
+    /// freedesktop 图标名称
+    fn icon(self) -> &'static str {
+        match self {
+            NotifyKind::Info => "dialog-information",
+            NotifyKind::Text => "edit-paste",
+            NotifyKind::Image => "image-x-generic",
+            NotifyKind::Connection => "network-transmit-receive",
+        }
+    }
+
+    /// freedesktop 紧急程度：图片比文本更显眼，一般提示最低
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn urgency(self) -> notify_rust::Urgency {
+        match self {
+            NotifyKind::Info | NotifyKind::Text | NotifyKind::Connection => notify_rust::Urgency::Low,
+            NotifyKind::Image => notify_rust::Urgency::Normal,
+        }
+    }
+}
 
 /// 通知管理器
 #[derive(Clone)]
 pub struct NotificationManager {
-    enabled: bool,
+    /// 所有克隆共享同一开关，托盘等入口切换后立即生效
+    enabled: Arc<AtomicBool>,
+    /// 系统通知服务是否可用，第一次发送失败后置为 false，之后只输出控制台提示
+    backend_available: Arc<AtomicBool>,
+    /// 同步通知的合并窗口，为零时不合并
+    throttle: Duration,
+    /// 系统通知的显示时长，为零时一直显示直到手动关闭
+    timeout: Duration,
+    /// 通知中显示的应用名称，Linux 上按它对通知分组
+    app_name: String,
+    /// 自定义图标文件，设置后替代按类别选择的图标
+    icon: Option<PathBuf>,
+    coalescer: Arc<Mutex<Coalescer>>,
+    /// 连接状态通知单独合并，不与同步通知混在一起汇总
+    connection_coalescer: Arc<Mutex<Coalescer>>,
 }
 
 impl NotificationManager {
     pub fn new() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            backend_available: Arc::new(AtomicBool::new(true)),
+            throttle: DEFAULT_NOTIFY_THROTTLE,
+            timeout: DEFAULT_NOTIFY_TIMEOUT,
+            app_name: DEFAULT_APP_NAME.to_string(),
+            icon: None,
+            coalescer: Arc::new(Mutex::new(Coalescer::default())),
+            connection_coalescer: Arc::new(Mutex::new(Coalescer::default())),
+        }
+    }
+
+    /// 设置同步通知的合并窗口，`Duration::ZERO` 表示禁用合并
+    pub fn with_throttle(mut self, throttle: Duration) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// 设置系统通知的显示时长，`Duration::ZERO` 表示一直显示直到手动关闭
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 设置通知中显示的应用名称
+    pub fn with_app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = app_name.into();
+        self
+    }
+
+    /// 使用自定义图标文件替代按类别选择的图标
+    pub fn with_icon(mut self, icon: impl Into<PathBuf>) -> Self {
+        self.icon = Some(icon.into());
+        self
+    }
+
+    /// 某类通知使用的图标：自定义图标优先，否则使用类别对应的 freedesktop 图标
+    fn icon_for(&self, kind: NotifyKind) -> String {
+        match &self.icon {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => kind.icon().to_string(),
+        }
+    }
+
+    /// 传给系统通知的超时设置
+    fn notification_timeout(&self) -> Timeout {
+        Timeout::from(self.timeout)
     }
 
     /// 发送系统通知
     pub fn send(&self, title: &str, message: &str) -> Result<()> {
-        if !self.enabled {
+        self.send_kind(NotifyKind::Info, title, message)
+    }
+
+    /// 按类别发送系统通知，类别决定图标，在 Linux 上还会设置紧急程度
+    pub fn send_kind(&self, kind: NotifyKind, title: &str, message: &str) -> Result<()> {
+        if !self.is_enabled() {
             return Ok(());
         }
 
-        println!("🔔 {}: {}", title, message); // 先在控制台显示
+        status!("🔔 {}: {}", title, message); // 先在控制台显示
+        
+        // 通知服务不可用时不再尝试，避免每次都进行一次失败的 D-Bus 调用
+        if !self.is_backend_available() {
+            return Ok(());
+        }
 
-        // 尝试发送系统通知
-        match Notification::new()
+        let mut notification = Notification::new();
+        notification
             .summary(title)
             .body(message)
-            .timeout(3000) // 3秒后消失
-            .show()
-        {
-            Ok(_) => {}
-            Err(e) => {
-                // 如果系统通知失败，不要崩溃程序
-                eprintln!("系统通知发送失败: {}", e);
+            .appname(&self.app_name)
+            .icon(&self.icon_for(kind))
+            .timeout(self.notification_timeout());
+        #[cfg(all(unix, not(target_os = "macos")))]
+        notification
+            .urgency(kind.urgency())
+            .hint(notify_rust::Hint::Category("transfer.complete".to_string()));
+
+        // 尝试发送系统通知，失败时不要崩溃程序
+        self.record_backend_result(notification.show().map(|_| ()));
+
+        Ok(())
+    }
+
+    /// 记录一次系统通知的发送结果，第一次失败时输出原因并在本次运行中停用系统通知
+    fn record_backend_result<E: std::fmt::Display>(&self, result: std::result::Result<(), E>) {
+        if let Err(e) = result {
+            if self.backend_available.swap(false, Ordering::Relaxed) {
+                eprintln!("⚠️ 系统通知发送失败: {}，本次运行不再发送系统通知，只在控制台显示", e);
             }
         }
+    }
+
+    /// 系统通知服务是否可用（尚未发送失败过）
+    pub fn is_backend_available(&self) -> bool {
+        self.backend_available.load(Ordering::Relaxed)
+    }
+
+    /// 重新探测系统通知服务：下一条通知会再次尝试发送，例如通知服务启动之后
+    pub fn reprobe(&self) {
+        self.backend_available.store(true, Ordering::Relaxed);
+    }
+
+    /// 发送剪贴板同步通知，窗口期内的后续通知合并为一条汇总
+    pub fn send_synced(&self, kind: NotifyKind, title: &str, message: &str) -> Result<()> {
+        self.send_coalesced(&self.coalescer, kind, title, message, |count| format!("{} 个剪贴板项目已同步", count))
+    }
+
+    /// 发送连接状态通知（连接、断开），与同步通知使用相同的合并窗口，但单独汇总
+    pub fn send_connection(&self, title: &str, message: &str) -> Result<()> {
+        self.send_coalesced(&self.connection_coalescer, NotifyKind::Connection, title, message, |count| {
+            format!("连接状态又变化了 {} 次", count)
+        })
+    }
+
+    /// 按合并窗口发送通知，窗口期内的后续通知在窗口结束时合并为一条汇总
+    fn send_coalesced(
+        &self,
+        coalescer: &Arc<Mutex<Coalescer>>,
+        kind: NotifyKind,
+        title: &str,
+        message: &str,
+        summary: fn(usize) -> String,
+    ) -> Result<()> {
+        if !self.is_enabled() || self.throttle.is_zero() {
+            return self.send_kind(kind, title, message);
+        }
+
+        if !coalescer.lock().unwrap().on_event() {
+            return Ok(());
+        }
+
+        // 窗口开启：立即显示第一条，窗口结束时汇总期间的其余通知
+        self.send_kind(kind, title, message)?;
+
+        let this = self.clone();
+        let coalescer = coalescer.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(this.throttle).await;
+                let pending = coalescer.lock().unwrap().on_window_end();
+                match pending {
+                    Some(count) => {
+                        let _ = this.send("剪贴板同步", &summary(count));
+                    }
+                    None => break,
+                }
+            }
+        });
 
         Ok(())
     }
 
-    /// 启用/禁用通知
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+    /// 启用/禁用通知，禁用后系统通知和控制台提示都不再输出
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
     }
 
     /// 检查是否启用通知
     pub fn is_enabled(&self) -> bool {
-        self.enabled
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for NotificationManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 通知合并状态
+#[derive(Debug, Default)]
+struct Coalescer {
+    window_open: bool,
+    pending: usize,
+}
+
+impl Coalescer {
+    /// 记录一次通知，返回是否应立即显示（即开启了新窗口）
+    fn on_event(&mut self) -> bool {
+        if self.window_open {
+            self.pending += 1;
+            false
+        } else {
+            self.window_open = true;
+            true
+        }
+    }
+
+    /// 窗口结束：有待汇总的通知时返回数量并继续下一个窗口，否则关闭窗口
+    fn on_window_end(&mut self) -> Option<usize> {
+        if self.pending == 0 {
+            self.window_open = false;
+            None
+        } else {
+            Some(std::mem::take(&mut self.pending))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalescer_burst() {
+        let mut coalescer = Coalescer::default();
+
+        // 第一条立即显示，其余合并
+        assert!(coalescer.on_event());
+        assert!(!coalescer.on_event());
+        assert!(!coalescer.on_event());
+        assert!(!coalescer.on_event());
+        assert_eq!(coalescer.on_window_end(), Some(3));
+
+        // 下一个窗口无新通知时关闭
+        assert_eq!(coalescer.on_window_end(), None);
+        assert!(coalescer.on_event());
+    }
+
+    #[test]
+    fn test_text_and_image_use_distinct_icons() {
+        assert_ne!(NotifyKind::Text.icon(), NotifyKind::Image.icon());
+        assert_eq!(NotifyKind::default(), NotifyKind::Info);
+    }
+
+    #[test]
+    fn test_notification_timeout() {
+        let notifier = NotificationManager::new();
+        assert_eq!(notifier.notification_timeout(), Timeout::Milliseconds(3000));
+
+        // 0 表示一直显示
+        let sticky = NotificationManager::new().with_timeout(Duration::ZERO);
+        assert_eq!(sticky.notification_timeout(), Timeout::Never);
+    }
+
+    #[test]
+    fn test_backend_disabled_after_first_failure() {
+        let notifier = NotificationManager::new();
+        let clone = notifier.clone();
+        notifier.record_backend_result(Ok::<(), &str>(()));
+        assert!(notifier.is_backend_available());
+
+        // 第一次失败后所有克隆都停用系统通知
+        notifier.record_backend_result(Err("org.freedesktop.Notifications was not provided"));
+        assert!(!clone.is_backend_available());
+
+        clone.reprobe();
+        assert!(notifier.is_backend_available());
+    }
+
+    #[test]
+    fn test_custom_icon_overrides_kind_icon() {
+        let notifier = NotificationManager::new();
+        assert_eq!(notifier.app_name, DEFAULT_APP_NAME);
+        assert_eq!(notifier.icon_for(NotifyKind::Image), NotifyKind::Image.icon());
+
+        let custom = NotificationManager::new()
+            .with_app_name("Work Clipboard")
+            .with_icon("/usr/share/icons/clipboard.png");
+        assert_eq!(custom.app_name, "Work Clipboard");
+        assert_eq!(custom.icon_for(NotifyKind::Text), "/usr/share/icons/clipboard.png");
+        assert_eq!(custom.icon_for(NotifyKind::Image), "/usr/share/icons/clipboard.png");
     }
 }
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::monitor::shutdown_signal;
+use crate::network_alternative::{ClipboardContent, ClipboardMessage, NetworkManager};
+use crate::structured::Color;
+
+/// 记录收到的消息的 JSON Lines 文件，每条消息一行，追加写入
+pub struct Transcript {
+    file: File,
+}
+
+/// 记录中的一行
+#[derive(Serialize)]
+struct TranscriptEntry<'a> {
+    /// 收到消息时的 Unix 毫秒时间戳
+    received_at: u64,
+    /// 发送方生成消息时的 Unix 秒时间戳
+    sent_at: u64,
+    sender_id: &'a str,
+    sender_name: &'a str,
+    content_type: &'static str,
+    /// 完整文本，只有文本消息才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+    /// 图片尺寸和编码后的字节数，只有图片消息才有，不记录图片数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<usize>,
+}
+
+impl Transcript {
+    /// 打开记录文件，文件已存在时在末尾追加
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow::anyhow!("无法打开记录文件 {}: {}", path.display(), e))?;
+        Ok(Self { file })
+    }
+
+    /// 追加一条消息并立即刷新
+    pub fn record(&mut self, message: &ClipboardMessage) -> Result<()> {
+        writeln!(self.file, "{}", transcript_line(message))?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// 将消息序列化为一行 JSON（不含换行）
+fn transcript_line(message: &ClipboardMessage) -> String {
+    let received_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let mut entry = TranscriptEntry {
+        received_at,
+        sent_at: message.timestamp,
+        sender_id: &message.sender_id,
+        sender_name: &message.sender_name,
+        content_type: message.content.kind(),
+        text: None,
+        width: None,
+        height: None,
+        bytes: None,
+    };
+    match &message.content {
+        ClipboardContent::Text(text) => entry.text = Some(text),
+        ClipboardContent::Color(color) => entry.text = Some(&color.text),
+        ClipboardContent::Html { text, .. } => entry.text = Some(text),
+        ClipboardContent::Image { width, height, data } => {
+            entry.width = Some(*width);
+            entry.height = Some(*height);
+            entry.bytes = Some(data.len());
+        }
+        ClipboardContent::Clear => {}
+    }
+    serde_json::to_string(&entry).expect("记录总能序列化为 JSON")
+}
+
+/// 观察模式：只接收并记录其他设备发来的内容，不访问本地剪贴板，也不发送或转发任何内容
+///
+/// 收到的消息已由网络层输出预览，`full` 时额外输出完整文本；按 Ctrl+C 或收到 SIGTERM 时退出
+pub async fn run_observer(network: &NetworkManager, full: bool, mut transcript: Option<Transcript>) {
+    let mut message_receiver = network.setup_message_queue().await;
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        let message = tokio::select! {
+            message = message_receiver.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            _ = &mut shutdown => break,
+        };
+
+        if full {
+            if let ClipboardContent::Text(text) | ClipboardContent::Color(Color { text, .. }) | ClipboardContent::Html { text, .. } =
+                &message.content
+            {
+                status!("📝 完整内容 ({} 字节):\n{}", text.len(), text);
+            }
+        }
+        if let Some(file) = &mut transcript {
+            if let Err(e) = file.record(&message) {
+                eprintln!("❌ 写入记录文件失败: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_records_full_text_and_image_size() {
+        let path = std::env::temp_dir().join(format!("clipboard-sync-alt-test-{}-transcript.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let long_text = "很长的文本".repeat(40);
+        let mut transcript = Transcript::open(&path).unwrap();
+        transcript
+            .record(&ClipboardMessage::new_text(long_text.clone(), "abc".to_string(), "笔记本".to_string()))
+            .unwrap();
+        transcript
+            .record(&ClipboardMessage::new_image(2, 3, vec![0; 16], "abc".to_string(), "笔记本".to_string()))
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        // 文本不截断
+        assert_eq!(lines[0]["content_type"], "text");
+        assert_eq!(lines[0]["text"], long_text.as_str());
+        assert_eq!(lines[0]["sender_name"], "笔记本");
+        // 图片只记录尺寸和大小
+        assert_eq!(lines[1]["content_type"], "image");
+        assert_eq!((lines[1]["width"].as_u64(), lines[1]["bytes"].as_u64()), (Some(2), Some(16)));
+        assert!(lines[1].get("text").is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+//! 系统剪贴板历史（Windows 10 起按 Win+V 打开）
+//!
+//! arboard 写入时不声明内容能否进入历史，由系统按默认规则处理；这里直接调用 Win32 接口，
+//! 在同一次剪贴板会话中写入文本并标记 `CanIncludeInClipboardHistory`，让每条同步来的内容都留在历史中，
+//! 不会因为下一次同步覆盖当前内容而找不回来
+
+use anyhow::Result;
+
+/// 是否能在当前平台把写入的内容推入系统剪贴板历史
+pub fn is_supported() -> bool {
+    cfg!(all(feature = "os-history", windows))
+}
+
+/// 系统是否开启了剪贴板历史，不支持的平台或无法判断时返回 `None`
+pub fn history_enabled() -> Option<bool> {
+    platform::history_enabled()
+}
+
+/// 写入文本并允许其进入系统剪贴板历史，调用方需保证此时没有其他线程访问剪贴板
+pub(crate) fn set_text(text: &str) -> Result<()> {
+    platform::set_text(text)
+}
+
+#[cfg(all(feature = "os-history", windows))]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use clipboard_win::{raw, register_format, Clipboard};
+    use windows_sys::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+    use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+    /// 打开剪贴板的尝试次数，其他程序正占用剪贴板时稍后重试
+    const OPEN_ATTEMPTS: usize = 10;
+
+    pub fn set_text(text: &str) -> Result<()> {
+        let _clipboard = Clipboard::new_attempts(OPEN_ATTEMPTS).map_err(|e| anyhow!("打开剪贴板失败: {}", e))?;
+        raw::set_string(text).map_err(|e| anyhow!("写入剪贴板失败: {}", e))?;
+        // 标记值为非零的 DWORD 表示允许进入历史，需与文本在同一次会话中写入
+        let format = register_format("CanIncludeInClipboardHistory")
+            .ok_or_else(|| anyhow!("注册剪贴板格式 CanIncludeInClipboardHistory 失败"))?;
+        raw::set_without_clear(format.get(), &1u32.to_ne_bytes())
+            .map_err(|e| anyhow!("标记剪贴板历史失败: {}", e))
+    }
+
+    pub fn history_enabled() -> Option<bool> {
+        let key: Vec<u16> = "Software\\Microsoft\\Clipboard\0".encode_utf16().collect();
+        let name: Vec<u16> = "EnableClipboardHistory\0".encode_utf16().collect();
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        // SAFETY: 键名和值名都以 NUL 结尾，输出缓冲区为一个 DWORD，大小与传入的一致
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                key.as_ptr(),
+                name.as_ptr(),
+                RRF_RT_REG_DWORD,
+                std::ptr::null_mut(),
+                &mut value as *mut u32 as *mut _,
+                &mut size,
+            )
+        };
+        match status {
+            ERROR_SUCCESS => Some(value != 0),
+            // 从未在设置中打开过剪贴板历史时没有这个值
+            ERROR_FILE_NOT_FOUND => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(all(feature = "os-history", windows)))]
+mod platform {
+    use anyhow::{bail, Result};
+
+    pub fn set_text(_text: &str) -> Result<()> {
+        bail!("当前平台不支持系统剪贴板历史")
+    }
+
+    pub fn history_enabled() -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_platform_degrades() {
+        if is_supported() {
+            return;
+        }
+        assert_eq!(history_enabled(), None);
+        assert!(set_text("hello").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use crate::network_alternative::SyncDirection;
+
+/// 配对链接的协议名
+pub const URL_SCHEME: &str = "clipsync";
+
+/// 解析后的连接目标: clipsync://name@host:port?secret=KEY&tls=1&direction=recv
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectTarget {
+    /// 目标设备名称，仅用于显示
+    pub name: Option<String>,
+    pub addr: SocketAddr,
+    /// 预共享密钥
+    pub secret: Option<String>,
+    /// 是否使用 TLS
+    pub tls: bool,
+    /// 与该设备之间的同步方向
+    pub direction: SyncDirection,
+}
+
+impl From<SocketAddr> for ConnectTarget {
+    fn from(addr: SocketAddr) -> Self {
+        Self { name: None, addr, secret: None, tls: false, direction: SyncDirection::Both }
+    }
+}
+
+impl fmt::Display for ConnectTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} ({})", name, self.addr),
+            None => write!(f, "{}", self.addr),
+        }
+    }
+}
+
+impl ConnectTarget {
+    /// 生成配对链接，与 `FromStr` 互逆
+    pub fn to_url(&self) -> String {
+        let mut url = url::Url::parse(&format!("{}://{}", URL_SCHEME, self.addr))
+            .expect("套接字地址总能组成合法链接");
+        if let Some(name) = &self.name {
+            let _ = url.set_username(name);
+        }
+        if let Some(secret) = &self.secret {
+            url.query_pairs_mut().append_pair("secret", secret);
+        }
+        if self.tls {
+            url.query_pairs_mut().append_pair("tls", "1");
+        }
+        if self.direction != SyncDirection::Both {
+            url.query_pairs_mut().append_pair("direction", &self.direction.to_string());
+        }
+        url.to_string()
+    }
+}
+
+impl FromStr for ConnectTarget {
+    type Err = anyhow::Error;
+
+    /// 解析配对链接，未知的查询参数会提示后忽略
+    fn from_str(input: &str) -> Result<Self> {
+        let url = url::Url::parse(input).map_err(|e| anyhow::anyhow!("无效的配对链接 {}: {}", input, e))?;
+        if url.scheme() != URL_SCHEME {
+            return Err(anyhow::anyhow!("不支持的链接协议: {}", url.scheme()));
+        }
+
+        let ip = match url.host() {
+            Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+            Some(url::Host::Domain(host)) => host
+                .parse()
+                .map_err(|_| anyhow::anyhow!("配对链接中的地址不是 IP: {}", host))?,
+            None => return Err(anyhow::anyhow!("配对链接缺少地址: {}", input)),
+        };
+        let port = url
+            .port()
+            .ok_or_else(|| anyhow::anyhow!("配对链接缺少端口: {}", input))?;
+
+        let name = match url.username() {
+            "" => None,
+            name => Some(
+                percent_encoding::percent_decode_str(name)
+                    .decode_utf8()
+                    .map_err(|_| anyhow::anyhow!("配对链接中的设备名称不是有效的 UTF-8"))?
+                    .into_owned(),
+            ),
+        };
+
+        let mut target = ConnectTarget { name, ..ConnectTarget::from(SocketAddr::new(ip, port)) };
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "secret" => target.secret = Some(value.into_owned()),
+                "tls" => {
+                    target.tls = match value.as_ref() {
+                        "1" | "true" => true,
+                        "0" | "false" => false,
+                        other => return Err(anyhow::anyhow!("无效的 tls 参数: {}", other)),
+                    }
+                }
+                "direction" => target.direction = value.parse()?,
+                other => eprintln!("⚠️ 忽略配对链接中未知的参数: {}", other),
+            }
+        }
+
+        Ok(target)
+    }
+}
+
+/// 是否是配对链接
+pub fn is_pairing_url(input: &str) -> bool {
+    input
+        .split_once("://")
+        .is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case(URL_SCHEME))
+}
+
+/// 将文本渲染为可在终端显示的二维码
+pub fn render_qr(text: &str) -> Result<String> {
+    let code = QrCode::new(text.as_bytes())?;
+    // 终端通常是深色背景，反转颜色后手机更容易识别
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// 本机可供其他设备连接的地址（排除回环和链路本地地址）
+pub fn local_addresses() -> Vec<IpAddr> {
+    match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces
+            .into_iter()
+            .filter(|iface| !iface.is_loopback() && !iface.is_link_local())
+            .map(|iface| iface.ip())
+            .collect(),
+        Err(e) => {
+            eprintln!("⚠️ 无法获取本地网络接口: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_target_round_trip() {
+        let targets = [
+            ConnectTarget::from("192.168.1.2:8765".parse::<SocketAddr>().unwrap()),
+            ConnectTarget {
+                name: Some("我的 电脑".to_string()),
+                addr: "[fe80::1]:9000".parse().unwrap(),
+                secret: Some("a&b=c".to_string()),
+                tls: true,
+                direction: SyncDirection::Receive,
+            },
+        ];
+        for target in targets {
+            let url = target.to_url();
+            assert!(is_pairing_url(&url));
+            assert_eq!(url.parse::<ConnectTarget>().unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn test_parse_connect_target() {
+        let target: ConnectTarget = "clipsync://laptop@[::1]:8765?secret=KEY&tls=1&extra=x".parse().unwrap();
+        assert_eq!(target.name.as_deref(), Some("laptop"));
+        assert_eq!(target.addr, "[::1]:8765".parse().unwrap());
+        assert_eq!(target.secret.as_deref(), Some("KEY"));
+        assert!(target.tls);
+    }
+
+    #[test]
+    fn test_parse_connect_target_rejects_invalid() {
+        assert!(!is_pairing_url("192.168.1.2:8765"));
+        assert!("clipsync://192.168.1.2".parse::<ConnectTarget>().is_err());
+        assert!("http://192.168.1.2:8765".parse::<ConnectTarget>().is_err());
+        assert!("clipsync://my-laptop:8765".parse::<ConnectTarget>().is_err());
+        assert!("clipsync://192.168.1.2:8765?tls=maybe".parse::<ConnectTarget>().is_err());
+        assert!("clipsync://192.168.1.2:8765?direction=up".parse::<ConnectTarget>().is_err());
+    }
+
+    #[test]
+    fn test_render_qr() {
+        let qr = render_qr("clipsync://192.168.1.2:8765").unwrap();
+        assert!(qr.lines().count() > 10);
+    }
+}
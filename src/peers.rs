@@ -0,0 +1,166 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+
+const PEERS_FILE: &str = "peers.json";
+
+/// 已配对过的设备
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnownPeer {
+    pub address: SocketAddr,
+    /// 最近一次连接成功的时间（Unix 秒）
+    pub last_connected: u64,
+}
+
+/// 已配对设备的持久化存储，以对方在握手中告知的设备标识为键保存在配置目录下的 JSON 文件中，
+/// 设备换了地址时更新原有的记录；不告知设备标识的旧版本设备以连接标识为键
+#[derive(Debug, Clone)]
+pub struct PeerStore {
+    path: PathBuf,
+    peers: BTreeMap<String, KnownPeer>,
+}
+
+impl PeerStore {
+    /// 默认存储路径: <配置目录>/clipboard-sync-alt/peers.json
+    pub fn default_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join(PEERS_FILE))
+    }
+
+    /// 从默认路径加载
+    pub fn load_default() -> Result<Self> {
+        let path = Self::default_path().ok_or_else(|| anyhow::anyhow!("无法确定配置目录"))?;
+        Self::load(path)
+    }
+
+    /// 从指定路径加载，文件不存在时返回空存储
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let peers = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow::anyhow!("无法解析已保存的设备 {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, peers })
+    }
+
+    /// 写回磁盘
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&self.peers)?)?;
+        Ok(())
+    }
+
+    /// 记录一次成功的连接并保存，覆盖该设备之前的地址；
+    /// 其他键（如旧版本以连接标识保存的记录）使用同一地址时一并删除，避免重复连接
+    pub fn record(&mut self, device_id: &str, address: SocketAddr) -> Result<()> {
+        let last_connected = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.peers.retain(|id, peer| id == device_id || peer.address != address);
+        self.peers
+            .insert(device_id.to_string(), KnownPeer { address, last_connected });
+        self.save()
+    }
+
+    /// 合并其他设备导出的已配对设备并保存，同一设备保留最近连接过的记录
+    pub fn merge(&mut self, peers: BTreeMap<String, KnownPeer>) -> Result<()> {
+        for (device_id, peer) in peers {
+            match self.peers.get(&device_id) {
+                Some(existing) if existing.last_connected >= peer.last_connected => {}
+                _ => {
+                    self.peers.insert(device_id, peer);
+                }
+            }
+        }
+        self.save()
+    }
+
+    /// 清空所有已保存的设备并保存
+    pub fn clear(&mut self) -> Result<()> {
+        self.peers.clear();
+        self.save()
+    }
+
+    /// 已保存设备的地址，最近连接过的排在前面
+    pub fn addresses(&self) -> Vec<SocketAddr> {
+        let mut peers: Vec<&KnownPeer> = self.peers.values().collect();
+        peers.sort_by_key(|peer| std::cmp::Reverse(peer.last_connected));
+        peers.into_iter().map(|peer| peer.address).collect()
+    }
+
+    /// 所有已保存的设备，以设备标识为键
+    pub fn peers(&self) -> &BTreeMap<String, KnownPeer> {
+        &self.peers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("clipboard-sync-alt-test-{}-{}", std::process::id(), name))
+            .join(PEERS_FILE)
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let store = PeerStore::load(temp_path("missing")).unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_record_persists_and_clear() {
+        let path = temp_path("roundtrip");
+        let addr: SocketAddr = "192.168.1.2:8765".parse().unwrap();
+
+        let mut store = PeerStore::load(&path).unwrap();
+        // 旧版本以连接标识保存的记录，同一地址以设备标识记录后被替换
+        store.record("server_192.168.1.2:8765", addr).unwrap();
+        store.record("a1b2c3d4e5f6", addr).unwrap();
+        // 重复连接同一设备只保留一条
+        store.record("a1b2c3d4e5f6", addr).unwrap();
+
+        let mut reloaded = PeerStore::load(&path).unwrap();
+        assert_eq!(reloaded.addresses(), vec![addr]);
+        assert!(reloaded.peers().contains_key("a1b2c3d4e5f6"));
+
+        // 合并时同一设备保留最近连接过的记录
+        let moved: SocketAddr = "192.168.1.3:8765".parse().unwrap();
+        let imported = BTreeMap::from([
+            ("a1b2c3d4e5f6".to_string(), KnownPeer { address: moved, last_connected: 0 }),
+            ("0f1e2d3c4b5a".to_string(), KnownPeer { address: "10.0.0.2:8765".parse().unwrap(), last_connected: 0 }),
+        ]);
+        reloaded.merge(imported).unwrap();
+        assert_eq!(PeerStore::load(&path).unwrap().addresses(), vec![addr, "10.0.0.2:8765".parse().unwrap()]);
+
+        // 设备换了地址后覆盖原来的地址，不再连接旧地址
+        reloaded.record("a1b2c3d4e5f6", moved).unwrap();
+        let reloaded_addresses = PeerStore::load(&path).unwrap().addresses();
+        assert_eq!(reloaded_addresses[0], moved);
+        assert!(!reloaded_addresses.contains(&addr));
+
+        reloaded.clear().unwrap();
+        assert!(PeerStore::load(&path).unwrap().is_empty());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
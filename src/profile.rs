@@ -0,0 +1,257 @@
+//! 导出和导入的配置，用于把现有的设置（设备名称、端口、选项、已配对设备）复制到新设备
+//!
+//! 导入的配置保存在配置目录下的 config.json 中，不带任何参数运行时按其中的命令启动
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::peers::KnownPeer;
+
+const PROFILE_FILE: &str = "config.json";
+/// 导入时随配置一起导入的密钥保存在配置目录下的该文件中
+const SECRET_FILE: &str = "secret";
+
+/// 配置文件格式的版本，格式不兼容地变化时递增
+pub const PROFILE_VERSION: u32 = 1;
+
+/// 可以保存为配置的子命令
+const COMMANDS: &[&str] = &["start", "connect"];
+
+/// 预共享密钥的来源，默认只记录来源，不包含密钥本身
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "from")]
+pub enum SecretRef {
+    /// 从密钥文件读取（`--secret-file`）
+    File { path: PathBuf },
+    /// 从环境变量 `CLIPSYNC_SECRET` 读取
+    Env,
+    /// 密钥本身，只在导出时指定 `--include-secret` 才会出现
+    Inline { value: String },
+}
+
+/// 一份完整的配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    /// 启动时运行的子命令: start 或 connect
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<SecretRef>,
+    /// 其余的命令行参数，按原样传给子命令
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 已配对的设备，导入时合并到本机的已配对设备中
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub peers: BTreeMap<String, KnownPeer>,
+}
+
+impl Profile {
+    /// 从平时运行的命令行（不含程序名，如 `start --name 笔记本 --port 9000`）中提取配置，
+    /// 设备名称、端口和密钥单独记录，其余参数原样保存
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let (command, rest) = args
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("缺少子命令（{}）", COMMANDS.join("、")))?;
+        let mut profile = Self {
+            version: PROFILE_VERSION,
+            command: command.clone(),
+            name: None,
+            port: None,
+            secret: None,
+            args: Vec::new(),
+            peers: BTreeMap::new(),
+        };
+        profile.check_command()?;
+
+        let mut secret = None;
+        let mut rest = rest.iter();
+        while let Some(arg) = rest.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            if !matches!(flag, "-n" | "--name" | "-p" | "--port" | "--secret" | "--secret-file") {
+                profile.args.push(arg.clone());
+                continue;
+            }
+            let value = match inline.or_else(|| rest.next().cloned()) {
+                Some(value) => value,
+                None => anyhow::bail!("{} 缺少参数值", flag),
+            };
+            match flag {
+                "-n" | "--name" => profile.name = Some(value),
+                "-p" | "--port" => {
+                    profile.port = Some(value.parse().map_err(|_| anyhow::anyhow!("无效的端口: {}", value))?)
+                }
+                // 同时给出时 --secret 优先，与启动时的优先级一致
+                "--secret" => secret = Some(SecretRef::Inline { value }),
+                _ => {
+                    if !matches!(secret, Some(SecretRef::Inline { .. })) {
+                        secret = Some(SecretRef::File { path: PathBuf::from(value) });
+                    }
+                }
+            }
+        }
+        profile.secret = secret.or_else(|| std::env::var_os(config::SECRET_ENV).map(|_| SecretRef::Env));
+        Ok(profile)
+    }
+
+    /// 还原为命令行参数（不含程序名）
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![self.command.clone()];
+        if let Some(name) = &self.name {
+            args.extend(["--name".to_string(), name.clone()]);
+        }
+        if let Some(port) = self.port {
+            args.extend(["--port".to_string(), port.to_string()]);
+        }
+        match &self.secret {
+            Some(SecretRef::File { path }) => args.extend(["--secret-file".to_string(), path.display().to_string()]),
+            Some(SecretRef::Inline { value }) => args.extend(["--secret".to_string(), value.clone()]),
+            // 启动时自动读取环境变量
+            Some(SecretRef::Env) | None => {}
+        }
+        args.extend(self.args.iter().cloned());
+        args
+    }
+
+    /// 把密钥来源替换为密钥本身
+    pub fn embed_secret(&mut self) -> Result<()> {
+        let value = match &self.secret {
+            Some(SecretRef::File { path }) => config::read_secret_file(path)?,
+            Some(SecretRef::Env) => std::env::var(config::SECRET_ENV)
+                .map_err(|_| anyhow::anyhow!("环境变量 {} 未设置", config::SECRET_ENV))?,
+            Some(SecretRef::Inline { .. }) | None => return Ok(()),
+        };
+        self.secret = Some(SecretRef::Inline { value });
+        Ok(())
+    }
+
+    /// 检查版本和子命令，参数本身由调用方按命令行的规则检查
+    pub fn validate(&self) -> Result<()> {
+        if self.version != PROFILE_VERSION {
+            anyhow::bail!("不支持的配置版本 {}（当前为 {}）", self.version, PROFILE_VERSION);
+        }
+        self.check_command()
+    }
+
+    fn check_command(&self) -> Result<()> {
+        if !COMMANDS.contains(&self.command.as_str()) {
+            anyhow::bail!("不支持保存为配置的子命令: {}（可选: {}）", self.command, COMMANDS.join("、"));
+        }
+        Ok(())
+    }
+
+    /// 导入的配置的保存路径: <配置目录>/clipboard-sync-alt/config.json
+    pub fn default_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join(PROFILE_FILE))
+    }
+
+    /// 读取已导入的配置，尚未导入过时返回 `None`
+    pub fn load_saved() -> Result<Option<Self>> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("无法读取配置 {}: {}", path.display(), e))?;
+        serde_json::from_slice(&bytes).map_err(|e| anyhow::anyhow!("无法解析配置 {}: {}", path.display(), e))
+    }
+
+    /// 保存到指定路径，文件已存在且没有指定 `force` 时拒绝覆盖
+    pub fn save(&self, path: &Path, force: bool) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        // 包含密钥时只允许本人读取
+        write_file(path, &data, force, matches!(self.secret, Some(SecretRef::Inline { .. })))
+    }
+}
+
+/// 把导入的密钥写入配置目录，返回密钥文件的路径
+pub fn save_secret(secret: &str) -> Result<PathBuf> {
+    let path = config::config_dir()
+        .ok_or_else(|| anyhow::anyhow!("无法确定配置目录"))?
+        .join(SECRET_FILE);
+    write_file(&path, secret.as_bytes(), true, true)?;
+    Ok(path)
+}
+
+fn write_file(path: &Path, data: &[u8], force: bool, private: bool) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => anyhow::anyhow!("{} 已存在，加上 --force 覆盖", path.display()),
+        _ => anyhow::anyhow!("无法写入 {}: {}", path.display(), e),
+    })?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_split_and_restore_args() {
+        let profile = Profile::from_args(&args("connect 192.168.1.2 -p 9000 --name=笔记本 --secret-file /k --compress zstd")).unwrap();
+        assert_eq!(profile.name.as_deref(), Some("笔记本"));
+        assert_eq!(profile.port, Some(9000));
+        assert_eq!(profile.secret, Some(SecretRef::File { path: PathBuf::from("/k") }));
+        assert_eq!(profile.args, args("192.168.1.2 --compress zstd"));
+        assert_eq!(
+            profile.to_args(),
+            args("connect --name 笔记本 --port 9000 --secret-file /k 192.168.1.2 --compress zstd")
+        );
+
+        assert!(Profile::from_args(&args("observe 192.168.1.2")).is_err());
+        assert!(Profile::from_args(&args("start --port")).is_err());
+        assert!(Profile::from_args(&args("start --port http")).is_err());
+    }
+
+    #[test]
+    fn test_save_refuses_to_overwrite() {
+        let dir = std::env::temp_dir().join(format!("clipboard-sync-alt-test-{}-profile", std::process::id()));
+        let path = dir.join(PROFILE_FILE);
+        let profile = Profile::from_args(&args("start --name 台式机 --secret 密钥")).unwrap();
+
+        profile.save(&path, false).unwrap();
+        assert!(profile.save(&path, false).is_err());
+        profile.save(&path, true).unwrap();
+
+        let loaded = Profile::load(&path).unwrap();
+        assert_eq!(loaded, profile);
+        loaded.validate().unwrap();
+        std::fs::write(&path, r#"{"version": 99, "command": "start"}"#).unwrap();
+        assert!(Profile::load(&path).unwrap().validate().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
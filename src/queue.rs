@@ -0,0 +1,243 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::Notify;
+
+use crate::network_alternative::ClipboardMessage;
+
+/// 接收队列的默认容量
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+/// 接收队列已满时如何处理新收到的消息
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueueFullPolicy {
+    /// 丢弃队列中最早的消息，保留最新的内容（默认）
+    #[default]
+    DropOldest,
+    /// 丢弃新收到的消息
+    DropNewest,
+    /// 暂停读取连接直到队列有空位，由 TCP 向发送方施加背压
+    Block,
+}
+
+impl FromStr for QueueFullPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "drop-oldest" => Ok(QueueFullPolicy::DropOldest),
+            "drop-newest" => Ok(QueueFullPolicy::DropNewest),
+            "block" => Ok(QueueFullPolicy::Block),
+            other => Err(anyhow::anyhow!(
+                "无效的队列策略 {:?}: 应为 drop-oldest、drop-newest 或 block",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for QueueFullPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            QueueFullPolicy::DropOldest => "drop-oldest",
+            QueueFullPolicy::DropNewest => "drop-newest",
+            QueueFullPolicy::Block => "block",
+        })
+    }
+}
+
+struct QueueState {
+    items: VecDeque<ClipboardMessage>,
+    /// 所有发送端都已释放
+    senders_closed: bool,
+    /// 接收端已释放
+    receiver_closed: bool,
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    capacity: usize,
+    policy: QueueFullPolicy,
+    /// 有新消息可读
+    readable: Notify,
+    /// 队列有空位或接收端已关闭
+    writable: Notify,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 创建容量有限的接收队列，返回发送端和接收端
+pub(crate) fn message_queue(capacity: usize, policy: QueueFullPolicy) -> (QueueSender, MessageQueue) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState {
+            items: VecDeque::new(),
+            senders_closed: false,
+            receiver_closed: false,
+        }),
+        capacity: capacity.max(1),
+        policy,
+        readable: Notify::new(),
+        writable: Notify::new(),
+    });
+    (QueueSender { shared: shared.clone() }, MessageQueue { shared })
+}
+
+/// 接收队列的发送端，由连接的读取任务共享，全部释放后接收端返回 `None`
+pub(crate) struct QueueSender {
+    shared: Arc<Shared>,
+}
+
+impl QueueSender {
+    /// 放入一条消息，队列已满时按策略丢弃或等待
+    pub(crate) async fn send(&self, message: ClipboardMessage) -> Result<()> {
+        loop {
+            let writable = self.shared.writable.notified();
+            {
+                let mut state = self.shared.lock();
+                if state.receiver_closed {
+                    return Err(anyhow::anyhow!("消息队列已关闭"));
+                }
+                if state.items.len() < self.shared.capacity {
+                    state.items.push_back(message);
+                    self.shared.readable.notify_one();
+                    return Ok(());
+                }
+                match self.shared.policy {
+                    QueueFullPolicy::DropOldest => {
+                        if let Some(dropped) = state.items.pop_front() {
+                            status!("⚠️ 接收队列已满 ({} 条)，丢弃最早的消息 (来自: {})", self.shared.capacity, dropped.sender_label());
+                        }
+                        state.items.push_back(message);
+                        self.shared.readable.notify_one();
+                        return Ok(());
+                    }
+                    QueueFullPolicy::DropNewest => {
+                        status!("⚠️ 接收队列已满 ({} 条)，丢弃新收到的消息 (来自: {})", self.shared.capacity, message.sender_label());
+                        return Ok(());
+                    }
+                    QueueFullPolicy::Block => {}
+                }
+            }
+            writable.await;
+        }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        self.shared.lock().senders_closed = true;
+        self.shared.readable.notify_one();
+    }
+}
+
+/// 收到的剪贴板消息的有界队列，由 `NetworkManager::setup_message_queue` 创建
+///
+/// 队列已满时按 `QueueFullPolicy` 处理，消费者处理不过来时内存占用不会无限增长
+pub struct MessageQueue {
+    shared: Arc<Shared>,
+}
+
+impl MessageQueue {
+    /// 取出下一条消息，网络管理器改用其他消息处理器后返回 `None`
+    pub async fn recv(&mut self) -> Option<ClipboardMessage> {
+        loop {
+            let readable = self.shared.readable.notified();
+            {
+                let mut state = self.shared.lock();
+                if let Some(message) = state.items.pop_front() {
+                    self.shared.writable.notify_one();
+                    return Some(message);
+                }
+                if state.senders_closed {
+                    return None;
+                }
+            }
+            readable.await;
+        }
+    }
+
+    /// 队列中等待处理的消息数
+    pub fn len(&self) -> usize {
+        self.shared.lock().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for MessageQueue {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_closed = true;
+        // 唤醒所有等待空位的读取任务，让它们返回错误
+        self.shared.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn text(content: &str) -> ClipboardMessage {
+        ClipboardMessage::new_text(content.to_string(), "abc".to_string(), "笔记本".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_drop_policies_keep_capacity() {
+        let (sender, mut queue) = message_queue(2, QueueFullPolicy::DropOldest);
+        for content in ["1", "2", "3"] {
+            sender.send(text(content)).await.unwrap();
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.recv().await.unwrap().content.preview(50), "2");
+        assert_eq!(queue.recv().await.unwrap().content.preview(50), "3");
+
+        let (sender, mut queue) = message_queue(2, QueueFullPolicy::DropNewest);
+        for content in ["1", "2", "3"] {
+            sender.send(text(content)).await.unwrap();
+        }
+        assert_eq!(queue.recv().await.unwrap().content.preview(50), "1");
+        assert_eq!(queue.recv().await.unwrap().content.preview(50), "2");
+        assert!(queue.is_empty());
+
+        // 发送端全部释放后接收端结束
+        drop(sender);
+        assert!(queue.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_block_policy_waits_for_space() {
+        let (sender, mut queue) = message_queue(1, QueueFullPolicy::Block);
+        sender.send(text("1")).await.unwrap();
+        let blocked = tokio::spawn(async move {
+            sender.send(text("2")).await.unwrap();
+            sender
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(queue.recv().await.unwrap().content.preview(50), "1");
+        let sender = tokio::time::timeout(Duration::from_secs(1), blocked).await.unwrap().unwrap();
+        assert_eq!(queue.recv().await.unwrap().content.preview(50), "2");
+
+        // 接收端关闭后发送返回错误，而不是一直等待
+        sender.send(text("3")).await.unwrap();
+        drop(queue);
+        assert!(sender.send(text("4")).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!("drop-newest".parse::<QueueFullPolicy>().unwrap(), QueueFullPolicy::DropNewest);
+        assert_eq!("Block".parse::<QueueFullPolicy>().unwrap(), QueueFullPolicy::Block);
+        assert!("drop".parse::<QueueFullPolicy>().is_err());
+        assert_eq!(QueueFullPolicy::default().to_string(), "drop-oldest");
+    }
+}
@@ -0,0 +1,300 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// 同步的剪贴板选区
+///
+/// 只有 Linux（X11 和部分 Wayland 合成器）区分 CLIPBOARD（Ctrl+C）和 PRIMARY（选中文本、中键粘贴），
+/// 其他平台只有一个剪贴板，选择任何选区都等同于 `Clipboard`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Selection {
+    /// Ctrl+C / Ctrl+V 使用的剪贴板（默认）
+    #[default]
+    Clipboard,
+    /// 选中文本即复制、中键粘贴的 PRIMARY 选区
+    Primary,
+    /// 同时监控两个选区，同步最近变化的一个；收到的文本写入两个选区，图片只写入 CLIPBOARD
+    Both,
+}
+
+impl Selection {
+    /// 当前平台是否区分 CLIPBOARD 和 PRIMARY
+    pub fn is_supported() -> bool {
+        cfg!(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))
+    }
+
+    /// 在当前平台实际生效的选区，不区分选区的平台总是 `Clipboard`
+    pub fn effective(self) -> Self {
+        if Self::is_supported() {
+            self
+        } else {
+            Selection::Clipboard
+        }
+    }
+
+    /// 写入文本的选区
+    pub(crate) fn text_targets(self) -> &'static [Target] {
+        match self {
+            Selection::Clipboard => &[Target::Clipboard],
+            Selection::Primary => &[Target::Primary],
+            Selection::Both => &[Target::Clipboard, Target::Primary],
+        }
+    }
+
+    /// 读写图片和 HTML 的选区，PRIMARY 中很少有图片和 HTML，`Both` 时只使用 CLIPBOARD
+    pub(crate) fn image_target(self) -> Target {
+        match self {
+            Selection::Primary => Target::Primary,
+            Selection::Clipboard | Selection::Both => Target::Clipboard,
+        }
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clipboard" => Ok(Selection::Clipboard),
+            "primary" => Ok(Selection::Primary),
+            "both" => Ok(Selection::Both),
+            other => Err(format!("未知的选区: {} (可选: clipboard, primary, both)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+            Selection::Both => "both",
+        })
+    }
+}
+
+/// 一次读写操作的目标选区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Target {
+    Clipboard,
+    Primary,
+}
+
+/// 同时监控两个选区时，记录各选区上次读到的文本，返回最近变化的一个
+///
+/// 两个选区同时变化时以 CLIPBOARD 为准；选区变为空（如取消选中）不算变化，继续返回之前的文本，避免来回切换
+#[derive(Debug)]
+pub(crate) struct SelectionTracker {
+    last: [Option<String>; 2],
+    current: Target,
+}
+
+impl Default for SelectionTracker {
+    fn default() -> Self {
+        Self { last: [None, None], current: Target::Clipboard }
+    }
+}
+
+impl SelectionTracker {
+    /// 记录两个选区当前的文本，返回应当视为剪贴板内容的文本
+    pub(crate) fn pick(&mut self, clipboard: Option<String>, primary: Option<String>) -> Option<String> {
+        let mut changed = false;
+        for (target, text) in [(Target::Clipboard, clipboard), (Target::Primary, primary)] {
+            let last = &mut self.last[target as usize];
+            if text.is_some() && *last != text {
+                *last = text;
+                if !changed {
+                    self.current = target;
+                    changed = true;
+                }
+            }
+        }
+        self.last[self.current as usize].clone()
+    }
+
+    /// 写入两个选区后记录写入的文本，不会被当作本地变化
+    pub(crate) fn record_write(&mut self, text: &str) {
+        self.last = [Some(text.to_string()), Some(text.to_string())];
+    }
+
+    /// 清空后两个选区都视为没有内容
+    pub(crate) fn record_clear(&mut self) {
+        self.last = [None, None];
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+mod platform {
+    use super::Target;
+    use arboard::{ClearExtLinux, Clipboard, Error, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+    #[cfg(feature = "image-support")]
+    use arboard::ImageData;
+    use std::cell::Cell;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::ConnectionExt as _;
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    fn kind(target: Target) -> LinuxClipboardKind {
+        match target {
+            Target::Clipboard => LinuxClipboardKind::Clipboard,
+            Target::Primary => LinuxClipboardKind::Primary,
+        }
+    }
+
+    pub fn get_text(clipboard: &mut Clipboard, target: Target) -> Result<String, Error> {
+        clipboard.get().clipboard(kind(target)).text()
+    }
+
+    pub fn set_text(clipboard: &mut Clipboard, target: Target, text: &str) -> Result<(), Error> {
+        clipboard.set().clipboard(kind(target)).text(text)
+    }
+
+    #[cfg(feature = "image-support")]
+    pub fn get_image(clipboard: &mut Clipboard, target: Target) -> Result<ImageData<'static>, Error> {
+        clipboard.get().clipboard(kind(target)).image()
+    }
+
+    #[cfg(feature = "image-support")]
+    pub fn set_image(clipboard: &mut Clipboard, target: Target, image: ImageData<'_>) -> Result<(), Error> {
+        clipboard.set().clipboard(kind(target)).image(image)
+    }
+
+    pub fn get_html(clipboard: &mut Clipboard, target: Target) -> Result<String, Error> {
+        clipboard.get().clipboard(kind(target)).html()
+    }
+
+    pub fn set_html(clipboard: &mut Clipboard, target: Target, html: &str, alt_text: &str) -> Result<(), Error> {
+        clipboard.set().clipboard(kind(target)).html(html, Some(alt_text))
+    }
+
+    pub fn clear(clipboard: &mut Clipboard, target: Target) -> Result<(), Error> {
+        clipboard.clear_with().clipboard(kind(target))
+    }
+
+    /// 通过 X11 的 XFixes 扩展接收选区所有者变化的通知，把收到的通知数作为剪贴板的变化序号
+    ///
+    /// 每次复制都会重新设置选区的所有者，检查是否有变化只需处理已收到的事件，不读取剪贴板内容
+    pub struct ChangeWatcher {
+        connection: RustConnection,
+        count: Cell<u64>,
+    }
+
+    impl ChangeWatcher {
+        /// 连接 X 服务器并订阅 `targets` 的变化，没有 X 服务器或服务器不支持 XFixes 时返回 `None`
+        pub fn new(targets: &[Target]) -> Option<Self> {
+            let (connection, screen) = x11rb::connect(None).ok()?;
+            connection.xfixes_query_version(5, 0).ok()?.reply().ok()?;
+            let root = connection.setup().roots.get(screen)?.root;
+            let mask = SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE;
+            for target in targets {
+                let name: &[u8] = match target {
+                    Target::Clipboard => b"CLIPBOARD",
+                    Target::Primary => b"PRIMARY",
+                };
+                let atom = connection.intern_atom(false, name).ok()?.reply().ok()?.atom;
+                connection.xfixes_select_selection_input(root, atom, mask).ok()?;
+            }
+            connection.flush().ok()?;
+            Some(Self { connection, count: Cell::new(0) })
+        }
+
+        /// 目前为止收到的变化通知数，与 X 服务器的连接断开时返回 `None`
+        pub fn sequence(&self) -> Option<u64> {
+            loop {
+                match self.connection.poll_for_event() {
+                    Ok(Some(Event::XfixesSelectionNotify(_))) => self.count.set(self.count.get() + 1),
+                    Ok(Some(_)) => {}
+                    Ok(None) => return Some(self.count.get()),
+                    Err(_) => return None,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten")))))]
+mod platform {
+    use super::Target;
+    use arboard::{Clipboard, Error};
+    #[cfg(feature = "image-support")]
+    use arboard::ImageData;
+
+    pub fn get_text(clipboard: &mut Clipboard, _target: Target) -> Result<String, Error> {
+        clipboard.get_text()
+    }
+
+    pub fn set_text(clipboard: &mut Clipboard, _target: Target, text: &str) -> Result<(), Error> {
+        clipboard.set_text(text)
+    }
+
+    #[cfg(feature = "image-support")]
+    pub fn get_image(clipboard: &mut Clipboard, _target: Target) -> Result<ImageData<'static>, Error> {
+        clipboard.get_image()
+    }
+
+    #[cfg(feature = "image-support")]
+    pub fn set_image(clipboard: &mut Clipboard, _target: Target, image: ImageData<'_>) -> Result<(), Error> {
+        clipboard.set_image(image)
+    }
+
+    pub fn get_html(clipboard: &mut Clipboard, _target: Target) -> Result<String, Error> {
+        clipboard.get().html()
+    }
+
+    pub fn set_html(clipboard: &mut Clipboard, _target: Target, html: &str, alt_text: &str) -> Result<(), Error> {
+        clipboard.set_html(html, Some(alt_text))
+    }
+
+    pub fn clear(clipboard: &mut Clipboard, _target: Target) -> Result<(), Error> {
+        clipboard.clear()
+    }
+
+    /// 其他平台直接读取系统提供的变化序号，不需要监听
+    pub struct ChangeWatcher;
+
+    impl ChangeWatcher {
+        pub fn new(_targets: &[Target]) -> Option<Self> {
+            None
+        }
+
+        pub fn sequence(&self) -> Option<u64> {
+            None
+        }
+    }
+}
+
+pub(crate) use platform::{clear, get_html, get_text, set_html, set_text, ChangeWatcher};
+#[cfg(feature = "image-support")]
+pub(crate) use platform::{get_image, set_image};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_follows_latest_change() {
+        let mut tracker = SelectionTracker::default();
+        let some = |text: &str| Some(text.to_string());
+        assert_eq!(tracker.pick(some("复制"), None), some("复制"));
+
+        // 选中文本后 PRIMARY 变化，切换到 PRIMARY
+        assert_eq!(tracker.pick(some("复制"), some("选中")), some("选中"));
+        // 取消选中不算变化
+        assert_eq!(tracker.pick(some("复制"), None), some("选中"));
+        // 两个选区同时变化时以 CLIPBOARD 为准
+        assert_eq!(tracker.pick(some("新复制"), some("新选中")), some("新复制"));
+
+        tracker.record_write("收到");
+        assert_eq!(tracker.pick(some("收到"), some("收到")), some("收到"));
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!("primary".parse::<Selection>().unwrap(), Selection::Primary);
+        assert_eq!(Selection::Both.to_string(), "both");
+        assert!("secondary".parse::<Selection>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! 同步会话的时长限制：空闲一段时间或运行超过最长时间后自动退出，避免忘记关闭的同步会话一直开着
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::monitor::SyncControl;
+
+/// 会话的时长限制，都为 `None` 时不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// 超过该时长没有剪贴板变化、收到消息或交互命令时退出
+    pub idle_timeout: Option<Duration>,
+    /// 无论是否有活动，运行超过该时长后退出
+    pub max_session: Option<Duration>,
+}
+
+/// 自动退出的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// 空闲时间达到 `--idle-timeout`
+    Idle(Duration),
+    /// 运行时间达到 `--max-session`
+    MaxSession(Duration),
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitReason::Idle(limit) => write!(f, "已空闲 {}（--idle-timeout）", format_duration(*limit)),
+            ExitReason::MaxSession(limit) => write!(f, "已达到最长会话时间 {}（--max-session）", format_duration(*limit)),
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 60 && secs.is_multiple_of(60) {
+        format!("{} 分钟", secs / 60)
+    } else {
+        format!("{} 秒", secs)
+    }
+}
+
+impl SessionLimits {
+    pub fn is_empty(&self) -> bool {
+        self.idle_timeout.is_none() && self.max_session.is_none()
+    }
+
+    /// 检查是否超出限制，两个限制同时超出时以最长会话时间为准
+    pub fn expired(&self, started: Instant, last_activity: Instant, now: Instant) -> Option<ExitReason> {
+        if let Some(limit) = self.max_session.filter(|limit| now.saturating_duration_since(started) >= *limit) {
+            return Some(ExitReason::MaxSession(limit));
+        }
+        self.idle_timeout
+            .filter(|limit| now.saturating_duration_since(last_activity) >= *limit)
+            .map(ExitReason::Idle)
+    }
+
+    /// 下一次需要检查的时间，没有限制时为 `None`
+    fn next_deadline(&self, started: Instant, last_activity: Instant) -> Option<Instant> {
+        let idle = self.idle_timeout.map(|limit| last_activity + limit);
+        let max = self.max_session.map(|limit| started + limit);
+        idle.into_iter().chain(max).min()
+    }
+}
+
+/// 最近一次活动的时间，监控循环和消息处理任务共享
+#[derive(Debug, Clone)]
+pub(crate) struct Activity(Arc<Mutex<Instant>>);
+
+impl Activity {
+    fn new(now: Instant) -> Self {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    /// 记录一次活动，重新开始计算空闲时间
+    pub(crate) fn touch(&self) {
+        *self.0.lock().unwrap() = Instant::now();
+    }
+
+    fn last(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// 在后台检查会话时长，超出限制时输出原因并请求退出；返回用于记录活动的句柄
+pub(crate) fn watch(limits: SessionLimits, control: SyncControl) -> Activity {
+    let started = Instant::now();
+    let activity = Activity::new(started);
+    if limits.is_empty() {
+        return activity;
+    }
+    let last = activity.clone();
+    tokio::spawn(async move {
+        while let Some(deadline) = limits.next_deadline(started, last.last()) {
+            tokio::select! {
+                _ = control.cancelled() => break,
+                _ = tokio::time::sleep_until(deadline.into()) => {}
+            }
+            // 等待期间有新的活动时按新的空闲起点重新计算
+            if let Some(reason) = limits.expired(started, last.last(), Instant::now()) {
+                status!("⏰ {}，自动退出", reason);
+                control.shutdown();
+                break;
+            }
+        }
+    });
+    activity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_expire() {
+        let minute = Duration::from_secs(60);
+        let started = Instant::now();
+        let limits = SessionLimits { idle_timeout: Some(minute * 10), max_session: Some(minute * 60) };
+
+        assert_eq!(limits.expired(started, started, started + minute * 9), None);
+        assert_eq!(limits.expired(started, started, started + minute * 10), Some(ExitReason::Idle(minute * 10)));
+        // 有活动时重新计算空闲时间，但不影响最长会话时间
+        let active = started + minute * 55;
+        assert_eq!(limits.next_deadline(started, active), Some(started + minute * 60));
+        assert_eq!(limits.expired(started, active, started + minute * 60), Some(ExitReason::MaxSession(minute * 60)));
+
+        assert!(SessionLimits::default().is_empty());
+        assert_eq!(SessionLimits::default().next_deadline(started, started), None);
+        assert_eq!(ExitReason::Idle(minute * 30).to_string(), "已空闲 30 分钟（--idle-timeout）");
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 识别为结构化内容的文本最大长度，更长的文本不尝试识别
+const MAX_STRUCTURED_LEN: usize = 64;
+
+/// 从文本中识别出的颜色，如设计工具复制的 `#ff8800` 或 `rgb(255, 136, 0)`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color {
+    /// 复制时的原始文本，接收方不支持颜色格式时原样写入文本
+    pub text: String,
+    /// 红、绿、蓝和不透明度分量
+    pub rgba: [u8; 4],
+}
+
+impl Color {
+    /// 识别十六进制（`#rgb`、`#rgba`、`#rrggbb`、`#rrggbbaa`）或 CSS `rgb()` / `rgba()` 写法的颜色，
+    /// 文本只能包含颜色本身（允许首尾空白）
+    pub fn parse(text: &str) -> Option<Self> {
+        if text.len() > MAX_STRUCTURED_LEN {
+            return None;
+        }
+        let value = text.trim();
+        let rgba = match value.strip_prefix('#') {
+            Some(hex) => parse_hex(hex)?,
+            None => parse_rgb_function(value)?,
+        };
+        Some(Self { text: text.to_string(), rgba })
+    }
+}
+
+impl fmt::Display for Color {
+    /// 以 `#rrggbb` 输出，不完全不透明时为 `#rrggbbaa`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.rgba;
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)?;
+        if a != 255 {
+            write!(f, "{:02x}", a)?;
+        }
+        Ok(())
+    }
+}
+
+/// 解析 3、4、6 或 8 位十六进制颜色
+fn parse_hex(hex: &str) -> Option<[u8; 4]> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok();
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 | 4 => {
+            let mut rgba = [255u8; 4];
+            for (i, channel) in rgba.iter_mut().enumerate().take(hex.len()) {
+                *channel = digit(i)? * 17;
+            }
+            Some(rgba)
+        }
+        6 | 8 => {
+            let mut rgba = [255u8; 4];
+            for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+                *channel = pair(i * 2)?;
+            }
+            Some(rgba)
+        }
+        _ => None,
+    }
+}
+
+/// 解析 `rgb(255, 136, 0)`、`rgba(255, 136, 0, 0.5)` 或 `rgb(255 136 0 / 50%)`
+fn parse_rgb_function(value: &str) -> Option<[u8; 4]> {
+    let lower = value.to_ascii_lowercase();
+    let args = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+
+    // 逗号写法的第四个参数或空格写法 `/` 之后的参数为不透明度
+    let (channels, alpha) = match args.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha)),
+        None => (args, None),
+    };
+    let mut parts: Vec<&str> = channels
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let alpha = match (alpha, parts.len()) {
+        (Some(alpha), 3) => Some(alpha.trim()),
+        (None, 4) => parts.pop(),
+        (None, 3) => None,
+        _ => return None,
+    };
+
+    let mut rgba = [255u8; 4];
+    for (channel, part) in rgba.iter_mut().zip(&parts) {
+        *channel = parse_channel(part)?;
+    }
+    if let Some(alpha) = alpha {
+        rgba[3] = parse_alpha(alpha)?;
+    }
+    Some(rgba)
+}
+
+/// 0-255 的整数或百分比
+fn parse_channel(part: &str) -> Option<u8> {
+    match part.strip_suffix('%') {
+        Some(percent) => scale(percent.parse().ok()?, 100.0),
+        None => part.parse().ok(),
+    }
+}
+
+/// 0-1 的小数或百分比
+fn parse_alpha(part: &str) -> Option<u8> {
+    match part.strip_suffix('%') {
+        Some(percent) => scale(percent.parse().ok()?, 100.0),
+        None => scale(part.parse().ok()?, 1.0),
+    }
+}
+
+fn scale(value: f64, max: f64) -> Option<u8> {
+    (0.0..=max).contains(&value).then(|| (value / max * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(text: &str) -> Option<[u8; 4]> {
+        Color::parse(text).map(|color| color.rgba)
+    }
+
+    #[test]
+    fn test_parse_hex_colors() {
+        assert_eq!(rgba("#ff8800"), Some([255, 136, 0, 255]));
+        assert_eq!(rgba("#FF880080"), Some([255, 136, 0, 128]));
+        assert_eq!(rgba("#f80"), Some([255, 136, 0, 255]));
+        assert_eq!(rgba("#f808"), Some([255, 136, 0, 136]));
+        assert_eq!(rgba("  #ff8800\n"), Some([255, 136, 0, 255]));
+
+        assert_eq!(rgba("ff8800"), None);
+        assert_eq!(rgba("#ff880"), None);
+        assert_eq!(rgba("#gg8800"), None);
+        assert_eq!(rgba("#ff8800 是橙色"), None);
+    }
+
+    #[test]
+    fn test_parse_rgb_functions() {
+        assert_eq!(rgba("rgb(255, 136, 0)"), Some([255, 136, 0, 255]));
+        assert_eq!(rgba("RGBA(255,136,0,0.5)"), Some([255, 136, 0, 128]));
+        assert_eq!(rgba("rgb(255 136 0 / 50%)"), Some([255, 136, 0, 128]));
+        assert_eq!(rgba("rgb(100%, 0%, 50%)"), Some([255, 0, 128, 255]));
+
+        assert_eq!(rgba("rgb(256, 0, 0)"), None);
+        assert_eq!(rgba("rgb(255, 136)"), None);
+        assert_eq!(rgba("rgba(255, 136, 0, 2)"), None);
+        assert_eq!(rgba("hello"), None);
+    }
+
+    #[test]
+    fn test_color_keeps_original_text() {
+        let color = Color::parse("rgb(255, 136, 0)").unwrap();
+        assert_eq!(color.text, "rgb(255, 136, 0)");
+        assert_eq!(color.to_string(), "#ff8800");
+        assert_eq!(Color::parse("#ff880080").unwrap().to_string(), "#ff880080");
+    }
+}
@@ -0,0 +1,134 @@
+//! 非 UTF-8 剪贴板文本的回退解码
+//!
+//! 部分程序（多为使用旧编码的老程序）把 GBK、Shift_JIS 等编码的字节放到剪贴板上，arboard 按 UTF-8 读取时失败，
+//! 这样的复制永远不会同步。指定回退编码后，读取失败时改为读取原始字节并按该编码解码，无法解码的部分替换为 U+FFFD
+
+use encoding_rs::Encoding;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::selection::Target;
+
+/// 剪贴板文本不是有效的 UTF-8 时使用的回退编码
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FallbackEncoding(&'static Encoding);
+
+impl FallbackEncoding {
+    /// 按回退编码解码，返回解码出的文本和是否有无法解码而被替换的字节
+    pub fn decode(&self, bytes: &[u8]) -> (String, bool) {
+        // 原始字节可能带有 C 字符串结尾的 NUL
+        let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        let (text, _, had_errors) = self.0.decode(&bytes[..end]);
+        (text.into_owned(), had_errors)
+    }
+}
+
+impl fmt::Debug for FallbackEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FallbackEncoding").field(&self.0.name()).finish()
+    }
+}
+
+impl FromStr for FallbackEncoding {
+    type Err = String;
+
+    /// 接受 WHATWG 编码标准中的名称和别名，如 gbk、gb18030、shift_jis、windows-1252、latin1
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Encoding::for_label(s.trim().as_bytes())
+            .map(FallbackEncoding)
+            .ok_or_else(|| format!("未知的文本编码: {} (如 gbk、shift_jis、windows-1252)", s))
+    }
+}
+
+impl fmt::Display for FallbackEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.name())
+    }
+}
+
+/// 读取剪贴板中文本的原始字节
+pub(crate) fn read_raw(target: Target) -> Result<Vec<u8>, arboard::Error> {
+    platform::read_raw(target)
+}
+
+/// Windows 上读取 `CF_TEXT`，即按系统 ANSI 代码页保存的文本
+#[cfg(windows)]
+mod platform {
+    use super::Target;
+    use clipboard_win::{formats, raw, Clipboard};
+
+    /// 打开剪贴板的尝试次数，其他程序正占用剪贴板时稍后重试
+    const OPEN_ATTEMPTS: usize = 10;
+
+    pub fn read_raw(_target: Target) -> Result<Vec<u8>, arboard::Error> {
+        let _clipboard = Clipboard::new_attempts(OPEN_ATTEMPTS)
+            .map_err(|e| arboard::Error::Unknown { description: e.to_string() })?;
+        let mut bytes = Vec::new();
+        raw::get_vec(formats::CF_TEXT, &mut bytes).map_err(|_| arboard::Error::ContentNotAvailable)?;
+        Ok(bytes)
+    }
+}
+
+/// Linux 上 arboard 不提供原始字节，借助 xclip（X11）或 wl-paste（Wayland）读取
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+mod platform {
+    use super::Target;
+    use std::process::Command;
+
+    pub fn read_raw(target: Target) -> Result<Vec<u8>, arboard::Error> {
+        let mut command = if std::env::var_os("DISPLAY").is_some() {
+            let mut command = Command::new("xclip");
+            let selection = match target {
+                Target::Clipboard => "clipboard",
+                Target::Primary => "primary",
+            };
+            command.args(["-o", "-selection", selection]);
+            command
+        } else {
+            let mut command = Command::new("wl-paste");
+            command.arg("--no-newline");
+            if target == Target::Primary {
+                command.arg("--primary");
+            }
+            command
+        };
+        let output = command.output().map_err(|e| arboard::Error::Unknown {
+            description: format!("无法读取剪贴板原始内容（需要安装 xclip 或 wl-clipboard）: {}", e),
+        })?;
+        if !output.status.success() {
+            return Err(arboard::Error::ContentNotAvailable);
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// macOS 等平台的剪贴板文本总是 Unicode，不会出现无法解码的文本
+#[cfg(not(any(windows, all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))))]
+mod platform {
+    use super::Target;
+
+    pub fn read_raw(_target: Target) -> Result<Vec<u8>, arboard::Error> {
+        Err(arboard::Error::ContentNotAvailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_legacy_encodings() {
+        let gbk: FallbackEncoding = "gbk".parse().unwrap();
+        assert_eq!(gbk.decode(&[0xc4, 0xe3, 0xba, 0xc3, 0x00]), ("你好".to_string(), false));
+
+        let latin1: FallbackEncoding = "latin1".parse().unwrap();
+        assert_eq!(latin1.to_string(), "windows-1252");
+        assert_eq!(latin1.decode(b"caf\xe9"), ("café".to_string(), false));
+
+        // 无法解码的字节替换为 U+FFFD
+        let sjis: FallbackEncoding = "shift_jis".parse().unwrap();
+        assert_eq!(sjis.decode(b"a\x81"), ("a\u{fffd}".to_string(), true));
+
+        assert!("klingon".parse::<FallbackEncoding>().is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::Result;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use crate::auth::HANDSHAKE_TIMEOUT;
+use crate::transport::{split_plain, BoxedReader, BoxedWriter};
+
+/// TLS 设置：`acceptor` 用于接受的连接，`connector` 用于主动发起的连接，未设置的一方使用明文 TCP
+#[derive(Clone, Default)]
+pub struct TlsSettings {
+    acceptor: Option<TlsAcceptor>,
+    connector: Option<TlsConnector>,
+    /// 校验服务器证书时使用的名称，默认使用连接的 IP 地址
+    server_name: Option<String>,
+}
+
+impl TlsSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 接受的连接使用 TLS，`client_ca` 用于校验客户端证书，`require_client_cert` 时拒绝没有有效证书的客户端
+    pub fn with_server_cert(
+        mut self,
+        cert: &Path,
+        key: &Path,
+        client_ca: Option<&Path>,
+        require_client_cert: bool,
+    ) -> Result<Self> {
+        let provider = provider();
+        let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+        let builder = match client_ca {
+            Some(ca) => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(root_store(ca)?), provider);
+                let verifier = if require_client_cert {
+                    verifier.build()?
+                } else {
+                    verifier.allow_unauthenticated().build()?
+                };
+                builder.with_client_cert_verifier(verifier)
+            }
+            None if require_client_cert => return Err(anyhow::anyhow!("要求客户端证书时必须指定 CA 证书")),
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder
+            .with_single_cert(load_certs(cert)?, load_key(key)?)
+            .map_err(|e| anyhow::anyhow!("服务器证书和私钥无效: {}", e))?;
+        self.acceptor = Some(TlsAcceptor::from(Arc::new(config)));
+        Ok(self)
+    }
+
+    /// 主动发起的连接使用 TLS，用 `ca` 校验服务器证书，`client_identity` 为需要出示的客户端证书和私钥
+    pub fn with_client(mut self, ca: &Path, client_identity: Option<(&Path, &Path)>) -> Result<Self> {
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(root_store(ca)?);
+        let config = match client_identity {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+                .map_err(|e| anyhow::anyhow!("客户端证书和私钥无效: {}", e))?,
+            None => builder.with_no_client_auth(),
+        };
+        self.connector = Some(TlsConnector::from(Arc::new(config)));
+        Ok(self)
+    }
+
+    /// 设置校验服务器证书时使用的名称（证书中的 DNS 名称），默认使用连接的 IP 地址
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// 主动发起的连接是否使用 TLS
+    pub fn connects_with_tls(&self) -> bool {
+        self.connector.is_some()
+    }
+
+    /// 在接受的连接上完成 TLS 握手，需要客户端证书时记录证书的 CN
+    pub(crate) async fn accept(&self, stream: TcpStream, addr: SocketAddr) -> Result<(BoxedReader, BoxedWriter)> {
+        let Some(acceptor) = &self.acceptor else {
+            return Ok(split_plain(stream));
+        };
+        let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+            .await
+            .map_err(|_| anyhow::anyhow!("TLS 握手超时"))?
+            .map_err(|e| anyhow::anyhow!("TLS 握手失败: {}", e))?;
+        if let Some(name) = stream.get_ref().1.peer_certificates().and_then(common_name) {
+            status!("🔐 {} 使用客户端证书认证: CN={}", addr, name);
+        }
+        let (reader, writer) = tokio::io::split(stream);
+        Ok((Box::new(reader), Box::new(writer)))
+    }
+
+    /// 在主动发起的连接上完成 TLS 握手
+    pub(crate) async fn connect(&self, stream: TcpStream, ip: IpAddr) -> Result<(BoxedReader, BoxedWriter)> {
+        let Some(connector) = &self.connector else {
+            return Ok(split_plain(stream));
+        };
+        let server_name = match &self.server_name {
+            Some(name) => ServerName::try_from(name.clone()).map_err(|_| anyhow::anyhow!("无效的服务器名称: {}", name))?,
+            None => ServerName::from(ip),
+        };
+        let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, connector.connect(server_name, stream))
+            .await
+            .map_err(|_| anyhow::anyhow!("TLS 握手超时"))?
+            .map_err(|e| anyhow::anyhow!("TLS 握手失败: {}", e))?;
+        if let Some(name) = stream.get_ref().1.peer_certificates().and_then(common_name) {
+            status!("🔐 服务器证书: CN={}", name);
+        }
+        let (reader, writer) = tokio::io::split(stream);
+        Ok((Box::new(reader), Box::new(writer)))
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// 读取 PEM 文件中的所有证书
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = std::fs::read(path).map_err(|e| anyhow::anyhow!("无法读取证书 {}: {}", path.display(), e))?;
+    let certs = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("证书 {} 格式错误: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("{} 中没有找到证书", path.display()));
+    }
+    Ok(certs)
+}
+
+/// 读取 PEM 文件中的私钥（PKCS#8、PKCS#1 或 SEC1）
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let pem = std::fs::read(path).map_err(|e| anyhow::anyhow!("无法读取私钥 {}: {}", path.display(), e))?;
+    PrivateKeyDer::from_pem_slice(&pem).map_err(|e| anyhow::anyhow!("私钥 {} 格式错误: {}", path.display(), e))
+}
+
+fn root_store(ca: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca)? {
+        roots
+            .add(cert)
+            .map_err(|e| anyhow::anyhow!("CA 证书 {} 无效: {}", ca.display(), e))?;
+    }
+    Ok(roots)
+}
+
+/// 证书链中第一张证书的 CN
+fn common_name(certs: &[CertificateDer]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(certs.first()?).ok()?;
+    let name = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    #[test]
+    fn test_common_name() {
+        let certs = load_certs(&fixture("client.pem")).unwrap();
+        assert_eq!(common_name(&certs).as_deref(), Some("laptop"));
+    }
+
+    #[test]
+    fn test_require_client_cert_needs_ca() {
+        let settings = TlsSettings::new().with_server_cert(&fixture("server.pem"), &fixture("server.key"), None, true);
+        assert!(settings.is_err());
+    }
+
+    #[test]
+    fn test_mismatched_key_rejected() {
+        let settings = TlsSettings::new().with_client(&fixture("ca.pem"), Some((&fixture("client.pem"), &fixture("server.key"))));
+        assert!(settings.is_err());
+    }
+}
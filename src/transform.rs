@@ -0,0 +1,83 @@
+use anyhow::Result;
+use regex::Regex;
+use std::str::FromStr;
+
+/// 规则中分隔正则和替换文本的标记
+const RULE_SEPARATOR: &str = "=>";
+
+/// 一条文本替换规则: `regex=>replacement`
+#[derive(Debug, Clone)]
+pub struct TransformRule {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl FromStr for TransformRule {
+    type Err = anyhow::Error;
+
+    /// 按第一个 `=>` 拆分，替换文本中可使用 `$1`、`${name}` 引用捕获组
+    fn from_str(rule: &str) -> Result<Self> {
+        let (pattern, replacement) = rule
+            .split_once(RULE_SEPARATOR)
+            .ok_or_else(|| anyhow::anyhow!("无效的替换规则 {:?}: 格式应为 regex=>replacement", rule))?;
+        let pattern = Regex::new(pattern)
+            .map_err(|e| anyhow::anyhow!("替换规则 {:?} 中的正则表达式无效: {}", rule, e))?;
+        Ok(Self { pattern, replacement: replacement.to_string() })
+    }
+}
+
+/// 按顺序应用的文本替换规则
+#[derive(Debug, Clone, Default)]
+pub struct TextTransform {
+    rules: Vec<TransformRule>,
+}
+
+impl TextTransform {
+    /// 编译所有规则，任意一条无效时返回错误
+    pub fn parse<S: AsRef<str>>(rules: &[S]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| rule.as_ref().parse())
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 依次应用所有规则
+    pub fn apply(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_string(), |text, rule| {
+            rule.pattern
+                .replace_all(&text, rule.replacement.as_str())
+                .into_owned()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_apply_in_order() {
+        let transform = TextTransform::parse(&[
+            r"ghp_[A-Za-z0-9]+=>[REDACTED]",
+            r"/home/(\w+)/=>/Users/$1/",
+            r"REDACTED=>hidden",
+        ])
+        .unwrap();
+        assert_eq!(
+            transform.apply("token ghp_abc123 in /home/alice/src"),
+            "token [hidden] in /Users/alice/src"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules_rejected() {
+        assert!(TextTransform::parse(&["no separator"]).is_err());
+        assert!(TextTransform::parse(&["(unclosed=>x"]).is_err());
+        assert!(TextTransform::parse::<&str>(&[]).unwrap().is_empty());
+    }
+}
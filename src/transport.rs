@@ -0,0 +1,499 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::compression::{self, CompressionPolicy, Encoding};
+use crate::error::SyncResult;
+use crate::frame::{self, Reassembler, PROTOCOL_VERSION};
+use crate::handshake;
+use crate::network_alternative::{ClipboardContent, ClipboardMessage};
+
+/// 连接的读端，明文 TCP、TLS 和各种传输方式使用相同的类型
+pub type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+/// 连接的写端
+pub type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// 拆分不使用 TLS 的连接
+pub(crate) fn split_plain(stream: TcpStream) -> (BoxedReader, BoxedWriter) {
+    let (reader, writer) = stream.into_split();
+    (Box::new(reader), Box::new(writer))
+}
+
+/// 传输层握手返回的 future
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<FramedConnection>> + Send + 'a>>;
+
+/// 连接使用的传输方式
+///
+/// 传输层在 TCP（启用 TLS 时为 TLS）连接之上完成自己的握手，返回收发帧的 `FramedConnection`。
+/// 版本协商、认证和消息收发都在 `FramedConnection` 上进行，新增传输方式只需要实现这个 trait，
+/// 再通过 `NetworkManager::with_transport` 使用。两端必须使用相同的传输方式
+pub trait Transport: Send + Sync {
+    /// 传输方式的名称，用于日志
+    fn name(&self) -> &str;
+
+    /// 在接受的连接上完成传输层的握手
+    fn accept(&self, reader: BoxedReader, writer: BoxedWriter) -> TransportFuture<'_>;
+
+    /// 在主动发起的连接上完成传输层的握手，`addr` 为对方的地址
+    fn connect(&self, reader: BoxedReader, writer: BoxedWriter, addr: SocketAddr) -> TransportFuture<'_>;
+
+    /// 服务器启动时提示给用户的连接地址，默认不提示
+    fn endpoint(&self, _port: u16) -> Option<String> {
+        None
+    }
+}
+
+/// 默认的传输方式：直接在 TCP 上传输长度前缀的帧
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+    fn name(&self) -> &str {
+        "tcp"
+    }
+
+    fn accept(&self, reader: BoxedReader, writer: BoxedWriter) -> TransportFuture<'_> {
+        Box::pin(async move { Ok(FramedConnection::new(reader, writer)) })
+    }
+
+    fn connect(&self, reader: BoxedReader, writer: BoxedWriter, _addr: SocketAddr) -> TransportFuture<'_> {
+        Box::pin(async move { Ok(FramedConnection::new(reader, writer)) })
+    }
+}
+
+/// 命令行中可以选择的内置传输方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransportKind {
+    /// 直接使用 TCP（默认）
+    #[default]
+    Tcp,
+    /// 使用 WebSocket，每一帧对应一个二进制消息，需要 `websocket` 特性
+    WebSocket,
+}
+
+impl TransportKind {
+    /// 当前构建是否支持该传输方式
+    pub fn is_available(self) -> bool {
+        match self {
+            TransportKind::Tcp => true,
+            TransportKind::WebSocket => cfg!(feature = "websocket"),
+        }
+    }
+
+    /// 创建对应的传输层，当前构建不支持时返回错误
+    pub fn build(self) -> Result<Arc<dyn Transport>> {
+        match self {
+            TransportKind::Tcp => Ok(Arc::new(TcpTransport)),
+            #[cfg(feature = "websocket")]
+            TransportKind::WebSocket => Ok(Arc::new(crate::websocket::WebSocketTransport)),
+            #[cfg(not(feature = "websocket"))]
+            TransportKind::WebSocket => Err(anyhow::anyhow!("WebSocket 传输需要使用 --features websocket 编译")),
+        }
+    }
+}
+
+impl FromStr for TransportKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tcp" => Ok(TransportKind::Tcp),
+            "ws" => Ok(TransportKind::WebSocket),
+            other => Err(anyhow::anyhow!("未知的传输方式: {} (可选: tcp, ws)", other)),
+        }
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransportKind::Tcp => "tcp",
+            TransportKind::WebSocket => "ws",
+        })
+    }
+}
+
+/// 连接的加密方式，在 TCP（启用 TLS 时为 TLS）之上、传输层之下生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encryption {
+    /// 不额外加密（默认）
+    #[default]
+    None,
+    /// Noise 协议握手后加密所有数据，有预共享密钥时以它认证双方，需要 `noise` 特性
+    Noise,
+}
+
+impl Encryption {
+    /// 当前构建是否支持该加密方式
+    pub fn is_available(self) -> bool {
+        match self {
+            Encryption::None => true,
+            Encryption::Noise => cfg!(feature = "noise"),
+        }
+    }
+}
+
+impl FromStr for Encryption {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Encryption::None),
+            "noise" => Ok(Encryption::Noise),
+            other => Err(anyhow::anyhow!("未知的加密方式: {} (可选: none, noise)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encryption::None => "none",
+            Encryption::Noise => "noise",
+        })
+    }
+}
+
+/// 一条待发送的消息，按各连接协商的编码方式分别编码，同一编码方式只编码一次
+pub(crate) struct OutgoingFrames {
+    data: Vec<u8>,
+    /// 发给不支持结构化内容的旧版本时使用的数据，内容不是结构化内容时为 `None`
+    legacy: Option<Vec<u8>>,
+    /// 对方能解码 `data` 所需的最低协议版本，低于该版本时发送 `legacy`
+    required_version: u32,
+    /// 按压缩策略和内容类型决定是否压缩，不压缩时所有连接都发送不压缩的帧
+    compress: bool,
+    encoded: HashMap<(Encoding, bool), Vec<Vec<u8>>>,
+}
+
+/// 把消息序列化为要发送的数据，默认为 `ClipboardMessage::to_bytes`
+pub(crate) type MessageEncoder = fn(&ClipboardMessage) -> SyncResult<Vec<u8>>;
+
+impl OutgoingFrames {
+    /// 序列化消息并立即编码不压缩的帧，消息过大时返回错误
+    pub(crate) fn new(message: &ClipboardMessage) -> Result<Self> {
+        Self::with_encoder(message, ClipboardMessage::to_bytes, CompressionPolicy::default())
+    }
+
+    /// 用指定的方式序列化消息，按 `policy` 决定是否压缩，序列化失败时返回 `SyncError::Serialization`
+    pub(crate) fn with_encoder(message: &ClipboardMessage, encode: MessageEncoder, policy: CompressionPolicy) -> Result<Self> {
+        let data = encode(message)?;
+        let mut encoded = HashMap::from([((Encoding::None, false), frame::encode_frames(&data)?)]);
+        let legacy = match message.content.structured_text() {
+            Some(text) => {
+                let legacy = encode(&ClipboardMessage { content: ClipboardContent::Text(text.to_string()), ..message.clone() })?;
+                encoded.insert((Encoding::None, true), frame::encode_frames(&legacy)?);
+                Some(legacy)
+            }
+            None => None,
+        };
+        Ok(Self {
+            data,
+            legacy,
+            required_version: message.content.required_version(),
+            compress: policy.applies_to(&message.content),
+            encoded,
+        })
+    }
+
+    /// 序列化后的消息
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// 按指定编码方式编码的帧，`legacy` 时结构化内容退回文本；不压缩该内容或压缩失败时使用不压缩的帧
+    fn frames(&mut self, encoding: Encoding, legacy: bool) -> &[Vec<u8>] {
+        let encoding = if self.compress { encoding } else { Encoding::None };
+        let legacy = legacy && self.legacy.is_some();
+        let key = (encoding, legacy);
+        if !self.encoded.contains_key(&key) {
+            let data = if legacy { self.legacy.as_deref().unwrap_or(&self.data) } else { &self.data };
+            let frames = compression::compress(encoding, data).and_then(|data| frame::encode_frames(&data));
+            match frames {
+                Ok(frames) => {
+                    self.encoded.insert(key, frames);
+                }
+                Err(e) => {
+                    eprintln!("⚠️ {} 压缩失败，改为不压缩发送: {}", encoding, e);
+                    let plain = self.encoded[&(Encoding::None, legacy)].clone();
+                    self.encoded.insert(key, plain);
+                }
+            }
+        }
+        &self.encoded[&key]
+    }
+}
+
+/// 读端收到的一项内容
+pub(crate) enum Incoming {
+    Message(ClipboardMessage),
+    /// 对方的心跳请求，需要以相同标识回复
+    Ping(u64),
+    /// 对方对心跳的回复
+    Pong(u64),
+    /// 对方请求本机发送当前的剪贴板内容
+    RequestCurrent,
+}
+
+/// 连接的读端，读取帧并还原为剪贴板消息
+pub struct FramedReader {
+    reader: BoxedReader,
+    reassembler: Reassembler,
+}
+
+impl FramedReader {
+    pub fn new(reader: BoxedReader) -> Self {
+        Self { reader, reassembler: Reassembler::new() }
+    }
+
+    /// 读取下一条完整消息（分块消息重组、解压后返回），连接正常关闭时返回 `None`
+    ///
+    /// 无法解析的消息和无效分块只记录日志并跳过，读取或帧格式错误则返回错误；心跳帧和请求直接跳过，不回复
+    pub async fn recv(&mut self) -> Result<Option<ClipboardMessage>> {
+        loop {
+            match self.recv_incoming().await? {
+                Some(Incoming::Message(message)) => return Ok(Some(message)),
+                Some(Incoming::Ping(_) | Incoming::Pong(_) | Incoming::RequestCurrent) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// 读取下一条完整消息或控制帧（心跳、请求当前内容），连接正常关闭时返回 `None`
+    pub(crate) async fn recv_incoming(&mut self) -> Result<Option<Incoming>> {
+        loop {
+            let frame = match frame::read_frame(&mut self.reader).await? {
+                Some(frame::Frame::Ping(token)) => return Ok(Some(Incoming::Ping(token))),
+                Some(frame::Frame::Pong(token)) => return Ok(Some(Incoming::Pong(token))),
+                Some(frame::Frame::RequestCurrent) => return Ok(Some(Incoming::RequestCurrent)),
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            let payload = match self.reassembler.push(frame, Instant::now()) {
+                Ok(Some(payload)) => payload,
+                Ok(None) => continue, // 等待剩余分块
+                Err(e) => {
+                    eprintln!("❌ 分块重组失败: {}", e);
+                    continue;
+                }
+            };
+
+            let payload = match compression::decompress(payload) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    eprintln!("❌ 解压消息失败: {}", e);
+                    continue;
+                }
+            };
+            match ClipboardMessage::from_bytes(&payload) {
+                Ok(message) => return Ok(Some(Incoming::Message(message))),
+                Err(e) => eprintln!("❌ 解析消息失败: {}", e),
+            }
+        }
+    }
+}
+
+/// 连接的写端，按协商的编码方式把剪贴板消息编码为帧写入
+pub struct FramedWriter {
+    writer: BoxedWriter,
+    encoding: Encoding,
+    /// 协商出的协议版本，低于内容所需的版本时结构化内容以文本发送
+    version: u32,
+}
+
+impl FramedWriter {
+    /// 创建写端，协商前不压缩
+    pub fn new(writer: BoxedWriter) -> Self {
+        Self { writer, encoding: Encoding::None, version: PROTOCOL_VERSION }
+    }
+
+    /// 发送时使用的编码方式
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// 发送一条消息，超过单帧上限时分块
+    pub async fn send(&mut self, message: &ClipboardMessage) -> Result<()> {
+        let mut frames = OutgoingFrames::new(message)?;
+        self.send_frames(&mut frames).await?;
+        Ok(())
+    }
+
+    /// 依次写入一条消息按本连接编码方式编码的所有帧
+    pub(crate) async fn send_frames(&mut self, frames: &mut OutgoingFrames) -> std::io::Result<()> {
+        let legacy = self.version < frames.required_version;
+        for frame in frames.frames(self.encoding, legacy) {
+            frame::trace_written(frame);
+            self.writer.write_all(frame).await?;
+        }
+        Ok(())
+    }
+
+    /// 协商出的协议版本是否支持心跳
+    pub(crate) fn supports_heartbeat(&self) -> bool {
+        self.version >= handshake::HEARTBEAT_VERSION
+    }
+
+    /// 发送心跳请求
+    pub(crate) async fn send_ping(&mut self, token: u64) -> std::io::Result<()> {
+        let frame = frame::encode_ping(token);
+        frame::trace_written(&frame);
+        self.writer.write_all(&frame).await
+    }
+
+    /// 协商出的协议版本是否支持请求当前的剪贴板内容
+    pub(crate) fn supports_request_current(&self) -> bool {
+        self.version >= handshake::REQUEST_CURRENT_VERSION
+    }
+
+    /// 请求对方发送当前的剪贴板内容
+    pub(crate) async fn send_request_current(&mut self) -> std::io::Result<()> {
+        let frame = frame::encode_request_current();
+        frame::trace_written(&frame);
+        self.writer.write_all(&frame).await
+    }
+
+    /// 回复对方的心跳请求
+    pub(crate) async fn send_pong(&mut self, token: u64) -> std::io::Result<()> {
+        let frame = frame::encode_pong(token);
+        frame::trace_written(&frame);
+        self.writer.write_all(&frame).await
+    }
+
+    /// 关闭写端，对方读到连接断开
+    pub async fn shutdown(&mut self) -> std::io::Result<()> {
+        self.writer.shutdown().await
+    }
+}
+
+/// 传输层握手后的连接，收发长度前缀的帧
+///
+/// 连接建立阶段（版本协商、认证）直接读写底层的字节流，之后用 `split` 拆分为读端和写端
+pub struct FramedConnection {
+    reader: FramedReader,
+    writer: FramedWriter,
+}
+
+impl FramedConnection {
+    pub fn new(reader: BoxedReader, writer: BoxedWriter) -> Self {
+        Self { reader: FramedReader::new(reader), writer: FramedWriter::new(writer) }
+    }
+
+    /// 发送一条消息
+    pub async fn send(&mut self, message: &ClipboardMessage) -> Result<()> {
+        self.writer.send(message).await
+    }
+
+    /// 读取下一条消息，连接正常关闭时返回 `None`
+    pub async fn recv(&mut self) -> Result<Option<ClipboardMessage>> {
+        self.reader.recv().await
+    }
+
+    /// 设置发送时使用的编码方式
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.writer.encoding = encoding;
+    }
+
+    /// 设置协商出的协议版本，对方版本较旧时结构化内容以文本发送
+    pub fn set_version(&mut self, version: u32) {
+        self.writer.version = version;
+    }
+
+    /// 底层的读端和写端，用于连接建立阶段的握手
+    pub(crate) fn streams(&mut self) -> (&mut BoxedReader, &mut BoxedWriter) {
+        (&mut self.reader.reader, &mut self.writer.writer)
+    }
+
+    /// 拆分为读端和写端，分别交给读取任务和连接池
+    pub fn split(self) -> (FramedReader, FramedWriter) {
+        (self.reader, self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> (FramedConnection, FramedConnection) {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let (a_reader, a_writer) = tokio::io::split(a);
+        let (b_reader, b_writer) = tokio::io::split(b);
+        (
+            FramedConnection::new(Box::new(a_reader), Box::new(a_writer)),
+            FramedConnection::new(Box::new(b_reader), Box::new(b_writer)),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_framed_connection_round_trip() {
+        let (mut a, b) = pair();
+        let (mut reader, _writer) = b.split();
+        a.set_encoding(Encoding::Zstd);
+
+        let text = "剪贴板".repeat(2000);
+        let message = ClipboardMessage::new_text(text.clone(), "abc".to_string(), "笔记本".to_string());
+        let sender = tokio::spawn(async move {
+            a.send(&message).await.unwrap();
+            a
+        });
+        let received = reader.recv().await.unwrap().unwrap();
+        assert_eq!(received.content.preview(usize::MAX), text);
+
+        // 对方关闭后读到连接断开
+        drop(sender.await.unwrap());
+        assert!(reader.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_structured_content_downgraded_for_old_peers() {
+        let (mut a, b) = pair();
+        let (mut reader, _writer) = b.split();
+        let message = ClipboardMessage::new(
+            ClipboardContent::from_text("#ff8800".to_string()),
+            "abc".to_string(),
+            "笔记本".to_string(),
+        );
+
+        a.send(&message).await.unwrap();
+        let received = reader.recv().await.unwrap().unwrap();
+        assert!(matches!(received.content, ClipboardContent::Color(color) if color.rgba == [255, 136, 0, 255]));
+
+        a.set_version(handshake::CAPABILITIES_VERSION);
+        a.send(&message).await.unwrap();
+        let received = reader.recv().await.unwrap().unwrap();
+        assert!(matches!(received.content, ClipboardContent::Text(text) if text == "#ff8800"));
+
+        // 支持颜色但不支持 HTML 的版本收到纯文本
+        let html = ClipboardContent::Html { html: "<b>粗体</b>".to_string(), text: "粗体".to_string() };
+        a.set_version(handshake::HEARTBEAT_VERSION);
+        a.send(&ClipboardMessage::new(html, "abc".to_string(), "笔记本".to_string())).await.unwrap();
+        let received = reader.recv().await.unwrap().unwrap();
+        assert!(matches!(received.content, ClipboardContent::Text(text) if text == "粗体"));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_transport_is_plain_framing() {
+        let (a, b) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(a);
+        let mut connection = TcpTransport.accept(Box::new(reader), Box::new(writer)).await.unwrap();
+        assert_eq!(TcpTransport.name(), "tcp");
+        assert!(TcpTransport.endpoint(8765).is_none());
+
+        let (b_reader, b_writer) = tokio::io::split(b);
+        let mut peer = FramedConnection::new(Box::new(b_reader), Box::new(b_writer));
+        let message = ClipboardMessage::new_text("hello".to_string(), "abc".to_string(), "笔记本".to_string());
+        peer.send(&message).await.unwrap();
+        assert_eq!(connection.recv().await.unwrap().unwrap().content.preview(50), "hello");
+        assert_eq!("ws".parse::<TransportKind>().unwrap(), TransportKind::WebSocket);
+        assert!("quic".parse::<TransportKind>().is_err());
+    }
+}
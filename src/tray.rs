@@ -0,0 +1,118 @@
+use clipboard_sync_alt::{status, NetworkManager, NotificationManager, SyncControl};
+
+/// 启动系统托盘图标（在独立线程中运行托盘事件循环）
+#[cfg(target_os = "linux")]
+pub fn spawn(network: NetworkManager, notifier: NotificationManager, control: SyncControl) {
+    let handle = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        if let Err(e) = linux::run(handle, network, notifier, control) {
+            eprintln!("❌ 托盘图标启动失败: {}", e);
+        }
+    });
+}
+
+/// 启动系统托盘图标（当前平台暂不支持，仅提示）
+#[cfg(not(target_os = "linux"))]
+pub fn spawn(_network: NetworkManager, _notifier: NotificationManager, _control: SyncControl) {
+    eprintln!("⚠️ 当前平台暂不支持托盘图标，继续在终端中运行");
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use anyhow::Result;
+    use std::time::{Duration, Instant};
+    use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIconBuilder};
+
+    // 托盘提示中连接数的刷新间隔
+    const TOOLTIP_REFRESH: Duration = Duration::from_secs(1);
+    // 读取连接数的最长等待时间，避免阻塞托盘事件循环
+    const COUNT_TIMEOUT: Duration = Duration::from_millis(200);
+    const ICON_SIZE: u32 = 32;
+
+    /// 托盘事件循环：gtk 要求托盘图标在运行事件循环的同一线程中创建
+    pub fn run(
+        handle: tokio::runtime::Handle,
+        network: NetworkManager,
+        notifier: NotificationManager,
+        control: SyncControl,
+    ) -> Result<()> {
+        gtk::init()?;
+
+        let pause_item = MenuItem::new("暂停同步", true, None);
+        let notify_item = CheckMenuItem::new("显示通知", true, notifier.is_enabled(), None);
+        let quit_item = MenuItem::new("退出", true, None);
+
+        let menu = Menu::new();
+        menu.append_items(&[
+            &pause_item,
+            &notify_item,
+            &PredefinedMenuItem::separator(),
+            &quit_item,
+        ])?;
+
+        let tray = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("剪贴板同步")
+            .with_icon(build_icon()?)
+            .build()?;
+
+        let mut peer_count = 0;
+        let mut last_refresh: Option<Instant> = None;
+
+        while !control.is_shutdown() {
+            gtk::main_iteration_do(false);
+
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if event.id == *pause_item.id() {
+                    let paused = control.toggle_paused();
+                    pause_item.set_text(if paused { "恢复同步" } else { "暂停同步" });
+                    status!("{}", if paused { "⏸️ 同步已暂停" } else { "▶️ 同步已恢复" });
+                    last_refresh = None;
+                } else if event.id == *notify_item.id() {
+                    notifier.set_enabled(notify_item.is_checked());
+                    // 重新开启通知时再次尝试系统通知服务
+                    if notify_item.is_checked() {
+                        notifier.reprobe();
+                    }
+                } else if event.id == *quit_item.id() {
+                    control.shutdown();
+                }
+            }
+
+            if last_refresh.is_none_or(|at| at.elapsed() >= TOOLTIP_REFRESH) {
+                if let Ok(count) =
+                    handle.block_on(tokio::time::timeout(COUNT_TIMEOUT, network.connection_count()))
+                {
+                    peer_count = count;
+                }
+                let status = if control.is_paused() { "已暂停" } else { "同步中" };
+                tray.set_tooltip(Some(format!(
+                    "剪贴板同步 - {} ({} 个设备已连接)",
+                    status, peer_count
+                )))?;
+                last_refresh = Some(Instant::now());
+            }
+
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(())
+    }
+
+    /// 生成一个简单的圆形图标
+    fn build_icon() -> Result<Icon> {
+        let center = ICON_SIZE as f32 / 2.0;
+        let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+        for y in 0..ICON_SIZE {
+            for x in 0..ICON_SIZE {
+                let dx = x as f32 + 0.5 - center;
+                let dy = y as f32 + 0.5 - center;
+                let alpha = if dx * dx + dy * dy <= center * center { 255 } else { 0 };
+                rgba.extend_from_slice(&[0x2d, 0x8c, 0xf0, alpha]);
+            }
+        }
+        Ok(Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)?)
+    }
+}
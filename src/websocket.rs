@@ -0,0 +1,110 @@
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::auth::HANDSHAKE_TIMEOUT;
+use crate::transport::{BoxedReader, BoxedWriter, FramedConnection, Transport, TransportFuture};
+
+/// 转发任务与连接处理之间的缓冲大小
+const BRIDGE_BUFFER: usize = 64 * 1024;
+
+/// WebSocket 传输，便于穿过 HTTP 代理或接入浏览器客户端
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebSocketTransport;
+
+impl Transport for WebSocketTransport {
+    fn name(&self) -> &str {
+        "ws"
+    }
+
+    fn accept(&self, reader: BoxedReader, writer: BoxedWriter) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let (reader, writer) = accept(reader, writer).await?;
+            Ok(FramedConnection::new(reader, writer))
+        })
+    }
+
+    fn connect(&self, reader: BoxedReader, writer: BoxedWriter, addr: SocketAddr) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let (reader, writer) = connect(reader, writer, addr).await?;
+            Ok(FramedConnection::new(reader, writer))
+        })
+    }
+
+    fn endpoint(&self, port: u16) -> Option<String> {
+        Some(format!("ws://<本机地址>:{}/", port))
+    }
+}
+
+/// 在接受的连接上完成 WebSocket 握手（启用 TLS 时在 TLS 之上）
+async fn accept(reader: BoxedReader, writer: BoxedWriter) -> Result<(BoxedReader, BoxedWriter)> {
+    let stream = tokio::io::join(reader, writer);
+    let ws = tokio::time::timeout(HANDSHAKE_TIMEOUT, tokio_tungstenite::accept_async(stream))
+        .await
+        .map_err(|_| anyhow::anyhow!("WebSocket 握手超时"))?
+        .map_err(|e| anyhow::anyhow!("WebSocket 握手失败: {}", e))?;
+    Ok(bridge(ws))
+}
+
+/// 在主动发起的连接上完成 WebSocket 握手
+async fn connect(reader: BoxedReader, writer: BoxedWriter, addr: SocketAddr) -> Result<(BoxedReader, BoxedWriter)> {
+    let stream = tokio::io::join(reader, writer);
+    let url = format!("ws://{}/", addr);
+    let (ws, _) = tokio::time::timeout(HANDSHAKE_TIMEOUT, tokio_tungstenite::client_async(url, stream))
+        .await
+        .map_err(|_| anyhow::anyhow!("WebSocket 握手超时"))?
+        .map_err(|e| anyhow::anyhow!("WebSocket 握手失败: {}", e))?;
+    Ok(bridge(ws))
+}
+
+/// 把 WebSocket 连接转换为与 TCP 相同的字节流，版本协商、认证和消息收发都不需要改变
+///
+/// 每一帧对应一个二进制消息，消息内容为帧类型和帧内容，不含 4 字节长度前缀（由 WebSocket 分帧）
+fn bridge<S>(ws: WebSocketStream<S>) -> (BoxedReader, BoxedWriter)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (local, remote) = tokio::io::duplex(BRIDGE_BUFFER);
+    let (mut remote_reader, mut remote_writer) = tokio::io::split(remote);
+    let (mut sink, mut stream) = ws.split();
+
+    // 发送方向：按长度前缀切出每一帧，作为一个二进制消息发送；本地关闭写端后关闭 WebSocket
+    tokio::spawn(async move {
+        loop {
+            let mut len = [0u8; 4];
+            if remote_reader.read_exact(&mut len).await.is_err() {
+                break;
+            }
+            let mut body = vec![0u8; u32::from_be_bytes(len) as usize];
+            if remote_reader.read_exact(&mut body).await.is_err() {
+                break;
+            }
+            if sink.send(Message::binary(body)).await.is_err() {
+                return;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    // 接收方向：每个二进制消息补上长度前缀后写入字节流，对方关闭后本地读到连接断开
+    tokio::spawn(async move {
+        while let Some(Ok(message)) = stream.next().await {
+            let data = match message {
+                Message::Binary(data) if !data.is_empty() => data,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            let len = (data.len() as u32).to_be_bytes();
+            if remote_writer.write_all(&len).await.is_err() || remote_writer.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        let _ = remote_writer.shutdown().await;
+    });
+
+    let (reader, writer) = tokio::io::split(local);
+    (Box::new(reader), Box::new(writer))
+}
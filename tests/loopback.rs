@@ -0,0 +1,985 @@
+//! 端到端回环测试：在本机启动服务器并连接客户端，验证消息经过真实 TCP 连接后完整到达
+//!
+//! 只使用 `NetworkManager` 和内存剪贴板（`MemoryClipboard`），不依赖系统剪贴板。
+
+use clipboard_sync_alt::{
+    access, run_clipboard_monitor, send_text, AccessList, BroadcastResult, ClipboardBackend, ClipboardManager, ContentPolicy, ClipboardContent,
+    ClipboardMessage, ConnectTarget, Encoding, MemoryClipboard, MonitorOptions, NetworkManager, NotificationManager, PeerStore, SyncControl,
+    SyncDirection, SyncError, SyncEvent,
+};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 在临时端口启动服务器并连接一个客户端
+async fn connected_pair() -> (NetworkManager, UnboundedReceiver<ClipboardMessage>, NetworkManager) {
+    let server = NetworkManager::new("服务器".to_string());
+    let receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+    assert_ne!(port, 0);
+
+    let client = NetworkManager::new("客户端".to_string());
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+
+    (server, receiver, client)
+}
+
+/// 等待服务器接受连接
+async fn wait_for_connection(server: &NetworkManager) {
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while server.connection_count().await == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("服务器未接受连接");
+}
+
+async fn receive(receiver: &mut UnboundedReceiver<ClipboardMessage>) -> ClipboardMessage {
+    tokio::time::timeout(RECEIVE_TIMEOUT, receiver.recv())
+        .await
+        .expect("等待消息超时")
+        .expect("消息通道已关闭")
+}
+
+async fn next_event(events: &mut UnboundedReceiver<SyncEvent>) -> SyncEvent {
+    tokio::time::timeout(RECEIVE_TIMEOUT, events.recv())
+        .await
+        .expect("等待事件超时")
+        .expect("事件通道已关闭")
+}
+
+#[tokio::test]
+async fn test_text_and_image_arrive_intact() {
+    let (server, mut receiver, client) = connected_pair().await;
+
+    let text = "你好，剪贴板 👋\nsecond line";
+    let result = client.broadcast_clipboard(text).await.unwrap();
+    assert_eq!(result, BroadcastResult { delivered: 1, failed: 0, queued: false });
+
+    let message = receive(&mut receiver).await;
+    assert_eq!(message.sender_name, "客户端");
+    assert_eq!(message.sender_id, client.get_device_id());
+    match message.content {
+        ClipboardContent::Text(received) => assert_eq!(received, text),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    let data: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    client.broadcast_image(128, 128, data.clone()).await.unwrap();
+
+    let message = receive(&mut receiver).await;
+    match message.content {
+        ClipboardContent::Image { width, height, data: received } => {
+            assert_eq!((width, height), (128, 128));
+            assert_eq!(received, data);
+        }
+        other => panic!("期望图片消息，实际为 {:?}", other.preview(50)),
+    }
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_peers_remembered_by_device_id() {
+    let dir = std::env::temp_dir().join(format!("clipboard-sync-alt-loopback-{}-peers", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = |name: &str| PeerStore::load(dir.join(name)).unwrap();
+
+    let server = NetworkManager::new("服务器".to_string()).with_peer_store(store("server.json"));
+    let port = server.start_server(0).await.unwrap();
+
+    // 不监听的客户端以服务器的设备标识记录它，服务器不记录客户端的临时端口
+    let client = NetworkManager::new("客户端".to_string()).with_peer_store(store("client.json"));
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+    wait_for_connection(&server).await;
+    let known = store("client.json");
+    assert_eq!(known.peers().keys().collect::<Vec<_>>(), vec![server.get_device_id()]);
+    assert_eq!(known.addresses(), vec![format!("127.0.0.1:{}", port).parse().unwrap()]);
+
+    // 自己也在监听的设备连接进来后，服务器以它的设备标识和监听端口记录
+    let other = NetworkManager::new("另一台服务器".to_string());
+    let other_port = other.start_server(0).await.unwrap();
+    other.connect_to_device("127.0.0.1", port).await.unwrap();
+    let known = tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        loop {
+            let known = store("server.json");
+            if !known.is_empty() {
+                return known;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("服务器未记录连接进来的设备");
+    assert_eq!(known.peers().keys().collect::<Vec<_>>(), vec![other.get_device_id()]);
+    assert_eq!(known.addresses(), vec![format!("127.0.0.1:{}", other_port).parse().unwrap()]);
+
+    server.shutdown().await;
+    other.shutdown().await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_broadcast_content_clear() {
+    let (server, mut receiver, client) = connected_pair().await;
+
+    client.broadcast_content(ClipboardContent::Clear).await.unwrap();
+    let message = receive(&mut receiver).await;
+    assert!(matches!(message.content, ClipboardContent::Clear));
+    assert_eq!(message.sender_id, client.get_device_id());
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_send_text_without_clipboard() {
+    let (server, mut receiver, client) = connected_pair().await;
+
+    let result = send_text(&client, "脚本推送", &MonitorOptions::default()).await.unwrap();
+    assert_eq!(result.delivered, 1);
+    // 关闭前已写入的消息仍会送达
+    client.shutdown().await;
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "脚本推送"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+    assert!(send_text(&client, "", &MonitorOptions::default()).await.is_err());
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_messages_arrive_in_order() {
+    let (server, mut receiver, client) = connected_pair().await;
+
+    for i in 0..10 {
+        client.broadcast_clipboard(&format!("消息 {}", i)).await.unwrap();
+    }
+    for i in 0..10 {
+        match receive(&mut receiver).await.content {
+            ClipboardContent::Text(text) => assert_eq!(text, format!("消息 {}", i)),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+    }
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_message_flushed_before_shutdown() {
+    let (server, mut receiver, client) = connected_pair().await;
+
+    // 单次推送模式发送后立即关闭，消息仍应完整到达
+    client.broadcast_clipboard("最后一条").await.unwrap();
+    client.shutdown().await;
+
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "最后一条"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_broadcast_without_peers_reports_zero() {
+    let network = NetworkManager::new("孤立设备".to_string());
+    let result = network.broadcast_clipboard("无人接收").await.unwrap();
+    assert_eq!(result.total(), 0);
+}
+
+#[tokio::test]
+async fn test_server_broadcast_reaches_client() {
+    let (server, _server_receiver, client) = connected_pair().await;
+    let mut client_receiver = client.setup_message_handler().await;
+
+    wait_for_connection(&server).await;
+
+    // 主动发起的连接同样可以接收消息，双方都是对等节点
+    server.broadcast_clipboard("来自服务器").await.unwrap();
+    match receive(&mut client_receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "来自服务器"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_multiple_clients_connected_concurrently() {
+    let server = NetworkManager::new("服务器".to_string());
+    let mut server_receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+
+    let mut clients = Vec::new();
+    for i in 0..3 {
+        let client = NetworkManager::new(format!("客户端{}", i));
+        let receiver = client.setup_message_handler().await;
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+        clients.push((client, receiver));
+    }
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while server.connection_count().await < clients.len() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("服务器未接受全部连接");
+
+    // 空闲的连接不会阻塞服务器广播
+    let result = server.broadcast_clipboard("给所有人").await.unwrap();
+    assert_eq!(result.delivered, clients.len());
+    for (_, receiver) in clients.iter_mut() {
+        match receive(receiver).await.content {
+            ClipboardContent::Text(text) => assert_eq!(text, "给所有人"),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+    }
+
+    // 每个客户端发送的消息都能到达服务器
+    for (client, _) in &clients {
+        client.broadcast_clipboard(client.get_device_id()).await.unwrap();
+    }
+    let mut senders: Vec<String> = Vec::new();
+    for _ in 0..clients.len() {
+        senders.push(receive(&mut server_receiver).await.sender_id);
+    }
+    senders.sort();
+    let mut expected: Vec<String> = clients.iter().map(|(c, _)| c.get_device_id().to_string()).collect();
+    expected.sort();
+    assert_eq!(senders, expected);
+
+    for (client, _) in &clients {
+        client.shutdown().await;
+    }
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_send_to_reaches_only_named_peer() {
+    let server = NetworkManager::new("服务器".to_string());
+    let port = server.start_server(0).await.unwrap();
+    let mut clients = Vec::new();
+    for i in 0..2 {
+        let client = NetworkManager::new(format!("客户端{}", i));
+        let receiver = client.setup_message_handler().await;
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+        clients.push((client, receiver));
+    }
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while server.connection_count().await < clients.len() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("服务器未接受全部连接");
+
+    let devices = server.connected_devices().await;
+    assert_eq!(devices.len(), 2);
+    server.send_to(&devices[0], ClipboardContent::from_text("只给你".to_string())).await.unwrap();
+    // 连接标识由客户端的临时端口决定，两个客户端中恰好一个收到
+    let [(_, first), (_, second)] = &mut clients[..] else { unreachable!() };
+    let (target, other) = tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        tokio::select! {
+            message = first.recv() => (message, second),
+            message = second.recv() => (message, first),
+        }
+    })
+    .await
+    .expect("等待消息超时");
+    match target.expect("消息通道已关闭").content {
+        ClipboardContent::Text(text) => assert_eq!(text, "只给你"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+    assert!(tokio::time::timeout(Duration::from_millis(200), other.recv()).await.is_err());
+
+    let error = server.send_to("client_10.0.0.1:1", ClipboardContent::Clear).await.unwrap_err();
+    assert!(matches!(error, SyncError::PeerNotConnected(_)), "{:?}", error);
+
+    for (client, _) in &clients {
+        client.shutdown().await;
+    }
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_disconnect_closes_peer_connection() {
+    let (server, _receiver, client) = connected_pair().await;
+    wait_for_connection(&server).await;
+
+    let device_id = server.connected_devices().await.remove(0);
+    server.disconnect(&device_id).await.unwrap();
+    assert_eq!(server.connection_count().await, 0);
+    // 对方读到连接断开后移除连接
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while client.connection_count().await > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("客户端未发现连接断开");
+
+    let error = server.disconnect(&device_id).await.unwrap_err();
+    assert!(matches!(error, SyncError::PeerNotConnected(_)), "{:?}", error);
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_send_on_connect_delivers_latest_content() {
+    let server = NetworkManager::new("服务器".to_string()).with_send_on_connect(true);
+    let port = server.start_server(0).await.unwrap();
+
+    // 没有设备连接时只保留每种类型最新的一条
+    let result = server.broadcast_clipboard("先复制的").await.unwrap();
+    assert_eq!(result, BroadcastResult { delivered: 0, failed: 0, queued: true });
+    server.broadcast_clipboard("后复制的").await.unwrap();
+
+    let client = NetworkManager::new("客户端".to_string());
+    let mut receiver = client.setup_message_handler().await;
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "后复制的"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    wait_for_connection(&server).await;
+    let result = server.broadcast_clipboard("连接后复制的").await.unwrap();
+    assert_eq!(result, BroadcastResult { delivered: 1, failed: 0, queued: false });
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "连接后复制的"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_heartbeat_measures_latency() {
+    let interval = Some(Duration::from_millis(50));
+    let server = NetworkManager::new("服务器".to_string()).with_heartbeat(interval);
+    let port = server.start_server(0).await.unwrap();
+    let client = NetworkManager::new("客户端".to_string()).with_heartbeat(interval);
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+    wait_for_connection(&server).await;
+
+    let health = tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        loop {
+            let health = server.connection_health().await;
+            if health.iter().any(|snapshot| snapshot.rtt.is_some()) {
+                return health;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("未测量到心跳延迟");
+    assert_eq!(health.len(), 1);
+    assert!(health[0].supports_heartbeat);
+    assert!(!health[0].is_unresponsive());
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_connection_limit_rejects_extra_clients() {
+    let peer = NetworkManager::new("对端".to_string());
+    let peer_port = peer.start_server(0).await.unwrap();
+
+    // 服务器已有一个主动连接，达到上限
+    let server = NetworkManager::new("服务器".to_string()).with_max_connections(1);
+    let port = server.start_server(0).await.unwrap();
+    server.connect_to_device("127.0.0.1", peer_port).await.unwrap();
+
+    let client = NetworkManager::new("客户端".to_string());
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+
+    // 服务器关闭多余的连接后，客户端检测到断开并移除
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while client.connection_count().await > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("超出上限的连接未被关闭");
+    assert_eq!(server.connection_count().await, 1);
+
+    client.shutdown().await;
+    server.shutdown().await;
+    peer.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_connection_limit_counts_pending_handshakes() {
+    let server = NetworkManager::new("服务器".to_string()).with_max_connections(1);
+    let port = server.start_server(0).await.unwrap();
+
+    // 只建立 TCP 连接、不握手的套接字也占用名额，之后的连接在握手前被关闭
+    let idle = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let client = NetworkManager::new("客户端".to_string());
+    assert!(client.connect_to_device("127.0.0.1", port).await.is_err());
+    assert_eq!(server.connection_count().await, 0);
+
+    // 握手中的连接关闭后释放名额
+    drop(idle);
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while client.connect_to_device("127.0.0.1", port).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("握手中的连接关闭后名额未释放");
+    wait_for_connection(&server).await;
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_secret_required_to_connect() {
+    let server = NetworkManager::new("服务器".to_string()).with_secret("共享密钥");
+    let mut receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+
+    let wrong = NetworkManager::new("错误密钥".to_string()).with_secret("猜的密钥");
+    assert!(wrong.connect_to_device("127.0.0.1", port).await.is_err());
+
+    let client = NetworkManager::new("客户端".to_string());
+    client.connect_to_device_with_secret("127.0.0.1", port, "共享密钥").await.unwrap();
+    client.broadcast_clipboard("已认证").await.unwrap();
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "已认证"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_connection_errors_are_matchable() {
+    let server = NetworkManager::new("服务器".to_string()).with_secret("共享密钥");
+    let port = server.start_server(0).await.unwrap();
+
+    let wrong = NetworkManager::new("错误密钥".to_string()).with_secret("猜的密钥");
+    let error = wrong.connect_to_device("127.0.0.1", port).await.unwrap_err();
+    assert!(matches!(error, SyncError::AuthFailed(_)), "{:?}", error);
+    server.shutdown().await;
+
+    // 找一个没有监听的端口
+    let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let client = NetworkManager::new("客户端".to_string());
+    let error = client.connect_to_device("127.0.0.1", unused).await.unwrap_err();
+    assert!(matches!(error, SyncError::Connect { .. }), "{:?}", error);
+}
+
+#[tokio::test]
+async fn test_disallowed_content_from_peer_is_dropped() {
+    let text_only = ContentPolicy::new(vec![access::parse_content_rule("127.0.0.1=text").unwrap()]);
+    let server = NetworkManager::new("服务器".to_string()).with_content_policy(text_only);
+    let mut receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+
+    let client = NetworkManager::new("客户端".to_string());
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+    wait_for_connection(&server).await;
+
+    client.broadcast_image(1, 1, vec![0x89, b'P', b'N', b'G']).await.unwrap();
+    client.broadcast_clear().await.unwrap();
+    client.broadcast_clipboard("只允许文本").await.unwrap();
+
+    // 图片和清空被丢弃，第一条收到的是文本
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "只允许文本"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+    assert!(receiver.try_recv().is_err());
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_peer_without_compression_receives_plain_text() {
+    let text = "可以压缩的长文本 ".repeat(2000);
+    for (server_encodings, client_encodings) in [
+        (vec![Encoding::None], Encoding::ALL.to_vec()),
+        (Encoding::ALL.to_vec(), vec![Encoding::Gzip]),
+    ] {
+        let server = NetworkManager::new("服务器".to_string()).with_encodings(server_encodings);
+        let mut server_receiver = server.setup_message_handler().await;
+        let port = server.start_server(0).await.unwrap();
+        let client = NetworkManager::new("客户端".to_string()).with_encodings(client_encodings);
+        let mut client_receiver = client.setup_message_handler().await;
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+        wait_for_connection(&server).await;
+
+        // 双方按对方声明的编码方式发送，收到的都是可读的原文
+        client.broadcast_clipboard(&text).await.unwrap();
+        match receive(&mut server_receiver).await.content {
+            ClipboardContent::Text(received) => assert_eq!(received, text),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+        server.broadcast_clipboard(&text).await.unwrap();
+        match receive(&mut client_receiver).await.content {
+            ClipboardContent::Text(received) => assert_eq!(received, text),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+
+        client.shutdown().await;
+        server.shutdown().await;
+    }
+}
+
+#[tokio::test]
+async fn test_denied_address_is_rejected() {
+    let denied = AccessList::new(Vec::new(), vec![access::parse_net("127.0.0.0/8").unwrap()]);
+    let server = NetworkManager::new("服务器".to_string()).with_access_list(denied);
+    let port = server.start_server(0).await.unwrap();
+
+    let client = NetworkManager::new("客户端".to_string());
+    assert!(client.connect_to_device("127.0.0.1", port).await.is_err());
+    assert_eq!(server.connection_count().await, 0);
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_per_peer_sync_direction() {
+    let server = NetworkManager::new("服务器".to_string());
+    let mut server_receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+
+    // 客户端只接收服务器的内容，不向它发送
+    let client = NetworkManager::new("客户端".to_string());
+    let mut client_receiver = client.setup_message_handler().await;
+    let target = ConnectTarget {
+        direction: SyncDirection::Receive,
+        ..ConnectTarget::from(format!("127.0.0.1:{}", port).parse::<std::net::SocketAddr>().unwrap())
+    };
+    client.connect(&target).await.unwrap();
+    wait_for_connection(&server).await;
+
+    let result = client.broadcast_clipboard("不应发送").await.unwrap();
+    assert_eq!(result.total(), 0);
+
+    server.broadcast_clipboard("来自服务器").await.unwrap();
+    match receive(&mut client_receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "来自服务器"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+    assert!(server_receiver.try_recv().is_err());
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_send_only_peer_ignores_incoming() {
+    let server = NetworkManager::new("服务器".to_string());
+    let mut server_receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+
+    // 客户端只向服务器发送，忽略服务器发来的内容
+    let client = NetworkManager::new("客户端".to_string());
+    let mut client_receiver = client.setup_message_handler().await;
+    let target = ConnectTarget {
+        direction: SyncDirection::Send,
+        ..ConnectTarget::from(format!("127.0.0.1:{}", port).parse::<std::net::SocketAddr>().unwrap())
+    };
+    client.connect(&target).await.unwrap();
+    wait_for_connection(&server).await;
+
+    server.broadcast_clipboard("应被忽略").await.unwrap();
+    client.broadcast_clipboard("来自客户端").await.unwrap();
+    match receive(&mut server_receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "来自客户端"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    // 给客户端留出读取时间，服务器的消息不应被投递
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(client_receiver.try_recv().is_err());
+
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_relay_forwards_between_clients() {
+    // 中继服务器不处理剪贴板，只转发消息
+    let relay = NetworkManager::new("中继".to_string()).with_relay(true);
+    let port = relay.start_server(0).await.unwrap();
+
+    let alice = NetworkManager::new("Alice".to_string());
+    let mut alice_receiver = alice.setup_message_handler().await;
+    alice.connect_to_device("127.0.0.1", port).await.unwrap();
+    let bob = NetworkManager::new("Bob".to_string());
+    let mut bob_receiver = bob.setup_message_handler().await;
+    bob.connect_to_device("127.0.0.1", port).await.unwrap();
+
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while relay.connection_count().await < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("中继未接受全部连接");
+
+    alice.broadcast_clipboard("经由中继").await.unwrap();
+    let message = receive(&mut bob_receiver).await;
+    assert_eq!(message.sender_id, alice.get_device_id());
+    match message.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "经由中继"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    // 消息不会发回来源
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(alice_receiver.try_recv().is_err());
+
+    alice.shutdown().await;
+    bob.shutdown().await;
+    relay.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_rendezvous_relay_routes_by_room() {
+    let rooms = [("家里".to_string(), "房间密钥".to_string())].into_iter().collect();
+    let relay = NetworkManager::new("中继".to_string()).with_rooms(rooms);
+    let port = relay.start_server(0).await.unwrap();
+
+    let alice = NetworkManager::new("Alice".to_string()).with_room("家里").with_secret("房间密钥");
+    alice.connect_to_device("127.0.0.1", port).await.unwrap();
+    let bob = NetworkManager::new("Bob".to_string()).with_room("家里").with_secret("房间密钥");
+    let mut bob_receiver = bob.setup_message_handler().await;
+    bob.connect_to_device("127.0.0.1", port).await.unwrap();
+    let carol = NetworkManager::new("Carol".to_string()).with_room("公司");
+    let mut carol_receiver = carol.setup_message_handler().await;
+    carol.connect_to_device("127.0.0.1", port).await.unwrap();
+
+    // 房间密钥不对或没有加入房间的设备无法连接
+    let mallory = NetworkManager::new("Mallory".to_string()).with_room("家里").with_secret("猜的密钥");
+    assert!(mallory.connect_to_device("127.0.0.1", port).await.is_err());
+
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while relay.connection_count().await < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("中继未接受全部连接");
+
+    alice.broadcast_clipboard("只发给家里").await.unwrap();
+    let message = receive(&mut bob_receiver).await;
+    assert_eq!(message.sender_id, alice.get_device_id());
+
+    // 其他房间收不到
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(carol_receiver.try_recv().is_err());
+
+    alice.shutdown().await;
+    bob.shutdown().await;
+    carol.shutdown().await;
+    relay.shutdown().await;
+}
+
+/// 在后台运行使用内存剪贴板的监控循环，返回用于停止它的控制句柄
+fn spawn_monitor(network: &NetworkManager, memory: &MemoryClipboard) -> SyncControl {
+    let control = SyncControl::new();
+    let notifier = NotificationManager::new();
+    notifier.set_enabled(false);
+    let opts = MonitorOptions {
+        poll_interval: Duration::from_millis(20),
+        control: control.clone(),
+        ..MonitorOptions::default()
+    };
+    let clipboard = ClipboardManager::with_backend(memory.clone());
+    let network = network.clone();
+    tokio::spawn(async move { run_clipboard_monitor(clipboard, &network, notifier, opts).await });
+    control
+}
+
+#[tokio::test]
+async fn test_receive_only_peer_requests_current_clipboard() {
+    let server = NetworkManager::new("服务器".to_string());
+    let port = server.start_server(0).await.unwrap();
+    let server_clipboard = MemoryClipboard::new();
+    server_clipboard.clone().set_text("连接前已复制").unwrap();
+    let control = spawn_monitor(&server, &server_clipboard);
+
+    // 仅接收的连接（如观察模式）也可以请求
+    let observer = NetworkManager::new("观察者".to_string()).with_request_current(true);
+    let mut receiver = observer.setup_message_handler().await;
+    let target = ConnectTarget {
+        direction: SyncDirection::Receive,
+        ..ConnectTarget::from(format!("127.0.0.1:{}", port).parse::<std::net::SocketAddr>().unwrap())
+    };
+    observer.connect(&target).await.unwrap();
+    match receive(&mut receiver).await.content {
+        ClipboardContent::Text(text) => assert_eq!(text, "连接前已复制"),
+        other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+    }
+
+    control.shutdown();
+    observer.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_monitors_sync_memory_clipboards() {
+    let server = NetworkManager::new("服务器".to_string());
+    let port = server.start_server(0).await.unwrap();
+    let client = NetworkManager::new("客户端".to_string());
+    let (server_clipboard, client_clipboard) = (MemoryClipboard::new(), MemoryClipboard::new());
+    let server_control = spawn_monitor(&server, &server_clipboard);
+    let client_control = spawn_monitor(&client, &client_clipboard);
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+    wait_for_connection(&server).await;
+
+    // 在一端“复制”，另一端的剪贴板随之更新
+    server_clipboard.clone().set_text("无头同步").unwrap();
+    tokio::time::timeout(RECEIVE_TIMEOUT, async {
+        while client_clipboard.clone().get_text().ok().as_deref() != Some("无头同步") {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("客户端剪贴板未收到内容");
+
+    server_control.shutdown();
+    client_control.shutdown();
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_event_channel_reports_connection_lifecycle() {
+    let server = NetworkManager::new("服务器".to_string());
+    let mut events = server.setup_event_handler();
+    // 原有的消息通道仍然可用
+    let mut receiver = server.setup_message_handler().await;
+    let port = server.start_server(0).await.unwrap();
+
+    let client = NetworkManager::new("客户端".to_string());
+    client.connect_to_device("127.0.0.1", port).await.unwrap();
+    client.broadcast_clipboard("事件").await.unwrap();
+    assert_eq!(receive(&mut receiver).await.sender_id, client.get_device_id());
+    client.shutdown().await;
+
+    let peer = match next_event(&mut events).await {
+        SyncEvent::Connected(peer) => peer,
+        other => panic!("期望连接事件，实际为 {:?}", other),
+    };
+    match next_event(&mut events).await {
+        SyncEvent::Message(message) => assert_eq!(message.sender_id, client.get_device_id()),
+        other => panic!("期望消息事件，实际为 {:?}", other),
+    }
+    match next_event(&mut events).await {
+        SyncEvent::Disconnected(disconnected) => assert_eq!(disconnected, peer),
+        other => panic!("期望断开事件，实际为 {:?}", other),
+    }
+
+    server.shutdown().await;
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use super::*;
+    use clipboard_sync_alt::TlsSettings;
+    use std::path::{Path, PathBuf};
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    /// 要求客户端证书的 TLS 服务器
+    async fn mtls_server() -> (NetworkManager, UnboundedReceiver<ClipboardMessage>, u16) {
+        let tls = TlsSettings::new()
+            .with_server_cert(&fixture("server.pem"), &fixture("server.key"), Some(&fixture("ca.pem")), true)
+            .unwrap();
+        let server = NetworkManager::new("服务器".to_string()).with_tls(tls);
+        let receiver = server.setup_message_handler().await;
+        let port = server.start_server(0).await.unwrap();
+        (server, receiver, port)
+    }
+
+    fn client(cert: Option<(&str, &str)>) -> NetworkManager {
+        let identity = cert.map(|(cert, key)| (fixture(cert), fixture(key)));
+        let tls = TlsSettings::new()
+            .with_client(&fixture("ca.pem"), identity.as_ref().map(|(cert, key)| (cert.as_path(), key.as_path())))
+            .unwrap();
+        NetworkManager::new("客户端".to_string()).with_tls(tls)
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_delivers_messages() {
+        let (server, mut receiver, port) = mtls_server().await;
+
+        let client = client(Some(("client.pem", "client.key")));
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+        client.broadcast_clipboard("经过 TLS").await.unwrap();
+        match receive(&mut receiver).await.content {
+            ClipboardContent::Text(text) => assert_eq!(text, "经过 TLS"),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+
+        client.shutdown().await;
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_client_without_trusted_cert_rejected() {
+        let (server, _receiver, port) = mtls_server().await;
+
+        // 没有证书、证书不是由受信任的 CA 签发、以及明文连接都会被拒绝
+        for client in [
+            client(None),
+            client(Some(("rogue.pem", "rogue.key"))),
+            NetworkManager::new("明文".to_string()),
+        ] {
+            assert!(client.connect_to_device("127.0.0.1", port).await.is_err());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.connection_count().await, 0);
+
+        server.shutdown().await;
+    }
+}
+
+#[cfg(feature = "websocket")]
+mod websocket {
+    use super::*;
+    use clipboard_sync_alt::WebSocketTransport;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_websocket_transport_delivers_messages() {
+        let server = NetworkManager::new("服务器".to_string()).with_transport(Arc::new(WebSocketTransport));
+        let mut server_receiver = server.setup_message_handler().await;
+        let port = server.start_server(0).await.unwrap();
+
+        let client = NetworkManager::new("客户端".to_string())
+            .with_transport(Arc::new(WebSocketTransport))
+            .with_encodings(vec![Encoding::None]);
+        let mut client_receiver = client.setup_message_handler().await;
+        client.connect_to_device("127.0.0.1", port).await.unwrap();
+        wait_for_connection(&server).await;
+
+        // 大图片会被分块，每个分块是一个 WebSocket 消息
+        let data: Vec<u8> = (0..12 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        client.broadcast_image(2048, 1536, data.clone()).await.unwrap();
+        match receive(&mut server_receiver).await.content {
+            ClipboardContent::Image { data: received, .. } => assert_eq!(received, data),
+            other => panic!("期望图片消息，实际为 {:?}", other.preview(50)),
+        }
+
+        server.broadcast_clipboard("经过 WebSocket").await.unwrap();
+        match receive(&mut client_receiver).await.content {
+            ClipboardContent::Text(text) => assert_eq!(text, "经过 WebSocket"),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+
+        client.shutdown().await;
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_tcp_client_rejected_by_websocket_server() {
+        let server = NetworkManager::new("服务器".to_string()).with_transport(Arc::new(WebSocketTransport));
+        let port = server.start_server(0).await.unwrap();
+
+        let client = NetworkManager::new("客户端".to_string()).with_connect_timeout(Duration::from_secs(2));
+        assert!(client.connect_to_device("127.0.0.1", port).await.is_err());
+
+        server.shutdown().await;
+    }
+}
+
+#[cfg(feature = "noise")]
+mod noise {
+    use super::*;
+    use clipboard_sync_alt::Encryption;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// 在客户端与服务器之间转发数据，并记录客户端发出的所有字节
+    async fn recording_proxy(target_port: u16) -> (u16, Arc<Mutex<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let sink = recorded.clone();
+        tokio::spawn(async move {
+            let (client, _) = listener.accept().await.unwrap();
+            let server = TcpStream::connect(("127.0.0.1", target_port)).await.unwrap();
+            let (mut client_reader, mut client_writer) = client.into_split();
+            let (mut server_reader, mut server_writer) = server.into_split();
+            tokio::spawn(async move { tokio::io::copy(&mut server_reader, &mut client_writer).await });
+            let mut buffer = [0u8; 4096];
+            while let Ok(len @ 1..) = client_reader.read(&mut buffer).await {
+                sink.lock().unwrap().extend_from_slice(&buffer[..len]);
+                if server_writer.write_all(&buffer[..len]).await.is_err() {
+                    break;
+                }
+            }
+        });
+        (port, recorded)
+    }
+
+    #[tokio::test]
+    async fn test_noise_encrypts_frames_on_the_wire() {
+        let server = NetworkManager::new("服务器".to_string())
+            .with_secret("共享密钥")
+            .with_encryption(Encryption::Noise);
+        let mut receiver = server.setup_message_handler().await;
+        let port = server.start_server(0).await.unwrap();
+        let (proxy_port, recorded) = recording_proxy(port).await;
+
+        let client = NetworkManager::new("客户端".to_string())
+            .with_secret("共享密钥")
+            .with_encryption(Encryption::Noise)
+            .with_encodings(vec![Encoding::None]);
+        client.connect_to_device("127.0.0.1", proxy_port).await.unwrap();
+        wait_for_connection(&server).await;
+
+        let text = "绝密的剪贴板内容 plaintext-marker";
+        client.broadcast_clipboard(text).await.unwrap();
+        match receive(&mut receiver).await.content {
+            ClipboardContent::Text(received) => assert_eq!(received, text),
+            other => panic!("期望文本消息，实际为 {:?}", other.preview(50)),
+        }
+
+        // 线路上只有密文：既找不到消息内容，也找不到设备名称
+        let recorded = recorded.lock().unwrap().clone();
+        assert!(!recorded.is_empty());
+        for needle in ["plaintext-marker".as_bytes(), "客户端".as_bytes()] {
+            assert!(!recorded.windows(needle.len()).any(|window| window == needle));
+        }
+
+        client.shutdown().await;
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_noise_rejects_mismatched_secret() {
+        let server = NetworkManager::new("服务器".to_string())
+            .with_secret("密钥一")
+            .with_encryption(Encryption::Noise);
+        let port = server.start_server(0).await.unwrap();
+
+        let client = NetworkManager::new("客户端".to_string())
+            .with_secret("密钥二")
+            .with_encryption(Encryption::Noise);
+        assert!(client.connect_to_device("127.0.0.1", port).await.is_err());
+
+        server.shutdown().await;
+    }
+}